@@ -0,0 +1,35 @@
+/* C interface of iceberg-catalog-postgres, built with the `ffi` feature. */
+
+#ifndef ICEBERG_CATALOG_POSTGRES_H
+#define ICEBERG_CATALOG_POSTGRES_H
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+typedef struct IcebergCatalog IcebergCatalog;
+
+/* Connect to the catalog and initialize it. `warehouse` may be NULL. Returns NULL on failure. */
+IcebergCatalog *iceberg_catalog_connect(const char *name, const char *url, const char *warehouse);
+
+/* Close the connections of the catalog and free it. */
+void iceberg_catalog_free(IcebergCatalog *catalog);
+
+/* Current metadata of the table as JSON, free with iceberg_string_free. Returns NULL on failure. */
+char *iceberg_catalog_load_table(const IcebergCatalog *catalog, const char *identifier);
+
+/* Swap the metadata pointer of the table. Returns 0 on success and -1 on failure. */
+int iceberg_catalog_commit(const IcebergCatalog *catalog, const char *identifier,
+                           const char *metadata_location, const char *previous_metadata_location);
+
+/* Message of the last error on the calling thread, or NULL. Owned by the library. */
+const char *iceberg_catalog_last_error(void);
+
+/* Free a string returned by the library. */
+void iceberg_string_free(char *value);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif
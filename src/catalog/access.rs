@@ -0,0 +1,135 @@
+/*!
+Authorization of catalog operations.
+
+A catalog with an [AccessPolicy] consults the policy with its principal before every operation on
+a namespace or table, and rejects denied operations with
+[CatalogError::AccessDenied](super::error::CatalogError::AccessDenied). Creating and committing
+return the loaded table, so they also require [Action::Read].
+*/
+
+use std::{collections::HashMap, fmt};
+
+use anyhow::Result;
+use iceberg_rs::catalog::namespace::Namespace;
+
+/// Kind of access an operation requires
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Action {
+    /// List, check and load tables
+    Read,
+    /// Create and register tables
+    Create,
+    /// Commit new metadata to existing tables
+    Commit,
+    /// Drop tables
+    Drop,
+    /// Bypass the safeguards of the catalog, like forcing the metadata pointer of a table
+    Admin,
+}
+
+impl fmt::Display for Action {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Action::Read => f.write_str("read"),
+            Action::Create => f.write_str("create"),
+            Action::Commit => f.write_str("commit"),
+            Action::Drop => f.write_str("drop"),
+            Action::Admin => f.write_str("admin"),
+        }
+    }
+}
+
+/// Decides whether a principal may perform an action
+#[async_trait::async_trait]
+pub trait AccessPolicy: Send + Sync {
+    /// Check if `principal` may perform `action` on the table `table` in `namespace`, or on the
+    /// namespace itself if no table is given.
+    async fn is_allowed(
+        &self,
+        principal: &str,
+        action: Action,
+        namespace: &Namespace,
+        table: Option<&str>,
+    ) -> Result<bool>;
+}
+
+/// Policy with explicit grants of actions on namespaces. A grant on a namespace applies to all
+/// tables in the namespace and in its child namespaces. Everything that isn't granted is denied.
+#[derive(Debug, Clone, Default)]
+pub struct GrantPolicy {
+    grants: HashMap<String, Vec<(Vec<String>, Vec<Action>)>>,
+}
+
+impl GrantPolicy {
+    /// Create a policy without grants
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Allow `principal` to perform `actions` on the namespace and its children
+    pub fn grant(mut self, principal: &str, namespace: &Namespace, actions: &[Action]) -> Self {
+        self.grants
+            .entry(principal.to_string())
+            .or_default()
+            .push((namespace.levels().to_vec(), actions.to_vec()));
+        self
+    }
+}
+
+#[async_trait::async_trait]
+impl AccessPolicy for GrantPolicy {
+    async fn is_allowed(
+        &self,
+        principal: &str,
+        action: Action,
+        namespace: &Namespace,
+        _table: Option<&str>,
+    ) -> Result<bool> {
+        Ok(self
+            .grants
+            .get(principal)
+            .map(|grants| {
+                grants.iter().any(|(levels, actions)| {
+                    namespace.levels().starts_with(levels) && actions.contains(&action)
+                })
+            })
+            .unwrap_or(false))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use iceberg_rs::catalog::namespace::Namespace;
+
+    use super::{AccessPolicy, Action, GrantPolicy};
+
+    #[tokio::test]
+    async fn test_grant_policy() {
+        let sales = Namespace::try_new(&["sales".to_string()]).unwrap();
+        let orders = Namespace::try_new(&["sales".to_string(), "orders".to_string()]).unwrap();
+        let policy = GrantPolicy::new()
+            .grant("analyst", &sales, &[Action::Read])
+            .grant(
+                "owner",
+                &sales,
+                &[Action::Read, Action::Commit, Action::Drop],
+            );
+
+        assert!(policy
+            .is_allowed("analyst", Action::Read, &orders, Some("table1"))
+            .await
+            .unwrap());
+        assert!(!policy
+            .is_allowed("analyst", Action::Drop, &orders, Some("table1"))
+            .await
+            .unwrap());
+        assert!(policy
+            .is_allowed("owner", Action::Drop, &orders, Some("table1"))
+            .await
+            .unwrap());
+        assert!(!policy
+            .is_allowed("unknown", Action::Read, &sales, None)
+            .await
+            .unwrap());
+    }
+}
@@ -0,0 +1,297 @@
+/*!
+Administrative operations that bypass the safeguards of the catalog, for disaster recovery.
+
+Normal commits only move the metadata pointer of a table if it still references the metadata the
+new file was derived from. If that chain is broken, for example because the current metadata file
+was lost or corrupted, no commit can ever succeed again.
+[PostgresCatalog::force_set_metadata_location] moves the pointer unconditionally.
+
+These operations are dangerous: concurrent writers lose their commits and files that only the
+replaced metadata references are no longer tracked. They are only compiled with the `admin`
+feature, they require an access policy that explicitly grants [Action::Admin] to the principal of
+the catalog, and every use is recorded in an audit table, which
+[PostgresCatalog::admin_audit_log] reads.
+*/
+
+use std::{
+    sync::Arc,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use anyhow::{anyhow, Result};
+use iceberg_rs::{
+    catalog::{table_identifier::TableIdentifier, Catalog},
+    table::Table,
+};
+use serde_json::Value;
+
+use super::{
+    access::Action,
+    checksum::checksum,
+    error::CatalogError,
+    format::parse_metadata,
+    history::{current_snapshot_id, snapshot_summary},
+    namespace::{namespace_key, table_condition, table_identifier},
+    notification::TableEventKind,
+    query::{epoch_millis, literal},
+    quota::table_size,
+    PostgresCatalog, CATALOG_NAME_COLUMN, CURRENT_SNAPSHOT_ID_COLUMN, LAST_SEQUENCE_NUMBER_COLUMN,
+    METADATA_CHECKSUM_COLUMN, METADATA_LOCATION_COLUMN, PREVIOUS_METADATA_LOCATION_COLUMN,
+    SNAPSHOT_SUMMARY_COLUMN, TABLE_NAMESPACE_COLUMN, TABLE_NAME_COLUMN, TABLE_SIZE_COLUMN,
+    TABLE_UUID_COLUMN, UPDATED_AT_COLUMN, VERSION_COLUMN,
+};
+
+static AUDIT_ID_COLUMN: &str = "audit_id";
+static PRINCIPAL_COLUMN: &str = "principal";
+static OPERATION_COLUMN: &str = "operation";
+static PERFORMED_AT_COLUMN: &str = "performed_at";
+
+/// Operation name of [PostgresCatalog::force_set_metadata_location] in the audit table
+static FORCE_SET_OPERATION: &str = "force_set_metadata_location";
+
+/// Recorded use of an administrative operation
+#[derive(Debug, Clone)]
+pub struct AuditEntry {
+    /// Increasing id of the entry
+    pub audit_id: i64,
+    /// Identifier of the table
+    pub identifier: TableIdentifier,
+    /// Principal of the catalog that performed the operation
+    pub principal: String,
+    /// Name of the operation
+    pub operation: String,
+    /// Metadata location before the operation
+    pub previous_metadata_location: Option<String>,
+    /// Metadata location after the operation
+    pub metadata_location: String,
+    /// Time of the operation
+    pub performed_at: SystemTime,
+}
+
+impl PostgresCatalog {
+    /// Point the table to the metadata file at `location` regardless of its current metadata.
+    /// The file has to be readable table metadata, but it doesn't have to descend from the
+    /// current metadata. Concurrent commits to the table fail or are lost.
+    ///
+    /// Requires an access policy that grants [Action::Admin] on the table. The change is
+    /// recorded in the audit table.
+    pub async fn force_set_metadata_location(
+        self: Arc<Self>,
+        identifier: TableIdentifier,
+        location: &str,
+    ) -> Result<Table> {
+        self.check_writable("Forcing the metadata location")?;
+        let identifier = self.case_sensitivity.normalize(&identifier)?;
+        self.authorize_admin(&identifier).await?;
+        let bytes = self.read_metadata(&identifier, location).await?;
+        parse_metadata(&bytes)?;
+        let metadata: Value =
+            serde_json::from_slice(&bytes).map_err(|err| anyhow!(err.to_string()))?;
+        let summary = self
+            .snapshot_summaries
+            .then(|| literal(&snapshot_summary(&metadata, location).to_string()) + "::JSONB");
+        let optional = |value: Option<i64>| value.map_or("NULL".to_string(), |v| v.to_string());
+        self.mark_write();
+        // The pointer and the audit entry are written by one statement, so neither exists
+        // without the other.
+        let n_rows = self
+            .execute(
+                &self.primary,
+                &("WITH updated AS (UPDATE ".to_string()
+                    + &self.catalog_table.qualified
+                    + " SET "
+                    + PREVIOUS_METADATA_LOCATION_COLUMN
+                    + " = "
+                    + METADATA_LOCATION_COLUMN
+                    + ", "
+                    + METADATA_LOCATION_COLUMN
+                    + " = "
+                    + &literal(location)
+                    + ", "
+                    + VERSION_COLUMN
+                    + " = "
+                    + VERSION_COLUMN
+                    + " + 1, "
+                    + UPDATED_AT_COLUMN
+                    + " = now(), "
+                    + TABLE_UUID_COLUMN
+                    + " = COALESCE("
+                    + &metadata["table-uuid"]
+                        .as_str()
+                        .map_or("NULL".to_string(), literal)
+                    + "::UUID, "
+                    + TABLE_UUID_COLUMN
+                    + "), "
+                    + TABLE_SIZE_COLUMN
+                    + " = "
+                    + &optional(table_size(&metadata))
+                    + ", "
+                    + METADATA_CHECKSUM_COLUMN
+                    + " = "
+                    + &literal(&checksum(&bytes))
+                    + ", "
+                    + SNAPSHOT_SUMMARY_COLUMN
+                    + " = "
+                    + summary.as_deref().unwrap_or("NULL")
+                    + ", "
+                    + CURRENT_SNAPSHOT_ID_COLUMN
+                    + " = "
+                    + &optional(current_snapshot_id(&metadata))
+                    + ", "
+                    + LAST_SEQUENCE_NUMBER_COLUMN
+                    + " = "
+                    + &optional(metadata["last-sequence-number"].as_i64())
+                    + " WHERE "
+                    + &table_condition(&self.name, &identifier)
+                    + " RETURNING "
+                    + PREVIOUS_METADATA_LOCATION_COLUMN
+                    + ") INSERT INTO "
+                    + &self.catalog_table.audit
+                    + " ("
+                    + CATALOG_NAME_COLUMN
+                    + ", "
+                    + TABLE_NAMESPACE_COLUMN
+                    + ", "
+                    + TABLE_NAME_COLUMN
+                    + ", "
+                    + PRINCIPAL_COLUMN
+                    + ", "
+                    + OPERATION_COLUMN
+                    + ", "
+                    + PREVIOUS_METADATA_LOCATION_COLUMN
+                    + ", "
+                    + METADATA_LOCATION_COLUMN
+                    + ") SELECT "
+                    + &literal(&self.name)
+                    + ", "
+                    + &literal(&namespace_key(identifier.namespace()))
+                    + ", "
+                    + &literal(identifier.name())
+                    + ", "
+                    + &literal(&self.current_principal())
+                    + ", "
+                    + &literal(FORCE_SET_OPERATION)
+                    + ", "
+                    + PREVIOUS_METADATA_LOCATION_COLUMN
+                    + ", "
+                    + &literal(location)
+                    + " FROM updated;"),
+            )
+            .await?;
+        if n_rows != 1 {
+            return Err(anyhow!(
+                "Forcing the metadata location failed. No table matched the identifier."
+            ));
+        }
+        self.notify(
+            TableEventKind::Committed,
+            &identifier,
+            Some(location),
+            Some(&metadata),
+        )
+        .await;
+        self.load_table(identifier).await
+    }
+
+    /// Recorded administrative operations on the table, oldest first. Requires [Action::Admin].
+    pub async fn admin_audit_log(&self, identifier: &TableIdentifier) -> Result<Vec<AuditEntry>> {
+        let identifier = self.case_sensitivity.normalize(identifier)?;
+        self.authorize_admin(&identifier).await?;
+        let rows = self
+            .query(
+                &self.primary,
+                &("SELECT ".to_string()
+                    + AUDIT_ID_COLUMN
+                    + ", "
+                    + TABLE_NAMESPACE_COLUMN
+                    + ", "
+                    + TABLE_NAME_COLUMN
+                    + ", "
+                    + PRINCIPAL_COLUMN
+                    + ", "
+                    + OPERATION_COLUMN
+                    + ", "
+                    + PREVIOUS_METADATA_LOCATION_COLUMN
+                    + ", "
+                    + METADATA_LOCATION_COLUMN
+                    + ", "
+                    + &epoch_millis(PERFORMED_AT_COLUMN)
+                    + " FROM "
+                    + &self.catalog_table.audit
+                    + " WHERE "
+                    + &table_condition(&self.name, &identifier)
+                    + " ORDER BY "
+                    + AUDIT_ID_COLUMN
+                    + ";"),
+            )
+            .await?;
+        rows.iter()
+            .map(|row| {
+                Ok(AuditEntry {
+                    audit_id: row.try_get_i64(AUDIT_ID_COLUMN)?,
+                    identifier: table_identifier(
+                        &row.try_get_string(TABLE_NAMESPACE_COLUMN)?,
+                        &row.try_get_string(TABLE_NAME_COLUMN)?,
+                    )?,
+                    principal: row.try_get_string(PRINCIPAL_COLUMN)?,
+                    operation: row.try_get_string(OPERATION_COLUMN)?,
+                    previous_metadata_location: row
+                        .try_get_opt_string(PREVIOUS_METADATA_LOCATION_COLUMN)?,
+                    metadata_location: row.try_get_string(METADATA_LOCATION_COLUMN)?,
+                    performed_at: UNIX_EPOCH
+                        + Duration::from_millis(row.try_get_i64(PERFORMED_AT_COLUMN)?.max(0) as u64),
+                })
+            })
+            .collect()
+    }
+
+    /// Fail unless an access policy explicitly grants [Action::Admin] on the table. Unlike the
+    /// other actions, administrative operations are denied if the catalog has no access policy.
+    async fn authorize_admin(&self, identifier: &TableIdentifier) -> Result<()> {
+        if self.access_policy.is_none() {
+            return Err(CatalogError::AccessDenied {
+                principal: self.current_principal(),
+                action: Action::Admin,
+                resource: identifier.to_string(),
+            }
+            .into());
+        }
+        self.authorize(
+            Action::Admin,
+            identifier.namespace(),
+            Some(identifier.name()),
+        )
+        .await
+    }
+
+    /// Create the audit table of the administrative operations.
+    pub(crate) async fn create_audit_table(&self) -> Result<()> {
+        self.execute(
+            &self.primary,
+            &("CREATE TABLE IF NOT EXISTS ".to_string()
+                + &self.catalog_table.audit
+                + " ("
+                + AUDIT_ID_COLUMN
+                + " BIGSERIAL PRIMARY KEY,"
+                + CATALOG_NAME_COLUMN
+                + " VARCHAR(255) NOT NULL,"
+                + TABLE_NAMESPACE_COLUMN
+                + " VARCHAR(255) NOT NULL,"
+                + TABLE_NAME_COLUMN
+                + " VARCHAR(255) NOT NULL,"
+                + PRINCIPAL_COLUMN
+                + " TEXT NOT NULL,"
+                + OPERATION_COLUMN
+                + " TEXT NOT NULL,"
+                + PREVIOUS_METADATA_LOCATION_COLUMN
+                + " TEXT,"
+                + METADATA_LOCATION_COLUMN
+                + " TEXT NOT NULL,"
+                + PERFORMED_AT_COLUMN
+                + " TIMESTAMPTZ NOT NULL DEFAULT now()"
+                + ");"),
+        )
+        .await?;
+        Ok(())
+    }
+}
@@ -0,0 +1,357 @@
+/*!
+Archival of tables, a middle ground between live and dropped tables.
+
+An archived table keeps its catalog entry and its metadata pointer, so it can still be loaded, but
+it disappears from the listings of tables and rejects commits with [CatalogError::Archived].
+[unarchive_table](PostgresCatalog::unarchive_table) makes it live again. An archive can expire,
+after which [purge_archived_tables](PostgresCatalog::purge_archived_tables) removes the entry and
+deletes the files of the table. Purging is meant to be run periodically, for example by a scheduled
+job, and never removes protected tables, see [protection](super::protection).
+
+Like the purge of [dropped tables](super::trash), the purge can be stopped with a
+[CancellationToken] or by dropping its future. Tables are purged one after the other: the location
+of the table is recorded, its files are deleted and only then its entry is removed, so an
+interrupted purge is completed by the next run.
+*/
+
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use anyhow::{anyhow, Result};
+use iceberg_rs::catalog::{namespace::Namespace, table_identifier::TableIdentifier};
+use tokio_util::sync::CancellationToken;
+
+use super::{
+    access::Action,
+    error::CatalogError,
+    namespace::{namespace_condition, table_condition, table_identifier},
+    notification::TableEventKind,
+    query::{epoch_millis, literal},
+    trash::IN_USE_COLUMN,
+    PostgresCatalog, ARCHIVED_AT_COLUMN, ARCHIVE_EXPIRES_AT_COLUMN, CATALOG_NAME_COLUMN,
+    METADATA_LOCATION_COLUMN, PROTECTED_COLUMN, TABLE_LOCATION_COLUMN, TABLE_NAMESPACE_COLUMN,
+    TABLE_NAME_COLUMN,
+};
+
+/// A table that was archived
+#[derive(Debug, Clone)]
+pub struct ArchivedTable {
+    /// Identifier of the table
+    pub identifier: TableIdentifier,
+    /// Location of the current metadata file
+    pub metadata_location: String,
+    /// Time the table was archived
+    pub archived_at: SystemTime,
+    /// Time after which the table can be purged, `None` if the archive doesn't expire
+    pub expires_at: Option<SystemTime>,
+}
+
+impl PostgresCatalog {
+    /// Archive the table. With a `retention`, the table can be purged once the retention has
+    /// passed. Fails if the table is archived already.
+    pub async fn archive_table(
+        &self,
+        identifier: &TableIdentifier,
+        retention: Option<Duration>,
+    ) -> Result<()> {
+        self.check_writable("Archiving the table")?;
+        let identifier = &self.case_sensitivity.normalize(identifier)?;
+        self.authorize(
+            Action::Drop,
+            identifier.namespace(),
+            Some(identifier.name()),
+        )
+        .await?;
+        let expires_at = match retention {
+            Some(retention) => {
+                "now() + interval '".to_string()
+                    + &retention.as_millis().to_string()
+                    + " milliseconds'"
+            }
+            None => "NULL".to_string(),
+        };
+        self.mark_write();
+        let n_rows = self
+            .execute(
+                &self.primary,
+                &("UPDATE ".to_string()
+                    + &self.catalog_table.qualified
+                    + " SET "
+                    + ARCHIVED_AT_COLUMN
+                    + " = now(), "
+                    + ARCHIVE_EXPIRES_AT_COLUMN
+                    + " = "
+                    + &expires_at
+                    + " WHERE "
+                    + &table_condition(&self.name, identifier)
+                    + " AND "
+                    + ARCHIVED_AT_COLUMN
+                    + " IS NULL;"),
+            )
+            .await?;
+        if n_rows == 0 {
+            self.check_not_archived(identifier).await?;
+            return Err(CatalogError::NotFound {
+                table: identifier.to_string(),
+            }
+            .into());
+        }
+        Ok(())
+    }
+
+    /// Make the archived table live again. Fails if the table isn't archived.
+    pub async fn unarchive_table(&self, identifier: &TableIdentifier) -> Result<()> {
+        self.check_writable("Unarchiving the table")?;
+        let identifier = &self.case_sensitivity.normalize(identifier)?;
+        self.authorize(
+            Action::Create,
+            identifier.namespace(),
+            Some(identifier.name()),
+        )
+        .await?;
+        self.mark_write();
+        let n_rows = self
+            .execute(
+                &self.primary,
+                &("UPDATE ".to_string()
+                    + &self.catalog_table.qualified
+                    + " SET "
+                    + ARCHIVED_AT_COLUMN
+                    + " = NULL, "
+                    + ARCHIVE_EXPIRES_AT_COLUMN
+                    + " = NULL WHERE "
+                    + &table_condition(&self.name, identifier)
+                    + " AND "
+                    + ARCHIVED_AT_COLUMN
+                    + " IS NOT NULL;"),
+            )
+            .await?;
+        if n_rows == 0 {
+            Err(anyhow!(
+                "Unarchiving the table failed. No archived table matched the identifier."
+            ))
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Archived tables of the namespace and its children, the most recently archived first.
+    pub async fn archived_tables(&self, namespace: &Namespace) -> Result<Vec<ArchivedTable>> {
+        let namespace = &self.case_sensitivity.normalize_namespace(namespace)?;
+        self.authorize(Action::Read, namespace, None).await?;
+        let rows = self
+            .query(
+                self.read_connection(),
+                &("SELECT ".to_string()
+                    + TABLE_NAMESPACE_COLUMN
+                    + ", "
+                    + TABLE_NAME_COLUMN
+                    + ", "
+                    + METADATA_LOCATION_COLUMN
+                    + ", "
+                    + &epoch_millis(ARCHIVED_AT_COLUMN)
+                    + ", "
+                    + &epoch_millis(ARCHIVE_EXPIRES_AT_COLUMN)
+                    + " FROM "
+                    + &self.catalog_table.qualified
+                    + " WHERE "
+                    + CATALOG_NAME_COLUMN
+                    + " = "
+                    + &literal(&self.name)
+                    + " AND ("
+                    + &namespace_condition(namespace)
+                    + ") AND "
+                    + ARCHIVED_AT_COLUMN
+                    + " IS NOT NULL ORDER BY "
+                    + ARCHIVED_AT_COLUMN
+                    + " DESC;"),
+            )
+            .await?;
+        let time = |millis: i64| UNIX_EPOCH + Duration::from_millis(millis.max(0) as u64);
+        rows.iter()
+            .map(|row| {
+                Ok(ArchivedTable {
+                    identifier: table_identifier(
+                        &row.try_get_string(TABLE_NAMESPACE_COLUMN)?,
+                        &row.try_get_string(TABLE_NAME_COLUMN)?,
+                    )?,
+                    metadata_location: row.try_get_string(METADATA_LOCATION_COLUMN)?,
+                    archived_at: time(row.try_get_i64(ARCHIVED_AT_COLUMN)?),
+                    expires_at: row.try_get_opt_i64(ARCHIVE_EXPIRES_AT_COLUMN)?.map(time),
+                })
+            })
+            .collect()
+    }
+
+    /// Remove the archived tables whose archive expired and delete their files. Protected tables
+    /// are kept. Returns the number of removed tables.
+    pub async fn purge_archived_tables(&self) -> Result<u64> {
+        self.purge_archived_tables_cancellable(&CancellationToken::new())
+            .await
+    }
+
+    /// Purge the archived tables like [purge_archived_tables](Self::purge_archived_tables) until
+    /// the token is cancelled. Returns the number of tables that were removed before the
+    /// cancellation, the table that was being purged is purged by the next run.
+    pub async fn purge_archived_tables_cancellable(
+        &self,
+        cancellation: &CancellationToken,
+    ) -> Result<u64> {
+        self.check_writable("Purging archived tables")?;
+        let expired = |table: &str| {
+            table.to_string()
+                + ARCHIVED_AT_COLUMN
+                + " IS NOT NULL AND "
+                + table
+                + ARCHIVE_EXPIRES_AT_COLUMN
+                + " <= now() AND NOT "
+                + table
+                + PROTECTED_COLUMN
+        };
+        let rows = self
+            .query(
+                &self.primary,
+                &("SELECT ".to_string()
+                    + TABLE_NAMESPACE_COLUMN
+                    + ", "
+                    + TABLE_NAME_COLUMN
+                    + ", "
+                    + METADATA_LOCATION_COLUMN
+                    + ", "
+                    + TABLE_LOCATION_COLUMN
+                    + " FROM "
+                    + &self.catalog_table.qualified
+                    + " WHERE "
+                    + CATALOG_NAME_COLUMN
+                    + " = "
+                    + &literal(&self.name)
+                    + " AND "
+                    + &expired("")
+                    + ";"),
+            )
+            .await?;
+        let mut purged = 0;
+        for row in &rows {
+            if cancellation.is_cancelled() {
+                break;
+            }
+            let namespace = row.try_get_string(TABLE_NAMESPACE_COLUMN)?;
+            let name = row.try_get_string(TABLE_NAME_COLUMN)?;
+            let identifier = table_identifier(&namespace, &name)?;
+            let metadata_location = row.try_get_string(METADATA_LOCATION_COLUMN)?;
+            let table_location = match row.try_get_opt_string(TABLE_LOCATION_COLUMN)? {
+                Some(location) => location,
+                None => self
+                    .read_metadata_json(&identifier, &metadata_location)
+                    .await?["location"]
+                    .as_str()
+                    .ok_or_else(|| anyhow!("The table metadata contains no location."))?
+                    .to_string(),
+            };
+            // The entry must still be expired and unchanged, unarchiving or protecting the table
+            // in between stops its purge.
+            let matches_entry = "t.".to_string()
+                + CATALOG_NAME_COLUMN
+                + " = "
+                + &literal(&self.name)
+                + " AND t."
+                + TABLE_NAMESPACE_COLUMN
+                + " = "
+                + &literal(&namespace)
+                + " AND t."
+                + TABLE_NAME_COLUMN
+                + " = "
+                + &literal(&name)
+                + " AND t."
+                + METADATA_LOCATION_COLUMN
+                + " = "
+                + &literal(&metadata_location)
+                + " AND "
+                + &expired("t.");
+            // Recording the location lets an interrupted purge resume after the metadata file
+            // was deleted. Dropped tables at the same location may still be restored.
+            let recorded = self
+                .execute_returning(
+                    &("UPDATE ".to_string()
+                        + &self.catalog_table.qualified
+                        + " t SET "
+                        + TABLE_LOCATION_COLUMN
+                        + " = "
+                        + &literal(&table_location)
+                        + " WHERE "
+                        + &matches_entry
+                        + " RETURNING EXISTS (SELECT 1 FROM "
+                        + &self.catalog_table.dropped
+                        + " d WHERE d."
+                        + CATALOG_NAME_COLUMN
+                        + " = t."
+                        + CATALOG_NAME_COLUMN
+                        + " AND d."
+                        + TABLE_LOCATION_COLUMN
+                        + " = t."
+                        + TABLE_LOCATION_COLUMN
+                        + ") AS "
+                        + IN_USE_COLUMN
+                        + ";"),
+                )
+                .await?;
+            self.mark_write();
+            let in_use = match recorded.first() {
+                Some(row) => row.try_get_bool(IN_USE_COLUMN)?,
+                // The table was unarchived, protected or committed to concurrently.
+                None => continue,
+            };
+            if !in_use {
+                tokio::select! {
+                    biased;
+                    _ = cancellation.cancelled() => break,
+                    deleted = self.delete_files(
+                        &identifier,
+                        &metadata_location,
+                        &table_location,
+                    ) => deleted?,
+                }
+            }
+            let n_rows = self
+                .execute(
+                    &self.primary,
+                    &("DELETE FROM ".to_string()
+                        + &self.catalog_table.qualified
+                        + " t WHERE "
+                        + &matches_entry
+                        + ";"),
+                )
+                .await?;
+            if n_rows > 0 {
+                self.invalidate_exists(&identifier);
+                self.notify(TableEventKind::Dropped, &identifier, None, None)
+                    .await;
+                purged += n_rows;
+            }
+        }
+        Ok(purged)
+    }
+
+    /// Fail with [CatalogError::Archived] if the table is archived
+    pub(crate) async fn check_not_archived(&self, identifier: &TableIdentifier) -> Result<()> {
+        let rows = self
+            .query(
+                &self.primary,
+                &("SELECT 1 FROM ".to_string()
+                    + &self.catalog_table.qualified
+                    + " WHERE "
+                    + &table_condition(&self.name, identifier)
+                    + " AND "
+                    + ARCHIVED_AT_COLUMN
+                    + " IS NOT NULL;"),
+            )
+            .await?;
+        if rows.is_empty() {
+            Ok(())
+        } else {
+            Err(CatalogError::Archived {
+                table: identifier.to_string(),
+            }
+            .into())
+        }
+    }
+}
@@ -0,0 +1,356 @@
+/*!
+Conversion between Iceberg and Arrow schemas.
+
+[schema_to_arrow] converts an Iceberg schema into the Arrow schema that Parquet and Arrow writers
+expect, with the Iceberg field id of every field in the `PARQUET:field_id` metadata.
+[arrow_to_schema] converts back. Fields that carry a field id keep it, the others are assigned
+fresh ids in the order of the Iceberg reference implementation: first the fields of a struct,
+then the fields nested in them.
+
+The conversion goes through the JSON representation of the schema from the Iceberg spec.
+Arrow types without an Iceberg counterpart, like unsigned integers, can't be converted.
+*/
+
+use std::collections::BTreeMap;
+
+use anyhow::{anyhow, Result};
+use arrow::datatypes::{DataType, Field, Schema, TimeUnit};
+use iceberg_rs::model::schema::SchemaV2;
+use serde_json::{json, Value};
+
+/// Metadata key of the Iceberg field id of an Arrow field
+pub static FIELD_ID_KEY: &str = "PARQUET:field_id";
+
+/// Time zone of Arrow timestamps converted from `timestamptz`
+static UTC: &str = "UTC";
+
+/// Convert an Iceberg schema into an Arrow schema.
+pub fn schema_to_arrow(schema: &SchemaV2) -> Result<Schema> {
+    let schema = serde_json::to_value(schema).map_err(|err| anyhow!(err.to_string()))?;
+    Ok(Schema::new(struct_to_arrow(&schema)?))
+}
+
+/// Convert an Arrow schema into an Iceberg schema with the given id, assigning field ids to the
+/// fields without `PARQUET:field_id` metadata.
+pub fn arrow_to_schema(schema: &Schema, schema_id: i32) -> Result<SchemaV2> {
+    let mut ids = FieldIds::new(schema.fields())?;
+    let fields = struct_from_arrow(schema.fields(), &mut ids)?;
+    serde_json::from_value(json!({
+        "type": "struct",
+        "schema-id": schema_id,
+        "fields": fields,
+    }))
+    .map_err(|err| anyhow!(err.to_string()))
+}
+
+fn struct_to_arrow(value: &Value) -> Result<Vec<Field>> {
+    value["fields"]
+        .as_array()
+        .ok_or_else(|| anyhow!("The struct {} has no fields.", value))?
+        .iter()
+        .map(|field| {
+            let name = field["name"]
+                .as_str()
+                .ok_or_else(|| anyhow!("The field {} has no name.", field))?;
+            let id = field["id"]
+                .as_i64()
+                .ok_or_else(|| anyhow!("The field {} has no id.", name))?;
+            let required = field["required"].as_bool().unwrap_or(false);
+            Ok(arrow_field(
+                name,
+                type_to_arrow(&field["type"])?,
+                !required,
+                id,
+            ))
+        })
+        .collect()
+}
+
+fn arrow_field(name: &str, data_type: DataType, nullable: bool, id: i64) -> Field {
+    Field::new(name, data_type, nullable).with_metadata(Some(BTreeMap::from_iter(vec![(
+        FIELD_ID_KEY.to_string(),
+        id.to_string(),
+    )])))
+}
+
+fn type_to_arrow(value: &Value) -> Result<DataType> {
+    if let Some(primitive) = value.as_str() {
+        return primitive_to_arrow(primitive);
+    }
+    let id = |key: &str| {
+        value[key]
+            .as_i64()
+            .ok_or_else(|| anyhow!("The type {} has no {}.", value, key))
+    };
+    match value["type"].as_str() {
+        Some("struct") => Ok(DataType::Struct(struct_to_arrow(value)?)),
+        Some("list") => Ok(DataType::List(Box::new(arrow_field(
+            "element",
+            type_to_arrow(&value["element"])?,
+            !value["element-required"].as_bool().unwrap_or(false),
+            id("element-id")?,
+        )))),
+        Some("map") => Ok(DataType::Map(
+            Box::new(Field::new(
+                "entries",
+                DataType::Struct(vec![
+                    arrow_field("key", type_to_arrow(&value["key"])?, false, id("key-id")?),
+                    arrow_field(
+                        "value",
+                        type_to_arrow(&value["value"])?,
+                        !value["value-required"].as_bool().unwrap_or(false),
+                        id("value-id")?,
+                    ),
+                ]),
+                false,
+            )),
+            false,
+        )),
+        _ => Err(anyhow!("The type {} is not supported.", value)),
+    }
+}
+
+fn primitive_to_arrow(primitive: &str) -> Result<DataType> {
+    match primitive {
+        "boolean" => Ok(DataType::Boolean),
+        "int" => Ok(DataType::Int32),
+        "long" => Ok(DataType::Int64),
+        "float" => Ok(DataType::Float32),
+        "double" => Ok(DataType::Float64),
+        "date" => Ok(DataType::Date32),
+        "time" => Ok(DataType::Time64(TimeUnit::Microsecond)),
+        "timestamp" => Ok(DataType::Timestamp(TimeUnit::Microsecond, None)),
+        "timestamptz" => Ok(DataType::Timestamp(
+            TimeUnit::Microsecond,
+            Some(UTC.to_string()),
+        )),
+        "string" => Ok(DataType::Utf8),
+        "uuid" => Ok(DataType::FixedSizeBinary(16)),
+        "binary" => Ok(DataType::Binary),
+        _ => {
+            if let Some(length) = primitive
+                .strip_prefix("fixed[")
+                .and_then(|rest| rest.strip_suffix(']'))
+            {
+                return Ok(DataType::FixedSizeBinary(
+                    length.trim().parse().map_err(|_| invalid(primitive))?,
+                ));
+            }
+            if let Some((precision, scale)) = primitive
+                .strip_prefix("decimal(")
+                .and_then(|rest| rest.strip_suffix(')'))
+                .and_then(|rest| rest.split_once(','))
+            {
+                return Ok(DataType::Decimal128(
+                    precision.trim().parse().map_err(|_| invalid(primitive))?,
+                    scale.trim().parse().map_err(|_| invalid(primitive))?,
+                ));
+            }
+            Err(invalid(primitive))
+        }
+    }
+}
+
+fn invalid(primitive: &str) -> anyhow::Error {
+    anyhow!("The type {} is not supported.", primitive)
+}
+
+/// Field ids of the converted schema. New ids start above the largest existing id.
+struct FieldIds {
+    next: i64,
+}
+
+impl FieldIds {
+    fn new(fields: &[Field]) -> Result<Self> {
+        let mut max = 0;
+        let mut stack = fields.iter().collect::<Vec<_>>();
+        while let Some(field) = stack.pop() {
+            if let Some(id) = field_id(field)? {
+                max = max.max(id);
+            }
+            match field.data_type() {
+                DataType::Struct(fields) => stack.extend(fields),
+                DataType::List(element) | DataType::LargeList(element) => stack.push(element),
+                DataType::Map(entries, _) => stack.push(entries),
+                _ => (),
+            }
+        }
+        Ok(FieldIds { next: max + 1 })
+    }
+
+    /// Id of the field, or a new id if it has none
+    fn assign(&mut self, field: &Field) -> Result<i64> {
+        match field_id(field)? {
+            Some(id) => Ok(id),
+            None => {
+                self.next += 1;
+                Ok(self.next - 1)
+            }
+        }
+    }
+}
+
+fn field_id(field: &Field) -> Result<Option<i64>> {
+    field
+        .metadata()
+        .and_then(|metadata| metadata.get(FIELD_ID_KEY))
+        .map(|id| {
+            id.parse()
+                .map_err(|_| anyhow!("The field id {} of {} is invalid.", id, field.name()))
+        })
+        .transpose()
+}
+
+fn struct_from_arrow(fields: &[Field], ids: &mut FieldIds) -> Result<Vec<Value>> {
+    // Assign the ids of all fields of the struct before the ids of their children.
+    let field_ids = fields
+        .iter()
+        .map(|field| ids.assign(field))
+        .collect::<Result<Vec<_>>>()?;
+    fields
+        .iter()
+        .zip(field_ids)
+        .map(|(field, id)| {
+            Ok(json!({
+                "id": id,
+                "name": field.name(),
+                "required": !field.is_nullable(),
+                "type": type_from_arrow(field.data_type(), ids)?,
+            }))
+        })
+        .collect()
+}
+
+fn type_from_arrow(data_type: &DataType, ids: &mut FieldIds) -> Result<Value> {
+    match data_type {
+        DataType::Boolean => Ok(json!("boolean")),
+        DataType::Int8 | DataType::Int16 | DataType::Int32 => Ok(json!("int")),
+        DataType::Int64 => Ok(json!("long")),
+        DataType::Float16 | DataType::Float32 => Ok(json!("float")),
+        DataType::Float64 => Ok(json!("double")),
+        DataType::Decimal128(precision, scale) => {
+            Ok(json!(format!("decimal({}, {})", precision, scale)))
+        }
+        DataType::Date32 | DataType::Date64 => Ok(json!("date")),
+        DataType::Time32(_) | DataType::Time64(_) => Ok(json!("time")),
+        DataType::Timestamp(_, None) => Ok(json!("timestamp")),
+        DataType::Timestamp(_, Some(_)) => Ok(json!("timestamptz")),
+        DataType::Utf8 | DataType::LargeUtf8 => Ok(json!("string")),
+        DataType::FixedSizeBinary(length) => Ok(json!(format!("fixed[{}]", length))),
+        DataType::Binary | DataType::LargeBinary => Ok(json!("binary")),
+        DataType::Struct(fields) => Ok(json!({
+            "type": "struct",
+            "fields": struct_from_arrow(fields, ids)?,
+        })),
+        DataType::List(element) | DataType::LargeList(element) => {
+            let id = ids.assign(element)?;
+            Ok(json!({
+                "type": "list",
+                "element-id": id,
+                "element-required": !element.is_nullable(),
+                "element": type_from_arrow(element.data_type(), ids)?,
+            }))
+        }
+        DataType::Map(entries, _) => {
+            let (key, value) = match entries.data_type() {
+                DataType::Struct(fields) if fields.len() == 2 => (&fields[0], &fields[1]),
+                _ => return Err(anyhow!("The map {} has no key and value.", entries.name())),
+            };
+            let key_id = ids.assign(key)?;
+            let value_id = ids.assign(value)?;
+            Ok(json!({
+                "type": "map",
+                "key-id": key_id,
+                "key": type_from_arrow(key.data_type(), ids)?,
+                "value-id": value_id,
+                "value-required": !value.is_nullable(),
+                "value": type_from_arrow(value.data_type(), ids)?,
+            }))
+        }
+        _ => Err(anyhow!(
+            "The Arrow type {} has no Iceberg counterpart.",
+            data_type
+        )),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use arrow::datatypes::{DataType, Field, Schema, TimeUnit};
+    use iceberg_rs::model::schema::SchemaV2;
+    use serde_json::json;
+
+    use super::{arrow_to_schema, field_id, schema_to_arrow};
+
+    #[test]
+    fn test_schema_to_arrow() {
+        let schema: SchemaV2 = serde_json::from_value(json!({
+            "type": "struct",
+            "schema-id": 1,
+            "fields": [
+                {"id": 1, "name": "id", "required": true, "type": "long"},
+                {"id": 2, "name": "price", "required": false, "type": "decimal(9, 2)"},
+                {"id": 3, "name": "tags", "required": false, "type": {
+                    "type": "list", "element-id": 5, "element-required": true, "element": "string"
+                }},
+                {"id": 4, "name": "created", "required": false, "type": "timestamptz"}
+            ]
+        }))
+        .unwrap();
+        let arrow = schema_to_arrow(&schema).unwrap();
+        assert_eq!(arrow.fields().len(), 4);
+        assert!(!arrow.field(0).is_nullable());
+        assert_eq!(arrow.field(1).data_type(), &DataType::Decimal128(9, 2));
+        match arrow.field(2).data_type() {
+            DataType::List(element) => {
+                assert_eq!(element.data_type(), &DataType::Utf8);
+                assert_eq!(field_id(element).unwrap(), Some(5));
+            }
+            data_type => panic!("{} is not a list", data_type),
+        }
+        assert_eq!(field_id(arrow.field(3)).unwrap(), Some(4));
+
+        let roundtrip = arrow_to_schema(&arrow, 1).unwrap();
+        assert_eq!(
+            serde_json::to_value(&roundtrip).unwrap(),
+            serde_json::to_value(&schema).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_arrow_to_schema_assigns_ids() {
+        let arrow = Schema::new(vec![
+            Field::new("id", DataType::Int64, false),
+            Field::new(
+                "location",
+                DataType::Struct(vec![
+                    Field::new("lat", DataType::Float64, true),
+                    Field::new("lon", DataType::Float64, true),
+                ]),
+                true,
+            ),
+            Field::new(
+                "seen",
+                DataType::Timestamp(TimeUnit::Nanosecond, None),
+                true,
+            ),
+        ]);
+        let schema = serde_json::to_value(arrow_to_schema(&arrow, 0).unwrap()).unwrap();
+        let ids = |fields: &serde_json::Value| {
+            fields
+                .as_array()
+                .unwrap()
+                .iter()
+                .map(|field| field["id"].as_i64().unwrap())
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(ids(&schema["fields"]), vec![1, 2, 3]);
+        assert_eq!(ids(&schema["fields"][1]["type"]["fields"]), vec![4, 5]);
+        assert_eq!(schema["fields"][2]["type"], "timestamp");
+        assert!(arrow_to_schema(
+            &Schema::new(vec![Field::new("unsigned", DataType::UInt64, true)]),
+            0
+        )
+        .is_err());
+    }
+}
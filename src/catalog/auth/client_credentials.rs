@@ -0,0 +1,228 @@
+/*!
+OAuth2 client credentials flow with tokens issued by the catalog server.
+
+The token endpoint of the REST spec (`POST /v1/oauth/tokens`) exchanges the id and secret of a
+client for an access token with [ClientCredentials::issue_token]. The tokens are signed with
+HMAC-SHA256 and carry the principal of the client and their expiry, so servers that share the
+signing key accept each other's tokens without shared state. Tokens can't be revoked before they
+expire, rotating the signing key invalidates all of them.
+*/
+
+use std::{
+    collections::HashMap,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use anyhow::Result;
+use sha2::{Digest, Sha256};
+
+use super::Authenticator;
+use crate::catalog::{error::CatalogError, secret::Secret};
+
+/// Lifetime of tokens if none is configured
+static DEFAULT_TOKEN_LIFETIME: Duration = Duration::from_secs(3600);
+/// Block size of SHA-256 for HMAC
+const BLOCK_SIZE: usize = 64;
+
+/// Access token in the response of the token endpoint
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AccessToken {
+    /// The token
+    pub access_token: Secret,
+    /// Type of the token, always `bearer`
+    pub token_type: &'static str,
+    /// Seconds until the token expires
+    pub expires_in: u64,
+}
+
+/// Registered clients and the key that signs their tokens
+#[derive(Debug, Clone)]
+pub struct ClientCredentials {
+    signing_key: Secret,
+    clients: HashMap<String, (Secret, String)>,
+    token_lifetime: Duration,
+}
+
+impl ClientCredentials {
+    /// Issue and validate tokens signed with the key
+    pub fn new(signing_key: Secret) -> Self {
+        ClientCredentials {
+            signing_key,
+            clients: HashMap::new(),
+            token_lifetime: DEFAULT_TOKEN_LIFETIME,
+        }
+    }
+
+    /// Register the client with its secret, authenticated as `principal`
+    pub fn with_client(mut self, client_id: &str, client_secret: Secret, principal: &str) -> Self {
+        self.clients.insert(
+            client_id.to_string(),
+            (client_secret, principal.to_string()),
+        );
+        self
+    }
+
+    /// Issue tokens that expire after the lifetime, an hour by default.
+    pub fn with_token_lifetime(mut self, lifetime: Duration) -> Self {
+        self.token_lifetime = lifetime;
+        self
+    }
+
+    /// Exchange the credentials of a client for an access token. Fails with
+    /// [CatalogError::Unauthenticated] if the client is unknown or the secret is wrong.
+    pub fn issue_token(&self, client_id: &str, client_secret: &str) -> Result<AccessToken> {
+        let principal = match self.clients.get(client_id) {
+            Some((secret, principal))
+                if constant_time_eq(secret.expose().as_bytes(), client_secret.as_bytes()) =>
+            {
+                principal
+            }
+            _ => {
+                return Err(CatalogError::Unauthenticated {
+                    reason: "The client credentials are invalid.".to_string(),
+                }
+                .into())
+            }
+        };
+        let expires_at = unix_seconds(SystemTime::now() + self.token_lifetime);
+        let payload = hex(principal.as_bytes()) + "." + &expires_at.to_string();
+        let signature = hex(&self.sign(&payload));
+        Ok(AccessToken {
+            access_token: Secret::new(payload + "." + &signature),
+            token_type: "bearer",
+            expires_in: self.token_lifetime.as_secs(),
+        })
+    }
+
+    /// HMAC-SHA256 of the payload with the signing key
+    fn sign(&self, payload: &str) -> Vec<u8> {
+        let mut key = [0u8; BLOCK_SIZE];
+        let signing_key = self.signing_key.expose().as_bytes();
+        if signing_key.len() > BLOCK_SIZE {
+            key[..32].copy_from_slice(&Sha256::digest(signing_key));
+        } else {
+            key[..signing_key.len()].copy_from_slice(signing_key);
+        }
+        let pad = |byte: u8| key.iter().map(|k| k ^ byte).collect::<Vec<_>>();
+        let inner = Sha256::new()
+            .chain_update(pad(0x36))
+            .chain_update(payload.as_bytes())
+            .finalize();
+        Sha256::new()
+            .chain_update(pad(0x5c))
+            .chain_update(inner)
+            .finalize()
+            .to_vec()
+    }
+}
+
+#[async_trait::async_trait]
+impl Authenticator for ClientCredentials {
+    async fn authenticate(&self, token: &str) -> Result<String> {
+        let invalid = |reason: &str| CatalogError::Unauthenticated {
+            reason: reason.to_string(),
+        };
+        let (payload, signature) = token
+            .rsplit_once('.')
+            .ok_or_else(|| invalid("The token is malformed."))?;
+        if !constant_time_eq(hex(&self.sign(payload)).as_bytes(), signature.as_bytes()) {
+            return Err(invalid("The signature of the token is invalid.").into());
+        }
+        let (principal, expires_at) = payload
+            .split_once('.')
+            .ok_or_else(|| invalid("The token is malformed."))?;
+        let expires_at = expires_at
+            .parse::<u64>()
+            .map_err(|_| invalid("The token is malformed."))?;
+        if expires_at <= unix_seconds(SystemTime::now()) {
+            return Err(invalid("The token expired.").into());
+        }
+        unhex(principal)
+            .and_then(|principal| String::from_utf8(principal).ok())
+            .ok_or_else(|| invalid("The token is malformed.").into())
+    }
+}
+
+fn unix_seconds(time: SystemTime) -> u64 {
+    time.duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs()
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+fn unhex(hex: &str) -> Option<Vec<u8>> {
+    if hex.len() % 2 != 0 {
+        return None;
+    }
+    (0..hex.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(hex.get(i..i + 2)?, 16).ok())
+        .collect()
+}
+
+/// Compare without exiting early, so that the time doesn't reveal the matching prefix.
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0, |diff, (a, b)| diff | (a ^ b)) == 0
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::{hex, ClientCredentials};
+    use crate::catalog::{auth::Authenticator, secret::Secret};
+
+    #[tokio::test]
+    async fn test_client_credentials() {
+        let credentials = ClientCredentials::new(Secret::new("signing-key")).with_client(
+            "spark",
+            Secret::new("client-secret"),
+            "etl",
+        );
+        let token = credentials.issue_token("spark", "client-secret").unwrap();
+        assert_eq!(token.expires_in, 3600);
+        assert_eq!(
+            credentials
+                .authenticate(token.access_token.expose())
+                .await
+                .unwrap(),
+            "etl"
+        );
+        assert!(credentials.issue_token("spark", "wrong").is_err());
+        assert!(credentials.issue_token("trino", "client-secret").is_err());
+
+        let forged = token
+            .access_token
+            .expose()
+            .replacen(&hex(b"etl"), &hex(b"admin"), 1);
+        assert!(credentials.authenticate(&forged).await.is_err());
+        let other = ClientCredentials::new(Secret::new("other-key"));
+        assert!(other
+            .authenticate(token.access_token.expose())
+            .await
+            .is_err());
+
+        let expired = credentials
+            .with_token_lifetime(Duration::from_secs(0))
+            .issue_token("spark", "client-secret")
+            .unwrap();
+        let credentials = ClientCredentials::new(Secret::new("signing-key"));
+        assert!(credentials
+            .authenticate(expired.access_token.expose())
+            .await
+            .is_err());
+    }
+
+    #[test]
+    fn test_hmac() {
+        // Test case 2 of RFC 4231
+        let credentials = ClientCredentials::new(Secret::new("Jefe"));
+        assert_eq!(
+            hex(&credentials.sign("what do ya want for nothing?")),
+            "5bdcc146bf60754e6a042426089575c75a003f089d2739839dec58b964ec3843"
+        );
+    }
+}
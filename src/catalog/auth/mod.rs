@@ -0,0 +1,150 @@
+/*!
+Authentication of the callers of a catalog server.
+
+A REST catalog server built on the catalog serves many callers with one catalog. The server
+authenticates the bearer token of every request with an [Authenticator] and runs the request with
+[on_behalf_of] the authenticated principal. The principal then replaces the principal configured
+with [with_principal](super::builder::PostgresCatalogBuilder::with_principal) for the
+[access policy](super::access), the audit log and the owners of commit locks.
+
+The authenticators are [StaticTokens] for fixed bearer tokens,
+[ClientCredentials](client_credentials::ClientCredentials) for the OAuth2 client credentials flow
+with tokens issued by the server itself and, with the `oidc` feature,
+[OidcAuthenticator](oidc::OidcAuthenticator) for access tokens of an OpenID Connect provider.
+*/
+
+use std::{collections::HashMap, future::Future};
+
+use anyhow::Result;
+
+use super::{error::CatalogError, PostgresCatalog};
+
+pub mod client_credentials;
+#[cfg(feature = "oidc")]
+pub mod oidc;
+
+tokio::task_local! {
+    static PRINCIPAL: String;
+}
+
+/// Authenticates the bearer tokens of requests
+#[async_trait::async_trait]
+pub trait Authenticator: Send + Sync {
+    /// Principal the token belongs to. Fails with [CatalogError::Unauthenticated] if the token
+    /// is invalid.
+    async fn authenticate(&self, token: &str) -> Result<String>;
+}
+
+/// Fixed bearer tokens of principals
+#[derive(Clone, Default)]
+pub struct StaticTokens {
+    principals: HashMap<String, String>,
+}
+
+impl StaticTokens {
+    /// Create an authenticator without tokens
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Authenticate `token` as `principal`
+    pub fn with_token(mut self, token: &str, principal: &str) -> Self {
+        self.principals
+            .insert(token.to_string(), principal.to_string());
+        self
+    }
+}
+
+impl std::fmt::Debug for StaticTokens {
+    /// The tokens are secrets, only the principals are shown.
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let mut principals = self.principals.values().collect::<Vec<_>>();
+        principals.sort();
+        f.debug_struct("StaticTokens")
+            .field("principals", &principals)
+            .finish()
+    }
+}
+
+#[async_trait::async_trait]
+impl Authenticator for StaticTokens {
+    async fn authenticate(&self, token: &str) -> Result<String> {
+        self.principals.get(token).cloned().ok_or_else(|| {
+            CatalogError::Unauthenticated {
+                reason: "The token is unknown.".to_string(),
+            }
+            .into()
+        })
+    }
+}
+
+/// Token of an `Authorization: Bearer <token>` header. Fails with
+/// [CatalogError::Unauthenticated] if the header has a different scheme.
+pub fn bearer_token(authorization: &str) -> Result<&str> {
+    let authorization = authorization.trim();
+    match authorization.split_once(' ') {
+        Some((scheme, token))
+            if scheme.eq_ignore_ascii_case("bearer") && !token.trim().is_empty() =>
+        {
+            Ok(token.trim())
+        }
+        _ => Err(CatalogError::Unauthenticated {
+            reason: "The request has no bearer token.".to_string(),
+        }
+        .into()),
+    }
+}
+
+/// Run the future on behalf of the principal, so that the catalog authorizes its operations for
+/// the principal.
+pub async fn on_behalf_of<F: Future>(principal: String, future: F) -> F::Output {
+    PRINCIPAL.scope(principal, future).await
+}
+
+impl PostgresCatalog {
+    /// Principal of the current operation, the principal of the request if it runs
+    /// [on_behalf_of] one and the principal of the catalog otherwise.
+    pub(crate) fn current_principal(&self) -> String {
+        PRINCIPAL
+            .try_with(Clone::clone)
+            .unwrap_or_else(|_| self.principal.clone())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{bearer_token, on_behalf_of, Authenticator, StaticTokens, PRINCIPAL};
+    use crate::catalog::error::CatalogError;
+
+    #[tokio::test]
+    async fn test_static_tokens() {
+        let authenticator = StaticTokens::new()
+            .with_token("token-1", "analyst")
+            .with_token("token-2", "engineer");
+        assert_eq!(
+            authenticator.authenticate("token-2").await.unwrap(),
+            "engineer"
+        );
+        let err = authenticator.authenticate("token-3").await.unwrap_err();
+        assert!(matches!(
+            err.downcast_ref::<CatalogError>(),
+            Some(CatalogError::Unauthenticated { .. })
+        ));
+        assert!(!format!("{:?}", authenticator).contains("token-1"));
+    }
+
+    #[tokio::test]
+    async fn test_bearer_token() {
+        assert_eq!(bearer_token("Bearer abc").unwrap(), "abc");
+        assert_eq!(bearer_token("bearer  abc ").unwrap(), "abc");
+        assert!(bearer_token("Basic YWJjOmRlZg==").is_err());
+        assert!(bearer_token("Bearer ").is_err());
+
+        let principal = on_behalf_of("analyst".to_string(), async {
+            PRINCIPAL.try_with(Clone::clone).unwrap()
+        })
+        .await;
+        assert_eq!(principal, "analyst");
+        assert!(PRINCIPAL.try_with(Clone::clone).is_err());
+    }
+}
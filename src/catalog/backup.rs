@@ -0,0 +1,577 @@
+/*!
+Backups of the catalog entries and point-in-time restore.
+
+A backup is a JSON file in the object store of the catalog with the entries and properties of the
+catalog, it doesn't contain the metadata or data files of the tables. Restoring a backup points
+every table back to the metadata file it had at the time of the backup. Tables change after a
+backup, so the restore reconciles the backup with the current state of the catalog:
+
+- Tables that were dropped after the backup are registered again.
+- Tables whose metadata file of the backup was deleted in the meantime, for example by snapshot
+  expiration, keep their current pointer.
+- Tables that were dropped and recreated with the same identifier keep the new table.
+- Tables created after the backup are kept.
+- Archived and [protected](super::protection) tables keep their current pointer.
+- Tables whose metadata file of the backup is rejected by a commit policy keep their current
+  pointer.
+
+The backup requires the permission to read every table, the restore the permission to commit to
+every table it reverts, to create every table it registers again and [Action::Admin] to restore
+the properties. The restore checks all permissions and plans every table before it changes the
+catalog, so a missing permission leaves the catalog unchanged. A table that changes concurrently
+while the restore is applied keeps its current pointer.
+
+Catalog properties of the backup are restored, properties added later are kept. A backup is only
+restored to the catalog it was taken of.
+*/
+
+use std::{
+    collections::HashMap,
+    fmt,
+    sync::Arc,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use anyhow::{anyhow, Result};
+use iceberg_rs::{
+    catalog::{table_identifier::TableIdentifier, Catalog},
+    object_store::path::Path,
+};
+use serde_json::{json, Value};
+use tokio::task::JoinHandle;
+
+use super::{
+    access::Action,
+    error::CatalogError,
+    namespace::{identifier_key, namespace_key, table_identifier},
+    query::literal,
+    PostgresCatalog, ARCHIVED_AT_COLUMN, CATALOG_NAME_COLUMN, CURRENT_SNAPSHOT_ID_COLUMN,
+    LAST_SEQUENCE_NUMBER_COLUMN, METADATA_CHECKSUM_COLUMN, METADATA_LOCATION_COLUMN,
+    PREVIOUS_METADATA_LOCATION_COLUMN, PROTECTED_COLUMN, SNAPSHOT_SUMMARY_COLUMN,
+    TABLE_LOCATION_COLUMN, TABLE_NAMESPACE_COLUMN, TABLE_NAME_COLUMN, TABLE_UUID_COLUMN,
+    VERSION_COLUMN,
+};
+
+/// Version of the backup format
+static BACKUP_FORMAT_VERSION: i64 = 1;
+
+/// What a restore did with a table
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RestoreOutcome {
+    /// The table already points to the metadata file of the backup
+    Unchanged,
+    /// The table was dropped after the backup and was registered again
+    Recreated,
+    /// The table was pointed back to the metadata file of the backup
+    Reverted {
+        /// Metadata location before the restore
+        from: String,
+        /// Metadata location of the backup
+        to: String,
+    },
+    /// The table keeps its current state
+    Kept {
+        /// Reason why the backup couldn't be restored
+        reason: String,
+    },
+    /// The table was created after the backup and is kept
+    CreatedAfterBackup,
+}
+
+impl fmt::Display for RestoreOutcome {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RestoreOutcome::Unchanged => f.write_str("unchanged"),
+            RestoreOutcome::Recreated => f.write_str("recreated"),
+            RestoreOutcome::Reverted { from, to } => write!(f, "reverted from {} to {}", from, to),
+            RestoreOutcome::Kept { reason } => write!(f, "kept: {}", reason),
+            RestoreOutcome::CreatedAfterBackup => f.write_str("created after the backup"),
+        }
+    }
+}
+
+/// Change of a table that the restore applies once every table is planned
+enum Change {
+    /// Point the table from the current metadata location back to the backup
+    Revert(String),
+    /// Register the table again
+    Insert,
+}
+
+/// Stored state of a table
+struct Entry {
+    metadata_location: String,
+    table_location: Option<String>,
+    table_uuid: Option<String>,
+    version: i64,
+    /// Whether the table is archived, always unset for the entries of a backup
+    archived: bool,
+    /// Whether the table is protected, always unset for the entries of a backup
+    protected: bool,
+}
+
+impl PostgresCatalog {
+    /// Write a backup of the entries and properties of the catalog to `location` in the object
+    /// store of the catalog. Returns the number of tables in the backup.
+    pub async fn backup(&self, location: &str) -> Result<usize> {
+        let entries = self.backup_entries().await?;
+        for (identifier, _) in entries.values() {
+            self.authorize(
+                Action::Read,
+                identifier.namespace(),
+                Some(identifier.name()),
+            )
+            .await?;
+        }
+        let properties = self.get_properties().await?;
+        let tables: Vec<Value> = entries
+            .iter()
+            .map(|(identifier, entry)| {
+                json!({
+                    "namespace": identifier.namespace().levels(),
+                    "name": identifier.name(),
+                    "metadata-location": entry.metadata_location,
+                    "table-location": entry.table_location,
+                    "table-uuid": entry.table_uuid,
+                    "version": entry.version,
+                })
+            })
+            .collect();
+        let backup = json!({
+            "format-version": BACKUP_FORMAT_VERSION,
+            "catalog": self.name,
+            "created-at-ms": SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .unwrap_or_default()
+                .as_millis() as i64,
+            "tables": tables,
+            "properties": properties,
+        });
+        let bytes = serde_json::to_vec(&backup).map_err(|err| anyhow!(err.to_string()))?;
+        self.object_store()
+            .put(&Path::from(location), bytes.into())
+            .await
+            .map_err(|err| anyhow!(err.to_string()))?;
+        Ok(entries.len())
+    }
+
+    /// Write a backup to `directory/backup-<milliseconds since epoch>.json` every `interval` until
+    /// the catalog is closed. Failed backups are retried at the next interval.
+    pub fn spawn_backups(self: Arc<Self>, directory: &str, interval: Duration) -> JoinHandle<()> {
+        let directory = directory.trim_end_matches('/').to_string();
+        tokio::spawn(async move {
+            loop {
+                tokio::time::sleep(interval).await;
+                if *self.closed.read().await {
+                    break;
+                }
+                let millis = SystemTime::now()
+                    .duration_since(UNIX_EPOCH)
+                    .unwrap_or_default()
+                    .as_millis();
+                let _ = self
+                    .backup(&format!("{}/backup-{}.json", directory, millis))
+                    .await;
+            }
+        })
+    }
+
+    /// Restore the catalog to the backup at `location`, see the [module documentation](self).
+    /// Fails if the backup was taken of a different catalog. With `dry_run`, the catalog isn't
+    /// changed and the outcomes describe what a restore would do.
+    pub async fn restore_backup(
+        &self,
+        location: &str,
+        dry_run: bool,
+    ) -> Result<Vec<(TableIdentifier, RestoreOutcome)>> {
+        if !dry_run {
+            self.check_writable("Restoring the backup")?;
+        }
+        let object_store = self.object_store();
+        let bytes = object_store
+            .get(&Path::from(location))
+            .await
+            .map_err(|err| anyhow!(err.to_string()))?
+            .bytes()
+            .await
+            .map_err(|err| anyhow!(err.to_string()))?;
+        let backup: Value =
+            serde_json::from_slice(&bytes).map_err(|err| anyhow!(err.to_string()))?;
+        if backup["format-version"].as_i64() != Some(BACKUP_FORMAT_VERSION) {
+            return Err(anyhow!(
+                "The backup {} has an unsupported format version.",
+                location
+            ));
+        }
+        if backup["catalog"].as_str() != Some(self.name.as_str()) {
+            return Err(anyhow!(
+                "The backup {} belongs to a different catalog than {}.",
+                location,
+                self.name
+            ));
+        }
+        self.authorize_catalog(&self.name).await?;
+
+        let mut current = self.backup_entries().await?;
+        let mut planned = Vec::new();
+        for table in backup["tables"].as_array().cloned().unwrap_or_default() {
+            let (identifier, entry) = backup_entry(&table)?;
+            let outcome = match current.remove(&identifier_key(&identifier)) {
+                Some((_, now)) if now.metadata_location == entry.metadata_location => {
+                    RestoreOutcome::Unchanged
+                }
+                Some((_, now)) if now.archived => RestoreOutcome::Kept {
+                    reason: "The table is archived.".to_string(),
+                },
+                Some((_, now)) if now.protected => RestoreOutcome::Kept {
+                    reason: "The table is protected.".to_string(),
+                },
+                Some((_, now))
+                    if now.table_uuid.is_some() && now.table_uuid != entry.table_uuid =>
+                {
+                    RestoreOutcome::Kept {
+                        reason: "The table was replaced by a different table.".to_string(),
+                    }
+                }
+                Some((_, now)) => {
+                    self.authorize(
+                        Action::Commit,
+                        identifier.namespace(),
+                        Some(identifier.name()),
+                    )
+                    .await?;
+                    if !self
+                        .metadata_exists(&identifier, &entry.metadata_location)
+                        .await?
+                    {
+                        RestoreOutcome::Kept {
+                            reason: "The metadata file of the backup doesn't exist anymore."
+                                .to_string(),
+                        }
+                    } else if let Some(reason) = self.policy_rejection(&identifier, &entry).await? {
+                        RestoreOutcome::Kept { reason }
+                    } else {
+                        let outcome = RestoreOutcome::Reverted {
+                            from: now.metadata_location.clone(),
+                            to: entry.metadata_location.clone(),
+                        };
+                        planned.push((
+                            identifier,
+                            outcome,
+                            Some((Change::Revert(now.metadata_location), entry)),
+                        ));
+                        continue;
+                    }
+                }
+                None => {
+                    self.authorize(
+                        Action::Create,
+                        identifier.namespace(),
+                        Some(identifier.name()),
+                    )
+                    .await?;
+                    if self
+                        .metadata_exists(&identifier, &entry.metadata_location)
+                        .await?
+                    {
+                        planned.push((
+                            identifier,
+                            RestoreOutcome::Recreated,
+                            Some((Change::Insert, entry)),
+                        ));
+                        continue;
+                    } else {
+                        RestoreOutcome::Kept {
+                            reason: "The table was dropped and its metadata file deleted."
+                                .to_string(),
+                        }
+                    }
+                }
+            };
+            planned.push((identifier, outcome, None));
+        }
+
+        // A table that can't be changed anymore keeps its state, the other tables are restored.
+        let mut outcomes = Vec::with_capacity(planned.len() + current.len());
+        for (identifier, outcome, change) in planned {
+            let outcome = match change {
+                Some((change, entry)) if !dry_run => {
+                    let applied = match change {
+                        Change::Revert(location) => {
+                            self.revert_pointer(&identifier, &location, &entry).await
+                        }
+                        Change::Insert => self.insert_entry(&identifier, &entry).await,
+                    };
+                    match applied {
+                        Ok(()) => outcome,
+                        Err(err) => RestoreOutcome::Kept {
+                            reason: err.to_string(),
+                        },
+                    }
+                }
+                _ => outcome,
+            };
+            outcomes.push((identifier, outcome));
+        }
+        for (_, (identifier, _)) in current {
+            outcomes.push((identifier, RestoreOutcome::CreatedAfterBackup));
+        }
+
+        if !dry_run {
+            let properties = backup["properties"]
+                .as_object()
+                .map(|properties| {
+                    properties
+                        .iter()
+                        .filter_map(|(key, value)| {
+                            value.as_str().map(|value| (key.clone(), value.to_string()))
+                        })
+                        .collect::<HashMap<_, _>>()
+                })
+                .unwrap_or_default();
+            self.set_properties(&properties).await?;
+            self.mark_write();
+        }
+        Ok(outcomes)
+    }
+
+    /// Entries of the catalog by the [key](identifier_key) of their table
+    async fn backup_entries(&self) -> Result<HashMap<(String, String), (TableIdentifier, Entry)>> {
+        let rows = self
+            .query(
+                &self.primary,
+                &("SELECT ".to_string()
+                    + TABLE_NAMESPACE_COLUMN
+                    + ", "
+                    + TABLE_NAME_COLUMN
+                    + ", "
+                    + METADATA_LOCATION_COLUMN
+                    + ", "
+                    + TABLE_LOCATION_COLUMN
+                    + ", "
+                    + TABLE_UUID_COLUMN
+                    + "::TEXT AS "
+                    + TABLE_UUID_COLUMN
+                    + ", "
+                    + VERSION_COLUMN
+                    + ", "
+                    + ARCHIVED_AT_COLUMN
+                    + " IS NOT NULL AS "
+                    + ARCHIVED_AT_COLUMN
+                    + ", "
+                    + PROTECTED_COLUMN
+                    + " FROM "
+                    + &self.catalog_table.qualified
+                    + " WHERE "
+                    + CATALOG_NAME_COLUMN
+                    + " = "
+                    + &literal(&self.name)
+                    + ";"),
+            )
+            .await?;
+        rows.iter()
+            .map(|row| {
+                let identifier = table_identifier(
+                    &row.try_get_string(TABLE_NAMESPACE_COLUMN)?,
+                    &row.try_get_string(TABLE_NAME_COLUMN)?,
+                )?;
+                Ok((
+                    identifier_key(&identifier),
+                    (
+                        identifier,
+                        Entry {
+                            metadata_location: row.try_get_string(METADATA_LOCATION_COLUMN)?,
+                            table_location: row.try_get_opt_string(TABLE_LOCATION_COLUMN)?,
+                            table_uuid: row.try_get_opt_string(TABLE_UUID_COLUMN)?,
+                            version: row.try_get_i64(VERSION_COLUMN)?,
+                            archived: row.try_get_bool(ARCHIVED_AT_COLUMN)?,
+                            protected: row.try_get_bool(PROTECTED_COLUMN)?,
+                        },
+                    ),
+                ))
+            })
+            .collect()
+    }
+
+    /// Point the table back to the metadata file of the backup if it wasn't changed since it was
+    /// read. The version keeps increasing, so that concurrent writers notice the change. The
+    /// checksum and the snapshot columns are cleared, they are set again by the next commit.
+    async fn revert_pointer(
+        &self,
+        identifier: &TableIdentifier,
+        current_location: &str,
+        entry: &Entry,
+    ) -> Result<()> {
+        let n_rows = self
+            .execute(
+                &self.primary,
+                &("UPDATE ".to_string()
+                    + &self.catalog_table.qualified
+                    + " SET "
+                    + METADATA_LOCATION_COLUMN
+                    + " = "
+                    + &literal(&entry.metadata_location)
+                    + ", "
+                    + PREVIOUS_METADATA_LOCATION_COLUMN
+                    + " = "
+                    + METADATA_LOCATION_COLUMN
+                    + ", "
+                    + VERSION_COLUMN
+                    + " = "
+                    + VERSION_COLUMN
+                    + " + 1, "
+                    + METADATA_CHECKSUM_COLUMN
+                    + " = NULL, "
+                    + SNAPSHOT_SUMMARY_COLUMN
+                    + " = NULL, "
+                    + CURRENT_SNAPSHOT_ID_COLUMN
+                    + " = NULL, "
+                    + LAST_SEQUENCE_NUMBER_COLUMN
+                    + " = NULL WHERE "
+                    + CATALOG_NAME_COLUMN
+                    + " = "
+                    + &literal(&self.name)
+                    + " AND "
+                    + TABLE_NAMESPACE_COLUMN
+                    + " = "
+                    + &literal(&namespace_key(identifier.namespace()))
+                    + " AND "
+                    + TABLE_NAME_COLUMN
+                    + " = "
+                    + &literal(identifier.name())
+                    + " AND "
+                    + METADATA_LOCATION_COLUMN
+                    + " = "
+                    + &literal(current_location)
+                    + ";"),
+            )
+            .await?;
+        if n_rows == 1 {
+            Ok(())
+        } else {
+            Err(anyhow!(
+                "Restoring the table {} failed. The table was changed concurrently.",
+                identifier
+            ))
+        }
+    }
+
+    /// Reason why a commit policy rejects pointing the table back to the metadata file of the
+    /// backup, `None` if the policies accept it
+    async fn policy_rejection(
+        &self,
+        identifier: &TableIdentifier,
+        entry: &Entry,
+    ) -> Result<Option<String>> {
+        let metadata = self
+            .read_metadata_json(identifier, &entry.metadata_location)
+            .await?;
+        match self.check_commit_policies(identifier, &metadata).await {
+            Ok(()) => Ok(None),
+            Err(err) => match err.downcast_ref::<CatalogError>() {
+                Some(CatalogError::CommitRejected { reason, .. }) => Ok(Some(reason.clone())),
+                _ => Err(err),
+            },
+        }
+    }
+
+    /// Register a table of the backup again.
+    async fn insert_entry(&self, identifier: &TableIdentifier, entry: &Entry) -> Result<()> {
+        let optional = |value: &Option<String>| {
+            value
+                .as_deref()
+                .map(literal)
+                .unwrap_or_else(|| "NULL".to_string())
+        };
+        self.execute(
+            &self.primary,
+            &("INSERT INTO ".to_string()
+                + &self.catalog_table.qualified
+                + " ("
+                + CATALOG_NAME_COLUMN
+                + ", "
+                + TABLE_NAMESPACE_COLUMN
+                + ", "
+                + TABLE_NAME_COLUMN
+                + ", "
+                + METADATA_LOCATION_COLUMN
+                + ", "
+                + TABLE_LOCATION_COLUMN
+                + ", "
+                + TABLE_UUID_COLUMN
+                + ", "
+                + VERSION_COLUMN
+                + ") VALUES ("
+                + &literal(&self.name)
+                + ", "
+                + &literal(&namespace_key(identifier.namespace()))
+                + ", "
+                + &literal(identifier.name())
+                + ", "
+                + &literal(&entry.metadata_location)
+                + ", "
+                + &optional(&entry.table_location)
+                + ", "
+                + &optional(&entry.table_uuid)
+                + ", "
+                + &entry.version.to_string()
+                + ");"),
+        )
+        .await?;
+        Ok(())
+    }
+}
+
+/// Identifier and entry of a table in a backup
+fn backup_entry(table: &Value) -> Result<(TableIdentifier, Entry)> {
+    let invalid = || anyhow!("The backup contains an invalid table entry.");
+    let mut names = table["namespace"]
+        .as_array()
+        .ok_or_else(invalid)?
+        .iter()
+        .map(|level| level.as_str().map(ToString::to_string).ok_or_else(invalid))
+        .collect::<Result<Vec<_>>>()?;
+    names.push(table["name"].as_str().ok_or_else(invalid)?.to_string());
+    Ok((
+        TableIdentifier::try_new(&names)?,
+        Entry {
+            metadata_location: table["metadata-location"]
+                .as_str()
+                .ok_or_else(invalid)?
+                .to_string(),
+            table_location: table["table-location"].as_str().map(ToString::to_string),
+            table_uuid: table["table-uuid"].as_str().map(ToString::to_string),
+            version: table["version"].as_i64().unwrap_or(0),
+            archived: false,
+            protected: false,
+        },
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::backup_entry;
+
+    #[test]
+    fn test_backup_entry() {
+        let (identifier, entry) = backup_entry(&json!({
+            "namespace": ["a", "b"],
+            "name": "table1",
+            "metadata-location": "warehouse/a/b/table1/metadata/1.metadata.json",
+            "table-location": "warehouse/a/b/table1",
+            "table-uuid": null,
+            "version": 3,
+        }))
+        .unwrap();
+        assert_eq!(format!("{}", identifier), "a.b.table1");
+        assert_eq!(
+            entry.metadata_location,
+            "warehouse/a/b/table1/metadata/1.metadata.json"
+        );
+        assert_eq!(entry.table_uuid, None);
+        assert_eq!(entry.version, 3);
+        assert!(backup_entry(&json!({"name": "table1"})).is_err());
+    }
+}
@@ -0,0 +1,335 @@
+/*!
+Operations on many tables at once.
+
+Planning a query over many tables checks and loads every table. [PostgresCatalog::tables_exist]
+and [PostgresCatalog::load_tables] look up all catalog entries with a single query and read the
+metadata files concurrently, at most as many at a time as configured with
+[with_load_parallelism](super::builder::PostgresCatalogBuilder::with_load_parallelism).
+[PostgresCatalog::list_tables_with_metadata] lists a namespace together with a [TableSummary] of
+every table, for catalog browsers that show more than the names.
+*/
+
+use std::{collections::HashMap, sync::Arc};
+
+use anyhow::{anyhow, Result};
+use futures::{stream, StreamExt, TryStreamExt};
+use iceberg_rs::{
+    catalog::{namespace::Namespace, table_identifier::TableIdentifier},
+    table::Table,
+};
+use serde_json::Value;
+
+use super::{
+    access::Action,
+    namespace::{namespace_key, table_identifier},
+    query::literal,
+    PostgresCatalog, ARCHIVED_AT_COLUMN, CATALOG_NAME_COLUMN, METADATA_CHECKSUM_COLUMN,
+    METADATA_LOCATION_COLUMN, TABLE_NAMESPACE_COLUMN, TABLE_NAME_COLUMN,
+};
+
+/// Number of metadata files that are read concurrently by default
+pub(crate) static DEFAULT_LOAD_PARALLELISM: usize = 8;
+
+/// Catalog entry of a table: metadata location and checksum
+pub(crate) type Entry = (String, Option<String>);
+
+/// Summary of a table from its current metadata
+#[derive(Debug, Clone)]
+pub struct TableSummary {
+    /// Identifier of the table
+    pub identifier: TableIdentifier,
+    /// Location of the current metadata file
+    pub metadata_location: String,
+    /// Location of the table data
+    pub location: String,
+    /// Format version of the metadata
+    pub format_version: i64,
+    /// Id of the current snapshot, if the table has one
+    pub current_snapshot_id: Option<i64>,
+    /// Time of the last metadata update in milliseconds since the epoch
+    pub last_updated_ms: i64,
+    /// Current schema of the table in the JSON representation of the Iceberg spec
+    pub schema: Value,
+    /// Properties of the table
+    pub properties: HashMap<String, String>,
+}
+
+impl TableSummary {
+    fn from_metadata(
+        identifier: TableIdentifier,
+        metadata_location: String,
+        metadata: &Value,
+    ) -> Result<Self> {
+        // Format version 1 has a single schema, version 2 a list with the id of the current one.
+        let schema = match metadata["schemas"].as_array() {
+            Some(schemas) => schemas
+                .iter()
+                .find(|schema| schema["schema-id"] == metadata["current-schema-id"])
+                .cloned(),
+            None => Some(metadata["schema"].clone()).filter(|schema| !schema.is_null()),
+        }
+        .ok_or_else(|| anyhow!("The metadata of {} has no current schema.", identifier))?;
+        Ok(TableSummary {
+            location: metadata["location"]
+                .as_str()
+                .ok_or_else(|| anyhow!("The metadata of {} has no location.", identifier))?
+                .to_string(),
+            identifier,
+            metadata_location,
+            format_version: metadata["format-version"].as_i64().unwrap_or(1),
+            current_snapshot_id: metadata["current-snapshot-id"]
+                .as_i64()
+                .filter(|id| *id != -1),
+            last_updated_ms: metadata["last-updated-ms"].as_i64().unwrap_or_default(),
+            schema,
+            properties: metadata["properties"]
+                .as_object()
+                .map(|properties| {
+                    properties
+                        .iter()
+                        .filter_map(|(key, value)| {
+                            value.as_str().map(|value| (key.clone(), value.to_string()))
+                        })
+                        .collect()
+                })
+                .unwrap_or_default(),
+        })
+    }
+}
+
+impl PostgresCatalog {
+    /// List the tables in the namespace with a summary of their current metadata, without the
+    /// [archived](super::archive) tables. The metadata files are read concurrently.
+    pub async fn list_tables_with_metadata(
+        &self,
+        namespace: &Namespace,
+    ) -> Result<Vec<TableSummary>> {
+        let namespace = &self.case_sensitivity.normalize_namespace(namespace)?;
+        self.authorize(Action::Read, namespace, None).await?;
+        let rows = self
+            .query(
+                self.read_connection(),
+                &("SELECT ".to_string()
+                    + TABLE_NAME_COLUMN
+                    + ", "
+                    + METADATA_LOCATION_COLUMN
+                    + " FROM "
+                    + &self.catalog_table.qualified
+                    + " WHERE "
+                    + CATALOG_NAME_COLUMN
+                    + " = "
+                    + &literal(&self.name)
+                    + " AND "
+                    + TABLE_NAMESPACE_COLUMN
+                    + " = "
+                    + &literal(&namespace_key(namespace))
+                    + " AND "
+                    + ARCHIVED_AT_COLUMN
+                    + " IS NULL ORDER BY "
+                    + TABLE_NAME_COLUMN
+                    + ";"),
+            )
+            .await?;
+        let tables = rows
+            .iter()
+            .map(|row| {
+                Ok((
+                    table_identifier(
+                        &namespace_key(namespace),
+                        &row.try_get_string(TABLE_NAME_COLUMN)?,
+                    )?,
+                    self.resolved_location(&row.try_get_string(METADATA_LOCATION_COLUMN)?),
+                ))
+            })
+            .collect::<Result<Vec<_>>>()?;
+        stream::iter(tables)
+            .map(|(identifier, metadata_location)| async move {
+                let metadata = self
+                    .read_metadata_json(&identifier, &metadata_location)
+                    .await?;
+                TableSummary::from_metadata(identifier, metadata_location, &metadata)
+            })
+            .buffered(self.load_parallelism)
+            .try_collect()
+            .await
+    }
+
+    /// Whether the tables exist, in the order of the identifiers.
+    pub async fn tables_exist(&self, identifiers: &[TableIdentifier]) -> Result<Vec<bool>> {
+        let identifiers = self.normalize_all(identifiers).await?;
+        let entries = self.entries(&identifiers).await?;
+        Ok(identifiers
+            .iter()
+            .map(|identifier| entries.contains_key(&key(identifier)))
+            .collect())
+    }
+
+    /// Load the tables, in the order of the identifiers. Fails if one of the tables doesn't
+    /// exist.
+    pub async fn load_tables(
+        self: Arc<Self>,
+        identifiers: Vec<TableIdentifier>,
+    ) -> Result<Vec<Table>> {
+        let identifiers = self.normalize_all(&identifiers).await?;
+        let mut entries = self.entries(&identifiers).await?;
+        let loads = identifiers
+            .into_iter()
+            .map(|identifier| {
+                let entry = entries.remove(&key(&identifier)).ok_or_else(|| {
+                    anyhow!(
+                        "Loading the table {} failed. No table matched the identifier.",
+                        identifier
+                    )
+                })?;
+                Ok((identifier, entry))
+            })
+            .collect::<Result<Vec<_>>>()?;
+        stream::iter(loads)
+            .map(|(identifier, (location, checksum))| {
+                let catalog = Arc::clone(&self);
+                async move {
+                    catalog
+                        .table_from_entry(identifier, &location, checksum)
+                        .await
+                }
+            })
+            .buffered(self.load_parallelism)
+            .try_collect()
+            .await
+    }
+
+    /// Normalize the identifiers and check that the tables may be read.
+    async fn normalize_all(&self, identifiers: &[TableIdentifier]) -> Result<Vec<TableIdentifier>> {
+        let mut normalized = Vec::with_capacity(identifiers.len());
+        for identifier in identifiers {
+            let identifier = self.case_sensitivity.normalize(identifier)?;
+            self.authorize(
+                Action::Read,
+                identifier.namespace(),
+                Some(identifier.name()),
+            )
+            .await?;
+            normalized.push(identifier);
+        }
+        Ok(normalized)
+    }
+
+    /// Catalog entries of the normalized identifiers that exist, by stored namespace and name
+    pub(crate) async fn entries(
+        &self,
+        identifiers: &[TableIdentifier],
+    ) -> Result<HashMap<(String, String), Entry>> {
+        if identifiers.is_empty() {
+            return Ok(HashMap::new());
+        }
+        let tables = identifiers
+            .iter()
+            .map(|identifier| {
+                let (namespace, name) = key(identifier);
+                "(".to_string() + &literal(&namespace) + ", " + &literal(&name) + ")"
+            })
+            .collect::<Vec<_>>()
+            .join(", ");
+        let rows = self
+            .query(
+                self.read_connection(),
+                &("SELECT ".to_string()
+                    + TABLE_NAMESPACE_COLUMN
+                    + ", "
+                    + TABLE_NAME_COLUMN
+                    + ", "
+                    + METADATA_LOCATION_COLUMN
+                    + ", "
+                    + METADATA_CHECKSUM_COLUMN
+                    + " FROM "
+                    + &self.catalog_table.qualified
+                    + " WHERE "
+                    + CATALOG_NAME_COLUMN
+                    + " = "
+                    + &literal(&self.name)
+                    + " AND ("
+                    + TABLE_NAMESPACE_COLUMN
+                    + ", "
+                    + TABLE_NAME_COLUMN
+                    + ") IN ("
+                    + &tables
+                    + ");"),
+            )
+            .await?;
+        rows.iter()
+            .map(|row| {
+                Ok((
+                    (
+                        row.try_get_string(TABLE_NAMESPACE_COLUMN)?,
+                        row.try_get_string(TABLE_NAME_COLUMN)?,
+                    ),
+                    (
+                        row.try_get_string(METADATA_LOCATION_COLUMN)?,
+                        row.try_get_opt_string(METADATA_CHECKSUM_COLUMN)?,
+                    ),
+                ))
+            })
+            .collect()
+    }
+}
+
+/// Stored namespace and name of the table
+fn key(identifier: &TableIdentifier) -> (String, String) {
+    (
+        namespace_key(identifier.namespace()),
+        identifier.name().to_string(),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use iceberg_rs::catalog::table_identifier::TableIdentifier;
+    use serde_json::json;
+
+    use super::TableSummary;
+
+    #[test]
+    fn test_summary_from_metadata() {
+        let identifier = TableIdentifier::parse("ns.table1").unwrap();
+        let summary = TableSummary::from_metadata(
+            identifier.clone(),
+            "metadata.json".to_string(),
+            &json!({
+                "format-version": 2,
+                "location": "s3://bucket/ns/table1",
+                "last-updated-ms": 1000,
+                "current-snapshot-id": -1,
+                "current-schema-id": 1,
+                "schemas": [
+                    {"type": "struct", "schema-id": 0, "fields": []},
+                    {"type": "struct", "schema-id": 1, "fields": [{"id": 1, "name": "one", "required": false, "type": "string"}]}
+                ],
+                "properties": {"owner": "analytics"}
+            }),
+        )
+        .unwrap();
+        assert_eq!(summary.schema["fields"][0]["name"], "one");
+        assert_eq!(summary.current_snapshot_id, None);
+        assert_eq!(summary.properties["owner"], "analytics");
+
+        let summary = TableSummary::from_metadata(
+            identifier.clone(),
+            "metadata.json".to_string(),
+            &json!({
+                "format-version": 1,
+                "location": "s3://bucket/ns/table1",
+                "current-snapshot-id": 3,
+                "schema": {"type": "struct", "fields": []}
+            }),
+        )
+        .unwrap();
+        assert_eq!(summary.format_version, 1);
+        assert_eq!(summary.current_snapshot_id, Some(3));
+        assert!(TableSummary::from_metadata(
+            identifier,
+            "metadata.json".to_string(),
+            &json!({"format-version": 1, "location": "s3://bucket/ns/table1"}),
+        )
+        .is_err());
+    }
+}
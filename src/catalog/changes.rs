@@ -0,0 +1,402 @@
+/*!
+Change data capture of the catalog table with an outbox table.
+
+With [with_change_capture](super::builder::PostgresCatalogBuilder::with_change_capture), `initialize`
+installs triggers on the catalog table that record every created, committed, renamed and dropped
+table in a change table. Consumers read the changes in order with
+[PostgresCatalog::poll_changes] or [PostgresCatalog::change_stream] and remember the
+[position](ChangePosition) of the last processed change. Unlike logical decoding, this requires no
+replication permissions.
+
+Change ids are assigned when a change is written, but the change only becomes visible when its
+transaction commits, so a change with a lower id can become visible after one with a higher id.
+The changes are therefore read in order of the ids of their transactions and only once their
+transaction is older than all running transactions. Every change that becomes visible later
+belongs to a newer transaction and comes after the position of the last returned change, so no
+change is skipped. This requires Postgres 13 or later. The triggers
+record the changes of all catalogs that share the catalog table, each catalog only reads its own
+changes. Processed changes can be removed with [PostgresCatalog::delete_changes].
+
+The trigger function runs with the rights of its owner and the `search_path` of its creation, so
+roles that may only write the catalog table, like [tenants](super::tenant), record their changes
+as well. Row-level security is enabled on the change table, other roles only read the changes that
+a tenant policy allows them to.
+*/
+
+use std::{
+    collections::VecDeque,
+    sync::Arc,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use anyhow::{anyhow, Result};
+use futures::Stream;
+use iceberg_rs::catalog::table_identifier::TableIdentifier;
+
+use super::{
+    namespace::table_identifier,
+    query::{epoch_millis, literal, CatalogRow},
+    PostgresCatalog, CATALOG_NAME_COLUMN, METADATA_LOCATION_COLUMN,
+    PREVIOUS_METADATA_LOCATION_COLUMN, TABLE_NAMESPACE_COLUMN, TABLE_NAME_COLUMN,
+};
+
+static CHANGE_ID_COLUMN: &str = "change_id";
+static OPERATION_COLUMN: &str = "operation";
+static TRANSACTION_ID_COLUMN: &str = "transaction_id";
+static CHANGED_AT_COLUMN: &str = "changed_at";
+
+/// Number of changes the change stream reads at once
+static CHANGE_BATCH_SIZE: usize = 1000;
+
+/// Kind of a change
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ChangeOperation {
+    /// The table was created or registered, or renamed to the identifier
+    Created,
+    /// New metadata was committed to the table
+    Updated,
+    /// The table was dropped, or renamed from the identifier
+    Dropped,
+}
+
+/// Position in the changes of a catalog, the default is the start
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct ChangePosition {
+    /// Id of the transaction that recorded the change
+    pub transaction_id: i64,
+    /// Id of the change, increasing within the transaction
+    pub change_id: i64,
+}
+
+/// Recorded change of a table
+#[derive(Debug, Clone)]
+pub struct CatalogChange {
+    /// Id of the change
+    pub change_id: i64,
+    /// Id of the transaction that recorded the change
+    pub transaction_id: i64,
+    /// Kind of the change
+    pub operation: ChangeOperation,
+    /// Identifier of the table
+    pub identifier: TableIdentifier,
+    /// Metadata location after the change, `None` for dropped tables
+    pub metadata_location: Option<String>,
+    /// Metadata location before the change, `None` for created tables
+    pub previous_metadata_location: Option<String>,
+    /// Time of the change
+    pub changed_at: SystemTime,
+}
+
+impl CatalogChange {
+    /// Position of the change, to continue reading after it
+    pub fn position(&self) -> ChangePosition {
+        ChangePosition {
+            transaction_id: self.transaction_id,
+            change_id: self.change_id,
+        }
+    }
+}
+
+impl PostgresCatalog {
+    /// Changes of the catalog after the position `since`, in order of their positions. Returns at
+    /// most `limit` changes. Start with the default position to read all recorded changes.
+    pub async fn poll_changes(
+        &self,
+        since: ChangePosition,
+        limit: usize,
+    ) -> Result<Vec<CatalogChange>> {
+        // Changes are only written on the primary, replicas could miss the latest changes.
+        let rows = self
+            .query(
+                &self.primary,
+                &("SELECT ".to_string()
+                    + CHANGE_ID_COLUMN
+                    + ", "
+                    + TRANSACTION_ID_COLUMN
+                    + "::TEXT::BIGINT AS "
+                    + TRANSACTION_ID_COLUMN
+                    + ", "
+                    + OPERATION_COLUMN
+                    + ", "
+                    + TABLE_NAMESPACE_COLUMN
+                    + ", "
+                    + TABLE_NAME_COLUMN
+                    + ", "
+                    + METADATA_LOCATION_COLUMN
+                    + ", "
+                    + PREVIOUS_METADATA_LOCATION_COLUMN
+                    + ", "
+                    + &epoch_millis(CHANGED_AT_COLUMN)
+                    + " FROM "
+                    + &self.catalog_table.changes
+                    + " WHERE "
+                    + CATALOG_NAME_COLUMN
+                    + " = "
+                    + &literal(&self.name)
+                    + " AND "
+                    + &position_column()
+                    + " > "
+                    + &position_literal(since)
+                    + " AND "
+                    + TRANSACTION_ID_COLUMN
+                    + " < pg_snapshot_xmin(pg_current_snapshot()) ORDER BY "
+                    + TRANSACTION_ID_COLUMN
+                    + ", "
+                    + CHANGE_ID_COLUMN
+                    + " LIMIT "
+                    + &limit.to_string()
+                    + ";"),
+            )
+            .await?;
+        rows.iter().map(catalog_change).collect()
+    }
+
+    /// Endless stream of the changes after the position `since`. The catalog table is polled
+    /// every `poll_interval` while there are no new changes. Failed polls are returned as errors
+    /// and retried after the poll interval.
+    pub fn change_stream(
+        self: Arc<Self>,
+        since: ChangePosition,
+        poll_interval: Duration,
+    ) -> impl Stream<Item = Result<CatalogChange>> {
+        futures::stream::unfold(
+            (self, since, VecDeque::new()),
+            move |(catalog, mut since, mut buffer)| async move {
+                loop {
+                    if let Some(change) = buffer.pop_front() {
+                        return Some((Ok(change), (catalog, since, buffer)));
+                    }
+                    match catalog.poll_changes(since, CHANGE_BATCH_SIZE).await {
+                        Ok(changes) => match changes.last() {
+                            Some(last) => {
+                                since = last.position();
+                                buffer.extend(changes);
+                            }
+                            None => tokio::time::sleep(poll_interval).await,
+                        },
+                        Err(err) => {
+                            tokio::time::sleep(poll_interval).await;
+                            return Some((Err(err), (catalog, since, buffer)));
+                        }
+                    }
+                }
+            },
+        )
+    }
+
+    /// Delete the changes of the catalog up to and including the position `up_to`. Returns the
+    /// number of deleted changes.
+    pub async fn delete_changes(&self, up_to: ChangePosition) -> Result<u64> {
+        self.check_writable("Deleting changes")?;
+        self.execute(
+            &self.primary,
+            &("DELETE FROM ".to_string()
+                + &self.catalog_table.changes
+                + " WHERE "
+                + CATALOG_NAME_COLUMN
+                + " = "
+                + &literal(&self.name)
+                + " AND "
+                + &position_column()
+                + " <= "
+                + &position_literal(up_to)
+                + ";"),
+        )
+        .await
+    }
+
+    /// Create the change table and the triggers that record changes of the catalog table.
+    pub(crate) async fn create_change_capture(&self) -> Result<()> {
+        let changes = &self.catalog_table.changes;
+        let function = self.catalog_table.function("record_change");
+        let columns = CATALOG_NAME_COLUMN.to_string()
+            + ", "
+            + TABLE_NAMESPACE_COLUMN
+            + ", "
+            + TABLE_NAME_COLUMN
+            + ", "
+            + OPERATION_COLUMN
+            + ", "
+            + METADATA_LOCATION_COLUMN
+            + ", "
+            + PREVIOUS_METADATA_LOCATION_COLUMN;
+        let record = |row: &str, operation: &str, location: &str, previous: &str| {
+            "INSERT INTO ".to_string()
+                + changes
+                + " ("
+                + &columns
+                + ") VALUES ("
+                + row
+                + "."
+                + CATALOG_NAME_COLUMN
+                + ", "
+                + row
+                + "."
+                + TABLE_NAMESPACE_COLUMN
+                + ", "
+                + row
+                + "."
+                + TABLE_NAME_COLUMN
+                + ", '"
+                + operation
+                + "', "
+                + location
+                + ", "
+                + previous
+                + ");"
+        };
+        let identity_changed = [
+            CATALOG_NAME_COLUMN,
+            TABLE_NAMESPACE_COLUMN,
+            TABLE_NAME_COLUMN,
+        ]
+        .iter()
+        .map(|column| "OLD.".to_string() + column + " IS DISTINCT FROM NEW." + column)
+        .collect::<Vec<_>>()
+        .join(" OR ");
+        let old_location = "OLD.".to_string() + METADATA_LOCATION_COLUMN;
+        let new_location = "NEW.".to_string() + METADATA_LOCATION_COLUMN;
+        self.execute(
+            &self.primary,
+            &("CREATE TABLE IF NOT EXISTS ".to_string()
+                + changes
+                + " ("
+                + CHANGE_ID_COLUMN
+                + " BIGSERIAL PRIMARY KEY,"
+                + CATALOG_NAME_COLUMN
+                + " VARCHAR(255) NOT NULL,"
+                + TABLE_NAMESPACE_COLUMN
+                + " VARCHAR(255) NOT NULL,"
+                + TABLE_NAME_COLUMN
+                + " VARCHAR(255) NOT NULL,"
+                + OPERATION_COLUMN
+                + " VARCHAR(16) NOT NULL,"
+                + METADATA_LOCATION_COLUMN
+                + " TEXT,"
+                + PREVIOUS_METADATA_LOCATION_COLUMN
+                + " TEXT,"
+                + TRANSACTION_ID_COLUMN
+                + " XID8 NOT NULL DEFAULT pg_current_xact_id(),"
+                + CHANGED_AT_COLUMN
+                + " TIMESTAMPTZ NOT NULL DEFAULT now()"
+                + ");"),
+        )
+        .await?;
+        self.execute(
+            &self.primary,
+            &("CREATE INDEX IF NOT EXISTS ".to_string()
+                + &self.catalog_table.index("change_position_idx")
+                + " ON "
+                + changes
+                + " ("
+                + CATALOG_NAME_COLUMN
+                + ", "
+                + TRANSACTION_ID_COLUMN
+                + ", "
+                + CHANGE_ID_COLUMN
+                + ");"),
+        )
+        .await?;
+        // Without policies only the owner reads the changes, tenants get theirs through the
+        // policies of `initialize_multi_tenant`.
+        self.execute(
+            &self.primary,
+            &("ALTER TABLE ".to_string() + changes + " ENABLE ROW LEVEL SECURITY;"),
+        )
+        .await?;
+        // Renames are recorded as drop of the old and creation of the new identifier.
+        self.execute(
+            &self.primary,
+            &("CREATE OR REPLACE FUNCTION ".to_string()
+                + &function
+                + "() RETURNS trigger LANGUAGE plpgsql SECURITY DEFINER SET search_path FROM CURRENT \
+                AS $$ BEGIN IF TG_OP = 'DELETE' THEN "
+                + &record("OLD", "DELETE", "NULL", &old_location)
+                + " RETURN OLD; ELSIF TG_OP = 'INSERT' THEN "
+                + &record("NEW", "INSERT", &new_location, "NULL")
+                + " ELSIF "
+                + &identity_changed
+                + " THEN "
+                + &record("OLD", "DELETE", "NULL", &old_location)
+                + " "
+                + &record("NEW", "INSERT", &new_location, "NULL")
+                + " ELSE "
+                + &record("NEW", "UPDATE", &new_location, &old_location)
+                + " END IF; RETURN NEW; END; $$;"),
+        )
+        .await?;
+        let trigger = self.catalog_table.index("change_trigger");
+        let update_trigger = self.catalog_table.index("change_update_trigger");
+        for statement in [
+            "DROP TRIGGER IF EXISTS ".to_string()
+                + &trigger
+                + " ON "
+                + &self.catalog_table.qualified
+                + ";",
+            "CREATE TRIGGER ".to_string()
+                + &trigger
+                + " AFTER INSERT OR DELETE ON "
+                + &self.catalog_table.qualified
+                + " FOR EACH ROW EXECUTE FUNCTION "
+                + &function
+                + "();",
+            "DROP TRIGGER IF EXISTS ".to_string()
+                + &update_trigger
+                + " ON "
+                + &self.catalog_table.qualified
+                + ";",
+            // Recording loads updates the catalog table as well, those aren't changes.
+            "CREATE TRIGGER ".to_string()
+                + &update_trigger
+                + " AFTER UPDATE ON "
+                + &self.catalog_table.qualified
+                + " FOR EACH ROW WHEN ("
+                + &old_location
+                + " IS DISTINCT FROM "
+                + &new_location
+                + " OR "
+                + &identity_changed
+                + ") EXECUTE FUNCTION "
+                + &function
+                + "();",
+        ] {
+            self.execute(&self.primary, &statement).await?;
+        }
+        Ok(())
+    }
+}
+
+/// Position of the changes in a row comparison
+fn position_column() -> String {
+    "(".to_string() + TRANSACTION_ID_COLUMN + ", " + CHANGE_ID_COLUMN + ")"
+}
+
+fn position_literal(position: ChangePosition) -> String {
+    "('".to_string()
+        + &position.transaction_id.max(0).to_string()
+        + "'::XID8, "
+        + &position.change_id.to_string()
+        + ")"
+}
+
+fn catalog_change(row: &CatalogRow) -> Result<CatalogChange> {
+    let operation = match row.try_get_string(OPERATION_COLUMN)?.as_str() {
+        "INSERT" => ChangeOperation::Created,
+        "UPDATE" => ChangeOperation::Updated,
+        "DELETE" => ChangeOperation::Dropped,
+        operation => return Err(anyhow!("The change operation {} is unknown.", operation)),
+    };
+    Ok(CatalogChange {
+        change_id: row.try_get_i64(CHANGE_ID_COLUMN)?,
+        transaction_id: row.try_get_i64(TRANSACTION_ID_COLUMN)?,
+        operation,
+        identifier: table_identifier(
+            &row.try_get_string(TABLE_NAMESPACE_COLUMN)?,
+            &row.try_get_string(TABLE_NAME_COLUMN)?,
+        )?,
+        metadata_location: row.try_get_opt_string(METADATA_LOCATION_COLUMN)?,
+        previous_metadata_location: row.try_get_opt_string(PREVIOUS_METADATA_LOCATION_COLUMN)?,
+        changed_at: UNIX_EPOCH
+            + Duration::from_millis(row.try_get_i64(CHANGED_AT_COLUMN)?.max(0) as u64),
+    })
+}
@@ -0,0 +1,33 @@
+/*!
+Checksums of metadata files.
+
+The catalog stores the SHA-256 of the metadata file with every pointer and verifies it when the
+table is loaded. A mismatch means that the file was changed after the commit, by corruption in the
+object store or by a write that bypassed the catalog, and fails with
+[CatalogError::IntegrityMismatch](super::error::CatalogError::IntegrityMismatch). The checksum is
+computed over the decompressed content, so it doesn't depend on the compression of the file.
+Entries without checksum, written by earlier versions, are not verified.
+*/
+
+use sha2::{Digest, Sha256};
+
+/// Hex-encoded SHA-256 of the content of a metadata file
+pub(crate) fn checksum(bytes: &[u8]) -> String {
+    Sha256::digest(bytes)
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::checksum;
+
+    #[test]
+    fn test_checksum() {
+        assert_eq!(
+            checksum(b"abc"),
+            "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad"
+        );
+    }
+}
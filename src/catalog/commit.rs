@@ -0,0 +1,143 @@
+/*!
+Changes to table metadata that the catalog makes itself.
+
+The catalog writes the changed metadata as the next metadata file of the table, in the metadata
+directory of the table, and swaps the pointer like any other commit. The commit fails if the table
+was changed concurrently.
+*/
+
+use std::{
+    sync::Arc,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use anyhow::{anyhow, Result};
+use iceberg_rs::{
+    catalog::table_identifier::TableIdentifier, object_store::path::Path, table::Table,
+};
+use serde_json::{json, Value};
+
+use super::{
+    storage::{location::fnv1a, resolver::object_path},
+    Expected, PostgresCatalog,
+};
+
+/// Table property with the directory of new metadata files
+pub(crate) static WRITE_METADATA_PATH: &str = "write.metadata.path";
+
+impl PostgresCatalog {
+    /// Write `metadata` as the next metadata file of the table and point the table to it if it
+    /// still points to `previous_location`. The previous file is added to the metadata log.
+    pub(crate) async fn commit_metadata(
+        self: Arc<Self>,
+        identifier: TableIdentifier,
+        previous_location: &str,
+        mut metadata: Value,
+    ) -> Result<Table> {
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_millis() as i64;
+        let previous_updated = metadata["last-updated-ms"].as_i64().unwrap_or(now);
+        let object = metadata
+            .as_object_mut()
+            .ok_or_else(|| anyhow!("The table metadata is not an object."))?;
+        object.insert("last-updated-ms".to_string(), json!(now));
+        let log = object
+            .entry("metadata-log")
+            .or_insert_with(|| json!([]))
+            .as_array_mut()
+            .ok_or_else(|| anyhow!("The metadata log of the table is not an array."))?;
+        log.push(json!({
+            "timestamp-ms": previous_updated,
+            "metadata-file": previous_location,
+        }));
+        let location = next_metadata_location(&metadata, previous_location, now)?;
+        let bytes = serde_json::to_vec(&metadata).map_err(|err| anyhow!(err.to_string()))?;
+        let object_store = self.object_store_for_location(&identifier, &location)?;
+        let path: Path = object_path(&location).into();
+        object_store
+            .put(&path, bytes.into())
+            .await
+            .map_err(|err| anyhow!(err.to_string()))?;
+        self.update_pointer(
+            identifier,
+            &location,
+            Expected::MetadataLocation(previous_location),
+        )
+        .await
+    }
+}
+
+/// Location of the metadata file that follows `previous_location`, named
+/// `<version>-<suffix>.metadata.json` like the files written by the Iceberg Java implementation.
+fn next_metadata_location(metadata: &Value, previous_location: &str, now: i64) -> Result<String> {
+    let directory = match metadata["properties"][WRITE_METADATA_PATH].as_str() {
+        Some(path) => path.trim_end_matches('/').to_string(),
+        None => {
+            metadata["location"]
+                .as_str()
+                .ok_or_else(|| anyhow!("The table metadata contains no location."))?
+                .trim_end_matches('/')
+                .to_string()
+                + "/metadata"
+        }
+    };
+    let version = metadata_version(previous_location).map_or(0, |version| version + 1);
+    let suffix = fnv1a((previous_location.to_string() + &now.to_string()).as_bytes());
+    Ok(format!(
+        "{}/{:05}-{:08x}.metadata.json",
+        directory, version, suffix
+    ))
+}
+
+/// Version of a metadata file named `00003-<suffix>.metadata.json` or `v3.metadata.json`
+fn metadata_version(location: &str) -> Option<u64> {
+    let file_name = location.rsplit('/').next()?;
+    let file_name = file_name.strip_prefix('v').unwrap_or(file_name);
+    let digits = file_name
+        .chars()
+        .take_while(|c| c.is_ascii_digit())
+        .collect::<String>();
+    digits.parse().ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::{metadata_version, next_metadata_location};
+
+    #[test]
+    fn test_metadata_version() {
+        assert_eq!(
+            metadata_version("s3://bucket/table/metadata/00003-abc.metadata.json"),
+            Some(3)
+        );
+        assert_eq!(
+            metadata_version("table/metadata/v12.metadata.json"),
+            Some(12)
+        );
+        assert_eq!(metadata_version("table/metadata/metadata.json"), None);
+    }
+
+    #[test]
+    fn test_next_metadata_location() {
+        let metadata = json!({"location": "s3://bucket/table/"});
+        let location = next_metadata_location(
+            &metadata,
+            "s3://bucket/table/metadata/00001-abc.metadata.json",
+            1,
+        )
+        .unwrap();
+        assert!(location.starts_with("s3://bucket/table/metadata/00002-"));
+        assert!(location.ends_with(".metadata.json"));
+
+        let metadata = json!({
+            "location": "s3://bucket/table",
+            "properties": {"write.metadata.path": "s3://bucket/moved/metadata"}
+        });
+        let location = next_metadata_location(&metadata, "v1.metadata.json", 1).unwrap();
+        assert!(location.starts_with("s3://bucket/moved/metadata/00002-"));
+    }
+}
@@ -0,0 +1,344 @@
+/*!
+Validation of commits before the metadata pointer is swapped.
+
+A catalog with [CommitPolicy]s passes the current and the new metadata of every commit to each
+policy before the pointer is swapped. A rejected commit fails with
+[CatalogError::CommitRejected](super::error::CatalogError::CommitRejected) and leaves the table
+unchanged. The current metadata is read before the swap, if the table changes in between, the swap
+fails as a concurrent modification and the retried commit is validated again.
+
+The metadata is passed as JSON, so that policies can inspect fields independent of the format
+version.
+*/
+
+use anyhow::Result;
+use iceberg_rs::catalog::table_identifier::TableIdentifier;
+use serde_json::Value;
+
+use super::{
+    error::CatalogError, namespace::namespace_key, query::literal, PostgresCatalog,
+    CATALOG_NAME_COLUMN, METADATA_LOCATION_COLUMN, TABLE_NAMESPACE_COLUMN, TABLE_NAME_COLUMN,
+};
+
+/// Result of a commit validation
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CommitDecision {
+    /// The commit may proceed
+    Accept,
+    /// The commit is rejected for the reason
+    Reject(String),
+}
+
+/// Decides whether new metadata may be committed to a table
+#[async_trait::async_trait]
+pub trait CommitPolicy: Send + Sync {
+    /// Check the commit of `new` metadata to the table whose metadata is `current`.
+    async fn check(
+        &self,
+        identifier: &TableIdentifier,
+        current: &Value,
+        new: &Value,
+    ) -> Result<CommitDecision>;
+}
+
+/// Rejects commits that remove one of the snapshot references, like `main` or a tag that has to
+/// be kept for audits.
+#[derive(Debug, Clone)]
+pub struct RequiredRefsPolicy {
+    refs: Vec<String>,
+}
+
+impl RequiredRefsPolicy {
+    /// Protect the references with the names
+    pub fn new(refs: &[&str]) -> Self {
+        RequiredRefsPolicy {
+            refs: refs.iter().map(ToString::to_string).collect(),
+        }
+    }
+}
+
+#[async_trait::async_trait]
+impl CommitPolicy for RequiredRefsPolicy {
+    async fn check(
+        &self,
+        _identifier: &TableIdentifier,
+        current: &Value,
+        new: &Value,
+    ) -> Result<CommitDecision> {
+        for name in &self.refs {
+            if !current["refs"][name].is_null() && new["refs"][name].is_null() {
+                return Ok(CommitDecision::Reject(format!(
+                    "The commit removes the required reference {}.",
+                    name
+                )));
+            }
+        }
+        Ok(CommitDecision::Accept)
+    }
+}
+
+/// Rejects commits that keep more than a maximum number of snapshots
+#[derive(Debug, Clone)]
+pub struct MaxSnapshotsPolicy {
+    max_snapshots: usize,
+}
+
+impl MaxSnapshotsPolicy {
+    /// Allow at most `max_snapshots` snapshots
+    pub fn new(max_snapshots: usize) -> Self {
+        MaxSnapshotsPolicy { max_snapshots }
+    }
+}
+
+#[async_trait::async_trait]
+impl CommitPolicy for MaxSnapshotsPolicy {
+    async fn check(
+        &self,
+        _identifier: &TableIdentifier,
+        _current: &Value,
+        new: &Value,
+    ) -> Result<CommitDecision> {
+        let snapshots = new["snapshots"].as_array().map(Vec::len).unwrap_or(0);
+        if snapshots > self.max_snapshots {
+            Ok(CommitDecision::Reject(format!(
+                "The table would have {} snapshots, at most {} are allowed.",
+                snapshots, self.max_snapshots
+            )))
+        } else {
+            Ok(CommitDecision::Accept)
+        }
+    }
+}
+
+/// Rejects schema changes that break readers of the current schema: dropping a field, changing
+/// its type other than by a type promotion allowed by the Iceberg spec, or making an optional
+/// field required.
+#[derive(Debug, Clone, Default)]
+pub struct SchemaCompatibilityPolicy;
+
+#[async_trait::async_trait]
+impl CommitPolicy for SchemaCompatibilityPolicy {
+    async fn check(
+        &self,
+        _identifier: &TableIdentifier,
+        current: &Value,
+        new: &Value,
+    ) -> Result<CommitDecision> {
+        let (current_schema, new_schema) = match (current_schema(current), current_schema(new)) {
+            (Some(current_schema), Some(new_schema)) => (current_schema, new_schema),
+            _ => return Ok(CommitDecision::Accept),
+        };
+        let new_fields = new_schema["fields"].as_array().cloned().unwrap_or_default();
+        for field in current_schema["fields"].as_array().into_iter().flatten() {
+            let name = field["name"].as_str().unwrap_or_default();
+            let new_field = match new_fields
+                .iter()
+                .find(|new_field| new_field["id"] == field["id"])
+            {
+                Some(new_field) => new_field,
+                None => {
+                    return Ok(CommitDecision::Reject(format!(
+                        "The commit drops the field {}.",
+                        name
+                    )))
+                }
+            };
+            if !is_promotion(&field["type"], &new_field["type"]) {
+                return Ok(CommitDecision::Reject(format!(
+                    "The commit changes the type of the field {} from {} to {}.",
+                    name, field["type"], new_field["type"]
+                )));
+            }
+            if field["required"] == Value::Bool(false) && new_field["required"] == Value::Bool(true)
+            {
+                return Ok(CommitDecision::Reject(format!(
+                    "The commit makes the optional field {} required.",
+                    name
+                )));
+            }
+        }
+        Ok(CommitDecision::Accept)
+    }
+}
+
+impl PostgresCatalog {
+    /// Run the commit policies on the commit of the `metadata` to the table. Fails with
+    /// [CatalogError::CommitRejected] if a policy rejects the commit.
+    pub(crate) async fn check_commit_policies(
+        &self,
+        identifier: &TableIdentifier,
+        metadata: &Value,
+    ) -> Result<()> {
+        if self.commit_policies.is_empty() {
+            return Ok(());
+        }
+        let rows = self
+            .query(
+                &self.primary,
+                &("SELECT ".to_string()
+                    + METADATA_LOCATION_COLUMN
+                    + " FROM "
+                    + &self.catalog_table.qualified
+                    + " WHERE "
+                    + CATALOG_NAME_COLUMN
+                    + " = "
+                    + &literal(&self.name)
+                    + " AND "
+                    + TABLE_NAMESPACE_COLUMN
+                    + " = "
+                    + &literal(&namespace_key(identifier.namespace()))
+                    + " AND "
+                    + TABLE_NAME_COLUMN
+                    + " = "
+                    + &literal(identifier.name())
+                    + ";"),
+            )
+            .await?;
+        // A missing table fails the swap that follows.
+        let location = match rows.first() {
+            Some(row) => row.try_get_string(METADATA_LOCATION_COLUMN)?,
+            None => return Ok(()),
+        };
+        let current = self.read_metadata_json(identifier, &location).await?;
+        for policy in &self.commit_policies {
+            if let CommitDecision::Reject(reason) =
+                policy.check(identifier, &current, metadata).await?
+            {
+                return Err(CatalogError::CommitRejected {
+                    table: identifier.to_string(),
+                    reason,
+                }
+                .into());
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Current schema of format version 2 or 1 metadata
+fn current_schema(metadata: &Value) -> Option<&Value> {
+    match metadata["current-schema-id"].as_i64() {
+        Some(id) => metadata["schemas"]
+            .as_array()?
+            .iter()
+            .find(|schema| schema["schema-id"].as_i64() == Some(id)),
+        None => metadata.get("schema"),
+    }
+}
+
+/// Check that a value of type `from` can be read as type `to`.
+fn is_promotion(from: &Value, to: &Value) -> bool {
+    if from == to {
+        return true;
+    }
+    match (from.as_str(), to.as_str()) {
+        (Some("int"), Some("long")) | (Some("float"), Some("double")) => true,
+        (Some(from), Some(to)) => match (decimal(from), decimal(to)) {
+            (Some((from_precision, from_scale)), Some((to_precision, to_scale))) => {
+                from_scale == to_scale && to_precision >= from_precision
+            }
+            _ => false,
+        },
+        _ => false,
+    }
+}
+
+/// Precision and scale of a `decimal(P, S)` type
+fn decimal(type_name: &str) -> Option<(u32, u32)> {
+    let arguments = type_name
+        .strip_prefix("decimal(")?
+        .strip_suffix(')')?
+        .split_once(',')?;
+    Some((
+        arguments.0.trim().parse().ok()?,
+        arguments.1.trim().parse().ok()?,
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use iceberg_rs::catalog::table_identifier::TableIdentifier;
+    use serde_json::json;
+
+    use super::{
+        CommitDecision, CommitPolicy, MaxSnapshotsPolicy, RequiredRefsPolicy,
+        SchemaCompatibilityPolicy,
+    };
+
+    #[tokio::test]
+    async fn test_required_refs_and_snapshots() {
+        let identifier = TableIdentifier::parse("test.policy").unwrap();
+        let current = json!({
+            "refs": {"main": {"snapshot-id": 1, "type": "branch"}},
+            "snapshots": [{"snapshot-id": 1}]
+        });
+        let new = json!({
+            "refs": {},
+            "snapshots": [{"snapshot-id": 1}, {"snapshot-id": 2}]
+        });
+        assert!(matches!(
+            RequiredRefsPolicy::new(&["main"])
+                .check(&identifier, &current, &new)
+                .await
+                .unwrap(),
+            CommitDecision::Reject(_)
+        ));
+        assert_eq!(
+            RequiredRefsPolicy::new(&["audit"])
+                .check(&identifier, &current, &new)
+                .await
+                .unwrap(),
+            CommitDecision::Accept
+        );
+        assert!(matches!(
+            MaxSnapshotsPolicy::new(1)
+                .check(&identifier, &current, &new)
+                .await
+                .unwrap(),
+            CommitDecision::Reject(_)
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_schema_compatibility() {
+        let identifier = TableIdentifier::parse("test.policy").unwrap();
+        let metadata = |fields| {
+            json!({
+                "current-schema-id": 1,
+                "schemas": [{"schema-id": 1, "type": "struct", "fields": fields}]
+            })
+        };
+        let current = metadata(json!([
+            {"id": 1, "name": "one", "required": false, "type": "int"},
+            {"id": 2, "name": "two", "required": false, "type": "decimal(9, 2)"}
+        ]));
+        let promoted = metadata(json!([
+            {"id": 1, "name": "one", "required": false, "type": "long"},
+            {"id": 2, "name": "two", "required": false, "type": "decimal(18, 2)"},
+            {"id": 3, "name": "three", "required": false, "type": "string"}
+        ]));
+        let dropped = metadata(json!([
+            {"id": 1, "name": "one", "required": false, "type": "int"}
+        ]));
+        let changed = metadata(json!([
+            {"id": 1, "name": "one", "required": false, "type": "string"},
+            {"id": 2, "name": "two", "required": false, "type": "decimal(9, 2)"}
+        ]));
+        let policy = SchemaCompatibilityPolicy;
+        assert_eq!(
+            policy
+                .check(&identifier, &current, &promoted)
+                .await
+                .unwrap(),
+            CommitDecision::Accept
+        );
+        assert!(matches!(
+            policy.check(&identifier, &current, &dropped).await.unwrap(),
+            CommitDecision::Reject(_)
+        ));
+        assert!(matches!(
+            policy.check(&identifier, &current, &changed).await.unwrap(),
+            CommitDecision::Reject(_)
+        ));
+    }
+}
@@ -0,0 +1,114 @@
+/*!
+Compression of metadata files.
+
+Tables with the property `write.metadata.compression-codec` set to `gzip` point to gzip-compressed
+metadata files. The metadata files are written uncompressed by the table, so the catalog writes a
+compressed copy with the suffix `.gz` when the new metadata is committed and stores the location of
+the copy. Metadata files are decompressed transparently when they are read, independent of their
+name, so compressed files written by other implementations can be loaded as well.
+*/
+
+use std::io::{Read, Write};
+
+use anyhow::{anyhow, Result};
+use flate2::{read::GzDecoder, write::GzEncoder, Compression};
+use iceberg_rs::{catalog::table_identifier::TableIdentifier, object_store::path::Path};
+
+use super::{storage::resolver::object_path, PostgresCatalog};
+
+/// Table property that selects the compression of metadata files
+pub static METADATA_COMPRESSION_CODEC: &str = "write.metadata.compression-codec";
+
+/// First bytes of a gzip stream
+static GZIP_MAGIC: [u8; 2] = [0x1f, 0x8b];
+
+impl PostgresCatalog {
+    /// Write a compressed copy of the metadata file at `location` with the content `bytes` if the
+    /// metadata requests compression. Returns the location the catalog points to.
+    pub(crate) async fn apply_metadata_codec(
+        &self,
+        identifier: &TableIdentifier,
+        location: &str,
+        metadata: &serde_json::Value,
+        bytes: &[u8],
+    ) -> Result<String> {
+        let codec = metadata["properties"][METADATA_COMPRESSION_CODEC]
+            .as_str()
+            .unwrap_or("none")
+            .to_ascii_lowercase();
+        match codec.as_str() {
+            "gzip" => (),
+            "none" => return Ok(location.to_string()),
+            _ => {
+                return Err(anyhow!(
+                    "The metadata compression codec {} is not supported.",
+                    codec
+                ))
+            }
+        }
+        let compressed_location = match compressed_location(location) {
+            Some(compressed_location) => compressed_location,
+            None => return Ok(location.to_string()),
+        };
+        let object_store = self.object_store_for_location(identifier, &compressed_location)?;
+        let path: Path = object_path(&compressed_location).into();
+        object_store
+            .put(&path, compress(bytes)?.into())
+            .await
+            .map_err(|err| anyhow!(err.to_string()))?;
+        Ok(compressed_location)
+    }
+}
+
+/// Location of the compressed copy of a metadata file, `None` if the file is compressed already
+fn compressed_location(location: &str) -> Option<String> {
+    if location.ends_with(".gz") || location.contains(".gz.") {
+        None
+    } else {
+        Some(location.to_string() + ".gz")
+    }
+}
+
+/// Decompress the content of a metadata file if it is gzip-compressed.
+pub(crate) fn decompress(bytes: Vec<u8>) -> Result<Vec<u8>> {
+    if !bytes.starts_with(&GZIP_MAGIC) {
+        return Ok(bytes);
+    }
+    let mut decompressed = Vec::new();
+    GzDecoder::new(bytes.as_slice())
+        .read_to_end(&mut decompressed)
+        .map_err(|err| anyhow!(err.to_string()))?;
+    Ok(decompressed)
+}
+
+pub(crate) fn compress(bytes: &[u8]) -> Result<Vec<u8>> {
+    let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+    encoder
+        .write_all(bytes)
+        .map_err(|err| anyhow!(err.to_string()))?;
+    encoder.finish().map_err(|err| anyhow!(err.to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{compress, compressed_location, decompress};
+
+    #[test]
+    fn test_round_trip() {
+        let metadata = br#"{"format-version":2}"#.to_vec();
+        let compressed = compress(&metadata).unwrap();
+        assert_ne!(compressed, metadata);
+        assert_eq!(decompress(compressed).unwrap(), metadata);
+        assert_eq!(decompress(metadata.clone()).unwrap(), metadata);
+    }
+
+    #[test]
+    fn test_compressed_location() {
+        assert_eq!(
+            compressed_location("table/metadata/00001.metadata.json").unwrap(),
+            "table/metadata/00001.metadata.json.gz"
+        );
+        assert!(compressed_location("table/metadata/00001.metadata.json.gz").is_none());
+        assert!(compressed_location("table/metadata/00001.gz.metadata.json").is_none());
+    }
+}
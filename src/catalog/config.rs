@@ -0,0 +1,584 @@
+/*!
+Configuration of a catalog from a TOML or YAML file or from the environment.
+
+Deployments configure the catalog in a file instead of in code. With the `config` feature, the
+configuration is read from a TOML or YAML file, for example:
+
+```toml
+name = "analytics"
+
+[connection]
+url = "postgres://catalog@db.example.com/iceberg_catalog"
+application-name = "iceberg-rest"
+query-timeout-secs = 30
+
+[pool]
+replicas = ["postgres://catalog@replica.example.com/iceberg_catalog"]
+transaction-pooling = true
+
+[tls]
+root-certificate = "/etc/ssl/certs/db-ca.pem"
+
+[warehouse]
+location = "s3://bucket/warehouse"
+properties = { "s3.region" = "eu-central-1" }
+
+[cache]
+table-exists-ttl-secs = 10
+
+[gc]
+soft-delete-retention-secs = 604800
+```
+
+Settings can be overridden with environment variables named after the section and the key with
+the prefix `ICEBERG_CATALOG_`, like `ICEBERG_CATALOG_CONNECTION_PASSWORD` or
+`ICEBERG_CATALOG_POOL_REPLICAS` with comma separated urls, so that secrets don't have to be part of
+the file. The most common settings also have short names: `ICEBERG_CATALOG_NAME`,
+`ICEBERG_CATALOG_URL`, `ICEBERG_CATALOG_USER`, `ICEBERG_CATALOG_PASSWORD` and `ICEBERG_WAREHOUSE`.
+Variables with the prefix that name no setting, for example of other tools of the deployment or of
+newer versions of the catalog, are logged as a warning and ignored.
+Container deployments that only set these variables need no configuration file at all, see
+[PostgresCatalog::from_env].
+
+[CatalogConfig::builder] turns the configuration into a [PostgresCatalogBuilder], which binaries
+and libraries can configure further before building the catalog.
+*/
+
+use std::{collections::HashMap, path::Path, path::PathBuf, sync::Arc, time::Duration};
+
+use anyhow::{anyhow, Result};
+use iceberg_rs::catalog::Catalog;
+use serde::{Deserialize, Deserializer};
+
+use super::{
+    builder::PostgresCatalogBuilder,
+    credentials::Credentials,
+    secret::Secret,
+    storage::{initial_object_store, warehouse_path, WAREHOUSE},
+    PostgresCatalog,
+};
+
+/// Prefix of the environment variables that override the configuration
+pub static ENV_PREFIX: &str = "ICEBERG_CATALOG_";
+/// Environment variable with the location of the warehouse
+pub static WAREHOUSE_VARIABLE: &str = "ICEBERG_WAREHOUSE";
+
+/// Configuration of a catalog
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields, rename_all = "kebab-case")]
+pub struct CatalogConfig {
+    /// Name of the catalog
+    pub name: String,
+    /// Connection to the catalog database
+    pub connection: ConnectionConfig,
+    /// Replicas and connection pooling
+    pub pool: PoolConfig,
+    /// Encryption of the connections
+    pub tls: TlsConfig,
+    /// Storage of the tables
+    pub warehouse: WarehouseConfig,
+    /// Caching
+    pub cache: CacheConfig,
+    /// Retention of dropped tables
+    pub gc: GcConfig,
+}
+
+/// Connection to the catalog database
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields, rename_all = "kebab-case")]
+pub struct ConnectionConfig {
+    /// Url of the primary, like `postgres://user@host/database`
+    pub url: String,
+    /// User, instead of the user of the url
+    pub user: Option<String>,
+    /// Password of the user, instead of the password of the url
+    #[serde(deserialize_with = "deserialize_secret")]
+    pub password: Option<Secret>,
+    /// `application_name` of the connections
+    pub application_name: Option<String>,
+    /// Timeout to establish a connection
+    pub connect_timeout_secs: Option<u64>,
+    /// Client side timeout of statements
+    pub query_timeout_secs: Option<u64>,
+    /// Timeout of commits
+    pub commit_timeout_secs: Option<u64>,
+    /// Server side `statement_timeout` of the sessions
+    pub statement_timeout_secs: Option<u64>,
+    /// Open the catalog read-only
+    pub read_only: bool,
+    /// Connect with the first operation instead of when the catalog is built
+    pub lazy_connect: bool,
+}
+
+/// Replicas and connection pooling
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields, rename_all = "kebab-case")]
+pub struct PoolConfig {
+    /// Urls of read replicas
+    pub replicas: Vec<String>,
+    /// Time after a write during which reads go to the primary
+    pub read_after_write_window_secs: Option<u64>,
+    /// Compatibility with connection poolers in transaction mode
+    pub transaction_pooling: bool,
+    /// Idle time after which TCP keepalive probes are sent
+    pub tcp_keepalive_secs: Option<u64>,
+    /// Idle time after which connections are checked before they are used
+    pub idle_validation_secs: Option<u64>,
+}
+
+/// Encryption of the connections
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields, rename_all = "kebab-case")]
+pub struct TlsConfig {
+    /// PEM file of a root certificate to trust in addition to the system roots
+    pub root_certificate: Option<PathBuf>,
+    /// PEM file of the client certificate chain for mutual TLS
+    pub client_certificate: Option<PathBuf>,
+    /// PEM file of the PKCS #8 key of the client certificate
+    pub client_key: Option<PathBuf>,
+}
+
+/// Storage of the tables
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields, rename_all = "kebab-case")]
+pub struct WarehouseConfig {
+    /// Location of the warehouse, like `s3://bucket/path`
+    pub location: Option<String>,
+    /// Properties of the object store, see [storage](super::storage)
+    pub properties: HashMap<String, String>,
+    /// Store the locations of tables relative to the warehouse
+    pub relative_locations: bool,
+}
+
+/// Caching
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields, rename_all = "kebab-case")]
+pub struct CacheConfig {
+    /// Time to cache the results of `table_exists`, no caching if unset. Not supported with
+    /// transaction pooling.
+    pub table_exists_ttl_secs: Option<u64>,
+}
+
+/// Retention of dropped tables
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields, rename_all = "kebab-case")]
+pub struct GcConfig {
+    /// Time dropped tables are kept before they can be purged, dropped tables are deleted
+    /// immediately if unset
+    pub soft_delete_retention_secs: Option<u64>,
+}
+
+impl CatalogConfig {
+    /// Configuration of the `ICEBERG_CATALOG_` variables of the environment
+    pub fn from_env() -> Result<Self> {
+        Self::default().with_env_overrides()
+    }
+
+    /// Parse a TOML configuration
+    #[cfg(feature = "config")]
+    pub fn from_toml(config: &str) -> Result<Self> {
+        toml::from_str(config).map_err(|err| anyhow!("The configuration is invalid. {}", err))
+    }
+
+    /// Parse a YAML configuration
+    #[cfg(feature = "config")]
+    pub fn from_yaml(config: &str) -> Result<Self> {
+        serde_yaml::from_str(config).map_err(|err| anyhow!("The configuration is invalid. {}", err))
+    }
+
+    /// Read the configuration file, in TOML or YAML format depending on its extension, and apply
+    /// the overrides of the environment.
+    #[cfg(feature = "config")]
+    pub fn from_file(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+        let config = std::fs::read_to_string(path).map_err(|err| {
+            anyhow!(
+                "The configuration {} can't be read. {}",
+                path.display(),
+                err
+            )
+        })?;
+        let config = match path.extension().and_then(|extension| extension.to_str()) {
+            Some("toml") => Self::from_toml(&config)?,
+            Some("yaml" | "yml") => Self::from_yaml(&config)?,
+            _ => {
+                return Err(anyhow!(
+                    "The configuration {} is neither a .toml nor a .yaml file.",
+                    path.display()
+                ))
+            }
+        };
+        config.with_env_overrides()
+    }
+
+    /// Override the settings with the `ICEBERG_CATALOG_` variables and `ICEBERG_WAREHOUSE` of the
+    /// environment.
+    pub fn with_env_overrides(self) -> Result<Self> {
+        self.with_overrides(std::env::vars())
+    }
+
+    /// Override the settings with the variables that start with `ICEBERG_CATALOG_` and
+    /// `ICEBERG_WAREHOUSE`, other variables are ignored. Variables with the prefix that name no
+    /// setting are logged as a warning and ignored, invalid values of settings fail.
+    pub fn with_overrides(
+        mut self,
+        variables: impl IntoIterator<Item = (String, String)>,
+    ) -> Result<Self> {
+        for (variable, value) in variables {
+            if variable == WAREHOUSE_VARIABLE {
+                self.warehouse.location = Some(value);
+                continue;
+            }
+            let key = match variable.strip_prefix(ENV_PREFIX) {
+                Some(key) => key,
+                None => continue,
+            };
+            let connection = &mut self.connection;
+            match key {
+                "NAME" => self.name = value,
+                "URL" | "CONNECTION_URL" => connection.url = value,
+                "USER" | "CONNECTION_USER" => connection.user = Some(value),
+                "PASSWORD" | "CONNECTION_PASSWORD" => {
+                    connection.password = Some(Secret::new(value))
+                }
+                "CONNECTION_APPLICATION_NAME" => connection.application_name = Some(value),
+                "CONNECTION_CONNECT_TIMEOUT_SECS" => {
+                    connection.connect_timeout_secs = Some(parse(&variable, &value)?)
+                }
+                "CONNECTION_QUERY_TIMEOUT_SECS" => {
+                    connection.query_timeout_secs = Some(parse(&variable, &value)?)
+                }
+                "CONNECTION_COMMIT_TIMEOUT_SECS" => {
+                    connection.commit_timeout_secs = Some(parse(&variable, &value)?)
+                }
+                "CONNECTION_STATEMENT_TIMEOUT_SECS" => {
+                    connection.statement_timeout_secs = Some(parse(&variable, &value)?)
+                }
+                "CONNECTION_READ_ONLY" => connection.read_only = parse(&variable, &value)?,
+                "CONNECTION_LAZY_CONNECT" => connection.lazy_connect = parse(&variable, &value)?,
+                "POOL_REPLICAS" => {
+                    self.pool.replicas = value
+                        .split(',')
+                        .map(str::trim)
+                        .filter(|url| !url.is_empty())
+                        .map(str::to_string)
+                        .collect()
+                }
+                "POOL_READ_AFTER_WRITE_WINDOW_SECS" => {
+                    self.pool.read_after_write_window_secs = Some(parse(&variable, &value)?)
+                }
+                "POOL_TRANSACTION_POOLING" => {
+                    self.pool.transaction_pooling = parse(&variable, &value)?
+                }
+                "POOL_TCP_KEEPALIVE_SECS" => {
+                    self.pool.tcp_keepalive_secs = Some(parse(&variable, &value)?)
+                }
+                "POOL_IDLE_VALIDATION_SECS" => {
+                    self.pool.idle_validation_secs = Some(parse(&variable, &value)?)
+                }
+                "TLS_ROOT_CERTIFICATE" => self.tls.root_certificate = Some(value.into()),
+                "TLS_CLIENT_CERTIFICATE" => self.tls.client_certificate = Some(value.into()),
+                "TLS_CLIENT_KEY" => self.tls.client_key = Some(value.into()),
+                "WAREHOUSE_LOCATION" => self.warehouse.location = Some(value),
+                "WAREHOUSE_RELATIVE_LOCATIONS" => {
+                    self.warehouse.relative_locations = parse(&variable, &value)?
+                }
+                "CACHE_TABLE_EXISTS_TTL_SECS" => {
+                    self.cache.table_exists_ttl_secs = Some(parse(&variable, &value)?)
+                }
+                "GC_SOFT_DELETE_RETENTION_SECS" => {
+                    self.gc.soft_delete_retention_secs = Some(parse(&variable, &value)?)
+                }
+                _ => log::warn!(
+                    "The environment variable {} is not a setting of the catalog and is ignored.",
+                    variable
+                ),
+            }
+        }
+        Ok(self)
+    }
+
+    /// Catalog properties of the warehouse, to pass to `initialize`
+    pub fn properties(&self) -> HashMap<String, String> {
+        let mut properties = self.warehouse.properties.clone();
+        if let Some(location) = &self.warehouse.location {
+            properties.insert(WAREHOUSE.to_string(), location.clone());
+        }
+        properties
+    }
+
+    /// Builder of a catalog with the configuration. Fails if the configuration has no name or
+    /// url, or if a certificate can't be read. Without a warehouse location, the catalog uses the
+    /// warehouse it was initialized with before, `initialize` fails if there is none.
+    pub fn builder(&self) -> Result<PostgresCatalogBuilder> {
+        if self.name.is_empty() {
+            return Err(anyhow!("The configuration has no catalog name."));
+        }
+        if self.connection.url.is_empty() {
+            return Err(anyhow!("The configuration has no connection url."));
+        }
+        let properties = self.properties();
+        let (object_store, warehouse_required) = initial_object_store(&properties)?;
+        let connection = &self.connection;
+        let mut builder = PostgresCatalogBuilder::new(&self.name, &connection.url, object_store)
+            .with_warehouse_required(warehouse_required)
+            .with_read_only(connection.read_only)
+            .with_lazy_connect(connection.lazy_connect)
+            .with_transaction_pooling(self.pool.transaction_pooling)
+            .with_relative_locations(self.warehouse.relative_locations);
+        if let Some(path) = warehouse_path(&properties)? {
+            builder = builder.with_warehouse_path(&path);
+        }
+        if let Some(password) = &connection.password {
+            let user = connection
+                .user
+                .clone()
+                .ok_or_else(|| anyhow!("The configuration has a password but no user."))?;
+            builder = builder.with_credentials(Credentials {
+                user,
+                password: password.clone(),
+            });
+        }
+        if let Some(application_name) = &connection.application_name {
+            builder = builder.with_application_name(application_name);
+        }
+        if let Some(timeout) = connection.connect_timeout_secs {
+            builder = builder.with_connect_timeout(Duration::from_secs(timeout));
+        }
+        if let Some(timeout) = connection.query_timeout_secs {
+            builder = builder.with_query_timeout(Duration::from_secs(timeout));
+        }
+        if let Some(timeout) = connection.commit_timeout_secs {
+            builder = builder.with_commit_timeout(Duration::from_secs(timeout));
+        }
+        if let Some(timeout) = connection.statement_timeout_secs {
+            builder = builder.with_statement_timeout(Duration::from_secs(timeout));
+        }
+        for replica in &self.pool.replicas {
+            builder = builder.with_replica(replica);
+        }
+        if let Some(window) = self.pool.read_after_write_window_secs {
+            builder = builder.with_read_after_write_window(Duration::from_secs(window));
+        }
+        if let Some(idle) = self.pool.tcp_keepalive_secs {
+            builder = builder.with_tcp_keepalive(Duration::from_secs(idle));
+        }
+        if let Some(idle) = self.pool.idle_validation_secs {
+            builder = builder.with_idle_validation(Duration::from_secs(idle));
+        }
+        if let Some(path) = &self.tls.root_certificate {
+            builder = builder.with_root_certificate(&read_file(path)?);
+        }
+        match (&self.tls.client_certificate, &self.tls.client_key) {
+            (Some(certificate), Some(key)) => {
+                builder =
+                    builder.with_client_certificate(&read_file(certificate)?, &read_file(key)?)
+            }
+            (None, None) => (),
+            _ => {
+                return Err(anyhow!(
+                    "The configuration needs both a client certificate and a client key."
+                ))
+            }
+        }
+        if let Some(ttl) = self.cache.table_exists_ttl_secs {
+            builder = builder.with_table_exists_cache(Duration::from_secs(ttl));
+        }
+        if let Some(retention) = self.gc.soft_delete_retention_secs {
+            builder = builder.with_soft_delete(Duration::from_secs(retention));
+        }
+        Ok(builder)
+    }
+}
+
+impl PostgresCatalog {
+    /// Connect to the catalog configured by the environment, see [CatalogConfig::from_env], and
+    /// initialize it. `ICEBERG_CATALOG_NAME` and `ICEBERG_CATALOG_URL` are required,
+    /// `ICEBERG_WAREHOUSE` only if the catalog wasn't initialized with a warehouse before. Fails if
+    /// no warehouse is known.
+    pub async fn from_env() -> Result<Arc<Self>> {
+        let config = CatalogConfig::from_env()?;
+        let catalog = Arc::new(config.builder()?.build().await?);
+        Arc::clone(&catalog)
+            .initialize(&config.properties())
+            .await?;
+        Ok(catalog)
+    }
+}
+
+fn deserialize_secret<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> std::result::Result<Option<Secret>, D::Error> {
+    Ok(Option::<String>::deserialize(deserializer)?.map(Secret::new))
+}
+
+fn parse<T: std::str::FromStr>(variable: &str, value: &str) -> Result<T> {
+    value
+        .trim()
+        .parse()
+        .map_err(|_| anyhow!("The environment variable {} is invalid.", variable))
+}
+
+fn read_file(path: &Path) -> Result<Vec<u8>> {
+    std::fs::read(path).map_err(|err| anyhow!("The file {} can't be read. {}", path.display(), err))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::CatalogConfig;
+
+    #[cfg(feature = "config")]
+    #[test]
+    fn test_toml_and_yaml() {
+        let toml = CatalogConfig::from_toml(
+            r#"
+            name = "analytics"
+
+            [connection]
+            url = "postgres://catalog@localhost/iceberg_catalog"
+            password = "secret"
+            query-timeout-secs = 30
+
+            [pool]
+            replicas = ["postgres://catalog@replica/iceberg_catalog"]
+
+            [warehouse]
+            location = "memory://warehouse/tables"
+
+            [gc]
+            soft-delete-retention-secs = 3600
+            "#,
+        )
+        .unwrap();
+        let yaml = CatalogConfig::from_yaml(
+            r#"
+            name: analytics
+            connection:
+              url: postgres://catalog@localhost/iceberg_catalog
+              password: secret
+              query-timeout-secs: 30
+            pool:
+              replicas:
+                - postgres://catalog@replica/iceberg_catalog
+            warehouse:
+              location: memory://warehouse/tables
+            gc:
+              soft-delete-retention-secs: 3600
+            "#,
+        )
+        .unwrap();
+        for config in [toml, yaml] {
+            assert_eq!(config.name, "analytics");
+            assert_eq!(config.connection.query_timeout_secs, Some(30));
+            assert_eq!(config.connection.password.unwrap().expose(), "secret");
+            assert_eq!(config.pool.replicas.len(), 1);
+            assert_eq!(config.gc.soft_delete_retention_secs, Some(3600));
+            assert_eq!(config.cache.table_exists_ttl_secs, None);
+        }
+        assert!(CatalogConfig::from_toml("[connection]\nurl = 1").is_err());
+        assert!(CatalogConfig::from_toml("[connection]\nhost = \"localhost\"").is_err());
+    }
+
+    #[test]
+    fn test_overrides() {
+        let mut config = CatalogConfig::default();
+        config.connection.user = Some("catalog".to_string());
+        let config = config
+            .with_overrides([
+                ("PATH".to_string(), "/usr/bin".to_string()),
+                ("ICEBERG_CATALOG_NAME".to_string(), "analytics".to_string()),
+                (
+                    "ICEBERG_CATALOG_CONNECTION_URL".to_string(),
+                    "postgres://localhost/iceberg_catalog".to_string(),
+                ),
+                (
+                    "ICEBERG_CATALOG_CONNECTION_PASSWORD".to_string(),
+                    "secret".to_string(),
+                ),
+                (
+                    "ICEBERG_CATALOG_POOL_REPLICAS".to_string(),
+                    "postgres://a/db, postgres://b/db".to_string(),
+                ),
+                (
+                    "ICEBERG_CATALOG_POOL_TRANSACTION_POOLING".to_string(),
+                    "true".to_string(),
+                ),
+                (
+                    "ICEBERG_CATALOG_WAREHOUSE_LOCATION".to_string(),
+                    "memory://warehouse".to_string(),
+                ),
+            ])
+            .unwrap();
+        assert_eq!(
+            config.connection.url,
+            "postgres://localhost/iceberg_catalog"
+        );
+        assert_eq!(config.connection.user.as_deref(), Some("catalog"));
+        assert_eq!(
+            config.connection.password.as_ref().unwrap().expose(),
+            "secret"
+        );
+        assert_eq!(
+            config.pool.replicas,
+            vec!["postgres://a/db", "postgres://b/db"]
+        );
+        assert!(config.pool.transaction_pooling);
+        assert_eq!(
+            config.properties().get("warehouse").map(String::as_str),
+            Some("memory://warehouse")
+        );
+        assert!(!format!("{:?}", config).contains("secret"));
+        assert!(config.builder().is_ok());
+
+        assert!(CatalogConfig::default()
+            .with_overrides([(
+                "ICEBERG_CATALOG_CACHE_TABLE_EXISTS_TTL_SECS".to_string(),
+                "ten".to_string()
+            )])
+            .is_err());
+        let config = CatalogConfig::default()
+            .with_overrides([("ICEBERG_CATALOG_HOST".to_string(), "db".to_string())])
+            .unwrap();
+        assert_eq!(
+            config.connection.url,
+            CatalogConfig::default().connection.url
+        );
+    }
+
+    #[test]
+    fn test_short_names() {
+        let config = CatalogConfig::default()
+            .with_overrides([
+                ("ICEBERG_CATALOG_NAME".to_string(), "analytics".to_string()),
+                (
+                    "ICEBERG_CATALOG_URL".to_string(),
+                    "postgres://localhost/iceberg_catalog".to_string(),
+                ),
+                ("ICEBERG_CATALOG_USER".to_string(), "catalog".to_string()),
+                ("ICEBERG_CATALOG_PASSWORD".to_string(), "secret".to_string()),
+                (
+                    "ICEBERG_WAREHOUSE".to_string(),
+                    "memory://warehouse".to_string(),
+                ),
+            ])
+            .unwrap();
+        assert_eq!(config.name, "analytics");
+        assert_eq!(
+            config.connection.url,
+            "postgres://localhost/iceberg_catalog"
+        );
+        assert_eq!(config.connection.user.as_deref(), Some("catalog"));
+        assert_eq!(
+            config.warehouse.location.as_deref(),
+            Some("memory://warehouse")
+        );
+        assert!(config.builder().is_ok());
+
+        let mut unnamed = config.clone();
+        unnamed.name = String::new();
+        assert!(unnamed.builder().is_err());
+        let mut without_warehouse = config;
+        without_warehouse.warehouse.location = None;
+        assert!(without_warehouse.builder().is_ok());
+    }
+}
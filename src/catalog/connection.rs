@@ -0,0 +1,327 @@
+/*!
+Connection to a single postgres server that is re-established when it was closed.
+
+With the `sqlx` feature, the connection can instead be a [sqlx::PgPool], which manages its
+connections itself.
+
+Firewalls and NAT gateways silently drop connections that have been idle for a while, which the
+client only notices when the next statement fails. With idle validation, a connection that has
+been idle for longer than the configured time is checked with an empty statement before it is
+used, and replaced by a new connection if the check fails.
+*/
+
+use std::{
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use anyhow::{anyhow, Result};
+use postgres_native_tls::MakeTlsConnector;
+use tokio::{sync::RwLock, task::JoinHandle};
+use tokio_postgres::{types::ToSql, Client, Config, NoTls, Statement};
+
+use super::{
+    credentials::CredentialsProvider,
+    query::{self, CatalogRow, StatementResult},
+    statements::{inline, Param, StatementCache},
+    trace,
+};
+
+/// Time to wait for the check of an idle connection
+static VALIDATION_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Connection to a single postgres server
+pub(crate) struct PostgresConnection {
+    /// Configuration to reconnect. Connections without configuration can't be re-established.
+    config: Option<Config>,
+    credentials: Option<Arc<dyn CredentialsProvider>>,
+    tls: Option<MakeTlsConnector>,
+    client: RwLock<Option<Arc<Client>>>,
+    task: Mutex<Option<JoinHandle<()>>>,
+    statements: StatementCache,
+    /// Idle time after which the connection is checked before it is used
+    idle_validation: Option<Duration>,
+    last_used: Mutex<Option<Instant>>,
+    #[cfg(feature = "sqlx")]
+    pool: Option<sqlx::PgPool>,
+}
+
+impl PostgresConnection {
+    /// Create a connection that isn't connected yet.
+    pub(crate) fn new(
+        config: Config,
+        credentials: Option<Arc<dyn CredentialsProvider>>,
+        tls: Option<MakeTlsConnector>,
+    ) -> Self {
+        PostgresConnection {
+            config: Some(config),
+            credentials,
+            tls,
+            client: RwLock::new(None),
+            task: Mutex::new(None),
+            statements: StatementCache::default(),
+            idle_validation: None,
+            last_used: Mutex::new(None),
+            #[cfg(feature = "sqlx")]
+            pool: None,
+        }
+    }
+
+    /// Check the connection before it is used if it has been idle for longer than `idle`.
+    pub(crate) fn with_idle_validation(mut self, idle: Option<Duration>) -> Self {
+        self.idle_validation = idle;
+        self
+    }
+
+    /// Wrap a client whose connection is driven by the caller.
+    pub(crate) fn from_client(client: Client) -> Self {
+        PostgresConnection {
+            config: None,
+            credentials: None,
+            tls: None,
+            client: RwLock::new(Some(Arc::new(client))),
+            task: Mutex::new(None),
+            statements: StatementCache::default(),
+            idle_validation: None,
+            last_used: Mutex::new(None),
+            #[cfg(feature = "sqlx")]
+            pool: None,
+        }
+    }
+
+    /// Run the statements on connections of the sqlx pool.
+    #[cfg(feature = "sqlx")]
+    pub(crate) fn from_pool(pool: sqlx::PgPool) -> Self {
+        PostgresConnection {
+            config: None,
+            credentials: None,
+            tls: None,
+            client: RwLock::new(None),
+            task: Mutex::new(None),
+            statements: StatementCache::default(),
+            idle_validation: None,
+            last_used: Mutex::new(None),
+            pool: Some(pool),
+        }
+    }
+
+    /// Run a statement that returns rows.
+    pub(crate) async fn query(&self, sql: &str, simple: bool) -> Result<Vec<CatalogRow>> {
+        trace::statement(sql, async {
+            let sql = &trace::comment(sql);
+            #[cfg(feature = "sqlx")]
+            if let Some(pool) = &self.pool {
+                return query::query_pool(pool, sql).await;
+            }
+            query::query(&*self.client().await?, sql, simple).await
+        })
+        .await
+    }
+
+    /// Run a statement and return the number of affected rows.
+    pub(crate) async fn execute(&self, sql: &str, simple: bool) -> Result<u64> {
+        trace::statement(sql, async {
+            let sql = &trace::comment(sql);
+            #[cfg(feature = "sqlx")]
+            if let Some(pool) = &self.pool {
+                return query::execute_pool(pool, sql).await;
+            }
+            query::execute(&*self.client().await?, sql, simple).await
+        })
+        .await
+    }
+
+    /// Run the statements in one transaction and return their results in order.
+    pub(crate) async fn transaction(&self, statements: &[String]) -> Result<Vec<StatementResult>> {
+        trace::statement(&statements.join(" "), async {
+            let statements = &statements
+                .iter()
+                .map(|sql| trace::comment(sql).into_owned())
+                .collect::<Vec<_>>();
+            #[cfg(feature = "sqlx")]
+            if let Some(pool) = &self.pool {
+                return query::transaction_pool(pool, statements).await;
+            }
+            query::transaction(&*self.client().await?, statements).await
+        })
+        .await
+    }
+
+    /// Run a prepared statement that returns rows. With the simple query protocol, the parameters
+    /// are inlined instead.
+    pub(crate) async fn query_prepared(
+        &self,
+        sql: &str,
+        params: &[Param<'_>],
+        simple: bool,
+    ) -> Result<Vec<CatalogRow>> {
+        // Traced statements carry the trace context in a comment, which would prepare a new
+        // statement for every trace.
+        let traced = trace::current().is_some();
+        #[cfg(feature = "sqlx")]
+        if let (Some(pool), false) = (&self.pool, traced) {
+            return query::query_pool_params(pool, sql, params).await;
+        }
+        if simple || traced {
+            return self.query(&inline(sql, params), true).await;
+        }
+        let client = self.client().await?;
+        let statement = self.prepare(&client, sql).await?;
+        Ok(client
+            .query(&statement, &sql_params(params))
+            .await
+            .map_err(|err| anyhow!(err))?
+            .into_iter()
+            .map(CatalogRow::Extended)
+            .collect())
+    }
+
+    /// Run a prepared statement and return the number of affected rows. With the simple query
+    /// protocol, the parameters are inlined instead.
+    pub(crate) async fn execute_prepared(
+        &self,
+        sql: &str,
+        params: &[Param<'_>],
+        simple: bool,
+    ) -> Result<u64> {
+        let traced = trace::current().is_some();
+        #[cfg(feature = "sqlx")]
+        if let (Some(pool), false) = (&self.pool, traced) {
+            return query::execute_pool_params(pool, sql, params).await;
+        }
+        if simple || traced {
+            return self.execute(&inline(sql, params), true).await;
+        }
+        let client = self.client().await?;
+        let statement = self.prepare(&client, sql).await?;
+        client
+            .execute(&statement, &sql_params(params))
+            .await
+            .map_err(|err| anyhow!(err))
+    }
+
+    /// Prepare the statement on the client, unless it was prepared on the client before.
+    async fn prepare(&self, client: &Arc<Client>, sql: &str) -> Result<Statement> {
+        if let Some(statement) = self.statements.get(client, sql) {
+            return Ok(statement);
+        }
+        let statement = client.prepare(sql).await.map_err(|err| anyhow!(err))?;
+        self.statements.insert(client, sql, statement.clone());
+        Ok(statement)
+    }
+
+    /// Get the client, connecting if the connection wasn't established yet, was closed or failed
+    /// the check after being idle.
+    pub(crate) async fn client(&self) -> Result<Arc<Client>> {
+        let current = self.client.read().await.clone();
+        if let Some(client) = &current {
+            if !client.is_closed() && self.validate(client).await {
+                self.touch();
+                return Ok(Arc::clone(client));
+            }
+        }
+        let mut client = self.client.write().await;
+        // Another task might have reconnected while waiting for the lock.
+        if let Some(client) = client.as_ref() {
+            let rejected = current
+                .as_ref()
+                .map_or(false, |current| Arc::ptr_eq(current, client));
+            if !client.is_closed() && !rejected {
+                self.touch();
+                return Ok(Arc::clone(client));
+            }
+        }
+        let new_client = Arc::new(self.establish().await?);
+        *client = Some(Arc::clone(&new_client));
+        self.touch();
+        Ok(new_client)
+    }
+
+    /// Whether the client can still be used. Clients that have been idle for longer than the
+    /// validation time are checked with an empty statement, others are assumed to be alive.
+    async fn validate(&self, client: &Client) -> bool {
+        let idle = match self.idle_validation {
+            Some(idle) => idle,
+            None => return true,
+        };
+        let last_used = self.last_used.lock().ok().and_then(|last_used| *last_used);
+        if last_used.map_or(true, |last_used| last_used.elapsed() < idle) {
+            return true;
+        }
+        matches!(
+            tokio::time::timeout(VALIDATION_TIMEOUT, client.simple_query("")).await,
+            Ok(Ok(_))
+        )
+    }
+
+    /// Mark the connection as used now
+    fn touch(&self) {
+        if let Ok(mut last_used) = self.last_used.lock() {
+            *last_used = Some(Instant::now());
+        }
+    }
+
+    async fn establish(&self) -> Result<Client> {
+        let mut config = self
+            .config
+            .clone()
+            .ok_or_else(|| anyhow!("The connection was closed and can't be re-established."))?;
+        if let Some(provider) = &self.credentials {
+            let credentials = provider.credentials().await?;
+            config.user(&credentials.user);
+            config.password(credentials.password.expose());
+        }
+        // Errors of the connection are reported to the client on its next request.
+        let (client, task) = match &self.tls {
+            Some(tls) => {
+                let (client, connection) = config
+                    .connect(tls.clone())
+                    .await
+                    .map_err(|err| anyhow!(err))?;
+                (
+                    client,
+                    tokio::spawn(async move {
+                        let _ = connection.await;
+                    }),
+                )
+            }
+            None => {
+                let (client, connection) =
+                    config.connect(NoTls).await.map_err(|err| anyhow!(err))?;
+                (
+                    client,
+                    tokio::spawn(async move {
+                        let _ = connection.await;
+                    }),
+                )
+            }
+        };
+        if let Ok(mut current) = self.task.lock() {
+            if let Some(previous) = current.replace(task) {
+                previous.abort();
+            }
+        }
+        Ok(client)
+    }
+
+    /// Stop the background task driving the connection.
+    pub(crate) fn close(&self) {
+        #[cfg(feature = "sqlx")]
+        if let Some(pool) = &self.pool {
+            let pool = pool.clone();
+            tokio::spawn(async move { pool.close().await });
+        }
+        if let Ok(mut task) = self.task.lock() {
+            if let Some(task) = task.take() {
+                task.abort();
+            }
+        }
+    }
+}
+
+fn sql_params<'a>(params: &'a [Param<'_>]) -> Vec<&'a (dyn ToSql + Sync)> {
+    params
+        .iter()
+        .map(|param| param as &(dyn ToSql + Sync))
+        .collect()
+}
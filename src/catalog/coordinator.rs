@@ -0,0 +1,242 @@
+/*!
+Queued commits to hot tables.
+
+The catalog changes the metadata of a table optimistically: it reads the current metadata, applies
+the change and commits it, which fails if another writer committed in between. For tables that
+many writers change at the same time, most attempts fail and have to be repeated.
+[with_commit_coordinator](super::builder::PostgresCatalogBuilder::with_commit_coordinator) queues
+the changes to such tables instead. A change waits for its turn, then it is applied to the current
+metadata and committed. If a writer outside the queue committed in between, the change is
+applied again to the new metadata.
+
+With [CoordinationMode::Process] the queue is local to the catalog, the changes of one process are
+committed in the order they arrived. With [CoordinationMode::Database] the turn additionally
+requires the [commit lock](super::lock) of the table, which serializes the changes of all
+processes that coordinate the table.
+
+The schema, partition spec and property changes of the catalog use the queue. Commits of engines
+that write their own metadata, through [update_table](iceberg_rs::catalog::Catalog::update_table)
+or [update_table_if_version](PostgresCatalog::update_table_if_version), wait for their turn as
+well, so they don't interleave with the queued changes and, with [CoordinationMode::Database],
+with the commits of other processes. The catalog can't apply the metadata of an engine to newer
+metadata though. An engine commit that is based on outdated metadata still fails with
+[CatalogError::CommitConflict] and is retried by the engine, the queue only keeps the retry from
+racing the other writers of the table. Commits to several tables with
+[commit_tables](PostgresCatalog::commit_tables) aren't queued.
+*/
+
+use std::{
+    collections::{HashMap, HashSet},
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use anyhow::{anyhow, Result};
+use iceberg_rs::{
+    catalog::{table_identifier::TableIdentifier, Catalog},
+    table::Table,
+};
+use serde_json::Value;
+use tokio::sync::OwnedMutexGuard;
+
+use super::{
+    error::CatalogError,
+    format::{format_version, upgrade_v1},
+    lock::CommitLock,
+    namespace::identifier_key,
+    Expected, PostgresCatalog,
+};
+
+/// Number of attempts of a queued change whose commit conflicted with a writer outside the queue
+static MAX_QUEUED_ATTEMPTS: usize = 5;
+
+/// How the turns of queued commits are coordinated
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CoordinationMode {
+    /// Commits of this catalog wait for each other
+    Process,
+    /// Commits of all processes wait for each other, through the commit lock of the table with the
+    /// lease. The commit lock is acquired within the timeout.
+    Database {
+        /// Lease of the commit lock
+        lease: Duration,
+        /// Maximum time to wait for the commit lock
+        timeout: Duration,
+    },
+}
+
+/// Queues of the coordinated tables
+pub(crate) struct CommitCoordinator {
+    mode: CoordinationMode,
+    tables: HashSet<(String, String)>,
+    queues: Mutex<HashMap<(String, String), Arc<tokio::sync::Mutex<()>>>>,
+}
+
+/// Turn of a queued commit, the next commit to the table waits until it is dropped
+struct Turn {
+    _queue: OwnedMutexGuard<()>,
+    lock: Option<CommitLock>,
+}
+
+impl Turn {
+    /// End the turn. A lock that isn't released expires with its lease, so failing to release it
+    /// doesn't fail the commit.
+    async fn end(self) {
+        if let Some(lock) = self.lock {
+            let _ = lock.release().await;
+        }
+    }
+}
+
+impl CommitCoordinator {
+    /// Coordinator for the tables, whose identifiers have to be normalized
+    pub(crate) fn new(tables: &[TableIdentifier], mode: CoordinationMode) -> Self {
+        CommitCoordinator {
+            mode,
+            tables: tables.iter().map(identifier_key).collect(),
+            queues: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Queue of the table, `None` if it isn't coordinated
+    fn queue(&self, identifier: &TableIdentifier) -> Result<Option<Arc<tokio::sync::Mutex<()>>>> {
+        let key = identifier_key(identifier);
+        if !self.tables.contains(&key) {
+            return Ok(None);
+        }
+        let mut queues = self.queues.lock().map_err(|err| anyhow!(err.to_string()))?;
+        Ok(Some(Arc::clone(queues.entry(key).or_default())))
+    }
+}
+
+impl PostgresCatalog {
+    /// Apply the change to the current metadata of the table and commit it. The change returns
+    /// whether it modified the metadata, unchanged tables are returned without commit. Format
+    /// version 1 metadata is upgraded before the change. Changes to coordinated tables wait for
+    /// their turn and are applied again if a writer outside the queue committed concurrently.
+    pub(crate) async fn change_metadata(
+        self: Arc<Self>,
+        identifier: TableIdentifier,
+        mut change: impl FnMut(&mut Value) -> Result<bool>,
+    ) -> Result<Table> {
+        let identifier = self.case_sensitivity.normalize(&identifier)?;
+        let turn = Arc::clone(&self).turn(&identifier).await?;
+        let attempts = if turn.is_some() {
+            MAX_QUEUED_ATTEMPTS
+        } else {
+            1
+        };
+        let turn_lock = turn.as_ref().and_then(|turn| turn.lock.as_ref());
+        let result = async {
+            let mut attempt = 1;
+            loop {
+                let table = Arc::clone(&self).load_table(identifier.clone()).await?;
+                let location = table.metadata_location().to_string();
+                let mut metadata = self.read_metadata_json(&identifier, &location).await?;
+                if format_version(&metadata) == 1 {
+                    upgrade_v1(&mut metadata)?;
+                }
+                if !change(&mut metadata)? {
+                    return Ok(table);
+                }
+                if let Some(lock) = turn_lock {
+                    lock.renew().await?;
+                }
+                let result = Arc::clone(&self)
+                    .commit_metadata(identifier.clone(), &location, metadata)
+                    .await;
+                match result {
+                    Err(err)
+                        if attempt < attempts
+                            && matches!(
+                                err.downcast_ref::<CatalogError>(),
+                                Some(CatalogError::CommitConflict { .. })
+                            ) =>
+                    {
+                        attempt += 1;
+                    }
+                    result => return result,
+                }
+            }
+        }
+        .await;
+        if let Some(turn) = turn {
+            turn.end().await;
+        }
+        result
+    }
+
+    /// Swap the metadata pointer of an engine commit. Commits to coordinated tables wait for
+    /// their turn, but aren't applied again on conflicts.
+    pub(crate) async fn queued_update(
+        self: Arc<Self>,
+        identifier: TableIdentifier,
+        metadata_file_location: &str,
+        expected: Expected<'_>,
+    ) -> Result<Table> {
+        let normalized = self.case_sensitivity.normalize(&identifier)?;
+        let turn = Arc::clone(&self).turn(&normalized).await?;
+        let result = self
+            .update_pointer(identifier, metadata_file_location, expected)
+            .await;
+        if let Some(turn) = turn {
+            turn.end().await;
+        }
+        result
+    }
+
+    /// Wait for the turn of the next commit to the table, `None` if the table isn't coordinated
+    async fn turn(self: Arc<Self>, identifier: &TableIdentifier) -> Result<Option<Turn>> {
+        let coordinator = match &self.commit_coordinator {
+            Some(coordinator) => coordinator,
+            None => return Ok(None),
+        };
+        let queue = match coordinator.queue(identifier)? {
+            Some(queue) => queue,
+            None => return Ok(None),
+        };
+        // The mutex of tokio is fair, waiting commits get their turn in the order they arrived.
+        let queue = queue.lock_owned().await;
+        let lock = match coordinator.mode {
+            CoordinationMode::Process => None,
+            CoordinationMode::Database { lease, timeout } => Some(
+                Arc::clone(&self)
+                    .acquire_commit_lock(identifier, lease, timeout)
+                    .await?,
+            ),
+        };
+        Ok(Some(Turn {
+            _queue: queue,
+            lock,
+        }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use iceberg_rs::catalog::table_identifier::TableIdentifier;
+
+    use super::{CommitCoordinator, CoordinationMode};
+
+    #[test]
+    fn test_queue() {
+        let hot = TableIdentifier::parse("ns.hot").unwrap();
+        let coordinator = CommitCoordinator::new(&[hot.clone()], CoordinationMode::Process);
+        let queue = coordinator.queue(&hot).unwrap().unwrap();
+        assert!(std::sync::Arc::ptr_eq(
+            &queue,
+            &coordinator.queue(&hot).unwrap().unwrap()
+        ));
+        assert!(coordinator
+            .queue(&TableIdentifier::parse("ns.cold").unwrap())
+            .unwrap()
+            .is_none());
+        // Displayed as `ns.hot.table` as well, but a different table
+        let dotted =
+            TableIdentifier::try_new(&["ns".to_string(), "hot.table".to_string()]).unwrap();
+        let nested = TableIdentifier::parse("ns.hot.table").unwrap();
+        let coordinator = CommitCoordinator::new(&[dotted.clone()], CoordinationMode::Process);
+        assert!(coordinator.queue(&dotted).unwrap().is_some());
+        assert!(coordinator.queue(&nested).unwrap().is_none());
+    }
+}
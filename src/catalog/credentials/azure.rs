@@ -0,0 +1,89 @@
+/*!
+Azure AD authentication for Azure Database for PostgreSQL.
+
+The credentials use an access token of the managed identity of the workload as the password. The
+token is requested from the instance metadata service and refreshed before it expires. Azure only
+accepts tokens over encrypted connections, so the catalog has to be configured with TLS and
+`sslmode=require`.
+*/
+
+use std::time::Duration;
+
+use anyhow::{anyhow, Result};
+
+use super::{Credentials, CredentialsProvider, Secret, TokenCache};
+
+static IMDS_TOKEN_URL: &str = "http://169.254.169.254/metadata/identity/oauth2/token";
+static IMDS_API_VERSION: &str = "2018-02-01";
+static OSSRDBMS_RESOURCE: &str = "https://ossrdbms-aad.database.windows.net";
+
+/// Credentials of an Azure managed identity
+pub struct AzureAdCredentials {
+    user: String,
+    client_id: Option<String>,
+    http: reqwest::Client,
+    token: TokenCache,
+}
+
+impl AzureAdCredentials {
+    /// Authenticate as `user`, the name of the database role mapped to the managed identity.
+    pub fn new(user: &str) -> Self {
+        AzureAdCredentials {
+            user: user.to_string(),
+            client_id: None,
+            http: reqwest::Client::new(),
+            token: TokenCache::new(),
+        }
+    }
+
+    /// Use the user-assigned managed identity with the given client id instead of the
+    /// system-assigned identity.
+    pub fn with_client_id(mut self, client_id: &str) -> Self {
+        self.client_id = Some(client_id.to_string());
+        self
+    }
+
+    async fn fetch_token(&self) -> Result<(String, Duration)> {
+        let mut query = vec![
+            ("api-version", IMDS_API_VERSION),
+            ("resource", OSSRDBMS_RESOURCE),
+        ];
+        if let Some(client_id) = &self.client_id {
+            query.push(("client_id", client_id.as_str()));
+        }
+        let response: serde_json::Value = self
+            .http
+            .get(IMDS_TOKEN_URL)
+            .query(&query)
+            .header("Metadata", "true")
+            .send()
+            .await
+            .map_err(|err| anyhow!(err.to_string()))?
+            .error_for_status()
+            .map_err(|err| anyhow!(err.to_string()))?
+            .json()
+            .await
+            .map_err(|err| anyhow!(err.to_string()))?;
+        let access_token = response["access_token"]
+            .as_str()
+            .ok_or_else(|| anyhow!("The metadata service returned no access token."))?
+            .to_string();
+        // The metadata service returns the lifetime as a string.
+        let expires_in = match &response["expires_in"] {
+            serde_json::Value::String(expires_in) => expires_in.parse().unwrap_or(0),
+            expires_in => expires_in.as_u64().unwrap_or(0),
+        };
+        Ok((access_token, Duration::from_secs(expires_in)))
+    }
+}
+
+#[async_trait::async_trait]
+impl CredentialsProvider for AzureAdCredentials {
+    async fn credentials(&self) -> Result<Credentials> {
+        let password = self.token.get(|| self.fetch_token()).await?;
+        Ok(Credentials {
+            user: self.user.clone(),
+            password: Secret::new(password),
+        })
+    }
+}
@@ -0,0 +1,72 @@
+/*!
+IAM database authentication for GCP Cloud SQL and AlloyDB.
+
+The credentials use an OAuth2 access token of the service account attached to the workload as the
+password. On GKE this is the service account bound with Workload Identity, so neither a sidecar
+proxy nor static credentials are required. Cloud SQL only accepts IAM authentication over
+encrypted connections, so the catalog has to be configured with TLS and `sslmode=require`.
+*/
+
+use std::time::Duration;
+
+use anyhow::{anyhow, Result};
+
+use super::{Credentials, CredentialsProvider, Secret, TokenCache};
+
+static METADATA_TOKEN_URL: &str =
+    "http://metadata.google.internal/computeMetadata/v1/instance/service-accounts/default/token";
+static SERVICE_ACCOUNT_SUFFIX: &str = ".gserviceaccount.com";
+
+/// Credentials for IAM database authentication with Cloud SQL and AlloyDB
+pub struct GcpIamCredentials {
+    user: String,
+    http: reqwest::Client,
+    token: TokenCache,
+}
+
+impl GcpIamCredentials {
+    /// Authenticate as the given service account. The database user is the email of the service
+    /// account without the `.gserviceaccount.com` suffix.
+    pub fn new(service_account: &str) -> Self {
+        GcpIamCredentials {
+            user: service_account
+                .strip_suffix(SERVICE_ACCOUNT_SUFFIX)
+                .unwrap_or(service_account)
+                .to_string(),
+            http: reqwest::Client::new(),
+            token: TokenCache::new(),
+        }
+    }
+
+    async fn fetch_token(&self) -> Result<(String, Duration)> {
+        let response: serde_json::Value = self
+            .http
+            .get(METADATA_TOKEN_URL)
+            .header("Metadata-Flavor", "Google")
+            .send()
+            .await
+            .map_err(|err| anyhow!(err.to_string()))?
+            .error_for_status()
+            .map_err(|err| anyhow!(err.to_string()))?
+            .json()
+            .await
+            .map_err(|err| anyhow!(err.to_string()))?;
+        let access_token = response["access_token"]
+            .as_str()
+            .ok_or_else(|| anyhow!("The metadata server returned no access token."))?
+            .to_string();
+        let expires_in = response["expires_in"].as_u64().unwrap_or(0);
+        Ok((access_token, Duration::from_secs(expires_in)))
+    }
+}
+
+#[async_trait::async_trait]
+impl CredentialsProvider for GcpIamCredentials {
+    async fn credentials(&self) -> Result<Credentials> {
+        let password = self.token.get(|| self.fetch_token()).await?;
+        Ok(Credentials {
+            user: self.user.clone(),
+            password: Secret::new(password),
+        })
+    }
+}
@@ -0,0 +1,76 @@
+/*!
+Credentials that are looked up whenever the catalog establishes a new connection.
+*/
+
+use anyhow::Result;
+
+use super::secret::Secret;
+
+#[cfg(feature = "azure")]
+pub mod azure;
+#[cfg(feature = "gcp")]
+pub mod gcp;
+
+/// User and password to authenticate a connection
+#[derive(Debug, Clone)]
+pub struct Credentials {
+    /// Name of the database user
+    pub user: String,
+    /// Password of the database user
+    pub password: Secret,
+}
+
+/// Provides the credentials for new connections. The catalog consults the provider every time it
+/// (re)connects, so that rotated secrets are picked up without restarting the application.
+/// Established connections are not affected by a rotation.
+#[async_trait::async_trait]
+pub trait CredentialsProvider: Send + Sync {
+    /// Get the current credentials
+    async fn credentials(&self) -> Result<Credentials>;
+}
+
+/// Static credentials, kept separately from the connection url.
+#[async_trait::async_trait]
+impl CredentialsProvider for Credentials {
+    async fn credentials(&self) -> Result<Credentials> {
+        Ok(self.clone())
+    }
+}
+
+/// Access token that is cached until shortly before it expires
+#[cfg(any(feature = "azure", feature = "gcp"))]
+pub(crate) struct TokenCache {
+    token: tokio::sync::Mutex<Option<(String, std::time::Instant)>>,
+}
+
+#[cfg(any(feature = "azure", feature = "gcp"))]
+impl TokenCache {
+    /// Tokens are refreshed if they expire within this margin.
+    const REFRESH_MARGIN: std::time::Duration = std::time::Duration::from_secs(300);
+
+    pub(crate) fn new() -> Self {
+        TokenCache {
+            token: tokio::sync::Mutex::new(None),
+        }
+    }
+
+    /// Get the cached token or fetch a new one. `fetch` returns the token and its lifetime.
+    pub(crate) async fn get<F, Fut>(&self, fetch: F) -> Result<String>
+    where
+        F: FnOnce() -> Fut,
+        Fut: std::future::Future<Output = Result<(String, std::time::Duration)>>,
+    {
+        let mut token = self.token.lock().await;
+        let now = std::time::Instant::now();
+        match token.as_ref() {
+            Some((access_token, expires_at)) if now + Self::REFRESH_MARGIN < *expires_at => {
+                Ok(access_token.clone())
+            }
+            _ => {
+                let (access_token, expires_in) = fetch().await?;
+                *token = Some((access_token.clone(), now + expires_in));
+                Ok(access_token)
+            }
+        }
+    }
+}
@@ -0,0 +1,214 @@
+/*!
+Owner and description of tables.
+
+Like tags, the owner and the description are stored with the catalog entry of a table, so they can
+be changed without writing new metadata. [PostgresCatalog::describe_table] returns them together
+with the tags and the times recorded in the catalog entry.
+*/
+
+use std::{
+    collections::HashMap,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use anyhow::{anyhow, Result};
+use iceberg_rs::catalog::table_identifier::TableIdentifier;
+use serde_json::{json, Value};
+
+use super::{
+    access::Action,
+    namespace::table_condition,
+    query::{epoch_millis, literal},
+    tags::parse_tags,
+    PostgresCatalog, CREATED_AT_COLUMN, DESCRIPTION_COLUMN, METADATA_LOCATION_COLUMN, OWNER_COLUMN,
+    PROTECTED_COLUMN, TAGS_COLUMN, UPDATED_AT_COLUMN,
+};
+
+/// Catalog-level information about a table
+#[derive(Debug, Clone)]
+pub struct TableDescription {
+    /// Identifier of the table
+    pub identifier: TableIdentifier,
+    /// Location of the current metadata file
+    pub metadata_location: String,
+    /// Owner of the table, if one was set
+    pub owner: Option<String>,
+    /// Description of the table, if one was set
+    pub description: Option<String>,
+    /// Tags of the table
+    pub tags: HashMap<String, String>,
+    /// Whether the table is protected against drops, see [protection](super::protection)
+    pub protected: bool,
+    /// Time the table was registered with the catalog
+    pub created_at: SystemTime,
+    /// Time of the last commit, or of the registration if the table wasn't committed since
+    pub updated_at: SystemTime,
+}
+
+impl TableDescription {
+    /// Description as JSON object with the fields `identifier`, `metadata-location`, `owner`,
+    /// `description`, `tags`, `protected`, `created-at-ms` and `updated-at-ms`.
+    pub fn to_json(&self) -> Value {
+        let millis = |time: SystemTime| {
+            time.duration_since(UNIX_EPOCH)
+                .unwrap_or_default()
+                .as_millis() as i64
+        };
+        json!({
+            "identifier": self.identifier.to_string(),
+            "metadata-location": self.metadata_location,
+            "owner": self.owner,
+            "description": self.description,
+            "tags": self.tags,
+            "protected": self.protected,
+            "created-at-ms": millis(self.created_at),
+            "updated-at-ms": millis(self.updated_at),
+        })
+    }
+}
+
+impl PostgresCatalog {
+    /// Set the owner of the table, `None` removes it.
+    pub async fn set_table_owner(
+        &self,
+        identifier: &TableIdentifier,
+        owner: Option<&str>,
+    ) -> Result<()> {
+        self.check_writable("Setting the owner")?;
+        self.update_description_column(identifier, OWNER_COLUMN, owner)
+            .await
+    }
+
+    /// Set the description of the table, `None` removes it.
+    pub async fn set_table_description(
+        &self,
+        identifier: &TableIdentifier,
+        description: Option<&str>,
+    ) -> Result<()> {
+        self.check_writable("Setting the description")?;
+        self.update_description_column(identifier, DESCRIPTION_COLUMN, description)
+            .await
+    }
+
+    /// Owner, description, tags and catalog entry times of the table
+    pub async fn describe_table(&self, identifier: &TableIdentifier) -> Result<TableDescription> {
+        let identifier = self.case_sensitivity.normalize(identifier)?;
+        self.authorize(
+            Action::Read,
+            identifier.namespace(),
+            Some(identifier.name()),
+        )
+        .await?;
+        let rows = self
+            .query(
+                self.read_connection(),
+                &("SELECT ".to_string()
+                    + METADATA_LOCATION_COLUMN
+                    + ", "
+                    + OWNER_COLUMN
+                    + ", "
+                    + DESCRIPTION_COLUMN
+                    + ", "
+                    + TAGS_COLUMN
+                    + "::TEXT AS "
+                    + TAGS_COLUMN
+                    + ", "
+                    + PROTECTED_COLUMN
+                    + ", "
+                    + &epoch_millis(CREATED_AT_COLUMN)
+                    + ", "
+                    + &epoch_millis(UPDATED_AT_COLUMN)
+                    + " FROM "
+                    + &self.catalog_table.qualified
+                    + " WHERE "
+                    + &table_condition(&self.name, &identifier)
+                    + ";"),
+            )
+            .await?;
+        let row = rows.first().ok_or_else(|| {
+            anyhow!("Describing the table failed. No table matched the identifier.")
+        })?;
+        let time = |millis: i64| UNIX_EPOCH + Duration::from_millis(millis.max(0) as u64);
+        Ok(TableDescription {
+            identifier,
+            metadata_location: row.try_get_string(METADATA_LOCATION_COLUMN)?,
+            owner: row.try_get_opt_string(OWNER_COLUMN)?,
+            description: row.try_get_opt_string(DESCRIPTION_COLUMN)?,
+            tags: parse_tags(&row.try_get_string(TAGS_COLUMN)?)?,
+            protected: row.try_get_bool(PROTECTED_COLUMN)?,
+            created_at: time(row.try_get_i64(CREATED_AT_COLUMN)?),
+            updated_at: time(row.try_get_i64(UPDATED_AT_COLUMN)?),
+        })
+    }
+
+    async fn update_description_column(
+        &self,
+        identifier: &TableIdentifier,
+        column: &str,
+        value: Option<&str>,
+    ) -> Result<()> {
+        let identifier = &self.case_sensitivity.normalize(identifier)?;
+        self.authorize(
+            Action::Commit,
+            identifier.namespace(),
+            Some(identifier.name()),
+        )
+        .await?;
+        self.mark_write();
+        let n_rows = self
+            .execute(
+                &self.primary,
+                &("UPDATE ".to_string()
+                    + &self.catalog_table.qualified
+                    + " SET "
+                    + column
+                    + " = "
+                    + &value.map(literal).unwrap_or_else(|| "NULL".to_string())
+                    + " WHERE "
+                    + &table_condition(&self.name, identifier)
+                    + ";"),
+            )
+            .await?;
+        if n_rows == 1 {
+            Ok(())
+        } else {
+            Err(anyhow!(
+                "Updating the {} failed. No table matched the identifier.",
+                column
+            ))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        collections::HashMap,
+        time::{Duration, UNIX_EPOCH},
+    };
+
+    use iceberg_rs::catalog::table_identifier::TableIdentifier;
+
+    use super::TableDescription;
+
+    #[test]
+    fn test_description_json() {
+        let description = TableDescription {
+            identifier: TableIdentifier::parse("sales.orders").unwrap(),
+            metadata_location: "s3://bucket/sales/orders/metadata/v1.metadata.json".to_string(),
+            owner: Some("sales".to_string()),
+            description: None,
+            tags: HashMap::from_iter(vec![("pii".to_string(), "false".to_string())]),
+            protected: true,
+            created_at: UNIX_EPOCH + Duration::from_secs(1),
+            updated_at: UNIX_EPOCH + Duration::from_secs(2),
+        };
+        let json = description.to_json();
+        assert_eq!(json["identifier"], "sales.orders");
+        assert_eq!(json["owner"], "sales");
+        assert!(json["description"].is_null());
+        assert_eq!(json["tags"]["pii"], "false");
+        assert_eq!(json["protected"], true);
+        assert_eq!(json["updated-at-ms"], 2000);
+    }
+}
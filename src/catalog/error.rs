@@ -0,0 +1,177 @@
+/*!
+Errors of the catalog that callers can react to.
+
+The catalog returns [anyhow::Error]s. Errors with a specific cause wrap a [CatalogError], which can
+be recovered with `err.downcast_ref::<CatalogError>()`.
+*/
+
+use std::{fmt, time::Duration};
+
+use super::{access::Action, quota::QuotaKind};
+
+/// Error with a cause that callers can handle
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CatalogError {
+    /// The operation modifies the catalog, but the catalog is read-only
+    ReadOnly {
+        /// Name of the rejected operation
+        operation: &'static str,
+    },
+    /// The access policy denied the operation
+    AccessDenied {
+        /// Principal of the catalog
+        principal: String,
+        /// Denied action
+        action: Action,
+        /// Namespace or table the action was denied on
+        resource: String,
+    },
+    /// The operation would exceed a quota of the namespace
+    QuotaExceeded {
+        /// Namespace of the quota
+        namespace: String,
+        /// Limited resource
+        kind: QuotaKind,
+        /// Limit of the quota
+        limit: i64,
+        /// Usage after the operation
+        requested: i64,
+    },
+    /// The metadata file doesn't match the checksum recorded when it was committed
+    IntegrityMismatch {
+        /// Location of the metadata file
+        location: String,
+        /// Recorded checksum
+        expected: String,
+        /// Checksum of the file
+        actual: String,
+    },
+    /// A commit policy rejected the new metadata of the table
+    CommitRejected {
+        /// Identifier of the table
+        table: String,
+        /// Reason given by the policy
+        reason: String,
+    },
+    /// The table doesn't exist in the catalog
+    NotFound {
+        /// Identifier of the table
+        table: String,
+    },
+    /// The table exists but isn't in the expected state, because it was changed concurrently or
+    /// the new metadata belongs to a different table. Reloading the table and retrying the commit
+    /// can succeed.
+    CommitConflict {
+        /// Identifier of the table
+        table: String,
+    },
+    /// The rate limit of the operation or of the catalog was exceeded
+    RateLimited {
+        /// Name of the limited operation
+        operation: String,
+    },
+    /// Another writer holds the commit lock of the table
+    Locked {
+        /// Identifier of the table
+        table: String,
+        /// Owner of the lock, empty if its lease expired in the meantime
+        owner: String,
+    },
+    /// The caller couldn't be authenticated
+    Unauthenticated {
+        /// Why the credentials of the caller were rejected
+        reason: String,
+    },
+    /// The table is protected against drops and renames, see [protection](super::protection)
+    Protected {
+        /// Identifier of the table
+        table: String,
+    },
+    /// The table is archived and rejects commits, see [archive](super::archive)
+    Archived {
+        /// Identifier of the table
+        table: String,
+    },
+    /// The operation didn't finish within its timeout. It may still have been applied.
+    TimedOut {
+        /// Name of the operation
+        operation: String,
+        /// Timeout of the operation
+        timeout: Duration,
+    },
+}
+
+impl fmt::Display for CatalogError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CatalogError::ReadOnly { operation } => {
+                write!(f, "{} failed. The catalog is read-only.", operation)
+            }
+            CatalogError::AccessDenied {
+                principal,
+                action,
+                resource,
+            } => write!(
+                f,
+                "Access denied. {} is not allowed to {} {}.",
+                principal, action, resource
+            ),
+            CatalogError::QuotaExceeded {
+                namespace,
+                kind,
+                limit,
+                requested,
+            } => write!(
+                f,
+                "Quota exceeded. The namespace {} is limited to {} {}, the operation requires {}.",
+                namespace, limit, kind, requested
+            ),
+            CatalogError::IntegrityMismatch {
+                location,
+                expected,
+                actual,
+            } => write!(
+                f,
+                "The metadata file {} was modified. Its checksum is {}, the catalog recorded {}.",
+                location, actual, expected
+            ),
+            CatalogError::CommitRejected { table, reason } => {
+                write!(f, "The commit to {} was rejected. {}", table, reason)
+            }
+            CatalogError::NotFound { table } => {
+                write!(f, "The table {} doesn't exist.", table)
+            }
+            CatalogError::CommitConflict { table } => write!(
+                f,
+                "Updating the table {} failed. The table was changed concurrently or the new metadata belongs to a different table.",
+                table
+            ),
+            CatalogError::RateLimited { operation } => write!(
+                f,
+                "Rate limit exceeded. Too many {} operations, retry later.",
+                operation
+            ),
+            CatalogError::Locked { table, owner } => {
+                write!(f, "The table {} is locked by {}.", table, owner)
+            }
+            CatalogError::Unauthenticated { reason } => {
+                write!(f, "Authentication failed. {}", reason)
+            }
+            CatalogError::Protected { table } => write!(
+                f,
+                "The table {} is protected. Remove the protection or override it to drop or rename the table.",
+                table
+            ),
+            CatalogError::Archived { table } => write!(
+                f,
+                "The table {} is archived. Unarchive it to commit to it.",
+                table
+            ),
+            CatalogError::TimedOut { operation, timeout } => {
+                write!(f, "{} timed out after {:?}.", operation, timeout)
+            }
+        }
+    }
+}
+
+impl std::error::Error for CatalogError {}
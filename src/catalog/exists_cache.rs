@@ -0,0 +1,390 @@
+/*!
+Cache for the results of `table_exists`.
+
+Query planners check the existence of the same tables many times while planning a single query.
+With [with_table_exists_cache](super::builder::PostgresCatalogBuilder::with_table_exists_cache),
+positive and negative results are cached for the configured time to live. Registering, dropping
+and restoring a table through the catalog invalidates its entry immediately.
+
+Changes by other catalog instances are received over `LISTEN`/`NOTIFY`: `initialize` installs a
+trigger on the catalog table that notifies every created, dropped or renamed table, and the catalog
+listens for these notifications on a separate connection. While that connection is down, changes
+of other instances are only picked up when the entries expire, and the whole cache is cleared when
+the connection is re-established. Catalogs on a sqlx pool or on a client driven by the caller don't
+listen. Poolers in transaction mode don't deliver notifications, so the cache would silently serve
+stale results; building a catalog with the cache and
+[with_transaction_pooling](super::builder::PostgresCatalogBuilder::with_transaction_pooling)
+fails.
+*/
+
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex, Weak},
+    time::{Duration, Instant},
+};
+
+use anyhow::{anyhow, Result};
+use futures::StreamExt;
+use iceberg_rs::catalog::table_identifier::TableIdentifier;
+use postgres_native_tls::MakeTlsConnector;
+use serde_json::Value;
+use tokio::{
+    io::{AsyncRead, AsyncWrite},
+    task::JoinHandle,
+};
+use tokio_postgres::{AsyncMessage, Config, Connection, NoTls};
+
+use super::{
+    credentials::CredentialsProvider,
+    namespace::namespace_key,
+    query::{identifier, literal},
+    PostgresCatalog, CATALOG_NAME_COLUMN, TABLE_NAMESPACE_COLUMN, TABLE_NAME_COLUMN,
+};
+
+/// Maximum number of cached results. Expired entries are removed when the cache is full.
+static MAX_ENTRIES: usize = 10_000;
+
+/// Time to wait before the listener reconnects
+static RECONNECT_DELAY: Duration = Duration::from_secs(1);
+
+/// Cached existence of tables, by catalog-local namespace key and table name
+pub(crate) struct ExistsCache {
+    ttl: Duration,
+    state: Mutex<State>,
+    listener: Mutex<Option<JoinHandle<()>>>,
+}
+
+#[derive(Default)]
+struct State {
+    entries: HashMap<(String, String), (bool, Instant)>,
+    /// Incremented by every invalidation, so that results of queries that ran concurrently with
+    /// an invalidation aren't cached.
+    generation: u64,
+}
+
+impl ExistsCache {
+    pub(crate) fn new(ttl: Duration) -> Self {
+        ExistsCache {
+            ttl,
+            state: Mutex::new(State::default()),
+            listener: Mutex::new(None),
+        }
+    }
+
+    /// Cached existence of the table, if there is an entry that didn't expire, and the current
+    /// generation to pass to [ExistsCache::insert].
+    pub(crate) fn get(&self, namespace: &str, name: &str) -> (Option<bool>, u64) {
+        match self.state.lock() {
+            Ok(state) => (
+                state
+                    .entries
+                    .get(&(namespace.to_string(), name.to_string()))
+                    .filter(|(_, inserted)| inserted.elapsed() < self.ttl)
+                    .map(|(exists, _)| *exists),
+                state.generation,
+            ),
+            Err(_) => (None, 0),
+        }
+    }
+
+    /// Cache the result of a query that started at `generation`. The result is discarded if the
+    /// cache was invalidated in the meantime.
+    pub(crate) fn insert(&self, namespace: &str, name: &str, exists: bool, generation: u64) {
+        if let Ok(mut state) = self.state.lock() {
+            if state.generation != generation {
+                return;
+            }
+            if state.entries.len() >= MAX_ENTRIES {
+                let ttl = self.ttl;
+                state
+                    .entries
+                    .retain(|_, (_, inserted)| inserted.elapsed() < ttl);
+                if state.entries.len() >= MAX_ENTRIES {
+                    state.entries.clear();
+                }
+            }
+            state.entries.insert(
+                (namespace.to_string(), name.to_string()),
+                (exists, Instant::now()),
+            );
+        }
+    }
+
+    /// Remove the entry of the table
+    pub(crate) fn invalidate(&self, namespace: &str, name: &str) {
+        if let Ok(mut state) = self.state.lock() {
+            state.generation += 1;
+            state
+                .entries
+                .remove(&(namespace.to_string(), name.to_string()));
+        }
+    }
+
+    /// Remove all entries
+    pub(crate) fn clear(&self) {
+        if let Ok(mut state) = self.state.lock() {
+            state.generation += 1;
+            state.entries.clear();
+        }
+    }
+
+    /// Invalidate the table of a notification of the trigger if it belongs to the catalog.
+    fn apply(&self, catalog: &str, payload: &str) {
+        let payload: Value = match serde_json::from_str(payload) {
+            Ok(payload) => payload,
+            // Entries of malformed notifications can't be identified.
+            Err(_) => return self.clear(),
+        };
+        if payload["catalog"].as_str() != Some(catalog) {
+            return;
+        }
+        match (payload["namespace"].as_str(), payload["name"].as_str()) {
+            (Some(namespace), Some(name)) => self.invalidate(namespace, name),
+            _ => self.clear(),
+        }
+    }
+
+    /// Listen for the notifications of the trigger on `channel` in a background task that
+    /// reconnects until the cache is closed.
+    pub(crate) fn listen(
+        self: &Arc<Self>,
+        catalog: String,
+        channel: String,
+        config: Config,
+        credentials: Option<Arc<dyn CredentialsProvider>>,
+        tls: Option<MakeTlsConnector>,
+    ) {
+        let cache = Arc::downgrade(self);
+        let task = tokio::spawn(async move {
+            // Errors end the connection, the loop reconnects after the delay.
+            while cache.strong_count() > 0 {
+                let _ = listen(
+                    &cache,
+                    &catalog,
+                    &channel,
+                    config.clone(),
+                    credentials.as_deref(),
+                    tls.clone(),
+                )
+                .await;
+                tokio::time::sleep(RECONNECT_DELAY).await;
+            }
+        });
+        if let Ok(mut listener) = self.listener.lock() {
+            if let Some(previous) = listener.replace(task) {
+                previous.abort();
+            }
+        }
+    }
+
+    /// Stop listening for notifications
+    pub(crate) fn close(&self) {
+        if let Ok(mut listener) = self.listener.lock() {
+            if let Some(task) = listener.take() {
+                task.abort();
+            }
+        }
+    }
+}
+
+impl Drop for ExistsCache {
+    fn drop(&mut self) {
+        self.close();
+    }
+}
+
+/// Connect, listen on the channel and apply the notifications until the connection ends.
+async fn listen(
+    cache: &Weak<ExistsCache>,
+    catalog: &str,
+    channel: &str,
+    mut config: Config,
+    credentials: Option<&dyn CredentialsProvider>,
+    tls: Option<MakeTlsConnector>,
+) -> Result<()> {
+    if let Some(provider) = credentials {
+        let credentials = provider.credentials().await?;
+        config.user(&credentials.user);
+        config.password(credentials.password.expose());
+    }
+    let (client, driver) = match tls {
+        Some(tls) => {
+            let (client, connection) = config.connect(tls).await.map_err(|err| anyhow!(err))?;
+            (
+                client,
+                tokio::spawn(apply_notifications(
+                    connection,
+                    cache.clone(),
+                    catalog.to_string(),
+                )),
+            )
+        }
+        None => {
+            let (client, connection) = config.connect(NoTls).await.map_err(|err| anyhow!(err))?;
+            (
+                client,
+                tokio::spawn(apply_notifications(
+                    connection,
+                    cache.clone(),
+                    catalog.to_string(),
+                )),
+            )
+        }
+    };
+    // Aborts the driver if the listener is aborted while waiting.
+    struct AbortOnDrop(JoinHandle<()>);
+    impl Drop for AbortOnDrop {
+        fn drop(&mut self) {
+            self.0.abort();
+        }
+    }
+    let mut driver = AbortOnDrop(driver);
+    client
+        .batch_execute(&("LISTEN ".to_string() + &identifier(channel) + ";"))
+        .await
+        .map_err(|err| anyhow!(err))?;
+    // Notifications sent while no connection was listening are lost.
+    if let Some(cache) = cache.upgrade() {
+        cache.clear();
+    }
+    let _ = (&mut driver.0).await;
+    Ok(())
+}
+
+async fn apply_notifications<S, T>(
+    mut connection: Connection<S, T>,
+    cache: Weak<ExistsCache>,
+    catalog: String,
+) where
+    S: AsyncRead + AsyncWrite + Unpin,
+    T: AsyncRead + AsyncWrite + Unpin,
+{
+    let mut messages = futures::stream::poll_fn(|cx| connection.poll_message(cx));
+    while let Some(Ok(message)) = messages.next().await {
+        if let AsyncMessage::Notification(notification) = message {
+            match cache.upgrade() {
+                Some(cache) => cache.apply(&catalog, notification.payload()),
+                None => return,
+            }
+        }
+    }
+}
+
+impl PostgresCatalog {
+    /// Remove the cached existence of a table that was created or dropped by this instance.
+    pub(crate) fn invalidate_exists(&self, identifier: &TableIdentifier) {
+        if let Some(cache) = &self.exists_cache {
+            cache.invalidate(&namespace_key(identifier.namespace()), identifier.name());
+        }
+    }
+
+    /// Remove all cached existence results after a change to the whole catalog.
+    pub(crate) fn clear_exists_cache(&self) {
+        if let Some(cache) = &self.exists_cache {
+            cache.clear();
+        }
+    }
+
+    /// Install the trigger that notifies the listeners of the table existence caches about
+    /// created, dropped and renamed tables.
+    pub(crate) async fn create_exists_notification(&self) -> Result<()> {
+        let function = self.catalog_table.function("notify_exists");
+        let trigger = self.catalog_table.index("exists_trigger");
+        let channel = literal(&self.catalog_table.channel("exists"));
+        let notify = |row: &str| {
+            "PERFORM pg_notify(".to_string()
+                + &channel
+                + ", json_build_object('catalog', "
+                + row
+                + "."
+                + CATALOG_NAME_COLUMN
+                + ", 'namespace', "
+                + row
+                + "."
+                + TABLE_NAMESPACE_COLUMN
+                + ", 'name', "
+                + row
+                + "."
+                + TABLE_NAME_COLUMN
+                + ")::text);"
+        };
+        self.execute(
+            &self.primary,
+            &("CREATE OR REPLACE FUNCTION ".to_string()
+                + &function
+                + "() RETURNS trigger LANGUAGE plpgsql AS $$ BEGIN IF TG_OP <> 'INSERT' THEN "
+                + &notify("OLD")
+                + " END IF; IF TG_OP <> 'DELETE' THEN "
+                + &notify("NEW")
+                + " END IF; RETURN NULL; END; $$;"),
+        )
+        .await?;
+        for statement in [
+            "DROP TRIGGER IF EXISTS ".to_string()
+                + &trigger
+                + " ON "
+                + &self.catalog_table.qualified
+                + ";",
+            "CREATE TRIGGER ".to_string()
+                + &trigger
+                + " AFTER INSERT OR DELETE OR UPDATE OF "
+                + CATALOG_NAME_COLUMN
+                + ", "
+                + TABLE_NAMESPACE_COLUMN
+                + ", "
+                + TABLE_NAME_COLUMN
+                + " ON "
+                + &self.catalog_table.qualified
+                + " FOR EACH ROW EXECUTE FUNCTION "
+                + &function
+                + "();",
+        ] {
+            self.execute(&self.primary, &statement).await?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::ExistsCache;
+
+    #[test]
+    fn test_exists_cache() {
+        let cache = ExistsCache::new(Duration::from_secs(60));
+        let (cached, generation) = cache.get("cache", "one");
+        assert_eq!(cached, None);
+        cache.insert("cache", "one", true, generation);
+        cache.insert("cache", "two", false, generation);
+        assert_eq!(cache.get("cache", "one").0, Some(true));
+        assert_eq!(cache.get("cache", "two").0, Some(false));
+
+        // Results of queries that started before an invalidation aren't cached.
+        let (_, generation) = cache.get("cache", "three");
+        cache.invalidate("cache", "one");
+        cache.insert("cache", "three", true, generation);
+        assert_eq!(cache.get("cache", "one").0, None);
+        assert_eq!(cache.get("cache", "three").0, None);
+
+        cache.apply(
+            "other_catalog",
+            r#"{"catalog":"catalog","namespace":"cache","name":"two"}"#,
+        );
+        assert_eq!(cache.get("cache", "two").0, Some(false));
+        cache.apply(
+            "catalog",
+            r#"{"catalog":"catalog","namespace":"cache","name":"two"}"#,
+        );
+        assert_eq!(cache.get("cache", "two").0, None);
+    }
+
+    #[test]
+    fn test_exists_cache_expiry() {
+        let cache = ExistsCache::new(Duration::ZERO);
+        let (_, generation) = cache.get("cache", "one");
+        cache.insert("cache", "one", true, generation);
+        assert_eq!(cache.get("cache", "one").0, None);
+    }
+}
@@ -0,0 +1,111 @@
+/*!
+Fault injection for tests of commit conflict handling.
+
+A [FaultInjector] passed to
+[with_fault_injector](super::builder::PostgresCatalogBuilder::with_fault_injector) acts on every
+commit after the new metadata file was written and before the metadata pointer is swapped. It can
+delay commits, hold them until the test releases them and let them fail as if the table was changed
+concurrently. Forced failures return the same error as a real conflict, so retry logic can be
+tested deterministically. Only available with the `fault-injection` feature.
+*/
+
+use std::{
+    sync::{
+        atomic::{AtomicU32, AtomicU64, Ordering},
+        Arc, Mutex,
+    },
+    time::Duration,
+};
+
+use tokio::sync::{OwnedRwLockWriteGuard, RwLock};
+
+/// Injects faults into the commits of a catalog
+#[derive(Debug, Default)]
+pub struct FaultInjector {
+    delay: Mutex<Option<Duration>>,
+    failures: AtomicU32,
+    injected_failures: AtomicU64,
+    gate: Arc<RwLock<()>>,
+}
+
+/// Holds all commits before the pointer swap until it is dropped
+pub struct CommitHold(OwnedRwLockWriteGuard<()>);
+
+impl FaultInjector {
+    /// Injector that doesn't inject any faults yet
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Delay every commit before the pointer swap, `None` removes the delay.
+    pub fn delay_commits(&self, delay: Option<Duration>) {
+        if let Ok(mut current) = self.delay.lock() {
+            *current = delay;
+        }
+    }
+
+    /// Let the next `n` commits fail as if the table was changed concurrently.
+    pub fn fail_next_commits(&self, n: u32) {
+        self.failures.store(n, Ordering::SeqCst);
+    }
+
+    /// Hold all commits before the pointer swap until the returned hold is dropped. Waits for
+    /// commits that already passed the hold.
+    pub async fn hold_commits(&self) -> CommitHold {
+        CommitHold(Arc::clone(&self.gate).write_owned().await)
+    }
+
+    /// Number of commits that failed because of the injector
+    pub fn injected_failures(&self) -> u64 {
+        self.injected_failures.load(Ordering::SeqCst)
+    }
+
+    /// Apply the faults to a commit. Returns `true` if the commit has to fail.
+    pub(crate) async fn before_swap(&self) -> bool {
+        let _passed = self.gate.read().await;
+        let delay = self.delay.lock().ok().and_then(|delay| *delay);
+        if let Some(delay) = delay {
+            tokio::time::sleep(delay).await;
+        }
+        let fail = self
+            .failures
+            .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |failures| {
+                failures.checked_sub(1)
+            })
+            .is_ok();
+        if fail {
+            self.injected_failures.fetch_add(1, Ordering::SeqCst);
+        }
+        fail
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{sync::Arc, time::Duration};
+
+    use super::FaultInjector;
+
+    #[tokio::test]
+    async fn test_fault_injector() {
+        let injector = Arc::new(FaultInjector::new());
+        assert!(!injector.before_swap().await);
+        injector.fail_next_commits(2);
+        assert!(injector.before_swap().await);
+        assert!(injector.before_swap().await);
+        assert!(!injector.before_swap().await);
+        assert_eq!(injector.injected_failures(), 2);
+
+        let hold = injector.hold_commits().await;
+        let commit = tokio::spawn({
+            let injector = Arc::clone(&injector);
+            async move { injector.before_swap().await }
+        });
+        let mut commit = commit;
+        assert!(tokio::time::timeout(Duration::from_millis(50), &mut commit)
+            .await
+            .is_err());
+        drop(hold);
+        assert!(!commit.await.unwrap());
+    }
+}
@@ -0,0 +1,302 @@
+/*!
+Federation of several catalogs stored in the same database.
+
+A [FederatedCatalog] presents the tables of several [PostgresCatalog]s as one catalog. An
+[IdentifierMapping] decides which catalog stores a namespace and how it is named there, and maps
+the stored namespaces back to the names clients use. This way one client configuration can reach
+several logical catalogs, and mappings can reject identifiers, for example ones without the prefix
+of an environment.
+
+```no_run
+# async fn federate(x: std::sync::Arc<iceberg_catalog_postgres::catalog::PostgresCatalog>, y: std::sync::Arc<iceberg_catalog_postgres::catalog::PostgresCatalog>) -> anyhow::Result<()> {
+use std::sync::Arc;
+
+use iceberg_catalog_postgres::catalog::federation::{FederatedCatalog, PrefixMapping};
+use iceberg_rs::catalog::{table_identifier::TableIdentifier, Catalog};
+
+// `prod_x.sales.orders` is the table `sales.orders` of the catalog `x`.
+let catalog = Arc::new(FederatedCatalog::new(Arc::new(PrefixMapping::new("prod_")), vec![x, y])?);
+let table = catalog.load_table(TableIdentifier::parse("prod_x.sales.orders")?).await?;
+# Ok(())
+# }
+```
+*/
+
+use std::{collections::HashMap, sync::Arc};
+
+use anyhow::{anyhow, Result};
+use iceberg_rs::{
+    catalog::{namespace::Namespace, table_identifier::TableIdentifier, Catalog},
+    model::schema::SchemaV2,
+    object_store::ObjectStore,
+    table::{table_builder::TableBuilder, Table},
+};
+
+use super::PostgresCatalog;
+
+/// Maps the namespaces clients use onto the catalogs and namespaces that store them. Tables keep
+/// their names, only their namespace is mapped.
+pub trait IdentifierMapping: Send + Sync {
+    /// Name of the catalog and stored namespace of a namespace as used by clients. An error
+    /// rejects the namespace.
+    fn map_namespace(&self, namespace: &Namespace) -> Result<(String, Namespace)>;
+
+    /// Namespace as used by clients of a namespace stored in the catalog
+    fn unmap_namespace(&self, catalog: &str, namespace: &Namespace) -> Result<Namespace>;
+
+    /// Name of the catalog and stored identifier of a table as used by clients
+    fn map_table(&self, identifier: &TableIdentifier) -> Result<(String, TableIdentifier)> {
+        let (catalog, namespace) = self.map_namespace(identifier.namespace())?;
+        Ok((catalog, join(&namespace, identifier.name())?))
+    }
+
+    /// Identifier as used by clients of a table stored in the catalog
+    fn unmap_table(&self, catalog: &str, identifier: &TableIdentifier) -> Result<TableIdentifier> {
+        join(
+            &self.unmap_namespace(catalog, identifier.namespace())?,
+            identifier.name(),
+        )
+    }
+}
+
+/// Maps the first namespace level `<prefix><catalog>` onto the catalog `<catalog>`, so that
+/// `prod_x.sales.orders` with the prefix `prod_` is the table `sales.orders` of the catalog `x`.
+/// Namespaces without the prefix are rejected.
+#[derive(Debug, Clone)]
+pub struct PrefixMapping {
+    prefix: String,
+}
+
+impl PrefixMapping {
+    /// Mapping for catalogs whose first namespace level starts with `prefix`
+    pub fn new(prefix: &str) -> Self {
+        PrefixMapping {
+            prefix: prefix.to_string(),
+        }
+    }
+}
+
+impl IdentifierMapping for PrefixMapping {
+    fn map_namespace(&self, namespace: &Namespace) -> Result<(String, Namespace)> {
+        let levels = namespace.levels();
+        let catalog = levels
+            .first()
+            .and_then(|level| level.strip_prefix(&self.prefix))
+            .filter(|catalog| !catalog.is_empty())
+            .ok_or_else(|| {
+                anyhow!(
+                    "The namespace {} doesn't start with a catalog with the prefix {}.",
+                    namespace,
+                    self.prefix
+                )
+            })?;
+        Ok((catalog.to_string(), Namespace::try_new(&levels[1..])?))
+    }
+
+    fn unmap_namespace(&self, catalog: &str, namespace: &Namespace) -> Result<Namespace> {
+        Namespace::try_new(
+            &[
+                vec![self.prefix.clone() + catalog],
+                namespace.levels().to_vec(),
+            ]
+            .concat(),
+        )
+    }
+}
+
+/// Catalog that routes every operation to the [PostgresCatalog] selected by its
+/// [IdentifierMapping]. Loaded and created tables carry the identifiers clients use and commit
+/// through the federated catalog. Tables created with [Catalog::build_table] belong to the
+/// catalog that stores them and carry the stored identifier.
+pub struct FederatedCatalog {
+    mapping: Arc<dyn IdentifierMapping>,
+    catalogs: HashMap<String, Arc<PostgresCatalog>>,
+    object_store: Arc<dyn ObjectStore>,
+}
+
+impl FederatedCatalog {
+    /// Federate the catalogs, which are addressed by their names. The object store of the first
+    /// catalog is the object store of the federated catalog.
+    pub fn new(
+        mapping: Arc<dyn IdentifierMapping>,
+        catalogs: Vec<Arc<PostgresCatalog>>,
+    ) -> Result<Self> {
+        let object_store = catalogs
+            .first()
+            .ok_or_else(|| anyhow!("A federated catalog needs at least one catalog."))?
+            .object_store();
+        let mut by_name = HashMap::new();
+        for catalog in catalogs {
+            let name = catalog.name().to_string();
+            if by_name.insert(name.clone(), catalog).is_some() {
+                return Err(anyhow!("The catalog {} is federated more than once.", name));
+            }
+        }
+        Ok(FederatedCatalog {
+            mapping,
+            catalogs: by_name,
+            object_store,
+        })
+    }
+
+    /// Catalog with the name, if it is federated
+    pub fn catalog(&self, name: &str) -> Option<&Arc<PostgresCatalog>> {
+        self.catalogs.get(name)
+    }
+
+    /// Object store of the table, resolved by the catalog that stores it
+    pub async fn object_store_for(
+        &self,
+        identifier: &TableIdentifier,
+    ) -> Result<Arc<dyn ObjectStore>> {
+        let (catalog, stored) = self.route(identifier)?;
+        catalog.object_store_for(&stored).await
+    }
+
+    /// Catalog and stored identifier of a table as used by clients
+    fn route(
+        &self,
+        identifier: &TableIdentifier,
+    ) -> Result<(&Arc<PostgresCatalog>, TableIdentifier)> {
+        let (name, identifier) = self.mapping.map_table(identifier)?;
+        let catalog = self
+            .catalogs
+            .get(&name)
+            .ok_or_else(|| anyhow!("The catalog {} isn't federated.", name))?;
+        Ok((catalog, identifier))
+    }
+
+    /// Rebind a table of a federated catalog to the federated catalog and the client identifier.
+    async fn rebind(self: Arc<Self>, identifier: TableIdentifier, table: Table) -> Result<Table> {
+        let catalog: Arc<dyn Catalog> = self;
+        Table::new_metastore_table(
+            identifier,
+            catalog,
+            table.metadata().clone(),
+            table.metadata_location(),
+        )
+        .await
+    }
+}
+
+#[async_trait::async_trait]
+impl Catalog for FederatedCatalog {
+    async fn list_tables(&self, namespace: &Namespace) -> Result<Vec<TableIdentifier>> {
+        let (name, stored) = self.mapping.map_namespace(namespace)?;
+        let catalog = self
+            .catalogs
+            .get(&name)
+            .ok_or_else(|| anyhow!("The catalog {} isn't federated.", name))?;
+        catalog
+            .list_tables(&stored)
+            .await?
+            .iter()
+            .map(|identifier| self.mapping.unmap_table(&name, identifier))
+            .collect()
+    }
+    async fn create_table(
+        self: Arc<Self>,
+        identifier: TableIdentifier,
+        schema: SchemaV2,
+    ) -> Result<Table> {
+        let (catalog, stored) = self.route(&identifier)?;
+        let table = Arc::clone(catalog).create_table(stored, schema).await?;
+        self.rebind(identifier, table).await
+    }
+    async fn table_exists(&self, identifier: &TableIdentifier) -> Result<bool> {
+        let (catalog, stored) = self.route(identifier)?;
+        catalog.table_exists(&stored).await
+    }
+    async fn drop_table(&self, identifier: &TableIdentifier) -> Result<()> {
+        let (catalog, stored) = self.route(identifier)?;
+        catalog.drop_table(&stored).await
+    }
+    async fn load_table(self: Arc<Self>, identifier: TableIdentifier) -> Result<Table> {
+        let (catalog, stored) = self.route(&identifier)?;
+        let table = Arc::clone(catalog).load_table(stored).await?;
+        self.rebind(identifier, table).await
+    }
+    async fn invalidate_table(&self, identifier: &TableIdentifier) -> Result<()> {
+        let (catalog, stored) = self.route(identifier)?;
+        catalog.invalidate_table(&stored).await
+    }
+    async fn register_table(
+        self: Arc<Self>,
+        identifier: TableIdentifier,
+        metadata_file_location: &str,
+    ) -> Result<Table> {
+        let (catalog, stored) = self.route(&identifier)?;
+        let table = Arc::clone(catalog)
+            .register_table(stored, metadata_file_location)
+            .await?;
+        self.rebind(identifier, table).await
+    }
+    async fn update_table(
+        self: Arc<Self>,
+        identifier: TableIdentifier,
+        metadata_file_location: &str,
+        previous_metadata_file_location: &str,
+    ) -> Result<Table> {
+        let (catalog, stored) = self.route(&identifier)?;
+        let table = Arc::clone(catalog)
+            .update_table(
+                stored,
+                metadata_file_location,
+                previous_metadata_file_location,
+            )
+            .await?;
+        self.rebind(identifier, table).await
+    }
+    async fn build_table(
+        self: Arc<Self>,
+        identifier: TableIdentifier,
+        schema: SchemaV2,
+    ) -> Result<TableBuilder> {
+        let (catalog, stored) = self.route(&identifier)?;
+        Arc::clone(catalog).build_table(stored, schema).await
+    }
+    /// Initialize all federated catalogs with the properties.
+    async fn initialize(self: Arc<Self>, properties: &HashMap<String, String>) -> Result<()> {
+        for catalog in self.catalogs.values() {
+            Arc::clone(catalog).initialize(properties).await?;
+        }
+        Ok(())
+    }
+    /// Object store of the first catalog. Tables in other object stores are resolved with
+    /// [object_store_for](FederatedCatalog::object_store_for).
+    fn object_store(&self) -> Arc<dyn ObjectStore> {
+        Arc::clone(&self.object_store)
+    }
+}
+
+/// Identifier of the table with the name in the namespace
+fn join(namespace: &Namespace, name: &str) -> Result<TableIdentifier> {
+    TableIdentifier::try_new(&[namespace.levels().to_vec(), vec![name.to_string()]].concat())
+}
+
+#[cfg(test)]
+mod tests {
+    use iceberg_rs::catalog::{namespace::Namespace, table_identifier::TableIdentifier};
+
+    use super::{IdentifierMapping, PrefixMapping};
+
+    #[test]
+    fn test_prefix_mapping() {
+        let mapping = PrefixMapping::new("prod_");
+        let (catalog, identifier) = mapping
+            .map_table(&TableIdentifier::parse("prod_x.sales.orders").unwrap())
+            .unwrap();
+        assert_eq!(catalog, "x");
+        assert_eq!(identifier.to_string(), "sales.orders");
+        assert_eq!(
+            mapping.unmap_table("x", &identifier).unwrap().to_string(),
+            "prod_x.sales.orders"
+        );
+        assert!(mapping
+            .map_table(&TableIdentifier::parse("dev_x.sales.orders").unwrap())
+            .is_err());
+        assert!(mapping
+            .map_namespace(&Namespace::try_new(&["prod_".to_string()]).unwrap())
+            .is_err());
+    }
+}
@@ -0,0 +1,263 @@
+/*!
+Iceberg format versions of table metadata.
+
+The table model of the catalog is format version 2. Metadata written with format version 1 by older
+writers is converted when it is loaded: the fields that version 2 requires are derived from the
+version 1 fields as described by the Iceberg specification. The file itself is only rewritten if
+the catalog is configured to upgrade such tables, see
+[with_v1_upgrade](super::builder::PostgresCatalogBuilder::with_v1_upgrade), or if the table is
+upgraded with [PostgresCatalog::upgrade_table_format]. Tables that are committed after loading are
+written as version 2 either way.
+*/
+
+use std::sync::Arc;
+
+use anyhow::{anyhow, Result};
+use iceberg_rs::{
+    catalog::{table_identifier::TableIdentifier, Catalog},
+    model::table_metadata::TableMetadata,
+    table::Table,
+};
+use serde_json::{json, Map, Value};
+
+use super::{checksum::checksum, PostgresCatalog};
+
+/// Newest format version the catalog can write
+pub(crate) static MAX_FORMAT_VERSION: i64 = 2;
+
+/// First field id of partition fields, as assigned by version 1 writers
+static PARTITION_DATA_ID_START: i64 = 1000;
+
+/// Format version of the metadata. Metadata without version is version 1.
+pub(crate) fn format_version(metadata: &Value) -> i64 {
+    metadata["format-version"].as_i64().unwrap_or(1)
+}
+
+/// Parse table metadata of any supported format version.
+pub(crate) fn parse_metadata(bytes: &[u8]) -> Result<TableMetadata> {
+    let mut metadata: Value =
+        serde_json::from_slice(bytes).map_err(|err| anyhow!(err.to_string()))?;
+    if format_version(&metadata) == 1 {
+        upgrade_v1(&mut metadata)?;
+    }
+    serde_json::from_value(metadata).map_err(|err| anyhow!(err.to_string()))
+}
+
+impl PostgresCatalog {
+    /// Rewrite the metadata of the table in the format version `target_version` and commit it.
+    /// Tables that already have the version are returned unchanged. The format version can only
+    /// be raised, and the metadata must be representable in the new version, for example
+    /// version 2 requires manifest lists.
+    pub async fn upgrade_table_format(
+        self: Arc<Self>,
+        identifier: TableIdentifier,
+        target_version: i64,
+    ) -> Result<Table> {
+        self.check_writable("Upgrading the table format")?;
+        if !(1..=MAX_FORMAT_VERSION).contains(&target_version) {
+            return Err(anyhow!(
+                "Upgrading the table format failed. The catalog doesn't support format version {}.",
+                target_version
+            ));
+        }
+        let table = Arc::clone(&self).load_table(identifier.clone()).await?;
+        let location = table.metadata_location().to_string();
+        let mut metadata = self.read_metadata_json(&identifier, &location).await?;
+        let version = format_version(&metadata);
+        if version == target_version {
+            return Ok(table);
+        }
+        if version > target_version {
+            return Err(anyhow!(
+                "Upgrading the table format failed. The table has format version {}, which can't be downgraded to {}.",
+                version,
+                target_version
+            ));
+        }
+        upgrade_v1(&mut metadata)?;
+        // Only metadata that the table model can read is committed.
+        serde_json::from_value::<TableMetadata>(metadata.clone())
+            .map_err(|err| anyhow!(err.to_string()))?;
+        self.commit_metadata(identifier, &location, metadata).await
+    }
+}
+
+/// Convert format version 1 metadata to format version 2. Fails for snapshots that list their
+/// manifests inline, because version 2 requires a manifest list file.
+pub(crate) fn upgrade_v1(metadata: &mut Value) -> Result<()> {
+    let location = metadata["location"]
+        .as_str()
+        .ok_or_else(|| anyhow!("The table metadata contains no location."))?
+        .to_string();
+    let object = metadata
+        .as_object_mut()
+        .ok_or_else(|| anyhow!("The table metadata is not an object."))?;
+
+    if !object.contains_key("schemas") {
+        let mut schema = object
+            .get("schema")
+            .cloned()
+            .ok_or_else(|| anyhow!("The table metadata contains no schema."))?;
+        schema
+            .as_object_mut()
+            .ok_or_else(|| anyhow!("The schema of the table is not an object."))?
+            .entry("schema-id")
+            .or_insert(json!(0));
+        let schema_id = schema["schema-id"].clone();
+        object.insert("schemas".to_string(), json!([schema]));
+        object.insert("current-schema-id".to_string(), schema_id);
+    }
+    object.remove("schema");
+
+    if !object.contains_key("partition-specs") {
+        let fields = object
+            .get("partition-spec")
+            .and_then(Value::as_array)
+            .cloned()
+            .unwrap_or_default();
+        object.insert(
+            "partition-specs".to_string(),
+            json!([{"spec-id": 0, "fields": fields}]),
+        );
+        object.insert("default-spec-id".to_string(), json!(0));
+    }
+    object.remove("partition-spec");
+    let mut last_partition_id = PARTITION_DATA_ID_START - 1;
+    for spec in object["partition-specs"]
+        .as_array_mut()
+        .ok_or_else(|| anyhow!("The partition specs of the table are not an array."))?
+    {
+        let mut next_id = PARTITION_DATA_ID_START;
+        for field in spec["fields"].as_array_mut().into_iter().flatten() {
+            let field = field
+                .as_object_mut()
+                .ok_or_else(|| anyhow!("A partition field of the table is not an object."))?;
+            let id = field
+                .entry("field-id")
+                .or_insert(json!(next_id))
+                .as_i64()
+                .unwrap_or(next_id);
+            next_id = id + 1;
+            last_partition_id = last_partition_id.max(id);
+        }
+    }
+    object
+        .entry("last-partition-id")
+        .or_insert(json!(last_partition_id));
+
+    if !object.contains_key("sort-orders") {
+        object.insert(
+            "sort-orders".to_string(),
+            json!([{"order-id": 0, "fields": []}]),
+        );
+        object.insert("default-sort-order-id".to_string(), json!(0));
+    }
+
+    if !object.contains_key("table-uuid") {
+        object.insert("table-uuid".to_string(), json!(derived_uuid(&location)));
+    }
+
+    for snapshot in object
+        .get_mut("snapshots")
+        .and_then(Value::as_array_mut)
+        .into_iter()
+        .flatten()
+    {
+        let snapshot = snapshot
+            .as_object_mut()
+            .ok_or_else(|| anyhow!("A snapshot of the table is not an object."))?;
+        if !snapshot.contains_key("manifest-list") {
+            return Err(anyhow!(
+                "The snapshot {} lists its manifests without manifest list, which format version 2 doesn't support.",
+                snapshot.get("snapshot-id").unwrap_or(&Value::Null)
+            ));
+        }
+        snapshot.entry("sequence-number").or_insert(json!(0));
+        snapshot
+            .entry("summary")
+            .or_insert_with(|| json!({"operation": "append"}));
+    }
+    object.entry("last-sequence-number").or_insert(json!(0));
+    object
+        .entry("properties")
+        .or_insert_with(|| Value::Object(Map::new()));
+    object.insert("format-version".to_string(), json!(2));
+    Ok(())
+}
+
+/// UUID derived from the table location, for version 1 tables without uuid. It is stable, so all
+/// readers assign the same uuid to the table.
+fn derived_uuid(location: &str) -> String {
+    let hash = checksum(location.as_bytes());
+    // Version 8 marks a custom UUID, the variant bits are `10`.
+    format!(
+        "{}-{}-8{}-{:x}{}-{}",
+        &hash[0..8],
+        &hash[8..12],
+        &hash[13..16],
+        0x8 | (u8::from_str_radix(&hash[16..17], 16).unwrap_or(0) & 0x3),
+        &hash[17..20],
+        &hash[20..32]
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::{derived_uuid, format_version, upgrade_v1};
+
+    #[test]
+    fn test_upgrade_v1() {
+        let mut metadata = json!({
+            "format-version": 1,
+            "location": "s3://bucket/ns/table",
+            "last-updated-ms": 1,
+            "last-column-id": 1,
+            "schema": {"type": "struct", "fields": [
+                {"id": 1, "name": "day", "required": false, "type": "date"}
+            ]},
+            "partition-spec": [{"name": "day", "transform": "identity", "source-id": 1}],
+            "snapshots": [{
+                "snapshot-id": 3,
+                "timestamp-ms": 1,
+                "manifest-list": "s3://bucket/ns/table/metadata/snap-3.avro"
+            }],
+            "current-snapshot-id": 3
+        });
+        upgrade_v1(&mut metadata).unwrap();
+        assert_eq!(format_version(&metadata), 2);
+        assert_eq!(metadata["schemas"][0]["schema-id"], 0);
+        assert_eq!(metadata["current-schema-id"], 0);
+        assert!(metadata.get("schema").is_none());
+        assert_eq!(
+            metadata["partition-specs"][0]["fields"][0]["field-id"],
+            1000
+        );
+        assert_eq!(metadata["last-partition-id"], 1000);
+        assert_eq!(metadata["default-sort-order-id"], 0);
+        assert_eq!(metadata["snapshots"][0]["sequence-number"], 0);
+        assert_eq!(metadata["snapshots"][0]["summary"]["operation"], "append");
+        assert_eq!(metadata["table-uuid"], derived_uuid("s3://bucket/ns/table"));
+
+        let mut metadata = json!({
+            "format-version": 1,
+            "location": "s3://bucket/ns/table",
+            "schema": {"type": "struct", "fields": []},
+            "snapshots": [{"snapshot-id": 3, "manifests": []}]
+        });
+        assert!(upgrade_v1(&mut metadata).is_err());
+    }
+
+    #[test]
+    fn test_derived_uuid() {
+        let uuid = derived_uuid("s3://bucket/ns/table");
+        let groups: Vec<&str> = uuid.split('-').collect();
+        assert_eq!(
+            groups.iter().map(|group| group.len()).collect::<Vec<_>>(),
+            vec![8, 4, 4, 4, 12]
+        );
+        assert!(groups[2].starts_with('8'));
+        assert!("89ab".contains(&groups[3][..1]));
+    }
+}
@@ -0,0 +1,341 @@
+/*!
+Consistency check between the catalog entries and the metadata files in the object store.
+
+The check verifies for every table that its current metadata file exists, matches its checksum,
+can be parsed and belongs to the table, and that the previous metadata files are still in place. The problems are
+collected in a [FsckReport] instead of failing the check, so that a single run shows the state of
+the whole catalog.
+*/
+
+use std::fmt;
+
+use anyhow::Result;
+use iceberg_rs::{
+    catalog::{namespace::Namespace, table_identifier::TableIdentifier},
+    object_store::{path::Path, Error as ObjectStoreError},
+};
+
+use super::{
+    access::Action,
+    checksum::checksum,
+    namespace::{namespace_condition, table_identifier},
+    query::literal,
+    storage::resolver::object_path,
+    PostgresCatalog, CATALOG_NAME_COLUMN, METADATA_CHECKSUM_COLUMN, METADATA_LOCATION_COLUMN,
+    PREVIOUS_METADATA_LOCATION_COLUMN, TABLE_LOCATION_COLUMN, TABLE_NAMESPACE_COLUMN,
+    TABLE_NAME_COLUMN, TABLE_UUID_COLUMN,
+};
+
+/// Severity of a finding
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Severity {
+    /// The entry is usable but lacks information that newer versions of the catalog record
+    Info,
+    /// The table can be loaded, but its history is incomplete
+    Warning,
+    /// The table can't be loaded or may load the metadata of a different table
+    Error,
+}
+
+impl fmt::Display for Severity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Severity::Info => f.write_str("info"),
+            Severity::Warning => f.write_str("warning"),
+            Severity::Error => f.write_str("error"),
+        }
+    }
+}
+
+/// Problem found with a table
+#[derive(Debug, Clone)]
+pub struct Finding {
+    /// Table with the problem
+    pub identifier: TableIdentifier,
+    /// Severity of the problem
+    pub severity: Severity,
+    /// Description of the problem
+    pub message: String,
+}
+
+impl fmt::Display for Finding {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} {}: {}", self.severity, self.identifier, self.message)
+    }
+}
+
+/// Result of a consistency check
+#[derive(Debug, Clone, Default)]
+pub struct FsckReport {
+    /// Number of checked tables
+    pub tables_checked: usize,
+    /// Problems found, in the order of the tables
+    pub findings: Vec<Finding>,
+}
+
+impl FsckReport {
+    /// Highest severity of the findings, `None` if nothing was found
+    pub fn max_severity(&self) -> Option<Severity> {
+        self.findings.iter().map(|finding| finding.severity).max()
+    }
+
+    /// Check that no finding is an error
+    pub fn is_consistent(&self) -> bool {
+        self.max_severity() < Some(Severity::Error)
+    }
+
+    /// Findings with at least the severity
+    pub fn findings_at_least(&self, severity: Severity) -> impl Iterator<Item = &Finding> {
+        self.findings
+            .iter()
+            .filter(move |finding| finding.severity >= severity)
+    }
+}
+
+impl PostgresCatalog {
+    /// Check the tables in the namespace and its children. Reads the current metadata file of
+    /// every table, so the check takes a while for large catalogs.
+    pub async fn fsck(&self, namespace: &Namespace) -> Result<FsckReport> {
+        let namespace = &self.case_sensitivity.normalize_namespace(namespace)?;
+        self.authorize(Action::Read, namespace, None).await?;
+        let rows = self
+            .query(
+                self.read_connection(),
+                &("SELECT ".to_string()
+                    + TABLE_NAMESPACE_COLUMN
+                    + ", "
+                    + TABLE_NAME_COLUMN
+                    + ", "
+                    + METADATA_LOCATION_COLUMN
+                    + ", "
+                    + PREVIOUS_METADATA_LOCATION_COLUMN
+                    + ", "
+                    + TABLE_LOCATION_COLUMN
+                    + ", "
+                    + TABLE_UUID_COLUMN
+                    + "::TEXT AS "
+                    + TABLE_UUID_COLUMN
+                    + ", "
+                    + METADATA_CHECKSUM_COLUMN
+                    + " FROM "
+                    + &self.catalog_table.qualified
+                    + " WHERE "
+                    + CATALOG_NAME_COLUMN
+                    + " = "
+                    + &literal(&self.name)
+                    + " AND ("
+                    + &namespace_condition(namespace)
+                    + ") ORDER BY "
+                    + TABLE_NAMESPACE_COLUMN
+                    + ", "
+                    + TABLE_NAME_COLUMN
+                    + ";"),
+            )
+            .await?;
+        let mut report = FsckReport::default();
+        for row in rows {
+            let identifier = table_identifier(
+                &row.try_get_string(TABLE_NAMESPACE_COLUMN)?,
+                &row.try_get_string(TABLE_NAME_COLUMN)?,
+            )?;
+            let entry = Entry {
+                metadata_location: row.try_get_opt_string(METADATA_LOCATION_COLUMN)?,
+                previous_metadata_location: row
+                    .try_get_opt_string(PREVIOUS_METADATA_LOCATION_COLUMN)?,
+                table_location: row.try_get_opt_string(TABLE_LOCATION_COLUMN)?,
+                table_uuid: row.try_get_opt_string(TABLE_UUID_COLUMN)?,
+                metadata_checksum: row.try_get_opt_string(METADATA_CHECKSUM_COLUMN)?,
+            };
+            report
+                .findings
+                .extend(self.check_table(&identifier, &entry).await);
+            report.tables_checked += 1;
+        }
+        Ok(report)
+    }
+
+    async fn check_table(&self, identifier: &TableIdentifier, entry: &Entry) -> Vec<Finding> {
+        let mut findings = Vec::new();
+        let finding = |severity, message: String| Finding {
+            identifier: identifier.clone(),
+            severity,
+            message,
+        };
+        let metadata_location = match &entry.metadata_location {
+            Some(location) => location,
+            None => {
+                findings.push(finding(
+                    Severity::Error,
+                    "The entry has no metadata location.".to_string(),
+                ));
+                return findings;
+            }
+        };
+        let bytes = match self.read_metadata(identifier, metadata_location).await {
+            Ok(bytes) => bytes,
+            Err(err) => {
+                findings.push(finding(
+                    Severity::Error,
+                    format!(
+                        "The metadata file {} can't be read: {}",
+                        metadata_location, err
+                    ),
+                ));
+                return findings;
+            }
+        };
+        if let Some(expected) = &entry.metadata_checksum {
+            let actual = checksum(&bytes);
+            if actual != *expected {
+                findings.push(finding(
+                    Severity::Error,
+                    format!(
+                        "The metadata file {} was modified. Its checksum is {}, the catalog recorded {}.",
+                        metadata_location, actual, expected
+                    ),
+                ));
+            }
+        }
+        let metadata: serde_json::Value = match serde_json::from_slice(&bytes) {
+            Ok(metadata) => metadata,
+            Err(err) => {
+                findings.push(finding(
+                    Severity::Error,
+                    format!(
+                        "The metadata file {} can't be parsed: {}",
+                        metadata_location, err
+                    ),
+                ));
+                return findings;
+            }
+        };
+
+        match (&entry.table_uuid, metadata["table-uuid"].as_str()) {
+            (Some(stored), Some(uuid)) if !stored.eq_ignore_ascii_case(uuid) => {
+                findings.push(finding(
+                    Severity::Error,
+                    format!(
+                        "The metadata belongs to the table {}, the catalog entry to the table {}.",
+                        uuid, stored
+                    ),
+                ))
+            }
+            (None, _) => findings.push(finding(
+                Severity::Info,
+                "The entry has no table uuid, it is recorded with the next commit.".to_string(),
+            )),
+            _ => (),
+        }
+        match (&entry.table_location, metadata["location"].as_str()) {
+            (Some(stored), Some(location))
+                if stored.trim_end_matches('/') != location.trim_end_matches('/') =>
+            {
+                findings.push(finding(
+                    Severity::Warning,
+                    format!(
+                        "The metadata has the location {}, the catalog entry the location {}.",
+                        location, stored
+                    ),
+                ))
+            }
+            (None, _) => findings.push(finding(
+                Severity::Info,
+                "The entry has no table location.".to_string(),
+            )),
+            _ => (),
+        }
+
+        if let Some(previous) = &entry.previous_metadata_location {
+            match self.metadata_exists(identifier, previous).await {
+                Ok(true) => (),
+                Ok(false) => findings.push(finding(
+                    Severity::Warning,
+                    format!("The previous metadata file {} doesn't exist.", previous),
+                )),
+                Err(err) => findings.push(finding(
+                    Severity::Warning,
+                    format!(
+                        "The previous metadata file {} can't be checked: {}",
+                        previous, err
+                    ),
+                )),
+            }
+        }
+        let log = metadata["metadata-log"]
+            .as_array()
+            .cloned()
+            .unwrap_or_default();
+        for file in log
+            .iter()
+            .filter_map(|entry| entry["metadata-file"].as_str())
+        {
+            if Some(file) == entry.previous_metadata_location.as_deref() {
+                continue;
+            }
+            if let Ok(false) = self.metadata_exists(identifier, file).await {
+                findings.push(finding(
+                    Severity::Warning,
+                    format!(
+                        "The metadata file {} of the metadata log doesn't exist.",
+                        file
+                    ),
+                ));
+            }
+        }
+        findings
+    }
+
+    pub(crate) async fn metadata_exists(
+        &self,
+        identifier: &TableIdentifier,
+        location: &str,
+    ) -> Result<bool> {
+        let object_store = self.object_store_for_location(identifier, location)?;
+        let path: Path = object_path(&self.resolved_location(location)).into();
+        match object_store.head(&path).await {
+            Ok(_) => Ok(true),
+            Err(ObjectStoreError::NotFound { .. }) => Ok(false),
+            Err(err) => Err(anyhow::anyhow!(err.to_string())),
+        }
+    }
+}
+
+/// Stored state of a table
+struct Entry {
+    metadata_location: Option<String>,
+    previous_metadata_location: Option<String>,
+    table_location: Option<String>,
+    table_uuid: Option<String>,
+    metadata_checksum: Option<String>,
+}
+
+#[cfg(test)]
+mod tests {
+    use iceberg_rs::catalog::table_identifier::TableIdentifier;
+
+    use super::{Finding, FsckReport, Severity};
+
+    #[test]
+    fn test_report_severity() {
+        let identifier = TableIdentifier::parse("test.fsck").unwrap();
+        let mut report = FsckReport::default();
+        assert_eq!(report.max_severity(), None);
+        assert!(report.is_consistent());
+
+        report.findings.push(Finding {
+            identifier: identifier.clone(),
+            severity: Severity::Warning,
+            message: "The previous metadata file doesn't exist.".to_string(),
+        });
+        assert!(report.is_consistent());
+        report.findings.push(Finding {
+            identifier,
+            severity: Severity::Error,
+            message: "The metadata file can't be read.".to_string(),
+        });
+        assert_eq!(report.max_severity(), Some(Severity::Error));
+        assert!(!report.is_consistent());
+        assert_eq!(report.findings_at_least(Severity::Warning).count(), 2);
+        assert_eq!(report.findings_at_least(Severity::Error).count(), 1);
+    }
+}
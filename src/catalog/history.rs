@@ -0,0 +1,223 @@
+/*!
+Snapshots and history of tables.
+
+[PostgresCatalog::list_snapshots] and [PostgresCatalog::table_history] read the snapshots from the
+current metadata file of the table. With
+[with_snapshot_summaries](super::builder::PostgresCatalogBuilder::with_snapshot_summaries), every
+commit also stores a summary of the snapshots in the catalog entry, and both methods are answered
+from Postgres without reading the object store. Entries committed without summary, or whose
+summary belongs to another metadata file because a different writer committed them, fall back to
+the metadata file.
+*/
+
+use std::collections::HashMap;
+
+use anyhow::{anyhow, Result};
+use iceberg_rs::catalog::table_identifier::TableIdentifier;
+use serde_json::{json, Value};
+
+use super::{
+    access::Action,
+    namespace::table_condition,
+    system::{snapshot_rows, SnapshotRow},
+    PostgresCatalog, METADATA_LOCATION_COLUMN, SNAPSHOT_SUMMARY_COLUMN,
+};
+
+/// Entry of the snapshot log, the snapshots that were current over time
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HistoryEntry {
+    /// Id of the snapshot
+    pub snapshot_id: i64,
+    /// Time the snapshot became current in milliseconds since the epoch
+    pub timestamp_ms: i64,
+    /// Operation that created the snapshot, `None` if the snapshot expired
+    pub operation: Option<String>,
+}
+
+impl PostgresCatalog {
+    /// Snapshots of the table, in the order of the metadata
+    pub async fn list_snapshots(&self, identifier: &TableIdentifier) -> Result<Vec<SnapshotRow>> {
+        let (identifier, summary) = self.snapshot_summary(identifier).await?;
+        snapshot_rows(&identifier, &summary)
+    }
+
+    /// Snapshots that were current over time, oldest first
+    pub async fn table_history(&self, identifier: &TableIdentifier) -> Result<Vec<HistoryEntry>> {
+        let (_, summary) = self.snapshot_summary(identifier).await?;
+        Ok(history(&summary))
+    }
+
+    /// Snapshot summary of the table, from the catalog entry if it was stored with the last
+    /// commit and from the metadata file otherwise
+    async fn snapshot_summary(
+        &self,
+        identifier: &TableIdentifier,
+    ) -> Result<(TableIdentifier, Value)> {
+        let identifier = self.case_sensitivity.normalize(identifier)?;
+        self.authorize(
+            Action::Read,
+            identifier.namespace(),
+            Some(identifier.name()),
+        )
+        .await?;
+        let rows = self
+            .query(
+                self.read_connection(),
+                &("SELECT ".to_string()
+                    + METADATA_LOCATION_COLUMN
+                    + ", "
+                    + SNAPSHOT_SUMMARY_COLUMN
+                    + "::TEXT AS "
+                    + SNAPSHOT_SUMMARY_COLUMN
+                    + " FROM "
+                    + &self.catalog_table.qualified
+                    + " WHERE "
+                    + &table_condition(&self.name, &identifier)
+                    + ";"),
+            )
+            .await?;
+        let row = rows.first().ok_or_else(|| {
+            anyhow!("Reading the snapshots failed. No table matched the identifier.")
+        })?;
+        let location = self.resolved_location(&row.try_get_string(METADATA_LOCATION_COLUMN)?);
+        let cached = row
+            .try_get_opt_string(SNAPSHOT_SUMMARY_COLUMN)?
+            .map(|summary| serde_json::from_str::<Value>(&summary))
+            .transpose()
+            .map_err(|err| anyhow!(err.to_string()))?
+            .filter(|summary| summary["metadata-location"] == location.as_str());
+        let summary = match cached {
+            Some(summary) => summary,
+            None => snapshot_summary(
+                &self.read_metadata_json(&identifier, &location).await?,
+                &location,
+            ),
+        };
+        Ok((identifier, summary))
+    }
+}
+
+/// Summary of the snapshots of the metadata file at the location that is stored in the catalog
+/// entry. It has the structure of the metadata, with the snapshots, the snapshot log and the
+/// current snapshot, but without the snapshot properties.
+pub(crate) fn snapshot_summary(metadata: &Value, metadata_location: &str) -> Value {
+    let snapshots = metadata["snapshots"]
+        .as_array()
+        .into_iter()
+        .flatten()
+        .map(|snapshot| {
+            json!({
+                "snapshot-id": snapshot["snapshot-id"],
+                "parent-snapshot-id": snapshot["parent-snapshot-id"],
+                "sequence-number": snapshot["sequence-number"],
+                "timestamp-ms": snapshot["timestamp-ms"],
+                "manifest-list": snapshot["manifest-list"],
+                "summary": {"operation": snapshot["summary"]["operation"]},
+            })
+        })
+        .collect::<Vec<_>>();
+    json!({
+        "metadata-location": metadata_location,
+        "current-snapshot-id": metadata["current-snapshot-id"],
+        "snapshots": snapshots,
+        "snapshot-log": metadata["snapshot-log"].as_array().cloned().unwrap_or_default(),
+    })
+}
+
+/// Id of the current snapshot, `None` for tables without snapshot, which some writers mark with
+/// `-1`
+pub(crate) fn current_snapshot_id(metadata: &Value) -> Option<i64> {
+    metadata["current-snapshot-id"]
+        .as_i64()
+        .filter(|id| *id != -1)
+}
+
+/// History of the snapshot log in the summary or metadata
+fn history(summary: &Value) -> Vec<HistoryEntry> {
+    let operations: HashMap<i64, &str> = summary["snapshots"]
+        .as_array()
+        .into_iter()
+        .flatten()
+        .filter_map(|snapshot| {
+            Some((
+                snapshot["snapshot-id"].as_i64()?,
+                snapshot["summary"]["operation"].as_str()?,
+            ))
+        })
+        .collect();
+    summary["snapshot-log"]
+        .as_array()
+        .into_iter()
+        .flatten()
+        .filter_map(|entry| {
+            let snapshot_id = entry["snapshot-id"].as_i64()?;
+            Some(HistoryEntry {
+                snapshot_id,
+                timestamp_ms: entry["timestamp-ms"].as_i64().unwrap_or_default(),
+                operation: operations
+                    .get(&snapshot_id)
+                    .map(|operation| operation.to_string()),
+            })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use iceberg_rs::catalog::table_identifier::TableIdentifier;
+    use serde_json::json;
+
+    use super::{history, snapshot_summary, HistoryEntry};
+    use crate::catalog::system::snapshot_rows;
+
+    #[test]
+    fn test_snapshot_summary() {
+        let metadata = json!({
+            "current-snapshot-id": 2,
+            "snapshots": [
+                {"snapshot-id": 1, "timestamp-ms": 10, "sequence-number": 1,
+                 "manifest-list": "s3://bucket/snap-1.avro",
+                 "summary": {"operation": "append", "added-records": "100"}},
+                {"snapshot-id": 2, "parent-snapshot-id": 1, "timestamp-ms": 20, "sequence-number": 2,
+                 "manifest-list": "s3://bucket/snap-2.avro",
+                 "summary": {"operation": "overwrite"}}
+            ],
+            "snapshot-log": [
+                {"snapshot-id": 0, "timestamp-ms": 5},
+                {"snapshot-id": 1, "timestamp-ms": 10},
+                {"snapshot-id": 2, "timestamp-ms": 20}
+            ]
+        });
+        let summary = snapshot_summary(&metadata, "s3://bucket/metadata/00002.metadata.json");
+        assert!(summary["snapshots"][0]["summary"]
+            .get("added-records")
+            .is_none());
+
+        let identifier = TableIdentifier::parse("ns.table").unwrap();
+        let rows = snapshot_rows(&identifier, &summary).unwrap();
+        assert_eq!(rows.len(), 2);
+        assert_eq!(rows[1].parent_snapshot_id, Some(1));
+        assert_eq!(rows[1].operation.as_deref(), Some("overwrite"));
+
+        assert_eq!(
+            history(&summary),
+            vec![
+                HistoryEntry {
+                    snapshot_id: 0,
+                    timestamp_ms: 5,
+                    operation: None
+                },
+                HistoryEntry {
+                    snapshot_id: 1,
+                    timestamp_ms: 10,
+                    operation: Some("append".to_string())
+                },
+                HistoryEntry {
+                    snapshot_id: 2,
+                    timestamp_ms: 20,
+                    operation: Some("overwrite".to_string())
+                },
+            ]
+        );
+    }
+}
@@ -0,0 +1,117 @@
+/*!
+Validation and normalization of table identifiers and namespaces.
+*/
+
+use anyhow::{anyhow, Result};
+use iceberg_rs::catalog::{namespace::Namespace, table_identifier::TableIdentifier};
+
+use super::namespace::namespace_key;
+
+/// Maximum length of a namespace or table name, given by the columns of the catalog table
+static MAX_NAME_LENGTH: usize = 255;
+
+/// How the catalog compares table and namespace names
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CaseSensitivity {
+    /// Names are stored as given, `Sales.Orders` and `sales.orders` are different tables.
+    Sensitive,
+    /// Names are converted to lower case before they are stored or looked up, like unquoted
+    /// identifiers in SQL. Tables that were created with upper case letters in case-sensitive
+    /// mode can't be found in this mode.
+    Insensitive,
+}
+
+impl Default for CaseSensitivity {
+    fn default() -> Self {
+        CaseSensitivity::Sensitive
+    }
+}
+
+impl CaseSensitivity {
+    fn normalize_name(&self, name: &str) -> Result<String> {
+        if name.is_empty() {
+            return Err(anyhow!("Names in the catalog must not be empty."));
+        }
+        if name.chars().any(char::is_control) {
+            return Err(anyhow!("The name {:?} contains control characters.", name));
+        }
+        if name.chars().count() > MAX_NAME_LENGTH {
+            return Err(anyhow!(
+                "The name {} is longer than {} characters.",
+                name,
+                MAX_NAME_LENGTH
+            ));
+        }
+        match self {
+            CaseSensitivity::Sensitive => Ok(name.to_string()),
+            CaseSensitivity::Insensitive => Ok(name.to_lowercase()),
+        }
+    }
+
+    /// Validate the levels of the namespace and normalize their case
+    pub(crate) fn normalize_namespace(&self, namespace: &Namespace) -> Result<Namespace> {
+        let levels = namespace
+            .levels()
+            .iter()
+            .map(|level| self.normalize_name(level))
+            .collect::<Result<Vec<_>>>()?;
+        let namespace = Namespace::try_new(&levels)?;
+        if namespace_key(&namespace).chars().count() > MAX_NAME_LENGTH {
+            return Err(anyhow!(
+                "The namespace {} is longer than {} characters.",
+                namespace,
+                MAX_NAME_LENGTH
+            ));
+        }
+        Ok(namespace)
+    }
+
+    /// Validate the namespace and name of the identifier and normalize their case
+    pub(crate) fn normalize(&self, identifier: &TableIdentifier) -> Result<TableIdentifier> {
+        let mut names = self
+            .normalize_namespace(identifier.namespace())?
+            .levels()
+            .to_vec();
+        names.push(self.normalize_name(identifier.name())?);
+        TableIdentifier::try_new(&names)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use iceberg_rs::catalog::table_identifier::TableIdentifier;
+
+    use super::CaseSensitivity;
+
+    #[test]
+    fn test_normalize() {
+        let identifier = TableIdentifier::try_new(&[
+            "Sales".to_string(),
+            "it's \"quoted\"".to_string(),
+            "Orders".to_string(),
+        ])
+        .unwrap();
+        assert_eq!(
+            format!(
+                "{}",
+                CaseSensitivity::Sensitive.normalize(&identifier).unwrap()
+            ),
+            format!("{}", identifier)
+        );
+        let normalized = CaseSensitivity::Insensitive.normalize(&identifier).unwrap();
+        assert_eq!(normalized.name(), "orders");
+        assert_eq!(
+            normalized.namespace().levels(),
+            &["sales".to_string(), "it's \"quoted\"".to_string()]
+        );
+    }
+
+    #[test]
+    fn test_invalid_names() {
+        let identifier =
+            TableIdentifier::try_new(&["test".to_string(), "table\u{0}".to_string()]).unwrap();
+        assert!(CaseSensitivity::Sensitive.normalize(&identifier).is_err());
+        let identifier = TableIdentifier::try_new(&["test".to_string(), "a".repeat(256)]).unwrap();
+        assert!(CaseSensitivity::Sensitive.normalize(&identifier).is_err());
+    }
+}
@@ -0,0 +1,385 @@
+/*!
+Commit locks of tables based on a lock table, for environments without advisory locks.
+
+Commits to a table are optimistic: the pointer only moves if it still references the metadata
+the commit started from. Writers that rewrite large metadata or commit often waste work on
+conflicts, [PostgresCatalog::acquire_commit_lock] serializes them instead. The lock is a row in the
+lock table with the id of its owner and the expiry of its lease. While the [CommitLock] is alive,
+a background task extends the lease. If the owner crashes, the lease expires and the next writer
+steals the lock; the expiry is compared and the row is taken over in one statement, so only one
+writer can steal it.
+
+The lock doesn't replace the optimistic check of the pointer. A writer that paused longer than its
+lease may lose the lock to another writer, [CommitLock::renew] detects this before the pointer is
+swapped.
+*/
+
+use std::{
+    cmp,
+    sync::{
+        atomic::{AtomicBool, AtomicU64, Ordering},
+        Arc,
+    },
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
+
+use anyhow::{anyhow, Result};
+use iceberg_rs::catalog::table_identifier::TableIdentifier;
+use tokio::task::JoinHandle;
+
+use super::{
+    access::Action,
+    error::CatalogError,
+    namespace::{namespace_key, table_condition},
+    query::literal,
+    PostgresCatalog, CATALOG_NAME_COLUMN, TABLE_NAMESPACE_COLUMN, TABLE_NAME_COLUMN,
+};
+
+static OWNER_ID_COLUMN: &str = "owner_id";
+static ACQUIRED_AT_COLUMN: &str = "acquired_at";
+static LEASE_EXPIRES_AT_COLUMN: &str = "lease_expires_at";
+
+/// Longest wait between two attempts to acquire a lock
+static MAX_ACQUIRE_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Counter that keeps the owner ids of one process apart
+static NEXT_OWNER: AtomicU64 = AtomicU64::new(0);
+
+/// Commit lock of a table, held until it is [released](CommitLock::release) or dropped. Dropping
+/// the lock stops extending the lease, another writer can steal it once the lease expired.
+pub struct CommitLock {
+    catalog: Arc<PostgresCatalog>,
+    identifier: TableIdentifier,
+    owner: String,
+    lease: Duration,
+    lost: Arc<AtomicBool>,
+    heartbeat: JoinHandle<()>,
+}
+
+impl PostgresCatalog {
+    /// Acquire the commit lock of the table for the principal of the catalog. The lease is
+    /// extended every third of its duration while the lock is alive. Expired leases of other
+    /// owners are stolen. Fails with [CatalogError::Locked] if the lock can't be acquired
+    /// within the timeout.
+    pub async fn acquire_commit_lock(
+        self: Arc<Self>,
+        identifier: &TableIdentifier,
+        lease: Duration,
+        timeout: Duration,
+    ) -> Result<CommitLock> {
+        self.check_writable("Acquiring the commit lock")?;
+        let identifier = self.case_sensitivity.normalize(identifier)?;
+        self.authorize(
+            Action::Commit,
+            identifier.namespace(),
+            Some(identifier.name()),
+        )
+        .await?;
+        if lease.is_zero() {
+            return Err(anyhow!("The lease of a commit lock can't be zero."));
+        }
+        let owner = owner_id(&self.current_principal());
+        let deadline = Instant::now() + timeout;
+        let mut interval = cmp::min(lease / 4, MAX_ACQUIRE_INTERVAL);
+        loop {
+            if self.try_acquire(&identifier, &owner, lease).await? {
+                break;
+            }
+            let now = Instant::now();
+            if now >= deadline {
+                return Err(CatalogError::Locked {
+                    table: identifier.to_string(),
+                    owner: self.lock_owner(&identifier).await?.unwrap_or_default(),
+                }
+                .into());
+            }
+            tokio::time::sleep(cmp::min(interval, deadline - now)).await;
+            interval = cmp::min(interval * 2, MAX_ACQUIRE_INTERVAL);
+        }
+        let lost = Arc::new(AtomicBool::new(false));
+        let heartbeat = tokio::spawn(heartbeat(
+            Arc::clone(&self),
+            identifier.clone(),
+            owner.clone(),
+            lease,
+            Arc::clone(&lost),
+        ));
+        Ok(CommitLock {
+            catalog: self,
+            identifier,
+            owner,
+            lease,
+            lost,
+            heartbeat,
+        })
+    }
+
+    /// Current owner of the commit lock of the table, `None` if it isn't locked or the lease
+    /// expired
+    pub async fn lock_owner(&self, identifier: &TableIdentifier) -> Result<Option<String>> {
+        let identifier = self.case_sensitivity.normalize(identifier)?;
+        let rows = self
+            .query(
+                &self.primary,
+                &("SELECT ".to_string()
+                    + OWNER_ID_COLUMN
+                    + " FROM "
+                    + &self.catalog_table.locks
+                    + " WHERE "
+                    + &table_condition(&self.name, &identifier)
+                    + " AND "
+                    + LEASE_EXPIRES_AT_COLUMN
+                    + " > now();"),
+            )
+            .await?;
+        rows.first()
+            .map(|row| row.try_get_string(OWNER_ID_COLUMN))
+            .transpose()
+    }
+
+    /// Take the lock if it is free or its lease expired. Whether the lock was acquired.
+    async fn try_acquire(
+        &self,
+        identifier: &TableIdentifier,
+        owner: &str,
+        lease: Duration,
+    ) -> Result<bool> {
+        // The conflicting row is locked while the condition is evaluated, so two writers can't
+        // both steal an expired lease.
+        let rows = self
+            .execute_returning(
+                &("INSERT INTO ".to_string()
+                    + &self.catalog_table.locks
+                    + " AS existing ("
+                    + CATALOG_NAME_COLUMN
+                    + ", "
+                    + TABLE_NAMESPACE_COLUMN
+                    + ", "
+                    + TABLE_NAME_COLUMN
+                    + ", "
+                    + OWNER_ID_COLUMN
+                    + ", "
+                    + LEASE_EXPIRES_AT_COLUMN
+                    + ") VALUES ("
+                    + &literal(&self.name)
+                    + ", "
+                    + &literal(&namespace_key(identifier.namespace()))
+                    + ", "
+                    + &literal(identifier.name())
+                    + ", "
+                    + &literal(owner)
+                    + ", "
+                    + &lease_expiry(lease)
+                    + ") ON CONFLICT ("
+                    + CATALOG_NAME_COLUMN
+                    + ", "
+                    + TABLE_NAMESPACE_COLUMN
+                    + ", "
+                    + TABLE_NAME_COLUMN
+                    + ") DO UPDATE SET "
+                    + OWNER_ID_COLUMN
+                    + " = EXCLUDED."
+                    + OWNER_ID_COLUMN
+                    + ", "
+                    + ACQUIRED_AT_COLUMN
+                    + " = now(), "
+                    + LEASE_EXPIRES_AT_COLUMN
+                    + " = EXCLUDED."
+                    + LEASE_EXPIRES_AT_COLUMN
+                    + " WHERE existing."
+                    + LEASE_EXPIRES_AT_COLUMN
+                    + " <= now() RETURNING "
+                    + OWNER_ID_COLUMN
+                    + ";"),
+            )
+            .await?;
+        Ok(!rows.is_empty())
+    }
+
+    /// Extend the lease of the owner. Whether the owner still held the lock.
+    async fn extend_lease(
+        &self,
+        identifier: &TableIdentifier,
+        owner: &str,
+        lease: Duration,
+    ) -> Result<bool> {
+        let n_rows = self
+            .execute(
+                &self.primary,
+                &("UPDATE ".to_string()
+                    + &self.catalog_table.locks
+                    + " SET "
+                    + LEASE_EXPIRES_AT_COLUMN
+                    + " = "
+                    + &lease_expiry(lease)
+                    + " WHERE "
+                    + &table_condition(&self.name, identifier)
+                    + " AND "
+                    + OWNER_ID_COLUMN
+                    + " = "
+                    + &literal(owner)
+                    + " AND "
+                    + LEASE_EXPIRES_AT_COLUMN
+                    + " > now();"),
+            )
+            .await?;
+        Ok(n_rows == 1)
+    }
+
+    /// Create the table with the commit locks.
+    pub(crate) async fn create_lock_table(&self) -> Result<()> {
+        self.execute(
+            &self.primary,
+            &("CREATE TABLE IF NOT EXISTS ".to_string()
+                + &self.catalog_table.locks
+                + " ("
+                + CATALOG_NAME_COLUMN
+                + " VARCHAR(255) NOT NULL,"
+                + TABLE_NAMESPACE_COLUMN
+                + " VARCHAR(255) NOT NULL,"
+                + TABLE_NAME_COLUMN
+                + " VARCHAR(255) NOT NULL,"
+                + OWNER_ID_COLUMN
+                + " TEXT NOT NULL,"
+                + ACQUIRED_AT_COLUMN
+                + " TIMESTAMPTZ NOT NULL DEFAULT now(),"
+                + LEASE_EXPIRES_AT_COLUMN
+                + " TIMESTAMPTZ NOT NULL,"
+                + "PRIMARY KEY ("
+                + CATALOG_NAME_COLUMN
+                + ", "
+                + TABLE_NAMESPACE_COLUMN
+                + ", "
+                + TABLE_NAME_COLUMN
+                + "));"),
+        )
+        .await?;
+        Ok(())
+    }
+}
+
+impl CommitLock {
+    /// Identifier of the locked table
+    pub fn identifier(&self) -> &TableIdentifier {
+        &self.identifier
+    }
+
+    /// Id of the owner in the lock table
+    pub fn owner(&self) -> &str {
+        &self.owner
+    }
+
+    /// Whether the lease was extended in time so far. A lock whose lease expired may have been
+    /// stolen by another writer.
+    pub fn is_held(&self) -> bool {
+        !self.lost.load(Ordering::SeqCst)
+    }
+
+    /// Extend the lease now and fail if the lock was lost. Call it right before the pointer of
+    /// the table is swapped, so that the commit happens within a valid lease.
+    pub async fn renew(&self) -> Result<()> {
+        if self.is_held()
+            && self
+                .catalog
+                .extend_lease(&self.identifier, &self.owner, self.lease)
+                .await?
+        {
+            return Ok(());
+        }
+        self.lost.store(true, Ordering::SeqCst);
+        Err(anyhow!(
+            "The commit lock of the table {} was lost, its lease expired.",
+            self.identifier
+        ))
+    }
+
+    /// Stop extending the lease and release the lock, so that other writers don't have to wait
+    /// for the lease to expire.
+    pub async fn release(self) -> Result<()> {
+        self.heartbeat.abort();
+        self.catalog
+            .execute(
+                &self.catalog.primary,
+                &("DELETE FROM ".to_string()
+                    + &self.catalog.catalog_table.locks
+                    + " WHERE "
+                    + &table_condition(&self.catalog.name, &self.identifier)
+                    + " AND "
+                    + OWNER_ID_COLUMN
+                    + " = "
+                    + &literal(&self.owner)
+                    + ";"),
+            )
+            .await?;
+        Ok(())
+    }
+}
+
+impl Drop for CommitLock {
+    fn drop(&mut self) {
+        self.heartbeat.abort();
+    }
+}
+
+/// Extend the lease every third of its duration until the lock is lost. Failed attempts are
+/// repeated at the next interval, the lock is only lost if the lease expired or another owner
+/// stole it.
+async fn heartbeat(
+    catalog: Arc<PostgresCatalog>,
+    identifier: TableIdentifier,
+    owner: String,
+    lease: Duration,
+    lost: Arc<AtomicBool>,
+) {
+    loop {
+        tokio::time::sleep(lease / 3).await;
+        if let Ok(false) = catalog.extend_lease(&identifier, &owner, lease).await {
+            lost.store(true, Ordering::SeqCst);
+            return;
+        }
+    }
+}
+
+/// Expression for the expiry of a lease that starts now
+fn lease_expiry(lease: Duration) -> String {
+    "now() + INTERVAL '".to_string() + &lease.as_millis().to_string() + " milliseconds'"
+}
+
+/// Id of a new owner, unique across processes and the locks of one process
+fn owner_id(principal: &str) -> String {
+    let nanos = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_nanos();
+    let principal = if principal.is_empty() {
+        "catalog"
+    } else {
+        principal
+    };
+    format!(
+        "{}-{}-{}-{}",
+        principal,
+        std::process::id(),
+        nanos,
+        NEXT_OWNER.fetch_add(1, Ordering::SeqCst)
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::{lease_expiry, owner_id};
+
+    #[test]
+    fn test_lease() {
+        assert_eq!(
+            lease_expiry(Duration::from_secs(30)),
+            "now() + INTERVAL '30000 milliseconds'"
+        );
+        let owner = owner_id("alice");
+        assert!(owner.starts_with("alice-"));
+        assert_ne!(owner, owner_id("alice"));
+        assert!(owner_id("").starts_with("catalog-"));
+    }
+}
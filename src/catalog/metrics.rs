@@ -0,0 +1,578 @@
+/*!
+Commit and scan reports of the Iceberg metrics reporting API.
+
+Reports are passed to [PostgresCatalog::report_metrics], which stores them in the catalog database
+if [with_metrics_persistence](super::builder::PostgresCatalogBuilder::with_metrics_persistence) is
+enabled and forwards them to the [MetricsReporter]s of the catalog. The catalog reports a
+[CommitReport] itself for every commit that adds a snapshot, with the counters of the snapshot
+summary. Scan reports are produced by the query engine.
+
+Reports that engines send to the metrics endpoint of a REST catalog server, like Spark, Trino or
+Rust writers, are ingested with [PostgresCatalog::ingest_metrics], which accepts the request body
+of the endpoint as it is. The engine that sent a report is recorded in its `metadata`, for example
+with `engine-name` and `engine-version`.
+
+Stored reports are returned by [PostgresCatalog::metrics_reports], or can be analyzed in SQL. The
+`report` column holds the report in the JSON format of the Iceberg REST specification.
+*/
+
+use std::{
+    collections::HashMap,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use anyhow::{anyhow, Result};
+use iceberg_rs::catalog::{table_identifier::TableIdentifier, Catalog};
+use serde_json::{json, Value};
+
+use super::{
+    access::Action, error::CatalogError, namespace::namespace_key, query::literal, PostgresCatalog,
+    CATALOG_NAME_COLUMN, TABLE_NAMESPACE_COLUMN, TABLE_NAME_COLUMN,
+};
+
+static REPORT_TYPE_COLUMN: &str = "report_type";
+static SNAPSHOT_ID_COLUMN: &str = "snapshot_id";
+static REPORT_COLUMN: &str = "report";
+static REPORTED_AT_COLUMN: &str = "reported_at";
+
+static COMMIT_REPORT: &str = "commit-report";
+static SCAN_REPORT: &str = "scan-report";
+
+/// Metrics of a commit
+#[derive(Debug, Clone)]
+pub struct CommitReport {
+    /// Identifier of the table
+    pub identifier: TableIdentifier,
+    /// Snapshot created by the commit
+    pub snapshot_id: i64,
+    /// Sequence number of the snapshot
+    pub sequence_number: i64,
+    /// Operation of the snapshot, like `append` or `overwrite`
+    pub operation: String,
+    /// Counters like `added-data-files` or `added-records`
+    pub metrics: HashMap<String, i64>,
+    /// Metadata of the report, like the `engine-name` of the engine that reported it
+    pub metadata: HashMap<String, String>,
+}
+
+impl CommitReport {
+    /// Report of the current snapshot of the table metadata, with the counters of its summary.
+    /// Returns `None` if the table has no current snapshot.
+    pub fn from_metadata(identifier: &TableIdentifier, metadata: &Value) -> Option<Self> {
+        let snapshot = current_snapshot(metadata)?;
+        let summary = &snapshot["summary"];
+        let metrics = summary
+            .as_object()
+            .into_iter()
+            .flatten()
+            .filter_map(|(name, value)| {
+                let value = value.as_str()?.parse().ok()?;
+                Some((name.clone(), value))
+            })
+            .collect();
+        Some(CommitReport {
+            identifier: identifier.clone(),
+            snapshot_id: snapshot["snapshot-id"].as_i64()?,
+            sequence_number: snapshot["sequence-number"].as_i64().unwrap_or(0),
+            operation: summary["operation"]
+                .as_str()
+                .unwrap_or("append")
+                .to_string(),
+            metrics,
+            metadata: HashMap::new(),
+        })
+    }
+}
+
+/// Metrics of a table scan
+#[derive(Debug, Clone)]
+pub struct ScanReport {
+    /// Identifier of the table
+    pub identifier: TableIdentifier,
+    /// Scanned snapshot
+    pub snapshot_id: i64,
+    /// Filter expression of the scan, the JSON of the expression if the engine reported it as
+    /// an expression
+    pub filter: String,
+    /// Schema used for the scan
+    pub schema_id: i64,
+    /// Ids of the projected fields
+    pub projected_field_ids: Vec<i64>,
+    /// Names of the projected fields
+    pub projected_field_names: Vec<String>,
+    /// Counters like `result-data-files` or `skipped-data-manifests`
+    pub metrics: HashMap<String, i64>,
+    /// Metadata of the report, like the `engine-name` of the engine that reported it
+    pub metadata: HashMap<String, String>,
+}
+
+/// Report of the metrics reporting API
+#[derive(Debug, Clone)]
+pub enum MetricsReport {
+    /// Metrics of a commit
+    Commit(CommitReport),
+    /// Metrics of a table scan
+    Scan(ScanReport),
+}
+
+impl MetricsReport {
+    /// Identifier of the reported table
+    pub fn identifier(&self) -> &TableIdentifier {
+        match self {
+            MetricsReport::Commit(report) => &report.identifier,
+            MetricsReport::Scan(report) => &report.identifier,
+        }
+    }
+
+    /// Report in the JSON format of the REST specification
+    pub fn to_json(&self) -> Value {
+        match self {
+            MetricsReport::Commit(report) => json!({
+                "report-type": COMMIT_REPORT,
+                "table-name": report.identifier.to_string(),
+                "snapshot-id": report.snapshot_id,
+                "sequence-number": report.sequence_number,
+                "operation": report.operation,
+                "metrics": metrics_json(&report.metrics),
+                "metadata": report.metadata,
+            }),
+            MetricsReport::Scan(report) => json!({
+                "report-type": SCAN_REPORT,
+                "table-name": report.identifier.to_string(),
+                "snapshot-id": report.snapshot_id,
+                "filter": report.filter,
+                "schema-id": report.schema_id,
+                "projected-field-ids": report.projected_field_ids,
+                "projected-field-names": report.projected_field_names,
+                "metrics": metrics_json(&report.metrics),
+                "metadata": report.metadata,
+            }),
+        }
+    }
+
+    /// Parse a report in the JSON format of the REST specification
+    pub fn from_json(report: &Value) -> Result<Self> {
+        Self::parse(report, None)
+    }
+
+    /// Parse the body of a request to the metrics endpoint of the REST specification for the table
+    /// of the endpoint. Engines name the table in the report in their own way, for example with
+    /// the name of their catalog, so the name in the report is ignored.
+    pub fn from_request(identifier: &TableIdentifier, request: &Value) -> Result<Self> {
+        Self::parse(request, Some(identifier))
+    }
+
+    fn parse(report: &Value, identifier: Option<&TableIdentifier>) -> Result<Self> {
+        let field = |name: &str| {
+            report
+                .get(name)
+                .ok_or_else(|| anyhow!("The metrics report has no {}.", name))
+        };
+        let integer = |name: &str| {
+            field(name)?
+                .as_i64()
+                .ok_or_else(|| anyhow!("The {} of the metrics report is not a number.", name))
+        };
+        let string = |name: &str| {
+            field(name)?
+                .as_str()
+                .map(ToString::to_string)
+                .ok_or_else(|| anyhow!("The {} of the metrics report is not a string.", name))
+        };
+        let identifier = match identifier {
+            Some(identifier) => identifier.clone(),
+            None => TableIdentifier::parse(&string("table-name")?)?,
+        };
+        let metrics = parse_metrics(field("metrics")?);
+        let metadata = report["metadata"]
+            .as_object()
+            .into_iter()
+            .flatten()
+            .filter_map(|(key, value)| Some((key.clone(), value.as_str()?.to_string())))
+            .collect();
+        match report["report-type"].as_str() {
+            Some(report_type) if report_type == COMMIT_REPORT => {
+                Ok(MetricsReport::Commit(CommitReport {
+                    identifier,
+                    snapshot_id: integer("snapshot-id")?,
+                    sequence_number: integer("sequence-number")?,
+                    operation: string("operation")?,
+                    metrics,
+                    metadata,
+                }))
+            }
+            Some(report_type) if report_type == SCAN_REPORT => {
+                Ok(MetricsReport::Scan(ScanReport {
+                    identifier,
+                    snapshot_id: integer("snapshot-id")?,
+                    filter: match field("filter")? {
+                        Value::String(filter) => filter.clone(),
+                        expression => expression.to_string(),
+                    },
+                    schema_id: integer("schema-id")?,
+                    projected_field_ids: report["projected-field-ids"]
+                        .as_array()
+                        .into_iter()
+                        .flatten()
+                        .filter_map(Value::as_i64)
+                        .collect(),
+                    projected_field_names: field("projected-field-names")?
+                        .as_array()
+                        .into_iter()
+                        .flatten()
+                        .filter_map(|name| name.as_str().map(ToString::to_string))
+                        .collect(),
+                    metrics,
+                    metadata,
+                }))
+            }
+            _ => Err(anyhow!("The metrics report has an unknown report type.")),
+        }
+    }
+
+    fn report_type(&self) -> &'static str {
+        match self {
+            MetricsReport::Commit(_) => COMMIT_REPORT,
+            MetricsReport::Scan(_) => SCAN_REPORT,
+        }
+    }
+
+    fn snapshot_id(&self) -> i64 {
+        match self {
+            MetricsReport::Commit(report) => report.snapshot_id,
+            MetricsReport::Scan(report) => report.snapshot_id,
+        }
+    }
+}
+
+/// Receives the metrics reports of the catalog
+#[async_trait::async_trait]
+pub trait MetricsReporter: Send + Sync {
+    /// Handle the report
+    async fn report(&self, report: &MetricsReport) -> Result<()>;
+}
+
+impl PostgresCatalog {
+    /// Store the report, if metrics persistence is enabled, and pass it to the metrics reporters
+    /// of the catalog.
+    pub async fn report_metrics(&self, report: MetricsReport) -> Result<()> {
+        let identifier = self.case_sensitivity.normalize(report.identifier())?;
+        if self.persist_metrics && !self.read_only {
+            // Not marked as a write, reports shouldn't move reads to the primary.
+            self.execute(
+                &self.primary,
+                &("INSERT INTO ".to_string()
+                    + &self.catalog_table.metrics
+                    + " ("
+                    + CATALOG_NAME_COLUMN
+                    + ", "
+                    + TABLE_NAMESPACE_COLUMN
+                    + ", "
+                    + TABLE_NAME_COLUMN
+                    + ", "
+                    + REPORT_TYPE_COLUMN
+                    + ", "
+                    + SNAPSHOT_ID_COLUMN
+                    + ", "
+                    + REPORT_COLUMN
+                    + ", "
+                    + REPORTED_AT_COLUMN
+                    + ") VALUES ("
+                    + &literal(&self.name)
+                    + ", "
+                    + &literal(&namespace_key(identifier.namespace()))
+                    + ", "
+                    + &literal(identifier.name())
+                    + ", "
+                    + &literal(report.report_type())
+                    + ", "
+                    + &report.snapshot_id().to_string()
+                    + ", "
+                    + &literal(&report.to_json().to_string())
+                    + "::JSONB, now());"),
+            )
+            .await?;
+        }
+        for reporter in &self.metrics_reporters {
+            reporter.report(&report).await?;
+        }
+        Ok(())
+    }
+
+    /// Ingest the body of a request to the metrics endpoint of the REST specification, a report
+    /// of an engine on the table. The caller has to be allowed to read the table. Fails with
+    /// [CatalogError::NotFound] if the table doesn't exist.
+    pub async fn ingest_metrics(
+        &self,
+        identifier: &TableIdentifier,
+        request: &Value,
+    ) -> Result<()> {
+        let identifier = self.case_sensitivity.normalize(identifier)?;
+        let report = MetricsReport::from_request(&identifier, request)?;
+        if !self.table_exists(&identifier).await? {
+            return Err(CatalogError::NotFound {
+                table: identifier.to_string(),
+            }
+            .into());
+        }
+        self.report_metrics(report).await
+    }
+
+    /// Stored reports of the table that were reported after `since`, oldest first.
+    pub async fn metrics_reports(
+        &self,
+        identifier: &TableIdentifier,
+        since: Option<SystemTime>,
+    ) -> Result<Vec<MetricsReport>> {
+        let identifier = self.case_sensitivity.normalize(identifier)?;
+        self.authorize(
+            Action::Read,
+            identifier.namespace(),
+            Some(identifier.name()),
+        )
+        .await?;
+        let since_condition = match since {
+            Some(since) => {
+                " AND ".to_string()
+                    + REPORTED_AT_COLUMN
+                    + " > to_timestamp("
+                    + &since
+                        .duration_since(UNIX_EPOCH)
+                        .unwrap_or_default()
+                        .as_millis()
+                        .to_string()
+                    + " / 1000.0)"
+            }
+            None => String::new(),
+        };
+        let rows = self
+            .query(
+                self.read_connection(),
+                &("SELECT ".to_string()
+                    + REPORT_COLUMN
+                    + "::TEXT AS "
+                    + REPORT_COLUMN
+                    + " FROM "
+                    + &self.catalog_table.metrics
+                    + " WHERE "
+                    + CATALOG_NAME_COLUMN
+                    + " = "
+                    + &literal(&self.name)
+                    + " AND "
+                    + TABLE_NAMESPACE_COLUMN
+                    + " = "
+                    + &literal(&namespace_key(identifier.namespace()))
+                    + " AND "
+                    + TABLE_NAME_COLUMN
+                    + " = "
+                    + &literal(identifier.name())
+                    + &since_condition
+                    + " ORDER BY "
+                    + REPORTED_AT_COLUMN
+                    + ";"),
+            )
+            .await?;
+        rows.iter()
+            .map(|row| {
+                let report: Value = serde_json::from_str(&row.try_get_string(REPORT_COLUMN)?)
+                    .map_err(|err| anyhow!(err.to_string()))?;
+                MetricsReport::from_json(&report)
+            })
+            .collect()
+    }
+
+    /// Report the commit of `metadata` if it added a snapshot. Failing to report doesn't fail the
+    /// commit.
+    pub(crate) async fn report_commit(&self, identifier: &TableIdentifier, metadata: &Value) {
+        if !self.persist_metrics && self.metrics_reporters.is_empty() {
+            return;
+        }
+        if !adds_snapshot(metadata) {
+            return;
+        }
+        if let Some(report) = CommitReport::from_metadata(identifier, metadata) {
+            let _ = self.report_metrics(MetricsReport::Commit(report)).await;
+        }
+    }
+
+    pub(crate) async fn create_metrics_table(&self) -> Result<()> {
+        self.execute(
+            &self.primary,
+            &("CREATE TABLE IF NOT EXISTS ".to_string()
+                + &self.catalog_table.metrics
+                + " ("
+                + CATALOG_NAME_COLUMN
+                + " VARCHAR(255) NOT NULL,"
+                + TABLE_NAMESPACE_COLUMN
+                + " VARCHAR(255) NOT NULL,"
+                + TABLE_NAME_COLUMN
+                + " VARCHAR(255) NOT NULL,"
+                + REPORT_TYPE_COLUMN
+                + " VARCHAR(32) NOT NULL,"
+                + SNAPSHOT_ID_COLUMN
+                + " BIGINT NOT NULL,"
+                + REPORT_COLUMN
+                + " JSONB NOT NULL,"
+                + REPORTED_AT_COLUMN
+                + " TIMESTAMPTZ NOT NULL"
+                + ");"),
+        )
+        .await?;
+        self.execute(
+            &self.primary,
+            &("CREATE INDEX IF NOT EXISTS ".to_string()
+                + &self.catalog_table.index("metrics_idx")
+                + " ON "
+                + &self.catalog_table.metrics
+                + " ("
+                + CATALOG_NAME_COLUMN
+                + ", "
+                + TABLE_NAMESPACE_COLUMN
+                + ", "
+                + TABLE_NAME_COLUMN
+                + ", "
+                + REPORTED_AT_COLUMN
+                + ");"),
+        )
+        .await?;
+        Ok(())
+    }
+}
+
+/// Check if the current snapshot was created after the previous metadata file was written. The
+/// metadata log records the time of the previous metadata file, metadata without log belongs to a
+/// new table.
+fn adds_snapshot(metadata: &Value) -> bool {
+    let snapshot_timestamp =
+        match current_snapshot(metadata).and_then(|snapshot| snapshot["timestamp-ms"].as_i64()) {
+            Some(timestamp) => timestamp,
+            None => return false,
+        };
+    match metadata["metadata-log"]
+        .as_array()
+        .and_then(|log| log.last())
+        .and_then(|entry| entry["timestamp-ms"].as_i64())
+    {
+        Some(previous_timestamp) => snapshot_timestamp > previous_timestamp,
+        None => true,
+    }
+}
+
+fn current_snapshot(metadata: &Value) -> Option<&Value> {
+    let current_snapshot_id = metadata["current-snapshot-id"].as_i64()?;
+    metadata["snapshots"]
+        .as_array()?
+        .iter()
+        .find(|snapshot| snapshot["snapshot-id"].as_i64() == Some(current_snapshot_id))
+}
+
+/// Counters in the format of the REST specification, sizes in bytes and everything else in counts
+fn metrics_json(metrics: &HashMap<String, i64>) -> Value {
+    metrics
+        .iter()
+        .map(|(name, value)| {
+            let unit = if name.ends_with("size") || name.ends_with("size-in-bytes") {
+                "bytes"
+            } else {
+                "count"
+            };
+            (name.clone(), json!({"unit": unit, "value": value}))
+        })
+        .collect::<serde_json::Map<_, _>>()
+        .into()
+}
+
+/// Parse counters and timers, timers are reduced to their total duration.
+fn parse_metrics(metrics: &Value) -> HashMap<String, i64> {
+    metrics
+        .as_object()
+        .into_iter()
+        .flatten()
+        .filter_map(|(name, metric)| {
+            let value = metric
+                .get("value")
+                .or_else(|| metric.get("total-duration"))?
+                .as_i64()?;
+            Some((name.clone(), value))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use iceberg_rs::catalog::table_identifier::TableIdentifier;
+    use serde_json::json;
+
+    use super::{adds_snapshot, CommitReport, MetricsReport};
+
+    #[test]
+    fn test_commit_report() {
+        let identifier = TableIdentifier::parse("test.metrics").unwrap();
+        let metadata = json!({
+            "current-snapshot-id": 2,
+            "snapshots": [{
+                "snapshot-id": 2,
+                "sequence-number": 2,
+                "timestamp-ms": 2000,
+                "summary": {
+                    "operation": "append",
+                    "added-data-files": "3",
+                    "added-files-size": "4096",
+                    "spark.app.id": "local-1"
+                }
+            }],
+            "metadata-log": [{"timestamp-ms": 1000, "metadata-file": "v1.metadata.json"}]
+        });
+        let report = CommitReport::from_metadata(&identifier, &metadata).unwrap();
+        assert_eq!(report.snapshot_id, 2);
+        assert_eq!(report.operation, "append");
+        assert_eq!(report.metrics.len(), 2);
+        assert_eq!(report.metrics["added-data-files"], 3);
+        assert!(adds_snapshot(&metadata));
+
+        let report = MetricsReport::Commit(report);
+        let json = report.to_json();
+        assert_eq!(json["metrics"]["added-files-size"]["unit"], "bytes");
+        assert_eq!(MetricsReport::from_json(&json).unwrap().to_json(), json);
+
+        let mut unchanged = metadata;
+        unchanged["metadata-log"][0]["timestamp-ms"] = json!(3000);
+        assert!(!adds_snapshot(&unchanged));
+    }
+
+    #[test]
+    fn test_report_request() {
+        let identifier = TableIdentifier::parse("db.events").unwrap();
+        let request = json!({
+            "report-type": "scan-report",
+            "table-name": "spark_catalog.db.events",
+            "snapshot-id": 7,
+            "filter": {"type": "eq", "term": "id", "value": 1},
+            "schema-id": 0,
+            "projected-field-ids": [1, 2],
+            "projected-field-names": ["id", "data"],
+            "metrics": {
+                "total-planning-duration": {"count": 1, "time-unit": "nanoseconds", "total-duration": 2644235116},
+                "result-data-files": {"unit": "count", "value": 1}
+            },
+            "metadata": {"engine-name": "spark", "engine-version": "3.5.0"}
+        });
+        let report = match MetricsReport::from_request(&identifier, &request).unwrap() {
+            MetricsReport::Scan(report) => report,
+            MetricsReport::Commit(_) => panic!("The report is no scan report."),
+        };
+        assert_eq!(report.identifier.to_string(), "db.events");
+        assert_eq!(report.filter, request["filter"].to_string());
+        assert_eq!(report.projected_field_ids, vec![1, 2]);
+        assert_eq!(report.metrics["total-planning-duration"], 2644235116);
+        assert_eq!(report.metadata["engine-name"], "spark");
+
+        let report = MetricsReport::Scan(report);
+        assert_eq!(
+            MetricsReport::from_json(&report.to_json())
+                .unwrap()
+                .to_json(),
+            report.to_json()
+        );
+    }
+}
@@ -0,0 +1,363 @@
+/*!
+Atomic commits to several tables.
+
+Engines that write to several tables in one job, like the `commitTransaction` endpoint of the REST
+specification, need the changes of all tables to become visible together or not at all.
+[PostgresCatalog::commit_tables] swaps the metadata pointers of all tables in one postgres
+transaction. Each table has to point to the expected metadata file, otherwise no table is changed
+and the commit fails with
+[CatalogError::CommitConflict](super::error::CatalogError::CommitConflict) for the first table
+that was changed concurrently.
+
+The caller writes the new metadata file of every table before, for a REST request by applying the
+updates of each table change to the current metadata of the table after checking its
+requirements. The checks of single commits, like access, commit policies and quotas, apply to
+every table.
+
+The pointers are swapped by a single transaction, so dropping the future of the commit never
+leaves some tables committed and others not. Whether a dropped commit was applied is only known
+after reloading the tables.
+*/
+
+use std::{sync::Arc, time::Instant};
+
+use anyhow::{anyhow, Result};
+use iceberg_rs::{
+    catalog::{table_identifier::TableIdentifier, Catalog},
+    table::Table,
+};
+use serde_json::Value;
+
+use super::{
+    access::Action, checksum::checksum, error::CatalogError, history, namespace::namespace_key,
+    notification::TableEventKind, query::literal, quota::table_size, rate_limit::Operation,
+    stats::CommitOutcome, timeout::with_timeout, PostgresCatalog, ARCHIVED_AT_COLUMN,
+    CATALOG_NAME_COLUMN, CURRENT_SNAPSHOT_ID_COLUMN, LAST_SEQUENCE_NUMBER_COLUMN,
+    METADATA_CHECKSUM_COLUMN, METADATA_LOCATION_COLUMN, PREVIOUS_METADATA_LOCATION_COLUMN,
+    SNAPSHOT_SUMMARY_COLUMN, TABLE_NAMESPACE_COLUMN, TABLE_NAME_COLUMN, TABLE_SIZE_COLUMN,
+    TABLE_UUID_COLUMN, UPDATED_AT_COLUMN, VERSION_COLUMN,
+};
+
+/// New metadata of a table in a commit to several tables
+#[derive(Debug, Clone)]
+pub struct TableCommit {
+    /// Identifier of the table
+    pub identifier: TableIdentifier,
+    /// Metadata location the table has to point to, the location the new metadata is based on
+    pub previous_location: String,
+    /// Location of the new metadata file
+    pub metadata_location: String,
+}
+
+/// Change of the catalog entry of a table
+struct Change {
+    identifier: TableIdentifier,
+    expected: String,
+    expected_stored: String,
+    location: String,
+    metadata: Value,
+    bytes: Vec<u8>,
+}
+
+impl PostgresCatalog {
+    /// Point all tables to their new metadata files in one transaction, if every table still
+    /// points to its previous location. Returns the committed tables in the order of the
+    /// commits.
+    pub async fn commit_tables(self: Arc<Self>, commits: Vec<TableCommit>) -> Result<Vec<Table>> {
+        self.check_writable("Committing the tables")?;
+        if commits.is_empty() {
+            return Ok(Vec::new());
+        }
+        self.throttle(Operation::Commit).await?;
+        let started = Instant::now();
+        let mut changes: Vec<Change> = Vec::with_capacity(commits.len());
+        for commit in commits {
+            let identifier = self.case_sensitivity.normalize(&commit.identifier)?;
+            if changes
+                .iter()
+                .any(|change| change.identifier.to_string() == identifier.to_string())
+            {
+                return Err(anyhow!(
+                    "Committing the tables failed. The table {} is committed to more than once.",
+                    identifier
+                ));
+            }
+            self.authorize(
+                Action::Commit,
+                identifier.namespace(),
+                Some(identifier.name()),
+            )
+            .await?;
+            let bytes = self
+                .read_metadata(&identifier, &commit.metadata_location)
+                .await?;
+            let metadata: Value =
+                serde_json::from_slice(&bytes).map_err(|err| anyhow!(err.to_string()))?;
+            self.check_commit_policies(&identifier, &metadata).await?;
+            self.check_quotas(&identifier, false, table_size(&metadata))
+                .await?;
+            let location = self
+                .apply_metadata_codec(&identifier, &commit.metadata_location, &metadata, &bytes)
+                .await?;
+            let expected = self.resolved_location(&commit.previous_location);
+            changes.push(Change {
+                expected_stored: self.stored_location(&expected),
+                expected,
+                location,
+                metadata,
+                bytes,
+                identifier,
+            });
+        }
+        self.mark_write();
+        let result = with_timeout(
+            self.timeouts.commit,
+            "Commit",
+            self.transaction(self.isolation_level, &self.commit_statements(&changes)),
+        )
+        .await;
+        let committed = match &result {
+            Ok(results) => results.last().map(|result| result.n_rows).unwrap_or(0),
+            Err(_) => 0,
+        };
+        if committed != changes.len() as u64 {
+            let err = match result {
+                Err(err) => err,
+                Ok(_) => self.commit_failure(&changes).await?,
+            };
+            let outcome = match err.downcast_ref::<CatalogError>() {
+                Some(CatalogError::CommitConflict { .. }) => CommitOutcome::Conflict,
+                _ => CommitOutcome::Failed,
+            };
+            for change in &changes {
+                self.record_commit(&change.identifier, outcome, started.elapsed())
+                    .await;
+            }
+            return Err(err);
+        }
+        let rows = result?.pop().map(|result| result.rows).unwrap_or_default();
+        for change in &changes {
+            let namespace = namespace_key(change.identifier.namespace());
+            for row in &rows {
+                if row.try_get_string(TABLE_NAMESPACE_COLUMN)? == namespace
+                    && row.try_get_string(TABLE_NAME_COLUMN)? == change.identifier.name()
+                {
+                    let version = row.try_get_i64(VERSION_COLUMN)?;
+                    let _ = self
+                        .write_version_hint(
+                            &change.identifier,
+                            &change.metadata,
+                            version,
+                            &change.bytes,
+                        )
+                        .await;
+                }
+            }
+            self.report_commit(&change.identifier, &change.metadata)
+                .await;
+            self.notify(
+                TableEventKind::Committed,
+                &change.identifier,
+                Some(change.location.as_str()),
+                Some(&change.metadata),
+            )
+            .await;
+            self.record_commit(
+                &change.identifier,
+                CommitOutcome::Committed,
+                started.elapsed(),
+            )
+            .await;
+        }
+        let mut tables = Vec::with_capacity(changes.len());
+        for change in changes {
+            tables.push(Arc::clone(&self).load_table(change.identifier).await?);
+        }
+        Ok(tables)
+    }
+
+    /// Statements that lock the entries and then swap all pointers, but only if every table
+    /// still points to its expected metadata file and belongs to the new metadata
+    fn commit_statements(&self, changes: &[Change]) -> Vec<String> {
+        let opt_literal = |value: Option<String>| match value {
+            Some(value) => literal(&value),
+            None => "NULL".to_string(),
+        };
+        let values = changes
+            .iter()
+            .map(|change| {
+                let identifier = &change.identifier;
+                let metadata = &change.metadata;
+                let summary = self
+                    .snapshot_summaries
+                    .then(|| history::snapshot_summary(metadata, &change.location).to_string());
+                "(".to_string()
+                    + &literal(&namespace_key(identifier.namespace()))
+                    + ", "
+                    + &literal(identifier.name())
+                    + ", "
+                    + &literal(&change.expected)
+                    + ", "
+                    + &literal(&change.expected_stored)
+                    + ", "
+                    + &literal(&self.stored_location(&change.location))
+                    + ", "
+                    + &opt_literal(metadata["table-uuid"].as_str().map(str::to_string))
+                    + ", "
+                    + &opt_literal(table_size(metadata).map(|size| size.to_string()))
+                    + ", "
+                    + &literal(&checksum(&change.bytes))
+                    + ", "
+                    + &opt_literal(summary)
+                    + ", "
+                    + &opt_literal(history::current_snapshot_id(metadata).map(|id| id.to_string()))
+                    + ", "
+                    + &opt_literal(
+                        metadata["last-sequence-number"]
+                            .as_i64()
+                            .map(|number| number.to_string()),
+                    )
+                    + ")"
+            })
+            .collect::<Vec<_>>()
+            .join(", ");
+        let changed = "changed (namespace, name, expected, expected_stored, location, uuid, size, \
+            checksum, summary, snapshot_id, sequence_number) AS (VALUES "
+            .to_string()
+            + &values
+            + ")";
+        // Condition that the entry `table` is the table of the row `values` of the changes
+        let matches = |table: &str, values: &str| {
+            table.to_string()
+                + "."
+                + CATALOG_NAME_COLUMN
+                + " = "
+                + &literal(&self.name)
+                + " AND "
+                + table
+                + "."
+                + TABLE_NAMESPACE_COLUMN
+                + " = "
+                + values
+                + ".namespace AND "
+                + table
+                + "."
+                + TABLE_NAME_COLUMN
+                + " = "
+                + values
+                + ".name"
+        };
+        // Condition that the entry `table` points to the expected metadata of the table, belongs
+        // to the new metadata and isn't archived
+        let unchanged = |table: &str, values: &str| {
+            matches(table, values)
+                + " AND "
+                + table
+                + "."
+                + METADATA_LOCATION_COLUMN
+                + " IN ("
+                + values
+                + ".expected, "
+                + values
+                + ".expected_stored) AND ("
+                + values
+                + ".uuid IS NULL OR "
+                + table
+                + "."
+                + TABLE_UUID_COLUMN
+                + " IS NULL OR "
+                + table
+                + "."
+                + TABLE_UUID_COLUMN
+                + " = "
+                + values
+                + ".uuid::UUID) AND "
+                + table
+                + "."
+                + ARCHIVED_AT_COLUMN
+                + " IS NULL"
+        };
+        // Concurrent commits to overlapping tables lock the entries in the same order, so they
+        // wait for each other instead of deadlocking.
+        let lock = "WITH ".to_string()
+            + &changed
+            + " SELECT 1 FROM "
+            + &self.catalog_table.qualified
+            + " AS entry, changed WHERE "
+            + &matches("entry", "changed")
+            + " ORDER BY entry."
+            + TABLE_NAMESPACE_COLUMN
+            + ", entry."
+            + TABLE_NAME_COLUMN
+            + " FOR UPDATE OF entry;";
+        // The entries are locked, so the check for unchanged pointers holds until the commit.
+        let update = "WITH ".to_string()
+            + &changed
+            + " UPDATE "
+            + &self.catalog_table.qualified
+            + " AS entry SET "
+            + METADATA_LOCATION_COLUMN
+            + " = changed.location, "
+            + PREVIOUS_METADATA_LOCATION_COLUMN
+            + " = entry."
+            + METADATA_LOCATION_COLUMN
+            + ", "
+            + VERSION_COLUMN
+            + " = entry."
+            + VERSION_COLUMN
+            + " + 1, "
+            + UPDATED_AT_COLUMN
+            + " = now(), "
+            + TABLE_UUID_COLUMN
+            + " = COALESCE(entry."
+            + TABLE_UUID_COLUMN
+            + ", changed.uuid::UUID), "
+            + TABLE_SIZE_COLUMN
+            + " = changed.size::BIGINT, "
+            + METADATA_CHECKSUM_COLUMN
+            + " = changed.checksum, "
+            + SNAPSHOT_SUMMARY_COLUMN
+            + " = changed.summary::JSONB, "
+            + CURRENT_SNAPSHOT_ID_COLUMN
+            + " = changed.snapshot_id::BIGINT, "
+            + LAST_SEQUENCE_NUMBER_COLUMN
+            + " = changed.sequence_number::BIGINT FROM changed WHERE "
+            + &unchanged("entry", "changed")
+            + " AND NOT EXISTS (SELECT 1 FROM changed AS expected WHERE NOT EXISTS (SELECT 1 FROM "
+            + &self.catalog_table.qualified
+            + " AS current WHERE "
+            + &unchanged("current", "expected")
+            + ")) RETURNING entry."
+            + TABLE_NAMESPACE_COLUMN
+            + ", entry."
+            + TABLE_NAME_COLUMN
+            + ", entry."
+            + VERSION_COLUMN
+            + ";";
+        vec![lock, update]
+    }
+
+    /// Error for a commit that changed no table: the first table that doesn't exist, was changed
+    /// concurrently or is archived
+    async fn commit_failure(&self, changes: &[Change]) -> Result<anyhow::Error> {
+        for change in changes {
+            let location = match self.stored_metadata_location(&change.identifier).await {
+                Ok(location) => location,
+                Err(err) => return Ok(err),
+            };
+            if location != change.expected && location != change.expected_stored {
+                return Ok(CatalogError::CommitConflict {
+                    table: change.identifier.to_string(),
+                }
+                .into());
+            }
+            if let Err(err) = self.check_not_archived(&change.identifier).await {
+                return Ok(err);
+            }
+        }
+        // All pointers are as expected, so the metadata of a table belongs to a different table.
+        Ok(anyhow!(
+            "Committing the tables failed. The new metadata of a table belongs to a different table."
+        ))
+    }
+}
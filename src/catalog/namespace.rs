@@ -0,0 +1,130 @@
+/*!
+Storage format of multi-level namespaces in the catalog table.
+
+A namespace is stored as its levels joined with `.`, like `a.b.c`. Dots and backslashes inside a
+level are escaped with a backslash, so that every namespace has exactly one stored value and the
+levels can be recovered from it. Namespaces without such characters are stored exactly as they are
+displayed.
+*/
+
+use anyhow::{anyhow, Result};
+use iceberg_rs::catalog::{namespace::Namespace, table_identifier::TableIdentifier};
+
+use super::{
+    query::{like_pattern, literal},
+    CATALOG_NAME_COLUMN, TABLE_NAMESPACE_COLUMN, TABLE_NAME_COLUMN,
+};
+
+/// Stored value of the namespace
+pub(crate) fn namespace_key(namespace: &Namespace) -> String {
+    namespace
+        .levels()
+        .iter()
+        .map(|level| level.replace('\\', "\\\\").replace('.', "\\."))
+        .collect::<Vec<_>>()
+        .join(".")
+}
+
+/// Levels of a stored namespace
+pub(crate) fn namespace_levels(key: &str) -> Result<Vec<String>> {
+    let mut levels = Vec::new();
+    let mut level = String::new();
+    let mut chars = key.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => level.push(
+                chars
+                    .next()
+                    .ok_or_else(|| anyhow!("The namespace {} ends with an escape.", key))?,
+            ),
+            '.' => levels.push(std::mem::take(&mut level)),
+            c => level.push(c),
+        }
+    }
+    levels.push(level);
+    Ok(levels)
+}
+
+/// Key of the table in maps. Unlike the displayed identifier, it differs for tables whose names
+/// only differ in the placement of dots.
+pub(crate) fn identifier_key(identifier: &TableIdentifier) -> (String, String) {
+    (
+        namespace_key(identifier.namespace()),
+        identifier.name().to_string(),
+    )
+}
+
+/// Identifier of a table from its stored namespace and its name
+pub(crate) fn table_identifier(namespace_key: &str, name: &str) -> Result<TableIdentifier> {
+    let mut names = namespace_levels(namespace_key)?;
+    names.push(name.to_string());
+    TableIdentifier::try_new(&names)
+}
+
+/// SQL condition that matches the entry of the table in the catalog
+pub(crate) fn table_condition(catalog: &str, identifier: &TableIdentifier) -> String {
+    CATALOG_NAME_COLUMN.to_string()
+        + " = "
+        + &literal(catalog)
+        + " AND "
+        + TABLE_NAMESPACE_COLUMN
+        + " = "
+        + &literal(&namespace_key(identifier.namespace()))
+        + " AND "
+        + TABLE_NAME_COLUMN
+        + " = "
+        + &literal(identifier.name())
+}
+
+/// Prefix shared by the stored values of all namespaces below the parent
+pub(crate) fn child_prefix(parent: &Namespace) -> String {
+    if parent.levels().is_empty() {
+        String::new()
+    } else {
+        namespace_key(parent) + "."
+    }
+}
+
+/// SQL condition that matches the entries of the namespace and of its children
+pub(crate) fn namespace_condition(namespace: &Namespace) -> String {
+    TABLE_NAMESPACE_COLUMN.to_string()
+        + " = "
+        + &literal(&namespace_key(namespace))
+        + " OR "
+        + TABLE_NAMESPACE_COLUMN
+        + " LIKE "
+        + &literal(&(like_pattern(&child_prefix(namespace)) + "%"))
+}
+
+#[cfg(test)]
+mod tests {
+    use iceberg_rs::catalog::namespace::Namespace;
+
+    use super::{child_prefix, namespace_key, namespace_levels, table_identifier};
+
+    #[test]
+    fn test_namespace_key() {
+        let namespace =
+            Namespace::try_new(&["a".to_string(), "b.c".to_string(), "d\\e".to_string()]).unwrap();
+        let key = namespace_key(&namespace);
+        assert_eq!(key, "a.b\\.c.d\\\\e");
+        assert_eq!(
+            namespace_levels(&key).unwrap(),
+            vec!["a".to_string(), "b.c".to_string(), "d\\e".to_string()]
+        );
+        assert!(namespace_levels("a\\").is_err());
+    }
+
+    #[test]
+    fn test_table_identifier() {
+        let identifier = table_identifier("a.b", "table1").unwrap();
+        assert_eq!(format!("{}", identifier), "a.b.table1");
+        assert_eq!(format!("{}", identifier.namespace()), "a.b");
+    }
+
+    #[test]
+    fn test_child_prefix() {
+        let parent = Namespace::try_new(&["a".to_string(), "b".to_string()]).unwrap();
+        assert_eq!(child_prefix(&parent), "a.b.");
+    }
+}
@@ -0,0 +1,399 @@
+/*!
+Default table properties of namespaces.
+
+Namespaces can define default properties, like the compression codec, the target file size or the
+snapshot retention, that are merged into the metadata of tables created with `create_table`,
+`build_table` or [create_table_with_location](PostgresCatalog::create_table_with_location). The
+defaults of a namespace apply to the tables of its children as well, defaults of a child override
+the ones of its parents. Catalog properties with the prefix `table-default.`, as in the Iceberg Java
+implementation, apply to all namespaces. Properties that the table sets itself are never
+overwritten.
+
+The defaults are merged when the table builder registers the new table, by rewriting the first
+metadata file of the table before the catalog points to it. Tables registered from existing
+metadata files with `register_table` are left unchanged.
+*/
+
+use std::collections::HashMap;
+
+use anyhow::{anyhow, Result};
+use iceberg_rs::{
+    catalog::{namespace::Namespace, table_identifier::TableIdentifier},
+    object_store::path::Path,
+};
+use serde_json::Value;
+
+use super::{
+    access::Action, namespace::namespace_key, query::literal, storage::resolver::object_path,
+    PostgresCatalog, CATALOG_NAME_COLUMN, PROPERTY_KEY_COLUMN, PROPERTY_VALUE_COLUMN,
+    TABLE_NAMESPACE_COLUMN,
+};
+
+/// Prefix of catalog properties that are defaults of all new tables
+pub static TABLE_DEFAULT_PREFIX: &str = "table-default.";
+
+impl PostgresCatalog {
+    /// Set default properties of the new tables in the namespace and its children. Existing
+    /// defaults with the same keys are overwritten.
+    pub async fn set_namespace_defaults(
+        &self,
+        namespace: &Namespace,
+        properties: &HashMap<String, String>,
+    ) -> Result<()> {
+        self.check_writable("Setting namespace defaults")?;
+        let namespace = &self.case_sensitivity.normalize_namespace(namespace)?;
+        self.authorize(Action::Create, namespace, None).await?;
+        if properties.is_empty() {
+            return Ok(());
+        }
+        let values = properties
+            .iter()
+            .map(|(key, value)| {
+                "(".to_string()
+                    + &literal(&self.name)
+                    + ", "
+                    + &literal(&namespace_key(namespace))
+                    + ", "
+                    + &literal(key)
+                    + ", "
+                    + &literal(value)
+                    + ")"
+            })
+            .collect::<Vec<_>>()
+            .join(", ");
+        self.execute(
+            &self.primary,
+            &("INSERT INTO ".to_string()
+                + &self.catalog_table.namespace_defaults
+                + " ("
+                + CATALOG_NAME_COLUMN
+                + ", "
+                + TABLE_NAMESPACE_COLUMN
+                + ", "
+                + PROPERTY_KEY_COLUMN
+                + ", "
+                + PROPERTY_VALUE_COLUMN
+                + ") VALUES "
+                + &values
+                + " ON CONFLICT ("
+                + CATALOG_NAME_COLUMN
+                + ", "
+                + TABLE_NAMESPACE_COLUMN
+                + ", "
+                + PROPERTY_KEY_COLUMN
+                + ") DO UPDATE SET "
+                + PROPERTY_VALUE_COLUMN
+                + " = EXCLUDED."
+                + PROPERTY_VALUE_COLUMN
+                + ";"),
+        )
+        .await?;
+        self.mark_write();
+        Ok(())
+    }
+
+    /// Remove default properties of the namespace. Defaults of its parents apply again.
+    pub async fn remove_namespace_defaults(
+        &self,
+        namespace: &Namespace,
+        keys: &[&str],
+    ) -> Result<()> {
+        self.check_writable("Removing namespace defaults")?;
+        let namespace = &self.case_sensitivity.normalize_namespace(namespace)?;
+        self.authorize(Action::Create, namespace, None).await?;
+        if keys.is_empty() {
+            return Ok(());
+        }
+        self.execute(
+            &self.primary,
+            &("DELETE FROM ".to_string()
+                + &self.catalog_table.namespace_defaults
+                + " WHERE "
+                + CATALOG_NAME_COLUMN
+                + " = "
+                + &literal(&self.name)
+                + " AND "
+                + TABLE_NAMESPACE_COLUMN
+                + " = "
+                + &literal(&namespace_key(namespace))
+                + " AND "
+                + PROPERTY_KEY_COLUMN
+                + " IN ("
+                + &keys
+                    .iter()
+                    .map(|key| literal(key))
+                    .collect::<Vec<_>>()
+                    .join(", ")
+                + ");"),
+        )
+        .await?;
+        self.mark_write();
+        Ok(())
+    }
+
+    /// Default properties that the namespace defines itself, without the ones it inherits.
+    pub async fn namespace_defaults(
+        &self,
+        namespace: &Namespace,
+    ) -> Result<HashMap<String, String>> {
+        let namespace = &self.case_sensitivity.normalize_namespace(namespace)?;
+        self.authorize(Action::Read, namespace, None).await?;
+        let defaults = self.stored_defaults(&[namespace_key(namespace)]).await?;
+        Ok(defaults.into_iter().next().unwrap_or_default())
+    }
+
+    /// Default properties of new tables in the namespace: the `table-default.` catalog properties,
+    /// overridden by the defaults of the namespace and its parents, children before parents.
+    pub async fn effective_namespace_defaults(
+        &self,
+        namespace: &Namespace,
+    ) -> Result<HashMap<String, String>> {
+        let namespace = &self.case_sensitivity.normalize_namespace(namespace)?;
+        self.authorize(Action::Read, namespace, None).await?;
+        let mut defaults = table_defaults(&self.get_properties().await?);
+        for namespace_defaults in self.stored_defaults(&ancestor_keys(namespace)?).await? {
+            defaults.extend(namespace_defaults);
+        }
+        Ok(defaults)
+    }
+
+    /// Remember that a builder for a new table was created, so that the defaults are merged when
+    /// it registers the table.
+    pub(crate) fn expect_new_table(&self, identifier: &TableIdentifier) {
+        if let Ok(mut new_tables) = self.new_tables.lock() {
+            new_tables.insert((
+                namespace_key(identifier.namespace()),
+                identifier.name().to_string(),
+            ));
+        }
+    }
+
+    /// Merge the defaults of the namespace into the first metadata file of a table created by a
+    /// builder of the catalog. Returns the content of the file, rewritten if defaults were added.
+    pub(crate) async fn apply_namespace_defaults(
+        &self,
+        identifier: &TableIdentifier,
+        location: &str,
+        bytes: Vec<u8>,
+        mut metadata: Value,
+    ) -> Result<(Vec<u8>, Value)> {
+        let is_new = self
+            .new_tables
+            .lock()
+            .map(|mut new_tables| {
+                new_tables.remove(&(
+                    namespace_key(identifier.namespace()),
+                    identifier.name().to_string(),
+                ))
+            })
+            .unwrap_or(false);
+        if !is_new {
+            return Ok((bytes, metadata));
+        }
+        let defaults = self
+            .effective_namespace_defaults(identifier.namespace())
+            .await?;
+        if !merge_defaults(&mut metadata, defaults)? {
+            return Ok((bytes, metadata));
+        }
+        let bytes = serde_json::to_vec(&metadata).map_err(|err| anyhow!(err.to_string()))?;
+        // The file isn't referenced by the catalog yet, so it can be replaced.
+        let object_store = self.object_store_for_location(identifier, location)?;
+        let path: Path = object_path(location).into();
+        object_store
+            .put(&path, bytes.clone().into())
+            .await
+            .map_err(|err| anyhow!(err.to_string()))?;
+        Ok((bytes, metadata))
+    }
+
+    /// Defaults stored for each of the namespaces, in the order of the keys.
+    async fn stored_defaults(&self, keys: &[String]) -> Result<Vec<HashMap<String, String>>> {
+        let rows = self
+            .query(
+                self.read_connection(),
+                &("SELECT ".to_string()
+                    + TABLE_NAMESPACE_COLUMN
+                    + ", "
+                    + PROPERTY_KEY_COLUMN
+                    + ", "
+                    + PROPERTY_VALUE_COLUMN
+                    + " FROM "
+                    + &self.catalog_table.namespace_defaults
+                    + " WHERE "
+                    + CATALOG_NAME_COLUMN
+                    + " = "
+                    + &literal(&self.name)
+                    + " AND "
+                    + TABLE_NAMESPACE_COLUMN
+                    + " IN ("
+                    + &keys
+                        .iter()
+                        .map(|key| literal(key))
+                        .collect::<Vec<_>>()
+                        .join(", ")
+                    + ");"),
+            )
+            .await?;
+        let mut defaults = vec![HashMap::new(); keys.len()];
+        for row in rows {
+            let namespace = row.try_get_string(TABLE_NAMESPACE_COLUMN)?;
+            if let Some(index) = keys.iter().position(|key| *key == namespace) {
+                defaults[index].insert(
+                    row.try_get_string(PROPERTY_KEY_COLUMN)?,
+                    row.try_get_string(PROPERTY_VALUE_COLUMN)?,
+                );
+            }
+        }
+        Ok(defaults)
+    }
+
+    pub(crate) async fn create_namespace_defaults_table(&self) -> Result<()> {
+        self.execute(
+            &self.primary,
+            &("CREATE TABLE IF NOT EXISTS ".to_string()
+                + &self.catalog_table.namespace_defaults
+                + " ("
+                + CATALOG_NAME_COLUMN
+                + " VARCHAR(255) NOT NULL,"
+                + TABLE_NAMESPACE_COLUMN
+                + " VARCHAR(255) NOT NULL,"
+                + PROPERTY_KEY_COLUMN
+                + " VARCHAR(255) NOT NULL,"
+                + PROPERTY_VALUE_COLUMN
+                + " TEXT NOT NULL,"
+                + "PRIMARY KEY ("
+                + CATALOG_NAME_COLUMN
+                + ", "
+                + TABLE_NAMESPACE_COLUMN
+                + ", "
+                + PROPERTY_KEY_COLUMN
+                + ")"
+                + ");"),
+        )
+        .await?;
+        Ok(())
+    }
+}
+
+/// Stored values of the namespace and its parents, parents first
+fn ancestor_keys(namespace: &Namespace) -> Result<Vec<String>> {
+    let levels = namespace.levels();
+    (1..=levels.len())
+        .map(|depth| Ok(namespace_key(&Namespace::try_new(&levels[..depth])?)))
+        .collect()
+}
+
+/// Defaults of all tables from the catalog properties
+fn table_defaults(properties: &HashMap<String, String>) -> HashMap<String, String> {
+    properties
+        .iter()
+        .filter_map(|(key, value)| {
+            key.strip_prefix(TABLE_DEFAULT_PREFIX)
+                .filter(|key| !key.is_empty())
+                .map(|key| (key.to_string(), value.clone()))
+        })
+        .collect()
+}
+
+/// Add the defaults that the metadata doesn't set to its properties. Returns whether a default
+/// was added.
+fn merge_defaults(metadata: &mut Value, defaults: HashMap<String, String>) -> Result<bool> {
+    if defaults.is_empty() {
+        return Ok(false);
+    }
+    let object = metadata
+        .as_object_mut()
+        .ok_or_else(|| anyhow!("The table metadata is not an object."))?;
+    let properties = object
+        .entry("properties")
+        .or_insert_with(|| Value::Object(Default::default()))
+        .as_object_mut()
+        .ok_or_else(|| anyhow!("The properties of the table metadata are not an object."))?;
+    let mut added = false;
+    for (key, value) in defaults {
+        if !properties.contains_key(&key) {
+            properties.insert(key, Value::String(value));
+            added = true;
+        }
+    }
+    Ok(added)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use iceberg_rs::catalog::namespace::Namespace;
+    use serde_json::json;
+
+    use super::{ancestor_keys, merge_defaults, table_defaults};
+
+    #[test]
+    fn test_ancestor_keys() {
+        let namespace =
+            Namespace::try_new(&["a".to_string(), "b.c".to_string(), "d".to_string()]).unwrap();
+        assert_eq!(
+            ancestor_keys(&namespace).unwrap(),
+            vec![
+                "a".to_string(),
+                "a.b\\.c".to_string(),
+                "a.b\\.c.d".to_string()
+            ]
+        );
+    }
+
+    #[test]
+    fn test_table_defaults() {
+        let properties = HashMap::from_iter(vec![
+            (
+                "table-default.write.format.default".to_string(),
+                "parquet".to_string(),
+            ),
+            ("warehouse".to_string(), "memory://".to_string()),
+        ]);
+        assert_eq!(
+            table_defaults(&properties),
+            HashMap::from_iter(vec![(
+                "write.format.default".to_string(),
+                "parquet".to_string()
+            )])
+        );
+    }
+
+    #[test]
+    fn test_merge_defaults() {
+        let mut metadata = json!({
+            "format-version": 2,
+            "properties": {"write.parquet.compression-codec": "zstd"}
+        });
+        let defaults = HashMap::from_iter(vec![
+            (
+                "write.parquet.compression-codec".to_string(),
+                "snappy".to_string(),
+            ),
+            (
+                "write.target-file-size-bytes".to_string(),
+                "134217728".to_string(),
+            ),
+        ]);
+        assert!(merge_defaults(&mut metadata, defaults.clone()).unwrap());
+        assert_eq!(
+            metadata["properties"]["write.parquet.compression-codec"],
+            "zstd"
+        );
+        assert_eq!(
+            metadata["properties"]["write.target-file-size-bytes"],
+            "134217728"
+        );
+        assert!(!merge_defaults(&mut metadata, defaults).unwrap());
+
+        let mut metadata = json!({"format-version": 2});
+        assert!(merge_defaults(
+            &mut metadata,
+            HashMap::from_iter(vec![("owner".to_string(), "platform".to_string())])
+        )
+        .unwrap());
+        assert_eq!(metadata["properties"]["owner"], "platform");
+    }
+}
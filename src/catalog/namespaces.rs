@@ -0,0 +1,409 @@
+/*!
+Operations on whole namespaces.
+
+Namespaces aren't stored separately, they exist through the tables, dropped tables and namespace
+defaults that reference them. Operations on a namespace therefore change all of these entries.
+*/
+
+use std::{
+    sync::Arc,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use anyhow::{anyhow, Result};
+use iceberg_rs::catalog::namespace::Namespace;
+use serde_json::json;
+
+use super::{
+    access::Action,
+    commit::WRITE_METADATA_PATH,
+    is_unique_violation,
+    namespace::{namespace_condition, namespace_key, table_identifier},
+    notification::TableEventKind,
+    query::literal,
+    rate_limit::Operation,
+    PostgresCatalog, CATALOG_NAME_COLUMN, METADATA_LOCATION_COLUMN, TABLE_LOCATION_COLUMN,
+    TABLE_NAMESPACE_COLUMN, TABLE_NAME_COLUMN, UPDATED_AT_COLUMN,
+};
+
+/// Table property with the directory of new data files
+pub(crate) static WRITE_DATA_PATH: &str = "write.data.path";
+
+/// Contents of a namespace
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NamespaceSummary {
+    /// Number of tables directly in the namespace
+    pub tables: u64,
+    /// Number of tables in the namespace and all namespaces below it
+    pub total_tables: u64,
+    /// Number of views. The catalog doesn't store views, so this is always 0.
+    pub views: u64,
+    /// Time of the last commit or registration of a table in the namespace or below it, `None`
+    /// if it contains no tables
+    pub last_modified: Option<SystemTime>,
+}
+
+impl PostgresCatalog {
+    /// Whether the namespace exists, which is the case if it or one of its children contains a
+    /// table. Like [PostgresCatalog::list_namespaces], dropped tables and namespace defaults
+    /// don't count.
+    pub async fn namespace_exists(&self, namespace: &Namespace) -> Result<bool> {
+        let namespace = &self.case_sensitivity.normalize_namespace(namespace)?;
+        self.authorize(Action::Read, namespace, None).await?;
+        let rows = self
+            .query(
+                self.read_connection(),
+                &("SELECT EXISTS (SELECT 1 FROM ".to_string()
+                    + &self.catalog_table.qualified
+                    + " WHERE "
+                    + CATALOG_NAME_COLUMN
+                    + " = "
+                    + &literal(&self.name)
+                    + " AND ("
+                    + &namespace_condition(namespace)
+                    + ")) AS exists;"),
+            )
+            .await?;
+        rows[0].try_get_bool("exists")
+    }
+
+    /// Number of tables and time of the last change in the namespace, computed in one query
+    pub async fn namespace_summary(&self, namespace: &Namespace) -> Result<NamespaceSummary> {
+        let namespace = &self.case_sensitivity.normalize_namespace(namespace)?;
+        self.authorize(Action::Read, namespace, None).await?;
+        let rows = self
+            .query(
+                self.read_connection(),
+                &("SELECT count(*) FILTER (WHERE ".to_string()
+                    + TABLE_NAMESPACE_COLUMN
+                    + " = "
+                    + &literal(&namespace_key(namespace))
+                    + ") AS tables, count(*) AS total_tables, (EXTRACT(EPOCH FROM max("
+                    + UPDATED_AT_COLUMN
+                    + ")) * 1000)::BIGINT AS "
+                    + UPDATED_AT_COLUMN
+                    + " FROM "
+                    + &self.catalog_table.qualified
+                    + " WHERE "
+                    + CATALOG_NAME_COLUMN
+                    + " = "
+                    + &literal(&self.name)
+                    + " AND ("
+                    + &namespace_condition(namespace)
+                    + ");"),
+            )
+            .await?;
+        let row = &rows[0];
+        Ok(NamespaceSummary {
+            tables: row.try_get_i64("tables")? as u64,
+            total_tables: row.try_get_i64("total_tables")? as u64,
+            views: 0,
+            last_modified: row
+                .try_get_opt_i64(UPDATED_AT_COLUMN)?
+                .map(|millis| UNIX_EPOCH + Duration::from_millis(millis.max(0) as u64)),
+        })
+    }
+
+    /// Rename the namespace `from` and all namespaces below it to `to`. The tables, dropped tables
+    /// and namespace defaults are moved in a single transaction. Fails if `to` already contains
+    /// tables or if a table of the namespace is [protected](super::protection). Returns the
+    /// number of renamed tables. Metrics and statistics stay recorded under the old identifiers.
+    ///
+    /// The files of the tables stay where they are. If `relocate` is set, the tables are
+    /// afterwards configured to write new data and metadata files below the location a new table
+    /// with the new identifier would get, unless they already configure `write.data.path` or
+    /// `write.metadata.path`. This writes new metadata for every table and isn't part of the
+    /// transaction.
+    pub async fn rename_namespace(
+        self: Arc<Self>,
+        from: &Namespace,
+        to: &Namespace,
+        relocate: bool,
+    ) -> Result<u64> {
+        self.check_writable("Renaming the namespace")?;
+        let from = &self.case_sensitivity.normalize_namespace(from)?;
+        let to = &self.case_sensitivity.normalize_namespace(to)?;
+        if from.levels().is_empty() || to.levels().is_empty() {
+            return Err(anyhow!(
+                "Renaming the namespace failed. The namespaces must not be empty."
+            ));
+        }
+        let (from_key, to_key) = (namespace_key(from), namespace_key(to));
+        if from_key == to_key {
+            return Err(anyhow!(
+                "Renaming the namespace failed. The namespace {} is renamed to itself.",
+                from
+            ));
+        }
+        self.authorize(Action::Drop, from, None).await?;
+        self.authorize(Action::Create, to, None).await?;
+        let occupied = self
+            .query(
+                &self.primary,
+                &("SELECT 1 FROM ".to_string()
+                    + &self.catalog_table.qualified
+                    + " WHERE "
+                    + CATALOG_NAME_COLUMN
+                    + " = "
+                    + &literal(&self.name)
+                    + " AND ("
+                    + &namespace_condition(to)
+                    + ") AND NOT ("
+                    + &namespace_condition(from)
+                    + ") LIMIT 1;"),
+            )
+            .await?;
+        if !occupied.is_empty() {
+            return Err(anyhow!(
+                "Renaming the namespace failed. The namespace {} already contains tables.",
+                to
+            ));
+        }
+        // Replaces the prefix `from` of the stored namespace, which keeps the levels below it.
+        let renamed = literal(&to_key)
+            + " || substr("
+            + TABLE_NAMESPACE_COLUMN
+            + ", "
+            + &(from_key.chars().count() + 1).to_string()
+            + ")";
+        let condition = CATALOG_NAME_COLUMN.to_string()
+            + " = "
+            + &literal(&self.name)
+            + " AND ("
+            + &namespace_condition(from)
+            + ")";
+        // Nothing is moved if a table of the namespace is protected.
+        let guard = self.unprotected_guard(&condition);
+        let update = |table: &str, returning: &str| {
+            "UPDATE ".to_string()
+                + table
+                + " SET "
+                + TABLE_NAMESPACE_COLUMN
+                + " = "
+                + &renamed
+                + " WHERE "
+                + &condition
+                + " AND "
+                + &guard
+                + " RETURNING "
+                + returning
+        };
+        self.mark_write();
+        // Statements in WITH always run to completion, also if their rows aren't selected.
+        let rows = self
+            .execute_returning(
+                &("WITH dropped AS (".to_string()
+                    + &update(&self.catalog_table.dropped, "1")
+                    + "), defaults AS ("
+                    + &update(&self.catalog_table.namespace_defaults, "1")
+                    + "), tables AS ("
+                    + &update(
+                        &self.catalog_table.qualified,
+                        &(TABLE_NAMESPACE_COLUMN.to_string()
+                            + ", "
+                            + TABLE_NAME_COLUMN
+                            + ", "
+                            + METADATA_LOCATION_COLUMN),
+                    )
+                    + ") SELECT * FROM tables;"),
+            )
+            .await
+            .map_err(|err| {
+                if is_unique_violation(&err) {
+                    anyhow!(
+                        "Renaming the namespace failed. The namespace {} is already in use.",
+                        to
+                    )
+                } else {
+                    err
+                }
+            })?;
+        if rows.is_empty() {
+            self.check_unprotected(&condition).await?;
+        }
+        self.clear_exists_cache();
+        if relocate {
+            for row in &rows {
+                let identifier = table_identifier(
+                    &row.try_get_string(TABLE_NAMESPACE_COLUMN)?,
+                    &row.try_get_string(TABLE_NAME_COLUMN)?,
+                )?;
+                let previous_location = row.try_get_string(METADATA_LOCATION_COLUMN)?;
+                let mut metadata = self
+                    .read_metadata_json(&identifier, &previous_location)
+                    .await?;
+                let location = self
+                    .location_provider
+                    .table_location(&self.warehouse_path(), &identifier);
+                let properties = metadata
+                    .as_object_mut()
+                    .ok_or_else(|| anyhow!("The table metadata is not an object."))?
+                    .entry("properties")
+                    .or_insert_with(|| json!({}))
+                    .as_object_mut()
+                    .ok_or_else(|| anyhow!("The table properties are not an object."))?;
+                let mut changed = false;
+                for (key, directory) in
+                    [(WRITE_DATA_PATH, "data"), (WRITE_METADATA_PATH, "metadata")]
+                {
+                    if !properties.contains_key(key) {
+                        properties
+                            .insert(key.to_string(), json!(location.clone() + "/" + directory));
+                        changed = true;
+                    }
+                }
+                if changed {
+                    Arc::clone(&self)
+                        .commit_metadata(identifier, &previous_location, metadata)
+                        .await?;
+                }
+            }
+        }
+        Ok(rows.len() as u64)
+    }
+
+    /// Drop the namespace and all namespaces below it, including their namespace defaults, and
+    /// return the number of dropped tables. A namespace that still contains tables is only
+    /// dropped with `cascade`, which drops the tables like
+    /// [Catalog::drop_table](iceberg_rs::catalog::Catalog::drop_table), all at once. Nothing is
+    /// dropped if a table of the namespace is [protected](super::protection). With `purge`, the
+    /// dropped tables of the namespace are removed from the trash and the data and metadata files
+    /// of all its tables are deleted. Files are deleted after the catalog entries, so a failed
+    /// purge leaves unreferenced files but never tables with missing files.
+    pub async fn drop_namespace(
+        &self,
+        namespace: &Namespace,
+        cascade: bool,
+        purge: bool,
+    ) -> Result<u64> {
+        self.check_writable("Dropping the namespace")?;
+        let namespace = &self.case_sensitivity.normalize_namespace(namespace)?;
+        if namespace.levels().is_empty() {
+            return Err(anyhow!(
+                "Dropping the namespace failed. The namespace must not be empty."
+            ));
+        }
+        self.authorize(Action::Drop, namespace, None).await?;
+        let condition = CATALOG_NAME_COLUMN.to_string()
+            + " = "
+            + &literal(&self.name)
+            + " AND ("
+            + &namespace_condition(namespace)
+            + ")";
+        let columns = TABLE_NAMESPACE_COLUMN.to_string()
+            + ", "
+            + TABLE_NAME_COLUMN
+            + ", "
+            + METADATA_LOCATION_COLUMN
+            + ", "
+            + TABLE_LOCATION_COLUMN;
+        let tables = self
+            .query(
+                &self.primary,
+                &("SELECT ".to_string()
+                    + &columns
+                    + " FROM "
+                    + &self.catalog_table.qualified
+                    + " WHERE "
+                    + &condition
+                    + ";"),
+            )
+            .await?;
+        if !tables.is_empty() && !cascade {
+            return Err(anyhow!(
+                "Dropping the namespace failed. The namespace {} contains {} tables.",
+                namespace,
+                tables.len()
+            ));
+        }
+        for row in &tables {
+            let identifier = table_identifier(
+                &row.try_get_string(TABLE_NAMESPACE_COLUMN)?,
+                &row.try_get_string(TABLE_NAME_COLUMN)?,
+            )?;
+            self.throttle(Operation::DropTable).await?;
+            self.authorize(
+                Action::Drop,
+                identifier.namespace(),
+                Some(identifier.name()),
+            )
+            .await?;
+        }
+        // The tables are dropped in one statement that checks the protection, so nothing is
+        // dropped if a table of the namespace is protected.
+        let unprotected = condition.clone() + " AND " + &self.unprotected_guard(&condition);
+        self.mark_write();
+        let dropped = if self.soft_delete_retention.is_some() {
+            self.move_to_trash(&unprotected).await?
+        } else {
+            self.execute_returning(
+                &("DELETE FROM ".to_string()
+                    + &self.catalog_table.qualified
+                    + " WHERE "
+                    + &unprotected
+                    + " AND "
+                    + &self.unprotected_condition()
+                    + " RETURNING "
+                    + &columns
+                    + ";"),
+            )
+            .await?
+        };
+        if dropped.len() < tables.len() {
+            self.check_unprotected(&condition).await?;
+        }
+        for row in &dropped {
+            let identifier = table_identifier(
+                &row.try_get_string(TABLE_NAMESPACE_COLUMN)?,
+                &row.try_get_string(TABLE_NAME_COLUMN)?,
+            )?;
+            self.invalidate_exists(&identifier);
+            self.notify(TableEventKind::Dropped, &identifier, None, None)
+                .await;
+        }
+        let defaults = "DELETE FROM ".to_string()
+            + &self.catalog_table.namespace_defaults
+            + " WHERE "
+            + &unprotected;
+        if !purge {
+            self.execute(&self.primary, &(defaults + ";")).await?;
+            return Ok(dropped.len() as u64);
+        }
+        let trash = self
+            .execute_returning(
+                &("WITH defaults AS (".to_string()
+                    + &defaults
+                    + ") DELETE FROM "
+                    + &self.catalog_table.dropped
+                    + " WHERE "
+                    + &condition
+                    + " RETURNING "
+                    + &columns
+                    + ";"),
+            )
+            .await?;
+        // With soft deletes, the tables dropped above are part of the trash.
+        let deleted = dropped
+            .iter()
+            .filter(|_| self.soft_delete_retention.is_none());
+        for row in deleted.chain(trash.iter()) {
+            let identifier = table_identifier(
+                &row.try_get_string(TABLE_NAMESPACE_COLUMN)?,
+                &row.try_get_string(TABLE_NAME_COLUMN)?,
+            )?;
+            let metadata_location = row.try_get_string(METADATA_LOCATION_COLUMN)?;
+            let table_location = match row.try_get_opt_string(TABLE_LOCATION_COLUMN)? {
+                Some(location) => location,
+                None => self
+                    .read_metadata_json(&identifier, &metadata_location)
+                    .await?["location"]
+                    .as_str()
+                    .ok_or_else(|| anyhow!("The table metadata contains no location."))?
+                    .to_string(),
+            };
+            self.delete_files(&identifier, &metadata_location, &table_location)
+                .await?;
+        }
+        Ok(dropped.len() as u64)
+    }
+}
@@ -0,0 +1,60 @@
+/*!
+Publishes table events to a Kafka topic.
+*/
+
+use std::time::Duration;
+
+use anyhow::{anyhow, Result};
+use rdkafka::{
+    producer::{FutureProducer, FutureRecord},
+    ClientConfig,
+};
+
+use super::{NotificationSink, TableEvent};
+
+/// Produces every event as JSON message to a topic. Messages are keyed by the table identifier, so
+/// that the events of a table stay in order.
+pub struct KafkaSink {
+    topic: String,
+    producer: FutureProducer,
+    timeout: Duration,
+}
+
+impl KafkaSink {
+    /// Produce to `topic` on the comma-separated `brokers`.
+    pub fn new(brokers: &str, topic: &str) -> Result<Self> {
+        Self::from_config(ClientConfig::new().set("bootstrap.servers", brokers), topic)
+    }
+
+    /// Produce to `topic` with a producer created from the client configuration, for example to
+    /// configure SASL authentication.
+    pub fn from_config(config: &ClientConfig, topic: &str) -> Result<Self> {
+        Ok(KafkaSink {
+            topic: topic.to_string(),
+            producer: config.create().map_err(|err| anyhow!(err.to_string()))?,
+            timeout: Duration::from_secs(10),
+        })
+    }
+
+    /// Maximum time to wait for a message to be queued, defaults to 10 seconds.
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+}
+
+#[async_trait::async_trait]
+impl NotificationSink for KafkaSink {
+    async fn publish(&self, event: &TableEvent) -> Result<()> {
+        let key = format!("{}.{}", event.catalog, event.identifier);
+        let payload = event.to_json().to_string();
+        self.producer
+            .send(
+                FutureRecord::to(&self.topic).key(&key).payload(&payload),
+                self.timeout,
+            )
+            .await
+            .map_err(|(err, _)| anyhow!(err.to_string()))?;
+        Ok(())
+    }
+}
@@ -0,0 +1,153 @@
+/*!
+Notifications about created, committed and dropped tables.
+
+After a table was created, committed or dropped, the catalog publishes a [TableEvent] to each of
+its [NotificationSink]s. Downstream systems like cache warmers or data quality checks can react to
+the event instead of polling the catalog. Notifications are sent on a best-effort basis, a failing
+sink doesn't fail the operation and events of a failing sink are lost.
+
+Events are published as JSON, see [TableEvent::to_json]. Besides custom sinks, events can be sent
+to an HTTP endpoint with the `webhook` feature and to a Kafka topic with the `kafka` feature.
+*/
+
+use std::{
+    fmt,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use anyhow::Result;
+use iceberg_rs::catalog::table_identifier::TableIdentifier;
+use serde_json::{json, Value};
+
+use super::PostgresCatalog;
+
+#[cfg(feature = "kafka")]
+pub mod kafka;
+#[cfg(feature = "webhook")]
+pub mod webhook;
+
+/// Change of a table
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum TableEventKind {
+    /// The table was created or registered
+    Created,
+    /// New metadata was committed to the table
+    Committed,
+    /// The table was dropped
+    Dropped,
+}
+
+impl fmt::Display for TableEventKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TableEventKind::Created => f.write_str("created"),
+            TableEventKind::Committed => f.write_str("committed"),
+            TableEventKind::Dropped => f.write_str("dropped"),
+        }
+    }
+}
+
+/// Notification about a change of a table
+#[derive(Debug, Clone)]
+pub struct TableEvent {
+    /// Kind of the change
+    pub kind: TableEventKind,
+    /// Name of the catalog
+    pub catalog: String,
+    /// Identifier of the table
+    pub identifier: TableIdentifier,
+    /// Current snapshot after the change, if the table has one
+    pub snapshot_id: Option<i64>,
+    /// Metadata file after the change, `None` for dropped tables
+    pub metadata_location: Option<String>,
+    /// Time of the change
+    pub timestamp: SystemTime,
+}
+
+impl TableEvent {
+    /// Event as JSON object with the fields `event-type`, `catalog`, `table`, `snapshot-id`,
+    /// `metadata-location` and `timestamp-ms`.
+    pub fn to_json(&self) -> Value {
+        json!({
+            "event-type": self.kind.to_string(),
+            "catalog": self.catalog,
+            "table": self.identifier.to_string(),
+            "snapshot-id": self.snapshot_id,
+            "metadata-location": self.metadata_location,
+            "timestamp-ms": self
+                .timestamp
+                .duration_since(UNIX_EPOCH)
+                .unwrap_or_default()
+                .as_millis() as i64,
+        })
+    }
+}
+
+/// Receives the table events of the catalog
+#[async_trait::async_trait]
+pub trait NotificationSink: Send + Sync {
+    /// Publish the event
+    async fn publish(&self, event: &TableEvent) -> Result<()>;
+}
+
+impl PostgresCatalog {
+    /// Publish the event for the table to all notification sinks. Failing sinks are ignored.
+    pub(crate) async fn notify(
+        &self,
+        kind: TableEventKind,
+        identifier: &TableIdentifier,
+        metadata_location: Option<&str>,
+        metadata: Option<&Value>,
+    ) {
+        if self.notification_sinks.is_empty() {
+            return;
+        }
+        let event = TableEvent {
+            kind,
+            catalog: self.name.clone(),
+            identifier: identifier.clone(),
+            snapshot_id: metadata.and_then(|metadata| metadata["current-snapshot-id"].as_i64()),
+            metadata_location: metadata_location.map(ToString::to_string),
+            timestamp: SystemTime::now(),
+        };
+        for sink in &self.notification_sinks {
+            let _ = sink.publish(&event).await;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, UNIX_EPOCH};
+
+    use iceberg_rs::catalog::table_identifier::TableIdentifier;
+
+    use super::{TableEvent, TableEventKind};
+
+    #[test]
+    fn test_event_json() {
+        let event = TableEvent {
+            kind: TableEventKind::Committed,
+            catalog: "test_catalog".to_string(),
+            identifier: TableIdentifier::parse("test.events").unwrap(),
+            snapshot_id: Some(3),
+            metadata_location: Some(
+                "s3://bucket/test/events/metadata/v2.metadata.json".to_string(),
+            ),
+            timestamp: UNIX_EPOCH + Duration::from_secs(1),
+        };
+        let json = event.to_json();
+        assert_eq!(json["event-type"], "committed");
+        assert_eq!(json["table"], "test.events");
+        assert_eq!(json["snapshot-id"], 3);
+        assert_eq!(json["timestamp-ms"], 1000);
+
+        let dropped = TableEvent {
+            kind: TableEventKind::Dropped,
+            snapshot_id: None,
+            metadata_location: None,
+            ..event
+        };
+        assert!(dropped.to_json()["metadata-location"].is_null());
+    }
+}
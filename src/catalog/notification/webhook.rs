@@ -0,0 +1,53 @@
+/*!
+Publishes table events to an HTTP endpoint.
+*/
+
+use std::time::Duration;
+
+use anyhow::{anyhow, Result};
+
+use super::{NotificationSink, TableEvent};
+
+/// Sends every event as JSON in the body of a `POST` request
+pub struct WebhookSink {
+    url: String,
+    headers: Vec<(String, String)>,
+    http: reqwest::Client,
+}
+
+impl WebhookSink {
+    /// Send the events to `url`. Requests time out after 10 seconds.
+    pub fn new(url: &str) -> Result<Self> {
+        Ok(WebhookSink {
+            url: url.to_string(),
+            headers: Vec::new(),
+            http: reqwest::Client::builder()
+                .timeout(Duration::from_secs(10))
+                .build()
+                .map_err(|err| anyhow!(err.to_string()))?,
+        })
+    }
+
+    /// Add a header to every request, like an `Authorization` header.
+    pub fn with_header(mut self, name: &str, value: &str) -> Self {
+        self.headers.push((name.to_string(), value.to_string()));
+        self
+    }
+}
+
+#[async_trait::async_trait]
+impl NotificationSink for WebhookSink {
+    async fn publish(&self, event: &TableEvent) -> Result<()> {
+        let mut request = self.http.post(&self.url).json(&event.to_json());
+        for (name, value) in &self.headers {
+            request = request.header(name, value);
+        }
+        request
+            .send()
+            .await
+            .map_err(|err| anyhow!(err.to_string()))?
+            .error_for_status()
+            .map_err(|err| anyhow!(err.to_string()))?;
+        Ok(())
+    }
+}
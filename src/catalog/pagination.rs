@@ -0,0 +1,230 @@
+/*!
+Listing of tables and namespaces in pages.
+
+Catalogs with many tables shouldn't return all of them at once. The page listings return at most
+`page_size` items, and never more than [MAX_PAGE_SIZE], ordered by name, and a token for the next
+page if there are more. The token is the last name of the page, the next page continues after it
+with keyset pagination, so pages stay consistent when tables are created or dropped in between and
+late pages of tables are as fast as early ones. This maps onto `pageToken` and `pageSize` of the
+listing endpoints of the REST spec.
+
+Namespaces have no rows of their own, the child namespaces of a page are derived from the
+namespaces of all tables below the parent. The namespace index narrows the scan to those tables,
+but every page of namespaces reads all of them before skipping to the token, so its cost grows with
+the number of tables below the parent rather than with the page size.
+*/
+
+use anyhow::{anyhow, Result};
+use iceberg_rs::catalog::{namespace::Namespace, table_identifier::TableIdentifier};
+
+use super::{
+    access::Action,
+    namespace::{child_prefix, namespace_key, namespace_levels, table_identifier},
+    query::{like_pattern, literal},
+    rate_limit::Operation,
+    PostgresCatalog, ARCHIVED_AT_COLUMN, CATALOG_NAME_COLUMN, TABLE_NAMESPACE_COLUMN,
+    TABLE_NAME_COLUMN,
+};
+
+/// Largest number of items in a page, larger page sizes are reduced to it
+pub static MAX_PAGE_SIZE: usize = 1000;
+
+/// Column of the direct child namespaces
+static CHILD_COLUMN: &str = "child";
+/// Leading level of a namespace key, up to the first unescaped dot
+static FIRST_LEVEL_PATTERN: &str = "^(?:[^.\\\\]|\\\\.)*";
+
+/// A page of a listing
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Page<T> {
+    /// Items of the page
+    pub items: Vec<T>,
+    /// Token of the next page, `None` if this is the last page
+    pub next_page_token: Option<String>,
+}
+
+impl PostgresCatalog {
+    /// List the tables in the namespace, the page of at most `page_size` tables after
+    /// `page_token`, or the first page without token. The page size is limited to
+    /// [MAX_PAGE_SIZE].
+    pub async fn list_tables_page(
+        &self,
+        namespace: &Namespace,
+        page_token: Option<&str>,
+        page_size: usize,
+    ) -> Result<Page<TableIdentifier>> {
+        let page_size = check_page_size(page_size)?;
+        self.throttle(Operation::ListTables).await?;
+        let namespace = &self.case_sensitivity.normalize_namespace(namespace)?;
+        self.authorize(Action::Read, namespace, None).await?;
+        let rows = self
+            .query(
+                self.read_connection(),
+                &("SELECT ".to_string()
+                    + TABLE_NAMESPACE_COLUMN
+                    + ", "
+                    + TABLE_NAME_COLUMN
+                    + " FROM "
+                    + &self.catalog_table.qualified
+                    + " WHERE "
+                    + CATALOG_NAME_COLUMN
+                    + " = "
+                    + &literal(&self.name)
+                    + " AND "
+                    + TABLE_NAMESPACE_COLUMN
+                    + " = "
+                    + &literal(&namespace_key(namespace))
+                    + " AND "
+                    + ARCHIVED_AT_COLUMN
+                    + " IS NULL"
+                    + &after(TABLE_NAME_COLUMN, page_token)
+                    + " ORDER BY "
+                    + TABLE_NAME_COLUMN
+                    + " LIMIT "
+                    + &page_size.saturating_add(1).to_string()
+                    + ";"),
+            )
+            .await?;
+        let names = rows
+            .iter()
+            .map(|row| row.try_get_string(TABLE_NAME_COLUMN))
+            .collect::<Result<Vec<_>>>()?;
+        let (names, next_page_token) = paginate(names, page_size);
+        Ok(Page {
+            items: names
+                .iter()
+                .map(|name| table_identifier(&namespace_key(namespace), name))
+                .collect::<Result<_>>()?,
+            next_page_token,
+        })
+    }
+
+    /// List the namespaces directly below `parent`, or the top-level namespaces without parent,
+    /// the page of at most `page_size` namespaces after `page_token`, or the first page without
+    /// token. The page size is limited to [MAX_PAGE_SIZE].
+    pub async fn list_namespaces_page(
+        &self,
+        parent: Option<&Namespace>,
+        page_token: Option<&str>,
+        page_size: usize,
+    ) -> Result<Page<Namespace>> {
+        let page_size = check_page_size(page_size)?;
+        self.throttle(Operation::ListNamespaces).await?;
+        let parent = parent
+            .map(|parent| self.case_sensitivity.normalize_namespace(parent))
+            .transpose()?;
+        if let Some(parent) = &parent {
+            self.authorize(Action::Read, parent, None).await?;
+        }
+        let prefix = parent.as_ref().map(child_prefix).unwrap_or_default();
+        // The direct children are the first levels of the namespaces below the parent.
+        let rows = self
+            .query(
+                self.read_connection(),
+                &("SELECT DISTINCT ".to_string()
+                    + CHILD_COLUMN
+                    + " FROM (SELECT substring(substr("
+                    + TABLE_NAMESPACE_COLUMN
+                    + ", "
+                    + &(prefix.chars().count() + 1).to_string()
+                    + ") FROM "
+                    + &literal(FIRST_LEVEL_PATTERN)
+                    + ") AS "
+                    + CHILD_COLUMN
+                    + " FROM "
+                    + &self.catalog_table.qualified
+                    + " WHERE "
+                    + CATALOG_NAME_COLUMN
+                    + " = "
+                    + &literal(&self.name)
+                    + " AND "
+                    + TABLE_NAMESPACE_COLUMN
+                    + " LIKE "
+                    + &literal(&(like_pattern(&prefix) + "%"))
+                    + ") AS children WHERE TRUE"
+                    + &after(CHILD_COLUMN, page_token)
+                    + " ORDER BY "
+                    + CHILD_COLUMN
+                    + " LIMIT "
+                    + &page_size.saturating_add(1).to_string()
+                    + ";"),
+            )
+            .await?;
+        let children = rows
+            .iter()
+            .map(|row| row.try_get_string(CHILD_COLUMN))
+            .collect::<Result<Vec<_>>>()?;
+        let (children, next_page_token) = paginate(children, page_size);
+        let levels = parent
+            .as_ref()
+            .map(|parent| parent.levels().to_vec())
+            .unwrap_or_default();
+        Ok(Page {
+            items: children
+                .iter()
+                .map(|child| {
+                    let mut levels = levels.clone();
+                    levels.extend(namespace_levels(child)?);
+                    Namespace::try_new(&levels)
+                })
+                .collect::<Result<_>>()?,
+            next_page_token,
+        })
+    }
+}
+
+/// The page size limited to [MAX_PAGE_SIZE]. Fails if the page size is 0.
+fn check_page_size(page_size: usize) -> Result<usize> {
+    if page_size == 0 {
+        Err(anyhow!("The page size has to be positive."))
+    } else {
+        Ok(page_size.min(MAX_PAGE_SIZE))
+    }
+}
+
+/// Condition for the keys after the page token, empty for the first page
+fn after(column: &str, page_token: Option<&str>) -> String {
+    match page_token {
+        Some(token) => " AND ".to_string() + column + " > " + &literal(token),
+        None => String::new(),
+    }
+}
+
+/// Keep the first `page_size` of the keys, which were queried with one extra key to tell if
+/// there is a next page.
+fn paginate(mut keys: Vec<String>, page_size: usize) -> (Vec<String>, Option<String>) {
+    if keys.len() > page_size {
+        keys.truncate(page_size);
+        let token = keys.last().cloned();
+        (keys, token)
+    } else {
+        (keys, None)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{after, check_page_size, paginate, MAX_PAGE_SIZE};
+
+    #[test]
+    fn test_paginate() {
+        let keys = vec!["a".to_string(), "b".to_string(), "c".to_string()];
+        assert_eq!(
+            paginate(keys.clone(), 2),
+            (
+                vec!["a".to_string(), "b".to_string()],
+                Some("b".to_string())
+            )
+        );
+        assert_eq!(paginate(keys.clone(), 3), (keys, None));
+        assert_eq!(after("name", None), "");
+        assert_eq!(after("name", Some("it's")), " AND name > 'it''s'");
+    }
+
+    #[test]
+    fn test_check_page_size() {
+        assert!(check_page_size(0).is_err());
+        assert_eq!(check_page_size(10).unwrap(), 10);
+        assert_eq!(check_page_size(usize::MAX).unwrap(), MAX_PAGE_SIZE);
+    }
+}
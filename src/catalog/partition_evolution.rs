@@ -0,0 +1,278 @@
+/*!
+Partition spec evolution through the catalog.
+
+[PostgresCatalog::update_partition_spec] makes a new partition spec the default spec of a table.
+Existing data files keep the spec they were written with, only new data is partitioned with the new
+spec. As required by format version 2, partition fields that existed in an earlier spec keep their
+field ids and a spec that matches an earlier spec reuses it instead of adding a copy.
+*/
+
+use std::{collections::HashSet, fmt, sync::Arc};
+
+use anyhow::{anyhow, Result};
+use iceberg_rs::{catalog::table_identifier::TableIdentifier, table::Table};
+use serde_json::{json, Value};
+
+use super::PostgresCatalog;
+
+/// Transform that derives a partition value from a column
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Transform {
+    /// The value itself
+    Identity,
+    /// Hash of the value modulo the number of buckets
+    Bucket(u32),
+    /// The value truncated to the width
+    Truncate(u32),
+    /// Year of a date or timestamp
+    Year,
+    /// Month of a date or timestamp
+    Month,
+    /// Day of a date or timestamp
+    Day,
+    /// Hour of a timestamp
+    Hour,
+}
+
+impl fmt::Display for Transform {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Transform::Identity => write!(f, "identity"),
+            Transform::Bucket(n) => write!(f, "bucket[{}]", n),
+            Transform::Truncate(width) => write!(f, "truncate[{}]", width),
+            Transform::Year => write!(f, "year"),
+            Transform::Month => write!(f, "month"),
+            Transform::Day => write!(f, "day"),
+            Transform::Hour => write!(f, "hour"),
+        }
+    }
+}
+
+impl Transform {
+    /// Default name of the partition field for the column
+    fn field_name(&self, column: &str) -> String {
+        match self {
+            Transform::Identity => column.to_string(),
+            Transform::Bucket(_) => column.to_string() + "_bucket",
+            Transform::Truncate(_) => column.to_string() + "_trunc",
+            Transform::Year => column.to_string() + "_year",
+            Transform::Month => column.to_string() + "_month",
+            Transform::Day => column.to_string() + "_day",
+            Transform::Hour => column.to_string() + "_hour",
+        }
+    }
+
+    /// Whether the transform applies to columns of the type
+    fn applies_to(&self, field_type: &Value) -> bool {
+        let field_type = match field_type.as_str() {
+            Some(field_type) => field_type,
+            // Nested types can't be partitioned
+            None => return false,
+        };
+        let is_timestamp = field_type.starts_with("timestamp");
+        match self {
+            Transform::Identity => true,
+            Transform::Bucket(n) => *n > 0 && !matches!(field_type, "boolean" | "float" | "double"),
+            Transform::Truncate(width) => {
+                *width > 0
+                    && (matches!(field_type, "int" | "long" | "string" | "binary")
+                        || field_type.starts_with("decimal"))
+            }
+            Transform::Year | Transform::Month | Transform::Day => {
+                field_type == "date" || is_timestamp
+            }
+            Transform::Hour => is_timestamp,
+        }
+    }
+}
+
+/// Field of a partition spec
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PartitionField {
+    /// Top-level column the partition value is derived from
+    pub source_column: String,
+    /// Transform applied to the column
+    pub transform: Transform,
+    /// Name of the partition field, derived from the column and transform if `None`
+    pub name: Option<String>,
+}
+
+impl PartitionField {
+    /// Partition field with the default name
+    pub fn new(source_column: &str, transform: Transform) -> Self {
+        PartitionField {
+            source_column: source_column.to_string(),
+            transform,
+            name: None,
+        }
+    }
+
+    /// Name of the partition field
+    fn name(&self) -> String {
+        self.name
+            .clone()
+            .unwrap_or_else(|| self.transform.field_name(&self.source_column))
+    }
+}
+
+impl PostgresCatalog {
+    /// Make the partition spec with the fields the default spec of the table and commit it. An
+    /// empty list of fields makes the table unpartitioned.
+    pub async fn update_partition_spec(
+        self: Arc<Self>,
+        identifier: TableIdentifier,
+        fields: &[PartitionField],
+    ) -> Result<Table> {
+        self.check_writable("Changing the partition spec")?;
+        self.change_metadata(identifier, |metadata| {
+            evolve_partition_spec(metadata, fields)?;
+            Ok(true)
+        })
+        .await
+    }
+}
+
+/// Set the default spec of the metadata to a spec with the fields.
+fn evolve_partition_spec(metadata: &mut Value, fields: &[PartitionField]) -> Result<()> {
+    let schema = metadata["schemas"]
+        .as_array()
+        .and_then(|schemas| {
+            schemas
+                .iter()
+                .find(|schema| schema["schema-id"] == metadata["current-schema-id"])
+        })
+        .ok_or_else(|| anyhow!("The current schema of the table doesn't exist."))?;
+    let specs = metadata["partition-specs"]
+        .as_array()
+        .ok_or_else(|| anyhow!("The partition specs of the table are not an array."))?;
+    let mut last_partition_id = metadata["last-partition-id"].as_i64().unwrap_or(999);
+    let mut names = HashSet::new();
+    let mut spec_fields = Vec::with_capacity(fields.len());
+    for field in fields {
+        let column = schema["fields"]
+            .as_array()
+            .and_then(|columns| {
+                columns
+                    .iter()
+                    .find(|column| column["name"] == field.source_column.as_str())
+            })
+            .ok_or_else(|| anyhow!("The column {} doesn't exist.", field.source_column))?;
+        if !field.transform.applies_to(&column["type"]) {
+            return Err(anyhow!(
+                "The transform {} doesn't apply to the column {} of type {}.",
+                field.transform,
+                field.source_column,
+                column["type"]
+            ));
+        }
+        let name = field.name();
+        if !names.insert(name.clone()) {
+            return Err(anyhow!("The partition field {} is defined twice.", name));
+        }
+        let transform = field.transform.to_string();
+        // Fields that existed before keep their id, so that partition values stay comparable.
+        let previous_id = specs
+            .iter()
+            .filter_map(|spec| spec["fields"].as_array())
+            .flatten()
+            .find(|previous| {
+                previous["source-id"] == column["id"] && previous["transform"] == transform.as_str()
+            })
+            .and_then(|previous| previous["field-id"].as_i64());
+        let field_id = match previous_id {
+            Some(id) => id,
+            None => {
+                last_partition_id += 1;
+                last_partition_id
+            }
+        };
+        spec_fields.push(json!({
+            "source-id": column["id"],
+            "field-id": field_id,
+            "name": name,
+            "transform": transform,
+        }));
+    }
+    let spec_fields = Value::Array(spec_fields);
+    let existing = specs
+        .iter()
+        .find(|spec| spec["fields"] == spec_fields)
+        .map(|spec| spec["spec-id"].clone());
+    let next_spec_id = specs
+        .iter()
+        .filter_map(|spec| spec["spec-id"].as_i64())
+        .max()
+        .map_or(0, |id| id + 1);
+    let spec_id = match existing {
+        Some(spec_id) => spec_id,
+        None => {
+            let spec_id = next_spec_id;
+            metadata["partition-specs"]
+                .as_array_mut()
+                .ok_or_else(|| anyhow!("The partition specs of the table are not an array."))?
+                .push(json!({"spec-id": spec_id, "fields": spec_fields}));
+            json!(spec_id)
+        }
+    };
+    metadata["default-spec-id"] = spec_id;
+    metadata["last-partition-id"] = json!(last_partition_id);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::{evolve_partition_spec, PartitionField, Transform};
+
+    #[test]
+    fn test_evolve_partition_spec() {
+        let mut metadata = json!({
+            "current-schema-id": 0,
+            "schemas": [{"schema-id": 0, "type": "struct", "fields": [
+                {"id": 1, "name": "id", "required": true, "type": "long"},
+                {"id": 2, "name": "created", "required": false, "type": "timestamptz"},
+                {"id": 3, "name": "price", "required": false, "type": "double"}
+            ]}],
+            "default-spec-id": 0,
+            "partition-specs": [{"spec-id": 0, "fields": [
+                {"source-id": 2, "field-id": 1000, "name": "created_day", "transform": "day"}
+            ]}],
+            "last-partition-id": 1000
+        });
+        evolve_partition_spec(
+            &mut metadata,
+            &[
+                PartitionField::new("created", Transform::Day),
+                PartitionField::new("id", Transform::Bucket(16)),
+            ],
+        )
+        .unwrap();
+        assert_eq!(metadata["default-spec-id"], 1);
+        assert_eq!(metadata["last-partition-id"], 1001);
+        let spec = &metadata["partition-specs"][1];
+        assert_eq!(spec["fields"][0]["field-id"], 1000);
+        assert_eq!(spec["fields"][1]["field-id"], 1001);
+        assert_eq!(spec["fields"][1]["name"], "id_bucket");
+        assert_eq!(spec["fields"][1]["transform"], "bucket[16]");
+
+        evolve_partition_spec(
+            &mut metadata,
+            &[PartitionField::new("created", Transform::Day)],
+        )
+        .unwrap();
+        assert_eq!(metadata["default-spec-id"], 0);
+        assert_eq!(metadata["partition-specs"].as_array().unwrap().len(), 2);
+
+        assert!(evolve_partition_spec(
+            &mut metadata,
+            &[PartitionField::new("price", Transform::Day)]
+        )
+        .is_err());
+        assert!(evolve_partition_spec(
+            &mut metadata,
+            &[PartitionField::new("missing", Transform::Identity)]
+        )
+        .is_err());
+    }
+}
@@ -0,0 +1,186 @@
+/*!
+Protection of business-critical tables against accidental drops and renames.
+
+A protected table can't be dropped or renamed, neither on its own nor with its namespace or
+catalog, so that automation that cleans up or reorganizes tables can't remove or move it by
+accident. Such operations fail with [CatalogError::Protected] before they change anything. The flag
+is stored with the catalog entry and set with
+[set_table_protected](PostgresCatalog::set_table_protected).
+
+A deliberate drop or rename either removes the flag first or runs within [override_protection],
+which lifts the protection for the operations of its future only. Renaming a single table means
+registering it under the new identifier and dropping the old entry, which the protection covers as
+well.
+*/
+
+use std::future::Future;
+
+use anyhow::Result;
+use iceberg_rs::catalog::table_identifier::TableIdentifier;
+
+use super::{
+    access::Action,
+    error::CatalogError,
+    namespace::{table_condition, table_identifier},
+    PostgresCatalog, PROTECTED_COLUMN, TABLE_NAMESPACE_COLUMN, TABLE_NAME_COLUMN,
+};
+
+tokio::task_local! {
+    static OVERRIDE: ();
+}
+
+/// Run the future with the protection of tables lifted, so that it can drop or rename protected
+/// tables.
+pub async fn override_protection<F: Future>(future: F) -> F::Output {
+    OVERRIDE.scope((), future).await
+}
+
+/// Whether the current operation runs within [override_protection]
+fn protection_overridden() -> bool {
+    OVERRIDE.try_with(|_| ()).is_ok()
+}
+
+impl PostgresCatalog {
+    /// Protect the table against drops and renames, or remove the protection. Requires the permission to
+    /// drop the table.
+    pub async fn set_table_protected(
+        &self,
+        identifier: &TableIdentifier,
+        protected: bool,
+    ) -> Result<()> {
+        self.check_writable("Protecting the table")?;
+        let identifier = &self.case_sensitivity.normalize(identifier)?;
+        self.authorize(
+            Action::Drop,
+            identifier.namespace(),
+            Some(identifier.name()),
+        )
+        .await?;
+        self.mark_write();
+        let n_rows = self
+            .execute(
+                &self.primary,
+                &("UPDATE ".to_string()
+                    + &self.catalog_table.qualified
+                    + " SET "
+                    + PROTECTED_COLUMN
+                    + " = "
+                    + if protected { "TRUE" } else { "FALSE" }
+                    + " WHERE "
+                    + &table_condition(&self.name, identifier)
+                    + ";"),
+            )
+            .await?;
+        if n_rows == 0 {
+            return Err(CatalogError::NotFound {
+                table: identifier.to_string(),
+            }
+            .into());
+        }
+        Ok(())
+    }
+
+    /// Whether the table is protected against drops and renames
+    pub async fn is_table_protected(&self, identifier: &TableIdentifier) -> Result<bool> {
+        let identifier = &self.case_sensitivity.normalize(identifier)?;
+        self.authorize(
+            Action::Read,
+            identifier.namespace(),
+            Some(identifier.name()),
+        )
+        .await?;
+        let rows = self
+            .query(
+                self.read_connection(),
+                &("SELECT ".to_string()
+                    + PROTECTED_COLUMN
+                    + " FROM "
+                    + &self.catalog_table.qualified
+                    + " WHERE "
+                    + &table_condition(&self.name, identifier)
+                    + ";"),
+            )
+            .await?;
+        match rows.first() {
+            Some(row) => row.try_get_bool(PROTECTED_COLUMN),
+            None => Err(CatalogError::NotFound {
+                table: identifier.to_string(),
+            }
+            .into()),
+        }
+    }
+
+    /// Condition on the catalog table that excludes protected tables, unless the protection is
+    /// overridden
+    pub(crate) fn unprotected_condition(&self) -> String {
+        if protection_overridden() {
+            "TRUE".to_string()
+        } else {
+            "NOT ".to_string() + PROTECTED_COLUMN
+        }
+    }
+
+    /// Condition that none of the tables matching the condition is protected, unless the
+    /// protection is overridden. Part of the statements that change several tables at once, so
+    /// that a table can't be protected between the check and the change.
+    pub(crate) fn unprotected_guard(&self, condition: &str) -> String {
+        if protection_overridden() {
+            "TRUE".to_string()
+        } else {
+            "NOT EXISTS (SELECT 1 FROM ".to_string()
+                + &self.catalog_table.qualified
+                + " WHERE ("
+                + condition
+                + ") AND "
+                + PROTECTED_COLUMN
+                + ")"
+        }
+    }
+
+    /// Fail with [CatalogError::Protected] if one of the tables matching the condition is
+    /// protected, unless the protection is overridden.
+    pub(crate) async fn check_unprotected(&self, condition: &str) -> Result<()> {
+        if protection_overridden() {
+            return Ok(());
+        }
+        let rows = self
+            .query(
+                &self.primary,
+                &("SELECT ".to_string()
+                    + TABLE_NAMESPACE_COLUMN
+                    + ", "
+                    + TABLE_NAME_COLUMN
+                    + " FROM "
+                    + &self.catalog_table.qualified
+                    + " WHERE ("
+                    + condition
+                    + ") AND "
+                    + PROTECTED_COLUMN
+                    + " LIMIT 1;"),
+            )
+            .await?;
+        match rows.first() {
+            Some(row) => Err(CatalogError::Protected {
+                table: table_identifier(
+                    &row.try_get_string(TABLE_NAMESPACE_COLUMN)?,
+                    &row.try_get_string(TABLE_NAME_COLUMN)?,
+                )?
+                .to_string(),
+            }
+            .into()),
+            None => Ok(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{override_protection, protection_overridden};
+
+    #[tokio::test]
+    async fn test_override_protection() {
+        assert!(!protection_overridden());
+        assert!(override_protection(async { protection_overridden() }).await);
+        assert!(!protection_overridden());
+    }
+}
@@ -0,0 +1,333 @@
+/*!
+Executes catalog statements over the extended or the simple query protocol.
+
+The simple query protocol doesn't create prepared statements on the server, which makes it safe to
+use behind connection poolers in transaction mode, like pgbouncer.
+
+With the `sqlx` feature, statements can also run on a [sqlx::PgPool]. Statements that are built at
+runtime are sent without arguments, which makes sqlx use the simple query protocol as well. The
+[prepared statements](super::statements) are sent with their parameters.
+
+Errors of the client are kept inside the returned [anyhow::Error], so that they can be classified
+for retries.
+*/
+
+use anyhow::{anyhow, Result};
+use tokio_postgres::{Client, Row, SimpleQueryMessage, SimpleQueryRow};
+
+#[cfg(feature = "sqlx")]
+use super::statements::Param;
+
+/// Row returned by a catalog query
+pub(crate) enum CatalogRow {
+    /// Row returned by the extended query protocol
+    Extended(Row),
+    /// Row returned by the simple query protocol, all values are text
+    Simple(SimpleQueryRow),
+    /// Row returned by a sqlx pool
+    #[cfg(feature = "sqlx")]
+    Sqlx(sqlx::postgres::PgRow),
+}
+
+impl CatalogRow {
+    /// Get the value of a nullable text column
+    pub(crate) fn try_get_opt_string(&self, column: &str) -> Result<Option<String>> {
+        match self {
+            CatalogRow::Extended(row) => row
+                .try_get::<_, Option<String>>(column)
+                .map_err(|err| anyhow!(err.to_string())),
+            CatalogRow::Simple(row) => row
+                .try_get(column)
+                .map(|value| value.map(ToString::to_string))
+                .map_err(|err| anyhow!(err.to_string())),
+            #[cfg(feature = "sqlx")]
+            CatalogRow::Sqlx(row) => sqlx::Row::try_get::<Option<String>, _>(row, column)
+                .map_err(|err| anyhow!(err.to_string())),
+        }
+    }
+
+    /// Get the value of a non-nullable text column
+    pub(crate) fn try_get_string(&self, column: &str) -> Result<String> {
+        self.try_get_opt_string(column)?
+            .ok_or_else(|| anyhow!("Column {} is null.", column))
+    }
+
+    /// Get the value of a bigint column
+    pub(crate) fn try_get_i64(&self, column: &str) -> Result<i64> {
+        match self {
+            CatalogRow::Extended(row) => row
+                .try_get::<_, i64>(column)
+                .map_err(|err| anyhow!(err.to_string())),
+            #[cfg(feature = "sqlx")]
+            CatalogRow::Sqlx(row) => {
+                sqlx::Row::try_get::<i64, _>(row, column).map_err(|err| anyhow!(err.to_string()))
+            }
+            CatalogRow::Simple(_) => self
+                .try_get_string(column)?
+                .parse()
+                .map_err(|err: std::num::ParseIntError| anyhow!(err.to_string())),
+        }
+    }
+
+    /// Get the value of a nullable bigint column
+    pub(crate) fn try_get_opt_i64(&self, column: &str) -> Result<Option<i64>> {
+        match self {
+            CatalogRow::Extended(row) => row
+                .try_get::<_, Option<i64>>(column)
+                .map_err(|err| anyhow!(err.to_string())),
+            #[cfg(feature = "sqlx")]
+            CatalogRow::Sqlx(row) => sqlx::Row::try_get::<Option<i64>, _>(row, column)
+                .map_err(|err| anyhow!(err.to_string())),
+            CatalogRow::Simple(_) => self
+                .try_get_opt_string(column)?
+                .map(|value| value.parse())
+                .transpose()
+                .map_err(|err: std::num::ParseIntError| anyhow!(err.to_string())),
+        }
+    }
+
+    /// Get the value of a boolean column
+    pub(crate) fn try_get_bool(&self, column: &str) -> Result<bool> {
+        match self {
+            CatalogRow::Extended(row) => row
+                .try_get::<_, bool>(column)
+                .map_err(|err| anyhow!(err.to_string())),
+            #[cfg(feature = "sqlx")]
+            CatalogRow::Sqlx(row) => {
+                sqlx::Row::try_get::<bool, _>(row, column).map_err(|err| anyhow!(err.to_string()))
+            }
+            CatalogRow::Simple(row) => {
+                match row
+                    .try_get(column)
+                    .map_err(|err| anyhow!(err.to_string()))?
+                {
+                    Some("t") => Ok(true),
+                    Some("f") => Ok(false),
+                    value => Err(anyhow!(
+                        "Column {} doesn't contain a boolean: {:?}",
+                        column,
+                        value
+                    )),
+                }
+            }
+        }
+    }
+}
+
+/// Result of one statement of a transaction
+pub(crate) struct StatementResult {
+    /// Number of rows the statement affected or returned
+    pub(crate) n_rows: u64,
+    /// Rows returned by the statement
+    pub(crate) rows: Vec<CatalogRow>,
+}
+
+/// Quote a value as a string literal. Single quotes are doubled, backslashes are kept as they are
+/// because the catalog relies on `standard_conforming_strings`, the default since postgres 9.1.
+pub(crate) fn literal(value: &str) -> String {
+    "'".to_string() + &value.replace('\'', "''") + "'"
+}
+
+/// Quote a name as an identifier, so that it can contain any character and keeps its case.
+pub(crate) fn identifier(name: &str) -> String {
+    "\"".to_string() + &name.replace('"', "\"\"") + "\""
+}
+
+/// Select a timestamp column as milliseconds since the epoch, under the name of the column.
+pub(crate) fn epoch_millis(column: &str) -> String {
+    "(EXTRACT(EPOCH FROM ".to_string() + column + ") * 1000)::BIGINT AS " + column
+}
+
+/// Escape the wildcards of a `LIKE` pattern, so that the value is matched literally.
+pub(crate) fn like_pattern(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('%', "\\%")
+        .replace('_', "\\_")
+}
+
+/// Run a statement that returns rows.
+pub(crate) async fn query(client: &Client, sql: &str, simple: bool) -> Result<Vec<CatalogRow>> {
+    if simple {
+        Ok(client
+            .simple_query(sql)
+            .await
+            .map_err(|err| anyhow!(err))?
+            .into_iter()
+            .filter_map(|message| match message {
+                SimpleQueryMessage::Row(row) => Some(CatalogRow::Simple(row)),
+                _ => None,
+            })
+            .collect())
+    } else {
+        Ok(client
+            .query(sql, &[])
+            .await
+            .map_err(|err| anyhow!(err))?
+            .into_iter()
+            .map(CatalogRow::Extended)
+            .collect())
+    }
+}
+
+/// Run a statement and return the number of affected rows.
+pub(crate) async fn execute(client: &Client, sql: &str, simple: bool) -> Result<u64> {
+    if simple {
+        client
+            .simple_query(sql)
+            .await
+            .map_err(|err| anyhow!(err))?
+            .into_iter()
+            .find_map(|message| match message {
+                SimpleQueryMessage::CommandComplete(n_rows) => Some(n_rows),
+                _ => None,
+            })
+            .ok_or_else(|| anyhow!("The statement didn't complete.".to_string()))
+    } else {
+        client.execute(sql, &[]).await.map_err(|err| anyhow!(err))
+    }
+}
+
+/// Run the statements in one transaction and return their results in order. The statements are
+/// sent in a single message over the simple query protocol, which postgres runs as one implicit
+/// transaction: the statements see the changes of the statements before them, and an error rolls
+/// back all of them. No statement of another task on the same client can run in between.
+pub(crate) async fn transaction(
+    client: &Client,
+    statements: &[String],
+) -> Result<Vec<StatementResult>> {
+    let mut results = Vec::with_capacity(statements.len());
+    let mut rows = Vec::new();
+    for message in client
+        .simple_query(&statements.join(" "))
+        .await
+        .map_err(|err| anyhow!(err))?
+    {
+        match message {
+            SimpleQueryMessage::Row(row) => rows.push(CatalogRow::Simple(row)),
+            SimpleQueryMessage::CommandComplete(n_rows) => results.push(StatementResult {
+                n_rows,
+                rows: std::mem::take(&mut rows),
+            }),
+            _ => (),
+        }
+    }
+    Ok(results)
+}
+
+/// Run the statements in one transaction on a connection of the sqlx pool.
+#[cfg(feature = "sqlx")]
+pub(crate) async fn transaction_pool(
+    pool: &sqlx::PgPool,
+    statements: &[String],
+) -> Result<Vec<StatementResult>> {
+    use futures::TryStreamExt;
+
+    let mut transaction = pool.begin().await.map_err(|err| anyhow!(err))?;
+    let mut results = Vec::with_capacity(statements.len());
+    for statement in statements {
+        let mut result = StatementResult {
+            n_rows: 0,
+            rows: Vec::new(),
+        };
+        let mut stream = sqlx::Executor::fetch_many(&mut *transaction, statement.as_str());
+        while let Some(item) = stream.try_next().await.map_err(|err| anyhow!(err))? {
+            match item {
+                sqlx::Either::Left(done) => result.n_rows += done.rows_affected(),
+                sqlx::Either::Right(row) => result.rows.push(CatalogRow::Sqlx(row)),
+            }
+        }
+        drop(stream);
+        results.push(result);
+    }
+    transaction.commit().await.map_err(|err| anyhow!(err))?;
+    Ok(results)
+}
+
+/// Run a statement that returns rows on a sqlx pool.
+#[cfg(feature = "sqlx")]
+pub(crate) async fn query_pool(pool: &sqlx::PgPool, sql: &str) -> Result<Vec<CatalogRow>> {
+    Ok(sqlx::Executor::fetch_all(pool, sql)
+        .await
+        .map_err(|err| anyhow!(err))?
+        .into_iter()
+        .map(CatalogRow::Sqlx)
+        .collect())
+}
+
+/// Run a statement on a sqlx pool and return the number of affected rows.
+#[cfg(feature = "sqlx")]
+pub(crate) async fn execute_pool(pool: &sqlx::PgPool, sql: &str) -> Result<u64> {
+    Ok(sqlx::Executor::execute(pool, sql)
+        .await
+        .map_err(|err| anyhow!(err))?
+        .rows_affected())
+}
+
+/// Run a statement with parameters that returns rows on a sqlx pool. sqlx prepares the
+/// statement once per connection of the pool.
+#[cfg(feature = "sqlx")]
+pub(crate) async fn query_pool_params(
+    pool: &sqlx::PgPool,
+    sql: &str,
+    params: &[Param<'_>],
+) -> Result<Vec<CatalogRow>> {
+    Ok(bind(sqlx::query(sql), params)
+        .fetch_all(pool)
+        .await
+        .map_err(|err| anyhow!(err))?
+        .into_iter()
+        .map(CatalogRow::Sqlx)
+        .collect())
+}
+
+/// Run a statement with parameters on a sqlx pool and return the number of affected rows.
+#[cfg(feature = "sqlx")]
+pub(crate) async fn execute_pool_params(
+    pool: &sqlx::PgPool,
+    sql: &str,
+    params: &[Param<'_>],
+) -> Result<u64> {
+    Ok(bind(sqlx::query(sql), params)
+        .execute(pool)
+        .await
+        .map_err(|err| anyhow!(err))?
+        .rows_affected())
+}
+
+#[cfg(feature = "sqlx")]
+fn bind<'q>(
+    query: sqlx::query::Query<'q, sqlx::Postgres, sqlx::postgres::PgArguments>,
+    params: &[Param<'q>],
+) -> sqlx::query::Query<'q, sqlx::Postgres, sqlx::postgres::PgArguments> {
+    params.iter().fold(query, |query, param| match *param {
+        Param::Text(value) => query.bind(value),
+        Param::OptText(value) => query.bind(value),
+        Param::BigInt(value) => query.bind(value),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{identifier, like_pattern, literal};
+
+    #[test]
+    fn test_literal() {
+        assert_eq!(literal("table1"), "'table1'");
+        assert_eq!(literal("it's"), "'it''s'");
+        assert_eq!(literal("'; DROP TABLE x; --"), "'''; DROP TABLE x; --'");
+        assert_eq!(literal("a\\b"), "'a\\b'");
+    }
+
+    #[test]
+    fn test_like_pattern() {
+        assert_eq!(like_pattern("a.b."), "a.b.");
+        assert_eq!(like_pattern("my_ns%\\"), "my\\_ns\\%\\\\");
+    }
+
+    #[test]
+    fn test_identifier() {
+        assert_eq!(identifier("iceberg_tables"), "\"iceberg_tables\"");
+        assert_eq!(identifier("my \"table\""), "\"my \"\"table\"\"\"");
+    }
+}
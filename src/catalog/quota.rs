@@ -0,0 +1,190 @@
+/*!
+Limits on the number of tables and the storage of namespaces.
+
+A [NamespaceQuota] applies to the tables of a namespace and of all its child namespaces. Creating a
+table fails with [CatalogError::QuotaExceeded] if the namespace already contains the maximum number
+of tables, creating or committing fails if the new metadata would raise the storage of the namespace
+above the maximum.
+
+The storage of a table is the `total-files-size` of the summary of its current snapshot, which the
+catalog records with every commit. Tables registered by earlier versions count as empty until their
+next commit, and concurrent commits can exceed a quota slightly, so the limits are approximate.
+*/
+
+use std::fmt;
+
+use anyhow::Result;
+use iceberg_rs::catalog::table_identifier::TableIdentifier;
+
+use super::{
+    error::CatalogError,
+    namespace::{namespace_condition, namespace_key},
+    query::literal,
+    PostgresCatalog, CATALOG_NAME_COLUMN, TABLE_NAMESPACE_COLUMN, TABLE_NAME_COLUMN,
+    TABLE_SIZE_COLUMN,
+};
+
+static TABLE_COUNT_COLUMN: &str = "table_count";
+
+/// Limited resource of a namespace
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum QuotaKind {
+    /// Number of tables
+    Tables,
+    /// Total size of the data files of the current snapshots in bytes
+    StorageBytes,
+}
+
+impl fmt::Display for QuotaKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            QuotaKind::Tables => f.write_str("tables"),
+            QuotaKind::StorageBytes => f.write_str("bytes of storage"),
+        }
+    }
+}
+
+/// Limits for the tables of a namespace and its children
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct NamespaceQuota {
+    max_tables: Option<i64>,
+    max_storage_bytes: Option<i64>,
+}
+
+impl NamespaceQuota {
+    /// Create a quota without limits
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Limit the number of tables
+    pub fn with_max_tables(mut self, max_tables: i64) -> Self {
+        self.max_tables = Some(max_tables);
+        self
+    }
+
+    /// Limit the total size of the data files of the current snapshots
+    pub fn with_max_storage_bytes(mut self, max_storage_bytes: i64) -> Self {
+        self.max_storage_bytes = Some(max_storage_bytes);
+        self
+    }
+}
+
+impl PostgresCatalog {
+    /// Fail with [CatalogError::QuotaExceeded] if creating the table or changing its size to
+    /// `table_size` exceeds a quota of its namespace or of a parent namespace.
+    pub(crate) async fn check_quotas(
+        &self,
+        identifier: &TableIdentifier,
+        new_table: bool,
+        table_size: Option<i64>,
+    ) -> Result<()> {
+        for (namespace, quota) in &self.quotas {
+            if !identifier
+                .namespace()
+                .levels()
+                .starts_with(namespace.levels())
+            {
+                continue;
+            }
+            let max_tables = quota.max_tables.filter(|_| new_table);
+            if max_tables.is_none() && quota.max_storage_bytes.is_none() {
+                continue;
+            }
+            // The usage of the other tables, the table itself is replaced by its new state.
+            let rows = self
+                .query(
+                    &self.primary,
+                    &("SELECT COUNT(*) AS ".to_string()
+                        + TABLE_COUNT_COLUMN
+                        + ", COALESCE(SUM("
+                        + TABLE_SIZE_COLUMN
+                        + "), 0)::BIGINT AS "
+                        + TABLE_SIZE_COLUMN
+                        + " FROM "
+                        + &self.catalog_table.qualified
+                        + " WHERE "
+                        + CATALOG_NAME_COLUMN
+                        + " = "
+                        + &literal(&self.name)
+                        + " AND ("
+                        + &namespace_condition(namespace)
+                        + ") AND NOT ("
+                        + TABLE_NAMESPACE_COLUMN
+                        + " = "
+                        + &literal(&namespace_key(identifier.namespace()))
+                        + " AND "
+                        + TABLE_NAME_COLUMN
+                        + " = "
+                        + &literal(identifier.name())
+                        + ");"),
+                )
+                .await?;
+            let (table_count, storage_bytes) = match rows.first() {
+                Some(row) => (
+                    row.try_get_i64(TABLE_COUNT_COLUMN)?,
+                    row.try_get_i64(TABLE_SIZE_COLUMN)?,
+                ),
+                None => (0, 0),
+            };
+            if let Some(max_tables) = max_tables {
+                if table_count + 1 > max_tables {
+                    return Err(CatalogError::QuotaExceeded {
+                        namespace: namespace.to_string(),
+                        kind: QuotaKind::Tables,
+                        limit: max_tables,
+                        requested: table_count + 1,
+                    }
+                    .into());
+                }
+            }
+            if let Some(max_storage_bytes) = quota.max_storage_bytes {
+                let requested = storage_bytes + table_size.unwrap_or(0);
+                if requested > max_storage_bytes {
+                    return Err(CatalogError::QuotaExceeded {
+                        namespace: namespace.to_string(),
+                        kind: QuotaKind::StorageBytes,
+                        limit: max_storage_bytes,
+                        requested,
+                    }
+                    .into());
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Size of the data files of the current snapshot, from the summary in the table metadata
+pub(crate) fn table_size(metadata: &serde_json::Value) -> Option<i64> {
+    let current_snapshot_id = metadata["current-snapshot-id"].as_i64()?;
+    let snapshot = metadata["snapshots"]
+        .as_array()?
+        .iter()
+        .find(|snapshot| snapshot["snapshot-id"].as_i64() == Some(current_snapshot_id))?;
+    match &snapshot["summary"]["total-files-size"] {
+        serde_json::Value::String(size) => size.parse().ok(),
+        size => size.as_i64(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::table_size;
+
+    #[test]
+    fn test_table_size() {
+        let metadata = json!({
+            "current-snapshot-id": 2,
+            "snapshots": [
+                {"snapshot-id": 1, "summary": {"operation": "append", "total-files-size": "100"}},
+                {"snapshot-id": 2, "summary": {"operation": "append", "total-files-size": "250"}}
+            ]
+        });
+        assert_eq!(table_size(&metadata), Some(250));
+        assert_eq!(table_size(&json!({"current-snapshot-id": -1})), None);
+        assert_eq!(table_size(&json!({"snapshots": []})), None);
+    }
+}
@@ -0,0 +1,229 @@
+/*!
+Rate limits of catalog operations.
+
+A job that loads tables in a tight loop can saturate the postgres server that all tenants of the
+catalog share. [with_rate_limit](super::builder::PostgresCatalogBuilder::with_rate_limit) and
+[with_global_rate_limit](super::builder::PostgresCatalogBuilder::with_global_rate_limit) limit the
+operations of a catalog with token buckets: every operation takes a token from the bucket of the
+operation and from the global bucket. The buckets hold up to `burst` tokens and are refilled with
+`per_second` tokens per second. An operation without token waits until its token is refilled,
+but fails with [CatalogError::RateLimited](super::error::CatalogError::RateLimited) if that takes
+longer than the maximum wait.
+*/
+
+use std::{
+    collections::HashMap,
+    fmt,
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+use anyhow::Result;
+
+use super::{error::CatalogError, PostgresCatalog};
+
+/// Maximum time an operation waits for its token by default
+pub(crate) static DEFAULT_MAX_RATE_LIMIT_WAIT: Duration = Duration::from_secs(1);
+
+/// Operations of the catalog that are rate limited
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Operation {
+    /// `list_tables`
+    ListTables,
+    /// `list_namespaces`
+    ListNamespaces,
+    /// `table_exists`
+    TableExists,
+    /// `load_table`
+    LoadTable,
+    /// `register_table`, which also creates tables
+    RegisterTable,
+    /// `update_table` and the changes the catalog commits itself
+    Commit,
+    /// `drop_table`
+    DropTable,
+}
+
+impl fmt::Display for Operation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Operation::ListTables => f.write_str("list_tables"),
+            Operation::ListNamespaces => f.write_str("list_namespaces"),
+            Operation::TableExists => f.write_str("table_exists"),
+            Operation::LoadTable => f.write_str("load_table"),
+            Operation::RegisterTable => f.write_str("register_table"),
+            Operation::Commit => f.write_str("commit"),
+            Operation::DropTable => f.write_str("drop_table"),
+        }
+    }
+}
+
+/// Sustained rate and burst of a rate limit
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RateLimit {
+    /// Operations per second
+    pub per_second: f64,
+    /// Operations that can run at once after a quiet period
+    pub burst: u32,
+}
+
+/// Token buckets of the rate limits of a catalog
+#[derive(Default)]
+pub(crate) struct RateLimiter {
+    global: Option<Bucket>,
+    operations: HashMap<Operation, Bucket>,
+    max_wait: Duration,
+}
+
+/// Token bucket of a rate limit. Tokens go negative for operations that wait for their token.
+struct Bucket {
+    limit: RateLimit,
+    state: Mutex<(f64, Instant)>,
+}
+
+impl Bucket {
+    fn new(limit: RateLimit) -> Self {
+        Bucket {
+            limit,
+            state: Mutex::new((limit.burst as f64, Instant::now())),
+        }
+    }
+
+    /// Take a token at `now` and return the time until it is available, `None` without taking
+    /// the token if that is longer than `max_wait`
+    fn reserve(&self, now: Instant, max_wait: Duration) -> Option<Duration> {
+        let mut state = match self.state.lock() {
+            Ok(state) => state,
+            Err(poisoned) => poisoned.into_inner(),
+        };
+        let (tokens, last) = *state;
+        let elapsed = now.saturating_duration_since(last).as_secs_f64();
+        let tokens = (tokens + elapsed * self.limit.per_second).min(self.limit.burst as f64);
+        let wait = if tokens >= 1.0 {
+            Duration::ZERO
+        } else if self.limit.per_second > 0.0 {
+            Duration::from_secs_f64(((1.0 - tokens) / self.limit.per_second).min(1e9))
+        } else {
+            return None;
+        };
+        if wait > max_wait {
+            *state = (tokens, now);
+            return None;
+        }
+        *state = (tokens - 1.0, now);
+        Some(wait)
+    }
+
+    /// Return a reserved token
+    fn release(&self) {
+        if let Ok(mut state) = self.state.lock() {
+            state.0 = (state.0 + 1.0).min(self.limit.burst as f64);
+        }
+    }
+}
+
+impl RateLimiter {
+    pub(crate) fn new(
+        global: Option<RateLimit>,
+        operations: &[(Operation, RateLimit)],
+        max_wait: Duration,
+    ) -> Self {
+        RateLimiter {
+            global: global.map(Bucket::new),
+            operations: operations
+                .iter()
+                .map(|(operation, limit)| (*operation, Bucket::new(*limit)))
+                .collect(),
+            max_wait,
+        }
+    }
+
+    /// Reserve a token of the operation and of the global limit, and return the time until both
+    /// are available
+    fn reserve(&self, operation: Operation, now: Instant) -> Result<Duration> {
+        let rejected = || CatalogError::RateLimited {
+            operation: operation.to_string(),
+        };
+        let bucket = self.operations.get(&operation);
+        let wait = match bucket {
+            Some(bucket) => bucket.reserve(now, self.max_wait).ok_or_else(rejected)?,
+            None => Duration::ZERO,
+        };
+        let global = match &self.global {
+            Some(global) => match global.reserve(now, self.max_wait) {
+                Some(wait) => wait,
+                None => {
+                    // The operation doesn't run, so it doesn't use its token either.
+                    if let Some(bucket) = bucket {
+                        bucket.release();
+                    }
+                    return Err(rejected().into());
+                }
+            },
+            None => Duration::ZERO,
+        };
+        Ok(wait.max(global))
+    }
+}
+
+impl PostgresCatalog {
+    /// Wait until the rate limits allow the operation, or fail if it would wait too long.
+    pub(crate) async fn throttle(&self, operation: Operation) -> Result<()> {
+        let wait = self.rate_limiter.reserve(operation, Instant::now())?;
+        if !wait.is_zero() {
+            tokio::time::sleep(wait).await;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, Instant};
+
+    use super::{Operation, RateLimit, RateLimiter};
+
+    #[test]
+    fn test_rate_limiter() {
+        let limiter = RateLimiter::new(
+            Some(RateLimit {
+                per_second: 100.0,
+                burst: 3,
+            }),
+            &[(
+                Operation::LoadTable,
+                RateLimit {
+                    per_second: 10.0,
+                    burst: 2,
+                },
+            )],
+            Duration::from_millis(150),
+        );
+        let now = Instant::now();
+        assert_eq!(
+            limiter.reserve(Operation::LoadTable, now).unwrap(),
+            Duration::ZERO
+        );
+        assert_eq!(
+            limiter.reserve(Operation::LoadTable, now).unwrap(),
+            Duration::ZERO
+        );
+        // The third load waits for the refill of the operation bucket.
+        assert_eq!(
+            limiter.reserve(Operation::LoadTable, now).unwrap(),
+            Duration::from_millis(100)
+        );
+        assert!(limiter.reserve(Operation::LoadTable, now).is_err());
+        // The global bucket is empty too, but refills faster.
+        assert_eq!(
+            limiter.reserve(Operation::TableExists, now).unwrap(),
+            Duration::from_millis(10)
+        );
+        assert_eq!(
+            limiter
+                .reserve(Operation::LoadTable, now + Duration::from_millis(200))
+                .unwrap(),
+            Duration::ZERO
+        );
+    }
+}
@@ -0,0 +1,382 @@
+/*!
+Relocation of tables to another bucket or path, for bucket migrations.
+
+After the files of the tables were copied to a new bucket,
+[relocate_tables](PostgresCatalog::relocate_tables) points all tables whose metadata or table
+location starts with the old prefix to the copies below the new prefix. The pointers of all tables
+are changed in one transaction, which fails without changes if any of the tables was committed to
+concurrently.
+
+The metadata files contain absolute locations as well. With `rewrite_metadata`, the copies of the
+current metadata files are rewritten, so that the location of the table, the metadata log, the
+manifest lists, the statistics files and the write paths in the properties use the new prefix.
+Manifest lists and manifests are Avro files and still reference the old locations of the data
+files, engines that read them need access to the old prefix or a mapping of it.
+
+Locations stored [relative to the warehouse](super::storage::location) don't start with a bucket,
+they are not relocated and follow the `warehouse` property of the catalog instead.
+*/
+
+use std::sync::Arc;
+
+use anyhow::{anyhow, Result};
+use iceberg_rs::{catalog::table_identifier::TableIdentifier, object_store::path::Path};
+use serde_json::Value;
+
+use super::{
+    access::Action,
+    checksum::checksum,
+    commit::WRITE_METADATA_PATH,
+    compression::compress,
+    namespace::{namespace_key, table_identifier},
+    query::{like_pattern, literal},
+    storage::resolver::object_path,
+    PostgresCatalog, CATALOG_NAME_COLUMN, METADATA_CHECKSUM_COLUMN, METADATA_LOCATION_COLUMN,
+    PREVIOUS_METADATA_LOCATION_COLUMN, SNAPSHOT_SUMMARY_COLUMN, TABLE_LOCATION_COLUMN,
+    TABLE_NAMESPACE_COLUMN, TABLE_NAME_COLUMN, UPDATED_AT_COLUMN, VERSION_COLUMN,
+};
+
+/// Table properties with locations of new files
+static LOCATION_PROPERTIES: [&str; 3] = [
+    "write.data.path",
+    WRITE_METADATA_PATH,
+    "write.object-storage.path",
+];
+
+/// Table pointed to new locations by a relocation
+#[derive(Debug, Clone)]
+pub struct Relocation {
+    /// Identifier of the table
+    pub identifier: TableIdentifier,
+    /// Metadata location before the relocation
+    pub from: String,
+    /// Metadata location after the relocation
+    pub to: String,
+}
+
+/// New values of the catalog entry of a relocated table
+struct Entry {
+    relocation: Relocation,
+    previous_metadata_location: Option<String>,
+    table_location: Option<String>,
+    checksum: Option<String>,
+}
+
+impl PostgresCatalog {
+    /// Point all tables of the catalog whose metadata or table location starts with
+    /// `prefix_from` to the same locations below `prefix_to`, see the
+    /// [module documentation](self). The files have to be copied to the new locations before.
+    /// With `rewrite_metadata`, the copies of the current metadata files are rewritten to
+    /// reference the new locations. Returns the relocated tables.
+    pub async fn relocate_tables(
+        self: Arc<Self>,
+        prefix_from: &str,
+        prefix_to: &str,
+        rewrite_metadata: bool,
+    ) -> Result<Vec<Relocation>> {
+        self.check_writable("Relocating the tables")?;
+        if prefix_from.is_empty() || prefix_to.is_empty() {
+            return Err(anyhow!(
+                "Relocating the tables failed. The prefixes must not be empty."
+            ));
+        }
+        if prefix_from == prefix_to {
+            return Ok(Vec::new());
+        }
+        let pattern = literal(&(like_pattern(prefix_from) + "%"));
+        let rows = self
+            .query(
+                &self.primary,
+                &("SELECT ".to_string()
+                    + TABLE_NAMESPACE_COLUMN
+                    + ", "
+                    + TABLE_NAME_COLUMN
+                    + ", "
+                    + METADATA_LOCATION_COLUMN
+                    + ", "
+                    + PREVIOUS_METADATA_LOCATION_COLUMN
+                    + ", "
+                    + TABLE_LOCATION_COLUMN
+                    + ", "
+                    + METADATA_CHECKSUM_COLUMN
+                    + " FROM "
+                    + &self.catalog_table.qualified
+                    + " WHERE "
+                    + CATALOG_NAME_COLUMN
+                    + " = "
+                    + &literal(&self.name)
+                    + " AND ("
+                    + METADATA_LOCATION_COLUMN
+                    + " LIKE "
+                    + &pattern
+                    + " OR "
+                    + TABLE_LOCATION_COLUMN
+                    + " LIKE "
+                    + &pattern
+                    + ") ORDER BY "
+                    + TABLE_NAMESPACE_COLUMN
+                    + ", "
+                    + TABLE_NAME_COLUMN
+                    + ";"),
+            )
+            .await?;
+        let relocate = |location: &str| {
+            replace_prefix(location, prefix_from, prefix_to).unwrap_or_else(|| location.to_string())
+        };
+        let mut entries = Vec::with_capacity(rows.len());
+        for row in &rows {
+            let identifier = table_identifier(
+                &row.try_get_string(TABLE_NAMESPACE_COLUMN)?,
+                &row.try_get_string(TABLE_NAME_COLUMN)?,
+            )?;
+            self.authorize(
+                Action::Commit,
+                identifier.namespace(),
+                Some(identifier.name()),
+            )
+            .await?;
+            let from = row.try_get_string(METADATA_LOCATION_COLUMN)?;
+            let to = relocate(&from);
+            let mut checksum = row.try_get_opt_string(METADATA_CHECKSUM_COLUMN)?;
+            if rewrite_metadata {
+                if let Some(rewritten) = self
+                    .rewrite_metadata_file(&identifier, &to, prefix_from, prefix_to)
+                    .await?
+                {
+                    checksum = Some(rewritten);
+                }
+            }
+            entries.push(Entry {
+                relocation: Relocation {
+                    identifier,
+                    from,
+                    to,
+                },
+                previous_metadata_location: row
+                    .try_get_opt_string(PREVIOUS_METADATA_LOCATION_COLUMN)?
+                    .map(|location| relocate(&location)),
+                table_location: row
+                    .try_get_opt_string(TABLE_LOCATION_COLUMN)?
+                    .map(|location| relocate(&location)),
+                checksum,
+            });
+        }
+        if entries.is_empty() {
+            return Ok(Vec::new());
+        }
+        self.mark_write();
+        let results = self
+            .transaction(self.isolation_level, &self.relocation_statements(&entries))
+            .await?;
+        let n_rows = results.last().map(|result| result.n_rows).unwrap_or(0);
+        if n_rows != entries.len() as u64 {
+            return Err(anyhow!(
+                "Relocating the tables failed. Tables were changed concurrently, no table was relocated."
+            ));
+        }
+        Ok(entries.into_iter().map(|entry| entry.relocation).collect())
+    }
+
+    /// Statements that lock the entries and then move all pointers, but only if none of the
+    /// tables was committed to since its entry was read
+    fn relocation_statements(&self, entries: &[Entry]) -> Vec<String> {
+        let opt_literal = |value: &Option<String>| match value {
+            Some(value) => literal(value),
+            None => "NULL".to_string(),
+        };
+        let values = entries
+            .iter()
+            .map(|entry| {
+                let identifier = &entry.relocation.identifier;
+                "(".to_string()
+                    + &literal(&namespace_key(identifier.namespace()))
+                    + ", "
+                    + &literal(identifier.name())
+                    + ", "
+                    + &literal(&entry.relocation.from)
+                    + ", "
+                    + &literal(&entry.relocation.to)
+                    + ", "
+                    + &opt_literal(&entry.previous_metadata_location)
+                    + ", "
+                    + &opt_literal(&entry.table_location)
+                    + ", "
+                    + &opt_literal(&entry.checksum)
+                    + ")"
+            })
+            .collect::<Vec<_>>()
+            .join(", ");
+        let relocated = "relocated (namespace, name, from_location, to_location, \
+            previous_location, table_location, checksum) AS (VALUES "
+            .to_string()
+            + &values
+            + ")";
+        // Condition that the entry `table` is the table of the row `values` of the relocation
+        let matches = |table: &str, values: &str| {
+            table.to_string()
+                + "."
+                + CATALOG_NAME_COLUMN
+                + " = "
+                + &literal(&self.name)
+                + " AND "
+                + table
+                + "."
+                + TABLE_NAMESPACE_COLUMN
+                + " = "
+                + values
+                + ".namespace AND "
+                + table
+                + "."
+                + TABLE_NAME_COLUMN
+                + " = "
+                + values
+                + ".name"
+        };
+        let lock = "WITH ".to_string()
+            + &relocated
+            + " SELECT 1 FROM "
+            + &self.catalog_table.qualified
+            + " AS entry, relocated WHERE "
+            + &matches("entry", "relocated")
+            + " FOR UPDATE OF entry;";
+        // The entries are locked, so the check for unchanged pointers holds until the commit.
+        let update = "WITH ".to_string()
+            + &relocated
+            + " UPDATE "
+            + &self.catalog_table.qualified
+            + " AS entry SET "
+            + METADATA_LOCATION_COLUMN
+            + " = relocated.to_location, "
+            + PREVIOUS_METADATA_LOCATION_COLUMN
+            + " = relocated.previous_location, "
+            + TABLE_LOCATION_COLUMN
+            + " = relocated.table_location, "
+            + METADATA_CHECKSUM_COLUMN
+            + " = relocated.checksum, "
+            + SNAPSHOT_SUMMARY_COLUMN
+            + " = jsonb_set(entry."
+            + SNAPSHOT_SUMMARY_COLUMN
+            + ", '{metadata-location}', to_jsonb(relocated.to_location)), "
+            + VERSION_COLUMN
+            + " = entry."
+            + VERSION_COLUMN
+            + " + 1, "
+            + UPDATED_AT_COLUMN
+            + " = now() FROM relocated WHERE "
+            + &matches("entry", "relocated")
+            + " AND entry."
+            + METADATA_LOCATION_COLUMN
+            + " = relocated.from_location AND NOT EXISTS (SELECT 1 FROM relocated AS expected \
+            WHERE NOT EXISTS (SELECT 1 FROM "
+            + &self.catalog_table.qualified
+            + " AS current WHERE "
+            + &matches("current", "expected")
+            + " AND current."
+            + METADATA_LOCATION_COLUMN
+            + " = expected.from_location));";
+        vec![lock, update]
+    }
+
+    /// Rewrite the locations in the metadata file at `location` from the old to the new prefix.
+    /// Returns the checksum of the rewritten metadata, `None` if nothing changed.
+    async fn rewrite_metadata_file(
+        &self,
+        identifier: &TableIdentifier,
+        location: &str,
+        prefix_from: &str,
+        prefix_to: &str,
+    ) -> Result<Option<String>> {
+        let mut metadata = self.read_metadata_json(identifier, location).await?;
+        if !rewrite_locations(&mut metadata, prefix_from, prefix_to) {
+            return Ok(None);
+        }
+        let bytes = serde_json::to_vec(&metadata).map_err(|err| anyhow!(err.to_string()))?;
+        let checksum = checksum(&bytes);
+        let content = if location.ends_with(".gz") || location.contains(".gz.") {
+            compress(&bytes)?
+        } else {
+            bytes
+        };
+        let object_store = self.object_store_for_location(identifier, location)?;
+        let path: Path = object_path(&self.resolved_location(location)).into();
+        object_store
+            .put(&path, content.into())
+            .await
+            .map_err(|err| anyhow!(err.to_string()))?;
+        Ok(Some(checksum))
+    }
+}
+
+/// The value with the prefix `from` replaced by `to`, `None` if it doesn't start with `from`
+fn replace_prefix(value: &str, from: &str, to: &str) -> Option<String> {
+    value.strip_prefix(from).map(|rest| to.to_string() + rest)
+}
+
+/// Replace the prefix of the locations in the table metadata. Returns whether any location
+/// changed.
+fn rewrite_locations(metadata: &mut Value, from: &str, to: &str) -> bool {
+    let mut changed = false;
+    let mut rewrite = |value: Option<&mut Value>| {
+        if let Some(value) = value {
+            if let Some(location) = value.as_str().and_then(|v| replace_prefix(v, from, to)) {
+                *value = Value::String(location);
+                changed = true;
+            }
+        }
+    };
+    rewrite(metadata.get_mut("location"));
+    for (list, field) in [
+        ("metadata-log", "metadata-file"),
+        ("snapshots", "manifest-list"),
+        ("statistics", "statistics-path"),
+        ("partition-statistics", "statistics-path"),
+    ] {
+        if let Some(entries) = metadata.get_mut(list).and_then(Value::as_array_mut) {
+            for entry in entries {
+                rewrite(entry.get_mut(field));
+            }
+        }
+    }
+    if let Some(properties) = metadata.get_mut("properties") {
+        for property in LOCATION_PROPERTIES {
+            rewrite(properties.get_mut(property));
+        }
+    }
+    changed
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::{replace_prefix, rewrite_locations};
+
+    #[test]
+    fn test_rewrite_locations() {
+        assert_eq!(
+            replace_prefix("s3://old/wh/t", "s3://old/", "s3://new/").unwrap(),
+            "s3://new/wh/t"
+        );
+        assert!(replace_prefix("s3://older/wh/t", "s3://old/", "s3://new/").is_none());
+
+        let mut metadata = json!({
+            "location": "s3://old/wh/t",
+            "metadata-log": [{"timestamp-ms": 1, "metadata-file": "s3://old/wh/t/metadata/v1.metadata.json"}],
+            "snapshots": [{"snapshot-id": 1, "manifest-list": "s3://old/wh/t/metadata/snap-1.avro"}],
+            "properties": {"write.data.path": "s3://other/data", "owner": "s3://old/"}
+        });
+        assert!(rewrite_locations(&mut metadata, "s3://old/", "s3://new/"));
+        assert_eq!(metadata["location"], "s3://new/wh/t");
+        assert_eq!(
+            metadata["metadata-log"][0]["metadata-file"],
+            "s3://new/wh/t/metadata/v1.metadata.json"
+        );
+        assert_eq!(
+            metadata["snapshots"][0]["manifest-list"],
+            "s3://new/wh/t/metadata/snap-1.avro"
+        );
+        assert_eq!(metadata["properties"]["write.data.path"], "s3://other/data");
+        assert_eq!(metadata["properties"]["owner"], "s3://old/");
+        assert!(!rewrite_locations(&mut metadata, "s3://old/", "s3://new/"));
+    }
+}
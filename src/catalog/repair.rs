@@ -0,0 +1,349 @@
+/*!
+Recovery of catalog entries from the metadata files in the warehouse.
+
+[repair_from_warehouse](PostgresCatalog::repair_from_warehouse) scans a prefix of the object store
+of the catalog for table directories, which contain their metadata files in a `metadata`
+directory. For every table it takes the newest metadata file that can be parsed and compares it
+with the catalog: tables the catalog doesn't know are registered, and pointers to older or missing
+metadata files are moved to the newest one. Tables are matched with their catalog entries by the
+table uuid. The identifier of an unknown table is derived from its path relative to the scanned
+prefix, in the layout of the [DefaultLocationProvider](super::storage::location::DefaultLocationProvider)
+or the [HashedLocationProvider](super::storage::location::HashedLocationProvider).
+*/
+
+use std::{collections::HashMap, fmt, sync::Arc};
+
+use anyhow::{anyhow, Result};
+use futures::TryStreamExt;
+use iceberg_rs::{
+    catalog::{table_identifier::TableIdentifier, Catalog},
+    object_store::{path::Path, ObjectMeta, ObjectStore},
+};
+
+use super::{
+    compression::decompress,
+    format::parse_metadata,
+    namespace::table_identifier,
+    query::literal,
+    storage::{location::fnv1a, resolver::object_path},
+    Expected, PostgresCatalog, CATALOG_NAME_COLUMN, METADATA_LOCATION_COLUMN,
+    TABLE_NAMESPACE_COLUMN, TABLE_NAME_COLUMN, TABLE_UUID_COLUMN,
+};
+
+/// Directory of the metadata files within a table directory
+static METADATA_DIRECTORY: &str = "metadata";
+/// Suffixes of uncompressed and compressed metadata files
+static METADATA_SUFFIXES: [&str; 2] = [".metadata.json", ".metadata.json.gz"];
+
+/// Change of the catalog found by a warehouse scan
+#[derive(Debug, Clone)]
+pub enum RepairAction {
+    /// Register a table that has no catalog entry
+    Register {
+        /// Identifier derived from the path of the table
+        identifier: TableIdentifier,
+        /// Newest metadata file of the table
+        metadata_location: String,
+    },
+    /// Point a table to a newer metadata file
+    UpdatePointer {
+        /// Identifier of the table
+        identifier: TableIdentifier,
+        /// Current metadata location of the table
+        from: String,
+        /// Newest metadata file of the table
+        to: String,
+    },
+    /// A table directory that can't be repaired automatically
+    Unresolved {
+        /// Path of the table directory
+        table_path: String,
+        /// Reason why the table can't be repaired
+        reason: String,
+    },
+}
+
+impl fmt::Display for RepairAction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RepairAction::Register {
+                identifier,
+                metadata_location,
+            } => write!(f, "register {} with {}", identifier, metadata_location),
+            RepairAction::UpdatePointer {
+                identifier,
+                from,
+                to,
+            } => write!(f, "point {} from {} to {}", identifier, from, to),
+            RepairAction::Unresolved { table_path, reason } => {
+                write!(f, "skip {}: {}", table_path, reason)
+            }
+        }
+    }
+}
+
+impl PostgresCatalog {
+    /// Scan the warehouse below `prefix` and repair the catalog entries of the tables found. With
+    /// `dry_run`, the catalog isn't changed and the returned actions describe what a repair would
+    /// do. Otherwise the actions are applied in order and the repair stops at the first failure.
+    pub async fn repair_from_warehouse(
+        self: Arc<Self>,
+        prefix: &str,
+        dry_run: bool,
+    ) -> Result<Vec<RepairAction>> {
+        if !dry_run {
+            self.check_writable("Repairing the catalog")?;
+        }
+        let object_store = self.object_store();
+        let prefix = prefix.trim_matches('/');
+        let files = object_store
+            .list(Some(&Path::from(prefix)))
+            .await
+            .map_err(|err| anyhow!(err.to_string()))?
+            .try_collect::<Vec<_>>()
+            .await
+            .map_err(|err| anyhow!(err.to_string()))?;
+        let mut tables: HashMap<String, Vec<ObjectMeta>> = HashMap::new();
+        for file in files {
+            if let Some(table_path) = table_path(file.location.as_ref()) {
+                tables.entry(table_path.to_string()).or_default().push(file);
+            }
+        }
+        let entries = self.entries_by_uuid().await?;
+
+        let mut table_paths: Vec<_> = tables.keys().cloned().collect();
+        table_paths.sort();
+        let mut actions = Vec::new();
+        for table_path in table_paths {
+            let mut candidates = tables.remove(&table_path).unwrap_or_default();
+            candidates.sort_by(|a, b| b.last_modified.cmp(&a.last_modified));
+            let mut newest = None;
+            for candidate in candidates {
+                let location = candidate.location.to_string();
+                if let Ok(metadata) = read_metadata(&object_store, &location).await {
+                    newest = Some((location, metadata));
+                    break;
+                }
+            }
+            let (location, metadata) = match newest {
+                Some(newest) => newest,
+                None => {
+                    actions.push(RepairAction::Unresolved {
+                        table_path,
+                        reason: "No metadata file can be parsed.".to_string(),
+                    });
+                    continue;
+                }
+            };
+            let uuid = metadata["table-uuid"]
+                .as_str()
+                .map(|uuid| uuid.to_ascii_lowercase());
+            match uuid.and_then(|uuid| entries.get(&uuid)) {
+                Some((identifier, current)) => {
+                    let current = &self.resolved_location(current);
+                    if *current == location {
+                        continue;
+                    }
+                    // The current metadata may live in another object store than the warehouse.
+                    let current_store = self.object_store_for_location(identifier, current)?;
+                    let current_updated = read_metadata(&current_store, &object_path(current))
+                        .await
+                        .ok()
+                        .and_then(|metadata| metadata["last-updated-ms"].as_i64());
+                    let newest_updated = metadata["last-updated-ms"].as_i64();
+                    if current_updated.is_none() || newest_updated > current_updated {
+                        actions.push(RepairAction::UpdatePointer {
+                            identifier: identifier.clone(),
+                            from: current.clone(),
+                            to: location,
+                        });
+                    }
+                }
+                None => match derive_identifier(prefix, &table_path) {
+                    Some(identifier) => actions.push(RepairAction::Register {
+                        identifier,
+                        metadata_location: location,
+                    }),
+                    None => actions.push(RepairAction::Unresolved {
+                        table_path,
+                        reason: "The identifier can't be derived from the path.".to_string(),
+                    }),
+                },
+            }
+        }
+
+        if !dry_run {
+            for action in &actions {
+                match action {
+                    RepairAction::Register {
+                        identifier,
+                        metadata_location,
+                    } => {
+                        Arc::clone(&self)
+                            .register_table(identifier.clone(), metadata_location)
+                            .await?;
+                    }
+                    RepairAction::UpdatePointer {
+                        identifier,
+                        from,
+                        to,
+                    } => {
+                        Arc::clone(&self)
+                            .update_pointer(
+                                identifier.clone(),
+                                to,
+                                Expected::MetadataLocation(from),
+                            )
+                            .await?;
+                    }
+                    RepairAction::Unresolved { .. } => (),
+                }
+            }
+        }
+        Ok(actions)
+    }
+
+    /// Identifier and metadata location of the tables of the catalog by their uuid
+    async fn entries_by_uuid(&self) -> Result<HashMap<String, (TableIdentifier, String)>> {
+        let rows = self
+            .query(
+                &self.primary,
+                &("SELECT ".to_string()
+                    + TABLE_NAMESPACE_COLUMN
+                    + ", "
+                    + TABLE_NAME_COLUMN
+                    + ", "
+                    + METADATA_LOCATION_COLUMN
+                    + ", "
+                    + TABLE_UUID_COLUMN
+                    + "::TEXT AS "
+                    + TABLE_UUID_COLUMN
+                    + " FROM "
+                    + &self.catalog_table.qualified
+                    + " WHERE "
+                    + CATALOG_NAME_COLUMN
+                    + " = "
+                    + &literal(&self.name)
+                    + " AND "
+                    + TABLE_UUID_COLUMN
+                    + " IS NOT NULL;"),
+            )
+            .await?;
+        rows.iter()
+            .map(|row| {
+                Ok((
+                    row.try_get_string(TABLE_UUID_COLUMN)?,
+                    (
+                        table_identifier(
+                            &row.try_get_string(TABLE_NAMESPACE_COLUMN)?,
+                            &row.try_get_string(TABLE_NAME_COLUMN)?,
+                        )?,
+                        row.try_get_string(METADATA_LOCATION_COLUMN)?,
+                    ),
+                ))
+            })
+            .collect()
+    }
+}
+
+/// Read a metadata file and check that it is valid table metadata.
+async fn read_metadata(
+    object_store: &Arc<dyn ObjectStore>,
+    location: &str,
+) -> Result<serde_json::Value> {
+    let bytes = decompress(
+        object_store
+            .get(&Path::from(location))
+            .await
+            .map_err(|err| anyhow!(err.to_string()))?
+            .bytes()
+            .await
+            .map_err(|err| anyhow!(err.to_string()))?
+            .to_vec(),
+    )?;
+    parse_metadata(&bytes)?;
+    serde_json::from_slice(&bytes).map_err(|err| anyhow!(err.to_string()))
+}
+
+/// Directory of the table the metadata file at `path` belongs to
+fn table_path(path: &str) -> Option<&str> {
+    if !METADATA_SUFFIXES
+        .iter()
+        .any(|suffix| path.ends_with(suffix))
+    {
+        return None;
+    }
+    let (directory, _) = path.rsplit_once('/')?;
+    let (table_path, metadata) = directory.rsplit_once('/')?;
+    if metadata == METADATA_DIRECTORY {
+        Some(table_path)
+    } else {
+        None
+    }
+}
+
+/// Identifier of the table at `table_path`, whose path relative to `prefix` consists of the
+/// namespace levels and the table name, optionally preceded by the hash of the hashed layout.
+fn derive_identifier(prefix: &str, table_path: &str) -> Option<TableIdentifier> {
+    let relative = if prefix.is_empty() {
+        table_path
+    } else {
+        table_path.strip_prefix(prefix)?.strip_prefix('/')?
+    };
+    let mut names: Vec<String> = relative.split('/').map(ToString::to_string).collect();
+    if names.len() > 2 && format!("{:08x}", fnv1a(names[1..].join(".").as_bytes())) == names[0] {
+        names.remove(0);
+    }
+    if names.len() < 2 {
+        return None;
+    }
+    TableIdentifier::try_new(&names).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use iceberg_rs::catalog::table_identifier::TableIdentifier;
+
+    use super::{derive_identifier, table_path};
+    use crate::catalog::storage::location::{HashedLocationProvider, LocationProvider};
+
+    #[test]
+    fn test_table_path() {
+        assert_eq!(
+            table_path("warehouse/test/table1/metadata/00001-abc.metadata.json"),
+            Some("warehouse/test/table1")
+        );
+        assert_eq!(
+            table_path("warehouse/test/table1/metadata/00002-abc.metadata.json.gz"),
+            Some("warehouse/test/table1")
+        );
+        assert_eq!(table_path("warehouse/test/table1/data/file.parquet"), None);
+        assert_eq!(
+            table_path("warehouse/test/table1/00001.metadata.json"),
+            None
+        );
+    }
+
+    #[test]
+    fn test_derive_identifier() {
+        assert_eq!(
+            format!(
+                "{}",
+                derive_identifier("warehouse", "warehouse/test/table1").unwrap()
+            ),
+            "test.table1"
+        );
+        assert_eq!(
+            format!("{}", derive_identifier("", "a/b/table1").unwrap()),
+            "a.b.table1"
+        );
+        let identifier = TableIdentifier::parse("test.table1").unwrap();
+        let location = HashedLocationProvider.table_location("warehouse", &identifier);
+        assert_eq!(
+            format!("{}", derive_identifier("warehouse", &location).unwrap()),
+            "test.table1"
+        );
+        assert!(derive_identifier("warehouse", "warehouse/table1").is_none());
+        assert!(derive_identifier("warehouse", "other/test/table1").is_none());
+    }
+}
@@ -0,0 +1,241 @@
+/*!
+Retries of transient database errors and a circuit breaker that fails fast while the database is
+unavailable.
+*/
+
+use std::{
+    future::Future,
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+use anyhow::{anyhow, Result};
+
+use super::error::CatalogError;
+
+/// Policy for retrying statements that failed with a transient error.
+///
+/// Read-only statements are retried for all transient errors. Writes are only retried if postgres
+/// guarantees that the statement was rolled back (serialization failures and deadlocks), because
+/// after a connection error or a timeout it is unknown whether the write was applied.
+#[derive(Debug, Clone)]
+pub struct RetryPolicy {
+    /// Maximum number of retries after the first attempt
+    pub max_retries: u32,
+    /// Backoff before the first retry, doubled for every following retry
+    pub initial_backoff: Duration,
+    /// Upper limit for the backoff
+    pub max_backoff: Duration,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        RetryPolicy {
+            max_retries: 0,
+            initial_backoff: Duration::from_millis(50),
+            max_backoff: Duration::from_secs(2),
+        }
+    }
+}
+
+impl RetryPolicy {
+    fn backoff(&self, attempt: u32) -> Duration {
+        self.initial_backoff
+            .checked_mul(2u32.saturating_pow(attempt))
+            .map(|backoff| backoff.min(self.max_backoff))
+            .unwrap_or(self.max_backoff)
+    }
+}
+
+/// Circuit breaker that opens after a number of consecutive failures caused by an unavailable
+/// database. While it is open, statements fail immediately. After the reset timeout a single
+/// statement is let through; if it succeeds the circuit closes again, otherwise it stays open.
+pub(crate) struct CircuitBreaker {
+    failure_threshold: u32,
+    reset_timeout: Duration,
+    state: Mutex<BreakerState>,
+}
+
+#[derive(Default)]
+struct BreakerState {
+    consecutive_failures: u32,
+    open_until: Option<Instant>,
+}
+
+impl CircuitBreaker {
+    pub(crate) fn new(failure_threshold: u32, reset_timeout: Duration) -> Self {
+        CircuitBreaker {
+            failure_threshold,
+            reset_timeout,
+            state: Mutex::new(BreakerState::default()),
+        }
+    }
+
+    fn check(&self) -> Result<()> {
+        let mut state = self.state.lock().map_err(|err| anyhow!(err.to_string()))?;
+        match state.open_until {
+            Some(open_until) if Instant::now() < open_until => Err(anyhow!(
+                "The circuit breaker is open. The database is unavailable.".to_string()
+            )),
+            Some(_) => {
+                // Let a single statement through to probe the database.
+                state.open_until = Some(Instant::now() + self.reset_timeout);
+                Ok(())
+            }
+            None => Ok(()),
+        }
+    }
+
+    fn record_success(&self) {
+        if let Ok(mut state) = self.state.lock() {
+            *state = BreakerState::default();
+        }
+    }
+
+    fn record_failure(&self) {
+        if let Ok(mut state) = self.state.lock() {
+            state.consecutive_failures += 1;
+            if state.consecutive_failures >= self.failure_threshold {
+                state.open_until = Some(Instant::now() + self.reset_timeout);
+            }
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Eq)]
+enum ErrorKind {
+    /// The statement was rolled back and can safely be retried
+    RolledBack,
+    /// The database couldn't be reached, the statement may or may not have been applied
+    Unavailable,
+    /// Any other error
+    Permanent,
+}
+
+fn classify(err: &anyhow::Error) -> ErrorKind {
+    #[cfg(feature = "sqlx")]
+    if let Some(err) = err.downcast_ref::<sqlx::Error>() {
+        return classify_sqlx(err);
+    }
+    // The statement may still run on the server, like after a lost connection.
+    if let Some(CatalogError::TimedOut { .. }) = err.downcast_ref::<CatalogError>() {
+        return ErrorKind::Unavailable;
+    }
+    let err = match err.downcast_ref::<tokio_postgres::Error>() {
+        Some(err) => err,
+        None => return ErrorKind::Permanent,
+    };
+    match err.code().map(|code| code.code()) {
+        Some("40001") | Some("40P01") => ErrorKind::RolledBack,
+        Some(code) if code.starts_with("08") => ErrorKind::Unavailable,
+        Some("57P01") | Some("57P02") | Some("57P03") => ErrorKind::Unavailable,
+        Some(_) => ErrorKind::Permanent,
+        None if err.is_closed() => ErrorKind::Unavailable,
+        None => match std::error::Error::source(err) {
+            Some(source) if source.is::<std::io::Error>() => ErrorKind::Unavailable,
+            _ => ErrorKind::Permanent,
+        },
+    }
+}
+
+#[cfg(feature = "sqlx")]
+fn classify_sqlx(err: &sqlx::Error) -> ErrorKind {
+    match err {
+        sqlx::Error::Database(err) => match err.code().as_deref() {
+            Some("40001") | Some("40P01") => ErrorKind::RolledBack,
+            Some(code) if code.starts_with("08") => ErrorKind::Unavailable,
+            Some("57P01") | Some("57P02") | Some("57P03") => ErrorKind::Unavailable,
+            _ => ErrorKind::Permanent,
+        },
+        sqlx::Error::Io(_) | sqlx::Error::PoolTimedOut => ErrorKind::Unavailable,
+        _ => ErrorKind::Permanent,
+    }
+}
+
+/// Run the operation, retrying it according to the policy.
+pub(crate) async fn retry<T, F, Fut>(
+    policy: &RetryPolicy,
+    breaker: Option<&CircuitBreaker>,
+    read_only: bool,
+    mut operation: F,
+) -> Result<T>
+where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = Result<T>>,
+{
+    let mut attempt = 0;
+    loop {
+        if let Some(breaker) = breaker {
+            breaker.check()?;
+        }
+        let err = match operation().await {
+            Ok(value) => {
+                if let Some(breaker) = breaker {
+                    breaker.record_success();
+                }
+                return Ok(value);
+            }
+            Err(err) => err,
+        };
+        let kind = classify(&err);
+        if kind == ErrorKind::Unavailable {
+            if let Some(breaker) = breaker {
+                breaker.record_failure();
+            }
+        }
+        let retryable = match kind {
+            ErrorKind::RolledBack => true,
+            ErrorKind::Unavailable => read_only,
+            ErrorKind::Permanent => false,
+        };
+        if !retryable || attempt >= policy.max_retries {
+            return Err(err);
+        }
+        tokio::time::sleep(policy.backoff(attempt)).await;
+        attempt += 1;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::{classify, CircuitBreaker, ErrorKind, RetryPolicy};
+    use crate::catalog::timeout::with_timeout;
+
+    #[test]
+    fn test_backoff() {
+        let policy = RetryPolicy {
+            max_retries: 5,
+            initial_backoff: Duration::from_millis(100),
+            max_backoff: Duration::from_millis(500),
+        };
+        assert_eq!(policy.backoff(0), Duration::from_millis(100));
+        assert_eq!(policy.backoff(2), Duration::from_millis(400));
+        assert_eq!(policy.backoff(3), Duration::from_millis(500));
+        assert_eq!(policy.backoff(40), Duration::from_millis(500));
+    }
+
+    #[test]
+    fn test_circuit_breaker_opens() {
+        let breaker = CircuitBreaker::new(2, Duration::from_secs(60));
+        breaker.record_failure();
+        assert!(breaker.check().is_ok());
+        breaker.record_failure();
+        assert!(breaker.check().is_err());
+        breaker.record_success();
+        assert!(breaker.check().is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_classify_timeout() {
+        let err = with_timeout(Some(Duration::from_millis(10)), "Query", async {
+            tokio::time::sleep(Duration::from_secs(1)).await;
+            Ok(())
+        })
+        .await
+        .unwrap_err();
+        assert_eq!(classify(&err), ErrorKind::Unavailable);
+        assert_eq!(classify(&anyhow::anyhow!("Other")), ErrorKind::Permanent);
+    }
+}
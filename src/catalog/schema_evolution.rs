@@ -0,0 +1,318 @@
+/*!
+Schema evolution through the catalog.
+
+The methods change the top-level columns of the current schema of a table. The changed schema is
+added to the metadata with a new schema id and becomes the current schema, columns keep their field
+ids, so data files written with earlier schemas stay readable. Every change is committed
+separately and fails if the table was changed concurrently, unless commits to the table are
+[queued](super::coordinator).
+*/
+
+use std::sync::Arc;
+
+use anyhow::{anyhow, Result};
+use iceberg_rs::{
+    catalog::table_identifier::TableIdentifier,
+    model::schema::{AllType, PrimitiveType},
+    table::Table,
+};
+use serde_json::{json, Value};
+
+use super::PostgresCatalog;
+
+impl PostgresCatalog {
+    /// Add an optional column with the type to the end of the schema. Nested fields of the type
+    /// get new field ids.
+    pub async fn add_column(
+        self: Arc<Self>,
+        identifier: TableIdentifier,
+        name: &str,
+        field_type: AllType,
+        doc: Option<&str>,
+    ) -> Result<Table> {
+        let field_type =
+            serde_json::to_value(&field_type).map_err(|err| anyhow!(err.to_string()))?;
+        self.evolve_schema(identifier, |schema, last_column_id, _| {
+            if find_field(schema, name).is_ok() {
+                return Err(anyhow!("The column {} already exists.", name));
+            }
+            *last_column_id += 1;
+            let id = *last_column_id;
+            let mut field_type = field_type.clone();
+            assign_ids(&mut field_type, last_column_id);
+            let mut field = json!({
+                "id": id,
+                "name": name,
+                "required": false,
+                "type": field_type,
+            });
+            if let Some(doc) = doc {
+                field["doc"] = json!(doc);
+            }
+            fields_mut(schema)?.push(field);
+            Ok(())
+        })
+        .await
+    }
+
+    /// Rename the column. Data files find the column by its field id, so they aren't rewritten.
+    pub async fn rename_column(
+        self: Arc<Self>,
+        identifier: TableIdentifier,
+        name: &str,
+        new_name: &str,
+    ) -> Result<Table> {
+        self.evolve_schema(identifier, |schema, _, _| {
+            if find_field(schema, new_name).is_ok() {
+                return Err(anyhow!("The column {} already exists.", new_name));
+            }
+            let index = find_field(schema, name)?;
+            fields_mut(schema)?[index]["name"] = json!(new_name);
+            Ok(())
+        })
+        .await
+    }
+
+    /// Remove the column from the schema. Columns that identify rows or that the current
+    /// partition spec or sort order use can't be dropped.
+    pub async fn drop_column(
+        self: Arc<Self>,
+        identifier: TableIdentifier,
+        name: &str,
+    ) -> Result<Table> {
+        self.evolve_schema(identifier, |schema, _, metadata| {
+            let index = find_field(schema, name)?;
+            let id = fields_mut(schema)?[index]["id"].clone();
+            let identifies = schema["identifier-field-ids"]
+                .as_array()
+                .map_or(false, |ids| ids.contains(&id));
+            if identifies || source_ids(metadata).contains(&id) {
+                return Err(anyhow!(
+                    "The column {} identifies rows or is used by the partition spec or sort order of the table.",
+                    name
+                ));
+            }
+            fields_mut(schema)?.remove(index);
+            Ok(())
+        })
+        .await
+    }
+
+    /// Change the type of the column to a wider type: `int` to `long`, `float` to `double` or a
+    /// decimal to a decimal with the same scale and a larger precision.
+    pub async fn update_column_type(
+        self: Arc<Self>,
+        identifier: TableIdentifier,
+        name: &str,
+        field_type: PrimitiveType,
+    ) -> Result<Table> {
+        let field_type =
+            serde_json::to_value(&field_type).map_err(|err| anyhow!(err.to_string()))?;
+        self.evolve_schema(identifier, |schema, _, _| {
+            let index = find_field(schema, name)?;
+            let field = &mut fields_mut(schema)?[index];
+            if !can_promote(&field["type"], &field_type) {
+                return Err(anyhow!(
+                    "The type of the column {} can't be changed from {} to {}.",
+                    name,
+                    field["type"],
+                    field_type
+                ));
+            }
+            field["type"] = field_type.clone();
+            Ok(())
+        })
+        .await
+    }
+
+    /// Apply the change to a copy of the current schema, with the last assigned column id and
+    /// the metadata, and commit the copy as new current schema.
+    async fn evolve_schema(
+        self: Arc<Self>,
+        identifier: TableIdentifier,
+        mut change: impl FnMut(&mut Value, &mut i64, &Value) -> Result<()>,
+    ) -> Result<Table> {
+        self.check_writable("Changing the schema")?;
+        self.change_metadata(identifier, |metadata| {
+            evolve(metadata, &mut change)?;
+            Ok(true)
+        })
+        .await
+    }
+}
+
+/// Apply the change to a copy of the current schema and make the copy the current schema of the
+/// metadata.
+fn evolve(
+    metadata: &mut Value,
+    change: &mut impl FnMut(&mut Value, &mut i64, &Value) -> Result<()>,
+) -> Result<()> {
+    let schemas = metadata["schemas"]
+        .as_array()
+        .ok_or_else(|| anyhow!("The schemas of the table are not an array."))?;
+    let mut schema = schemas
+        .iter()
+        .find(|schema| schema["schema-id"] == metadata["current-schema-id"])
+        .cloned()
+        .ok_or_else(|| anyhow!("The current schema of the table doesn't exist."))?;
+    let schema_id = schemas
+        .iter()
+        .filter_map(|schema| schema["schema-id"].as_i64())
+        .max()
+        .unwrap_or(0)
+        + 1;
+    let mut last_column_id = metadata["last-column-id"].as_i64().unwrap_or(0);
+    change(&mut schema, &mut last_column_id, metadata)?;
+    schema["schema-id"] = json!(schema_id);
+    metadata["schemas"]
+        .as_array_mut()
+        .ok_or_else(|| anyhow!("The schemas of the table are not an array."))?
+        .push(schema);
+    metadata["current-schema-id"] = json!(schema_id);
+    metadata["last-column-id"] = json!(last_column_id);
+    Ok(())
+}
+
+/// Top-level fields of the schema
+fn fields_mut(schema: &mut Value) -> Result<&mut Vec<Value>> {
+    schema["fields"]
+        .as_array_mut()
+        .ok_or_else(|| anyhow!("The fields of the schema are not an array."))
+}
+
+/// Position of the top-level field with the name
+fn find_field(schema: &Value, name: &str) -> Result<usize> {
+    schema["fields"]
+        .as_array()
+        .and_then(|fields| fields.iter().position(|field| field["name"] == name))
+        .ok_or_else(|| anyhow!("The column {} doesn't exist.", name))
+}
+
+/// Field ids used by the default partition spec and the default sort order
+fn source_ids(metadata: &Value) -> Vec<Value> {
+    let default = |list: &str, id: &str, default_id: &str| {
+        metadata[list]
+            .as_array()
+            .and_then(|items| items.iter().find(|item| item[id] == metadata[default_id]))
+            .and_then(|item| item["fields"].as_array())
+            .into_iter()
+            .flatten()
+            .map(|field| field["source-id"].clone())
+            .collect::<Vec<_>>()
+    };
+    [
+        default("partition-specs", "spec-id", "default-spec-id"),
+        default("sort-orders", "order-id", "default-sort-order-id"),
+    ]
+    .concat()
+}
+
+/// Assign new ids to the nested fields of a type, continuing after `last_id`.
+fn assign_ids(field_type: &mut Value, last_id: &mut i64) {
+    match field_type["type"].as_str() {
+        Some("struct") => {
+            for field in field_type["fields"].as_array_mut().into_iter().flatten() {
+                *last_id += 1;
+                field["id"] = json!(*last_id);
+                assign_ids(&mut field["type"], last_id);
+            }
+        }
+        Some("list") => {
+            *last_id += 1;
+            field_type["element-id"] = json!(*last_id);
+            assign_ids(&mut field_type["element"], last_id);
+        }
+        Some("map") => {
+            *last_id += 1;
+            field_type["key-id"] = json!(*last_id);
+            *last_id += 1;
+            field_type["value-id"] = json!(*last_id);
+            assign_ids(&mut field_type["key"], last_id);
+            assign_ids(&mut field_type["value"], last_id);
+        }
+        _ => (),
+    }
+}
+
+/// Whether values of type `from` can be read as type `to`, as allowed by the Iceberg
+/// specification
+fn can_promote(from: &Value, to: &Value) -> bool {
+    match (from.as_str(), to.as_str()) {
+        (Some("int"), Some("long")) | (Some("float"), Some("double")) => true,
+        (Some(from), Some(to)) => match (decimal(from), decimal(to)) {
+            (Some((precision, scale)), Some((new_precision, new_scale))) => {
+                scale == new_scale && new_precision >= precision
+            }
+            _ => false,
+        },
+        _ => false,
+    }
+}
+
+/// Precision and scale of a `decimal(P, S)` type
+fn decimal(field_type: &str) -> Option<(u32, u32)> {
+    let (precision, scale) = field_type
+        .strip_prefix("decimal(")?
+        .strip_suffix(')')?
+        .split_once(',')?;
+    Some((precision.trim().parse().ok()?, scale.trim().parse().ok()?))
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::{assign_ids, can_promote, source_ids};
+
+    #[test]
+    fn test_can_promote() {
+        assert!(can_promote(&json!("int"), &json!("long")));
+        assert!(can_promote(&json!("float"), &json!("double")));
+        assert!(can_promote(
+            &json!("decimal(9, 2)"),
+            &json!("decimal(18,2)")
+        ));
+        assert!(!can_promote(
+            &json!("decimal(9,2)"),
+            &json!("decimal(18,3)")
+        ));
+        assert!(!can_promote(&json!("long"), &json!("int")));
+        assert!(!can_promote(&json!("string"), &json!("binary")));
+    }
+
+    #[test]
+    fn test_assign_ids() {
+        let mut field_type = json!({
+            "type": "struct",
+            "fields": [
+                {"id": 1, "name": "tags", "required": false, "type": {
+                    "type": "list", "element-id": 2, "element": "string", "element-required": false
+                }},
+                {"id": 3, "name": "attributes", "required": false, "type": {
+                    "type": "map", "key-id": 4, "key": "string", "value-id": 5, "value": "long",
+                    "value-required": false
+                }}
+            ]
+        });
+        let mut last_id = 10;
+        assign_ids(&mut field_type, &mut last_id);
+        assert_eq!(last_id, 15);
+        assert_eq!(field_type["fields"][0]["id"], 11);
+        assert_eq!(field_type["fields"][0]["type"]["element-id"], 12);
+        assert_eq!(field_type["fields"][1]["type"]["value-id"], 15);
+    }
+
+    #[test]
+    fn test_source_ids() {
+        let metadata = json!({
+            "default-spec-id": 1,
+            "partition-specs": [
+                {"spec-id": 0, "fields": [{"source-id": 1}]},
+                {"spec-id": 1, "fields": [{"source-id": 2}]}
+            ],
+            "default-sort-order-id": 0,
+            "sort-orders": [{"order-id": 0, "fields": [{"source-id": 3}]}]
+        });
+        assert_eq!(source_ids(&metadata), vec![json!(2), json!(3)]);
+    }
+}
@@ -0,0 +1,120 @@
+/*!
+Catalog in a SQLite database for local development and tests without a Postgres server.
+
+An [EmbeddedCatalog] is a [SqlCatalog] on a SQLite database in a file or in memory, so it behaves
+like any other catalog of this crate for creating, loading, committing and dropping tables.
+*/
+
+use std::{path::Path, sync::Arc};
+
+use anyhow::{anyhow, Result};
+use iceberg_rs::{
+    catalog::Catalog,
+    object_store::{local::LocalFileSystem, memory::InMemory},
+};
+
+use super::{sqlite::SqliteBackend, SqlCatalog};
+
+/// Catalog that stores its tables in a SQLite database
+pub type EmbeddedCatalog = SqlCatalog;
+
+impl SqlCatalog {
+    /// Open the catalog in the SQLite database file `database` and store the table files in
+    /// `directory` on the local filesystem. The database and the directory are created if they
+    /// don't exist, and the catalog is initialized.
+    pub async fn open_embedded(
+        name: &str,
+        database: impl AsRef<Path>,
+        directory: impl AsRef<Path>,
+    ) -> Result<Arc<Self>> {
+        std::fs::create_dir_all(directory.as_ref()).map_err(|err| anyhow!(err.to_string()))?;
+        let directory = directory
+            .as_ref()
+            .canonicalize()
+            .map_err(|err| anyhow!(err.to_string()))?;
+        let backend =
+            SqliteBackend::new(&("sqlite://".to_string() + &database.as_ref().to_string_lossy()))
+                .await?;
+        let catalog = Arc::new(
+            SqlCatalog::new(name, Arc::new(backend), Arc::new(LocalFileSystem::new()))
+                .with_warehouse_path(directory.to_string_lossy().trim_start_matches('/')),
+        );
+        Arc::clone(&catalog).initialize(&Default::default()).await?;
+        Ok(catalog)
+    }
+
+    /// Initialized catalog in an in-memory SQLite database that stores the table files in an
+    /// in-memory object store. Everything is discarded when the catalog is dropped.
+    pub async fn in_memory(name: &str) -> Result<Arc<Self>> {
+        let catalog = Arc::new(SqlCatalog::new(
+            name,
+            Arc::new(SqliteBackend::in_memory().await?),
+            Arc::new(InMemory::new()),
+        ));
+        Arc::clone(&catalog).initialize(&Default::default()).await?;
+        Ok(catalog)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use iceberg_rs::catalog::table_identifier::TableIdentifier;
+    use iceberg_rs::catalog::Catalog;
+    use iceberg_rs::model::schema::{AllType, PrimitiveType, SchemaStruct, SchemaV2, StructField};
+
+    use super::EmbeddedCatalog;
+
+    fn schema() -> SchemaV2 {
+        SchemaV2 {
+            schema_id: 1,
+            identifier_field_ids: None,
+            name_mapping: None,
+            struct_fields: SchemaStruct {
+                fields: vec![StructField {
+                    id: 1,
+                    name: "one".to_string(),
+                    required: false,
+                    field_type: AllType::Primitive(PrimitiveType::String),
+                    doc: None,
+                }],
+            },
+        }
+    }
+
+    #[tokio::test]
+    async fn test_in_memory() {
+        let catalog = EmbeddedCatalog::in_memory("embedded").await.unwrap();
+        let identifier = TableIdentifier::parse("embedded.table1").unwrap();
+        Arc::clone(&catalog)
+            .create_table(identifier.clone(), schema())
+            .await
+            .unwrap();
+        assert!(catalog.table_exists(&identifier).await.unwrap());
+        Arc::clone(&catalog).load_table(identifier).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_database_file() {
+        let directory = std::env::temp_dir().join("iceberg_catalog_postgres_embedded");
+        let _ = std::fs::remove_dir_all(&directory);
+        std::fs::create_dir_all(&directory).unwrap();
+        let database = directory.join("catalog.db");
+        let identifier = TableIdentifier::parse("embedded.persisted").unwrap();
+        {
+            let catalog = EmbeddedCatalog::open_embedded("embedded", &database, &directory)
+                .await
+                .unwrap();
+            Arc::clone(&catalog)
+                .create_table(identifier.clone(), schema())
+                .await
+                .unwrap();
+        }
+
+        let catalog = EmbeddedCatalog::open_embedded("embedded", &database, &directory)
+            .await
+            .unwrap();
+        Arc::clone(&catalog).load_table(identifier).await.unwrap();
+    }
+}
@@ -0,0 +1,180 @@
+/*!
+Backend of the [SqlCatalog](super::SqlCatalog) that keeps the tables in memory.
+
+A [MemoryCatalog] behaves like the catalogs on a database, commits are compare-and-swap operations
+on the metadata location and fail with the same errors, but it needs neither a database server nor
+a database driver. It is intended for unit tests of applications that use a database catalog in
+production.
+*/
+
+use std::{collections::BTreeMap, sync::Mutex};
+
+use anyhow::{anyhow, Result};
+use iceberg_rs::object_store::memory::InMemory;
+
+use super::{SqlCatalog, SqlCatalogBackend};
+
+/// Key of a table: catalog name, stored namespace and table name
+type TableKey = (String, String, String);
+
+/// Keeps the metadata locations of the tables in a map
+#[derive(Debug, Default)]
+pub struct MemoryBackend {
+    tables: Mutex<BTreeMap<TableKey, String>>,
+}
+
+impl MemoryBackend {
+    /// Backend without tables
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn tables(&self) -> Result<std::sync::MutexGuard<'_, BTreeMap<TableKey, String>>> {
+        self.tables
+            .lock()
+            .map_err(|_| anyhow!("The tables of the memory backend are poisoned."))
+    }
+}
+
+fn key(catalog: &str, namespace: &str, name: &str) -> TableKey {
+    (catalog.to_string(), namespace.to_string(), name.to_string())
+}
+
+#[async_trait::async_trait]
+impl SqlCatalogBackend for MemoryBackend {
+    async fn create_tables(&self) -> Result<()> {
+        Ok(())
+    }
+
+    async fn list_tables(&self, catalog: &str, namespace: &str) -> Result<Vec<String>> {
+        Ok(self
+            .tables()?
+            .keys()
+            .filter(|(table_catalog, table_namespace, _)| {
+                table_catalog == catalog && table_namespace == namespace
+            })
+            .map(|(_, _, name)| name.clone())
+            .collect())
+    }
+
+    async fn metadata_location(
+        &self,
+        catalog: &str,
+        namespace: &str,
+        name: &str,
+    ) -> Result<Option<String>> {
+        Ok(self.tables()?.get(&key(catalog, namespace, name)).cloned())
+    }
+
+    async fn insert_table(
+        &self,
+        catalog: &str,
+        namespace: &str,
+        name: &str,
+        metadata_location: &str,
+    ) -> Result<bool> {
+        let mut tables = self.tables()?;
+        let key = key(catalog, namespace, name);
+        if tables.contains_key(&key) {
+            Ok(false)
+        } else {
+            tables.insert(key, metadata_location.to_string());
+            Ok(true)
+        }
+    }
+
+    async fn update_table(
+        &self,
+        catalog: &str,
+        namespace: &str,
+        name: &str,
+        metadata_location: &str,
+        previous: &str,
+    ) -> Result<bool> {
+        match self.tables()?.get_mut(&key(catalog, namespace, name)) {
+            Some(current) if current == previous => {
+                *current = metadata_location.to_string();
+                Ok(true)
+            }
+            _ => Ok(false),
+        }
+    }
+
+    async fn delete_table(&self, catalog: &str, namespace: &str, name: &str) -> Result<bool> {
+        Ok(self
+            .tables()?
+            .remove(&key(catalog, namespace, name))
+            .is_some())
+    }
+}
+
+/// Catalog that keeps its tables and their files in memory
+pub type MemoryCatalog = SqlCatalog;
+
+impl SqlCatalog {
+    /// Catalog on a [MemoryBackend] that stores the table files in an in-memory object store.
+    /// It doesn't have to be initialized.
+    pub fn memory(name: &str) -> Self {
+        SqlCatalog::new(
+            name,
+            std::sync::Arc::new(MemoryBackend::new()),
+            std::sync::Arc::new(InMemory::new()),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use iceberg_rs::catalog::table_identifier::TableIdentifier;
+    use iceberg_rs::catalog::Catalog;
+    use iceberg_rs::model::schema::{AllType, PrimitiveType, SchemaStruct, SchemaV2, StructField};
+
+    use super::MemoryCatalog;
+
+    #[tokio::test]
+    async fn test_memory_catalog() {
+        let catalog = Arc::new(MemoryCatalog::memory("memory"));
+        let schema = SchemaV2 {
+            schema_id: 1,
+            identifier_field_ids: None,
+            name_mapping: None,
+            struct_fields: SchemaStruct {
+                fields: vec![StructField {
+                    id: 1,
+                    name: "one".to_string(),
+                    required: false,
+                    field_type: AllType::Primitive(PrimitiveType::String),
+                    doc: None,
+                }],
+            },
+        };
+        let identifier = TableIdentifier::parse("memory.table1").unwrap();
+        let table = Arc::clone(&catalog)
+            .create_table(identifier.clone(), schema)
+            .await
+            .unwrap();
+        let metadata_location = table.metadata_location().to_string();
+        assert!(Arc::clone(&catalog)
+            .register_table(identifier.clone(), &metadata_location)
+            .await
+            .is_err());
+        assert!(Arc::clone(&catalog)
+            .update_table(
+                identifier.clone(),
+                &metadata_location,
+                "other.metadata.json"
+            )
+            .await
+            .is_err());
+        Arc::clone(&catalog)
+            .update_table(identifier.clone(), &metadata_location, &metadata_location)
+            .await
+            .unwrap();
+
+        catalog.drop_table(&identifier).await.unwrap();
+        assert!(catalog.drop_table(&identifier).await.is_err());
+        assert!(Arc::clone(&catalog).load_table(identifier).await.is_err());
+    }
+}
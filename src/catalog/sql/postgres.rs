@@ -0,0 +1,215 @@
+/*!
+Backend of the [SqlCatalog](super::SqlCatalog) for Postgres.
+*/
+
+use anyhow::{anyhow, Result};
+use tokio_postgres::Config;
+
+use super::{SqlCatalogBackend, SQL_CATALOG_TABLE};
+use crate::catalog::{
+    connection::PostgresConnection,
+    query::{identifier, literal},
+    secret::redact_url,
+    CATALOG_NAME_COLUMN, METADATA_LOCATION_COLUMN, PREVIOUS_METADATA_LOCATION_COLUMN,
+    TABLE_NAMESPACE_COLUMN, TABLE_NAME_COLUMN,
+};
+
+/// Runs the statements of the catalog on a Postgres server. The connection is established on first
+/// use and re-established when it was closed.
+pub struct PostgresBackend {
+    connection: PostgresConnection,
+    table: String,
+}
+
+impl PostgresBackend {
+    /// Connect to the database at `url` without TLS.
+    pub fn new(url: &str) -> Result<Self> {
+        let config: Config = url.parse().map_err(|err: tokio_postgres::Error| {
+            anyhow!("Invalid connection url {}: {}", redact_url(url), err)
+        })?;
+        Ok(PostgresBackend {
+            connection: PostgresConnection::new(config, None, None),
+            table: identifier(SQL_CATALOG_TABLE),
+        })
+    }
+
+    fn table_condition(catalog: &str, namespace: &str, name: &str) -> String {
+        CATALOG_NAME_COLUMN.to_string()
+            + " = "
+            + &literal(catalog)
+            + " AND "
+            + TABLE_NAMESPACE_COLUMN
+            + " = "
+            + &literal(namespace)
+            + " AND "
+            + TABLE_NAME_COLUMN
+            + " = "
+            + &literal(name)
+    }
+}
+
+#[async_trait::async_trait]
+impl SqlCatalogBackend for PostgresBackend {
+    async fn create_tables(&self) -> Result<()> {
+        self.connection
+            .execute(
+                &("CREATE TABLE IF NOT EXISTS ".to_string()
+                    + &self.table
+                    + " ("
+                    + CATALOG_NAME_COLUMN
+                    + " VARCHAR(255) NOT NULL,"
+                    + TABLE_NAMESPACE_COLUMN
+                    + " VARCHAR(255) NOT NULL,"
+                    + TABLE_NAME_COLUMN
+                    + " VARCHAR(255) NOT NULL,"
+                    + METADATA_LOCATION_COLUMN
+                    + " TEXT,"
+                    + PREVIOUS_METADATA_LOCATION_COLUMN
+                    + " TEXT,"
+                    + "PRIMARY KEY ("
+                    + CATALOG_NAME_COLUMN
+                    + ", "
+                    + TABLE_NAMESPACE_COLUMN
+                    + ", "
+                    + TABLE_NAME_COLUMN
+                    + "));"),
+                false,
+            )
+            .await?;
+        Ok(())
+    }
+
+    async fn list_tables(&self, catalog: &str, namespace: &str) -> Result<Vec<String>> {
+        self.connection
+            .query(
+                &("SELECT ".to_string()
+                    + TABLE_NAME_COLUMN
+                    + " FROM "
+                    + &self.table
+                    + " WHERE "
+                    + CATALOG_NAME_COLUMN
+                    + " = "
+                    + &literal(catalog)
+                    + " AND "
+                    + TABLE_NAMESPACE_COLUMN
+                    + " = "
+                    + &literal(namespace)
+                    + ";"),
+                false,
+            )
+            .await?
+            .iter()
+            .map(|row| row.try_get_string(TABLE_NAME_COLUMN))
+            .collect()
+    }
+
+    async fn metadata_location(
+        &self,
+        catalog: &str,
+        namespace: &str,
+        name: &str,
+    ) -> Result<Option<String>> {
+        match self
+            .connection
+            .query(
+                &("SELECT ".to_string()
+                    + METADATA_LOCATION_COLUMN
+                    + " FROM "
+                    + &self.table
+                    + " WHERE "
+                    + &Self::table_condition(catalog, namespace, name)
+                    + ";"),
+                false,
+            )
+            .await?
+            .first()
+        {
+            Some(row) => Ok(Some(row.try_get_string(METADATA_LOCATION_COLUMN)?)),
+            None => Ok(None),
+        }
+    }
+
+    async fn insert_table(
+        &self,
+        catalog: &str,
+        namespace: &str,
+        name: &str,
+        metadata_location: &str,
+    ) -> Result<bool> {
+        let n_rows = self
+            .connection
+            .execute(
+                &("INSERT INTO ".to_string()
+                    + &self.table
+                    + " ("
+                    + CATALOG_NAME_COLUMN
+                    + ", "
+                    + TABLE_NAMESPACE_COLUMN
+                    + ", "
+                    + TABLE_NAME_COLUMN
+                    + ", "
+                    + METADATA_LOCATION_COLUMN
+                    + ") VALUES ("
+                    + &literal(catalog)
+                    + ", "
+                    + &literal(namespace)
+                    + ", "
+                    + &literal(name)
+                    + ", "
+                    + &literal(metadata_location)
+                    + ") ON CONFLICT DO NOTHING;"),
+                false,
+            )
+            .await?;
+        Ok(n_rows == 1)
+    }
+
+    async fn update_table(
+        &self,
+        catalog: &str,
+        namespace: &str,
+        name: &str,
+        metadata_location: &str,
+        previous: &str,
+    ) -> Result<bool> {
+        let n_rows = self
+            .connection
+            .execute(
+                &("UPDATE ".to_string()
+                    + &self.table
+                    + " SET "
+                    + METADATA_LOCATION_COLUMN
+                    + " = "
+                    + &literal(metadata_location)
+                    + ", "
+                    + PREVIOUS_METADATA_LOCATION_COLUMN
+                    + " = "
+                    + &literal(previous)
+                    + " WHERE "
+                    + &Self::table_condition(catalog, namespace, name)
+                    + " AND "
+                    + METADATA_LOCATION_COLUMN
+                    + " = "
+                    + &literal(previous)
+                    + ";"),
+                false,
+            )
+            .await?;
+        Ok(n_rows == 1)
+    }
+
+    async fn delete_table(&self, catalog: &str, namespace: &str, name: &str) -> Result<bool> {
+        let n_rows = self
+            .connection
+            .execute(
+                &("DELETE FROM ".to_string()
+                    + &self.table
+                    + " WHERE "
+                    + &Self::table_condition(catalog, namespace, name)
+                    + ";"),
+                false,
+            )
+            .await?;
+        Ok(n_rows == 1)
+    }
+}
@@ -0,0 +1,231 @@
+/*!
+Backend of the [SqlCatalog](super::SqlCatalog) for SQLite.
+*/
+
+use std::str::FromStr;
+
+use anyhow::{anyhow, Result};
+use sqlx::{
+    sqlite::{SqliteConnectOptions, SqlitePoolOptions},
+    Row, SqlitePool,
+};
+
+use super::{SqlCatalogBackend, SQL_CATALOG_TABLE};
+use crate::catalog::{
+    CATALOG_NAME_COLUMN, METADATA_LOCATION_COLUMN, PREVIOUS_METADATA_LOCATION_COLUMN,
+    TABLE_NAMESPACE_COLUMN, TABLE_NAME_COLUMN,
+};
+
+/// Runs the statements of the catalog on a SQLite database
+pub struct SqliteBackend {
+    pool: SqlitePool,
+}
+
+impl SqliteBackend {
+    /// Open the database at `url`, like `sqlite://catalog.db`. The database file is created if it
+    /// doesn't exist.
+    pub async fn new(url: &str) -> Result<Self> {
+        let options = SqliteConnectOptions::from_str(url)
+            .map_err(|err| anyhow!(err.to_string()))?
+            .create_if_missing(true);
+        Ok(SqliteBackend {
+            pool: SqlitePool::connect_with(options)
+                .await
+                .map_err(|err| anyhow!(err))?,
+        })
+    }
+
+    /// Database that only exists in memory and is discarded when the backend is dropped
+    pub async fn in_memory() -> Result<Self> {
+        // Every connection to `:memory:` opens a separate database, so the pool keeps a single
+        // connection that is never closed.
+        let pool = SqlitePoolOptions::new()
+            .max_connections(1)
+            .min_connections(1)
+            .idle_timeout(None)
+            .max_lifetime(None)
+            .connect("sqlite::memory:")
+            .await
+            .map_err(|err| anyhow!(err))?;
+        Ok(SqliteBackend { pool })
+    }
+
+    /// Use the connections of the pool
+    pub fn from_pool(pool: SqlitePool) -> Self {
+        SqliteBackend { pool }
+    }
+}
+
+fn table_condition() -> String {
+    CATALOG_NAME_COLUMN.to_string()
+        + " = ? AND "
+        + TABLE_NAMESPACE_COLUMN
+        + " = ? AND "
+        + TABLE_NAME_COLUMN
+        + " = ?"
+}
+
+#[async_trait::async_trait]
+impl SqlCatalogBackend for SqliteBackend {
+    async fn create_tables(&self) -> Result<()> {
+        sqlx::query(
+            &("CREATE TABLE IF NOT EXISTS ".to_string()
+                + SQL_CATALOG_TABLE
+                + " ("
+                + CATALOG_NAME_COLUMN
+                + " VARCHAR(255) NOT NULL,"
+                + TABLE_NAMESPACE_COLUMN
+                + " VARCHAR(255) NOT NULL,"
+                + TABLE_NAME_COLUMN
+                + " VARCHAR(255) NOT NULL,"
+                + METADATA_LOCATION_COLUMN
+                + " TEXT,"
+                + PREVIOUS_METADATA_LOCATION_COLUMN
+                + " TEXT,"
+                + "PRIMARY KEY ("
+                + CATALOG_NAME_COLUMN
+                + ", "
+                + TABLE_NAMESPACE_COLUMN
+                + ", "
+                + TABLE_NAME_COLUMN
+                + "));"),
+        )
+        .execute(&self.pool)
+        .await
+        .map_err(|err| anyhow!(err))?;
+        Ok(())
+    }
+
+    async fn list_tables(&self, catalog: &str, namespace: &str) -> Result<Vec<String>> {
+        sqlx::query(
+            &("SELECT ".to_string()
+                + TABLE_NAME_COLUMN
+                + " FROM "
+                + SQL_CATALOG_TABLE
+                + " WHERE "
+                + CATALOG_NAME_COLUMN
+                + " = ? AND "
+                + TABLE_NAMESPACE_COLUMN
+                + " = ?;"),
+        )
+        .bind(catalog)
+        .bind(namespace)
+        .fetch_all(&self.pool)
+        .await
+        .map_err(|err| anyhow!(err))?
+        .iter()
+        .map(|row| {
+            row.try_get(TABLE_NAME_COLUMN)
+                .map_err(|err| anyhow!(err.to_string()))
+        })
+        .collect()
+    }
+
+    async fn metadata_location(
+        &self,
+        catalog: &str,
+        namespace: &str,
+        name: &str,
+    ) -> Result<Option<String>> {
+        sqlx::query(
+            &("SELECT ".to_string()
+                + METADATA_LOCATION_COLUMN
+                + " FROM "
+                + SQL_CATALOG_TABLE
+                + " WHERE "
+                + &table_condition()
+                + ";"),
+        )
+        .bind(catalog)
+        .bind(namespace)
+        .bind(name)
+        .fetch_optional(&self.pool)
+        .await
+        .map_err(|err| anyhow!(err))?
+        .map(|row| {
+            row.try_get(METADATA_LOCATION_COLUMN)
+                .map_err(|err| anyhow!(err.to_string()))
+        })
+        .transpose()
+    }
+
+    async fn insert_table(
+        &self,
+        catalog: &str,
+        namespace: &str,
+        name: &str,
+        metadata_location: &str,
+    ) -> Result<bool> {
+        let result = sqlx::query(
+            &("INSERT INTO ".to_string()
+                + SQL_CATALOG_TABLE
+                + " ("
+                + CATALOG_NAME_COLUMN
+                + ", "
+                + TABLE_NAMESPACE_COLUMN
+                + ", "
+                + TABLE_NAME_COLUMN
+                + ", "
+                + METADATA_LOCATION_COLUMN
+                + ") VALUES (?, ?, ?, ?) ON CONFLICT DO NOTHING;"),
+        )
+        .bind(catalog)
+        .bind(namespace)
+        .bind(name)
+        .bind(metadata_location)
+        .execute(&self.pool)
+        .await
+        .map_err(|err| anyhow!(err))?;
+        Ok(result.rows_affected() == 1)
+    }
+
+    async fn update_table(
+        &self,
+        catalog: &str,
+        namespace: &str,
+        name: &str,
+        metadata_location: &str,
+        previous: &str,
+    ) -> Result<bool> {
+        let result = sqlx::query(
+            &("UPDATE ".to_string()
+                + SQL_CATALOG_TABLE
+                + " SET "
+                + METADATA_LOCATION_COLUMN
+                + " = ?, "
+                + PREVIOUS_METADATA_LOCATION_COLUMN
+                + " = ? WHERE "
+                + &table_condition()
+                + " AND "
+                + METADATA_LOCATION_COLUMN
+                + " = ?;"),
+        )
+        .bind(metadata_location)
+        .bind(previous)
+        .bind(catalog)
+        .bind(namespace)
+        .bind(name)
+        .bind(previous)
+        .execute(&self.pool)
+        .await
+        .map_err(|err| anyhow!(err))?;
+        Ok(result.rows_affected() == 1)
+    }
+
+    async fn delete_table(&self, catalog: &str, namespace: &str, name: &str) -> Result<bool> {
+        let result = sqlx::query(
+            &("DELETE FROM ".to_string()
+                + SQL_CATALOG_TABLE
+                + " WHERE "
+                + &table_condition()
+                + ";"),
+        )
+        .bind(catalog)
+        .bind(namespace)
+        .bind(name)
+        .execute(&self.pool)
+        .await
+        .map_err(|err| anyhow!(err))?;
+        Ok(result.rows_affected() == 1)
+    }
+}
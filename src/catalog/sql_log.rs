@@ -0,0 +1,183 @@
+/*!
+Debug logging of the statements of the catalog.
+
+With [with_sql_logging](super::builder::PostgresCatalogBuilder::with_sql_logging), every statement
+the catalog runs is logged with the [log] crate at debug level and target
+`iceberg_catalog_postgres::sql`, with the catalog, the duration, the number of rows and the error
+if it failed. Statements that are retried are logged once per attempt.
+
+Prepared statements are logged with their placeholders followed by the parameters. With
+[SqlLogging::Redacted], string literals and parameters are replaced by a placeholder, because
+they can contain credentials, for example in the catalog properties. [SqlLogging::Full] logs them
+as they are.
+*/
+
+use std::{future::Future, time::Instant};
+
+use anyhow::Result;
+
+use super::{
+    query::{CatalogRow, StatementResult},
+    statements::Param,
+    PostgresCatalog,
+};
+
+/// Target of the log records
+static LOG_TARGET: &str = "iceberg_catalog_postgres::sql";
+/// Placeholder of redacted values
+static REDACTED: &str = "[REDACTED]";
+
+/// Logging of the statements of the catalog
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SqlLogging {
+    /// Statements aren't logged
+    Disabled,
+    /// Statements are logged without the values of string literals and parameters
+    Redacted,
+    /// Statements are logged with all values
+    Full,
+}
+
+impl Default for SqlLogging {
+    fn default() -> Self {
+        SqlLogging::Disabled
+    }
+}
+
+/// Result of a statement with a number of rows
+pub(crate) trait RowCount {
+    /// Rows returned or affected by the statement
+    fn row_count(&self) -> u64;
+}
+
+impl RowCount for u64 {
+    fn row_count(&self) -> u64 {
+        *self
+    }
+}
+
+impl RowCount for Vec<CatalogRow> {
+    fn row_count(&self) -> u64 {
+        self.len() as u64
+    }
+}
+
+impl RowCount for Vec<StatementResult> {
+    fn row_count(&self) -> u64 {
+        self.iter()
+            .map(|result| result.n_rows.max(result.rows.len() as u64))
+            .sum()
+    }
+}
+
+impl PostgresCatalog {
+    /// Run the statement and log it with its duration and outcome.
+    pub(crate) async fn logged<T: RowCount>(
+        &self,
+        sql: &str,
+        params: &[Param<'_>],
+        statement: impl Future<Output = Result<T>>,
+    ) -> Result<T> {
+        if self.sql_logging == SqlLogging::Disabled
+            || !log::log_enabled!(target: LOG_TARGET, log::Level::Debug)
+        {
+            return statement.await;
+        }
+        let started = Instant::now();
+        let result = statement.await;
+        let duration = started.elapsed().as_secs_f64() * 1000.0;
+        let sql = format_statement(sql, params, self.sql_logging == SqlLogging::Redacted);
+        match &result {
+            Ok(value) => log::debug!(
+                target: LOG_TARGET,
+                "catalog={} duration_ms={:.3} rows={} sql={}",
+                self.name,
+                duration,
+                value.row_count(),
+                sql
+            ),
+            Err(err) => log::debug!(
+                target: LOG_TARGET,
+                "catalog={} duration_ms={:.3} error={:?} sql={}",
+                self.name,
+                duration,
+                err.to_string(),
+                sql
+            ),
+        }
+        result
+    }
+}
+
+/// The statement with its parameters, optionally with redacted values
+fn format_statement(sql: &str, params: &[Param<'_>], redact: bool) -> String {
+    let mut statement = if redact {
+        redact_literals(sql)
+    } else {
+        sql.to_string()
+    };
+    if !params.is_empty() {
+        let params = params
+            .iter()
+            .map(|param| {
+                if redact {
+                    REDACTED.to_string()
+                } else {
+                    param.literal()
+                }
+            })
+            .collect::<Vec<_>>();
+        statement = statement + " params=[" + &params.join(", ") + "]";
+    }
+    statement
+}
+
+/// Replace the content of the string literals of the statement
+fn redact_literals(sql: &str) -> String {
+    let mut redacted = String::with_capacity(sql.len());
+    let mut in_literal = false;
+    let mut chars = sql.chars().peekable();
+    while let Some(c) = chars.next() {
+        match (in_literal, c) {
+            (false, '\'') => {
+                in_literal = true;
+                redacted.push('\'');
+                redacted.push_str(REDACTED);
+            }
+            // A doubled quote is an escaped quote within the literal.
+            (true, '\'') if chars.peek() == Some(&'\'') => {
+                chars.next();
+            }
+            (true, '\'') => {
+                in_literal = false;
+                redacted.push('\'');
+            }
+            (true, _) => (),
+            (false, c) => redacted.push(c),
+        }
+    }
+    redacted
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{format_statement, redact_literals};
+    use crate::catalog::statements::Param;
+
+    #[test]
+    fn test_redact() {
+        assert_eq!(
+            redact_literals("SELECT * FROM t WHERE a = 'it''s' AND b = 1 AND \"c\" = 'x';"),
+            "SELECT * FROM t WHERE a = '[REDACTED]' AND b = 1 AND \"c\" = '[REDACTED]';"
+        );
+        let params = [Param::Text("secret"), Param::BigInt(Some(3))];
+        assert_eq!(
+            format_statement("SELECT $1, $2", &params, true),
+            "SELECT $1, $2 params=[[REDACTED], [REDACTED]]"
+        );
+        assert_eq!(
+            format_statement("SELECT $1, $2", &params, false),
+            "SELECT $1, $2 params=['secret', 3]"
+        );
+    }
+}
@@ -0,0 +1,283 @@
+/*!
+Prepared statements for the operations that run most often.
+
+Checking, loading, registering and committing tables run the same statements with different
+values. They are written once per catalog with `$n` parameters, prepared once per connection and
+reused, which saves parsing and planning them on every call. Behind a transaction pooler the
+parameters are inlined as literals and the statements are sent over the simple query protocol,
+because prepared statements don't outlive the transaction there.
+*/
+
+use std::{
+    collections::HashMap,
+    error::Error,
+    sync::{Arc, Mutex, Weak},
+};
+
+use bytes::BytesMut;
+use tokio_postgres::{
+    types::{to_sql_checked, IsNull, ToSql, Type},
+    Client, Statement,
+};
+
+use super::{
+    query::literal, table::CatalogTable, ARCHIVED_AT_COLUMN, CATALOG_NAME_COLUMN,
+    CURRENT_SNAPSHOT_ID_COLUMN, LAST_SEQUENCE_NUMBER_COLUMN, METADATA_CHECKSUM_COLUMN,
+    METADATA_LOCATION_COLUMN, PREVIOUS_METADATA_LOCATION_COLUMN, SNAPSHOT_SUMMARY_COLUMN,
+    TABLE_LOCATION_COLUMN, TABLE_NAMESPACE_COLUMN, TABLE_NAME_COLUMN, TABLE_SIZE_COLUMN,
+    TABLE_UUID_COLUMN, UPDATED_AT_COLUMN, VERSION_COLUMN,
+};
+
+/// Value of a statement parameter
+#[derive(Debug, Clone, Copy)]
+pub(crate) enum Param<'a> {
+    /// Text value
+    Text(&'a str),
+    /// Nullable text value
+    OptText(Option<&'a str>),
+    /// Nullable bigint value
+    BigInt(Option<i64>),
+}
+
+impl Param<'_> {
+    /// The value as SQL literal, for statements with inlined parameters
+    pub(crate) fn literal(&self) -> String {
+        match self {
+            Param::Text(value) | Param::OptText(Some(value)) => literal(value),
+            Param::BigInt(Some(value)) => value.to_string(),
+            Param::OptText(None) | Param::BigInt(None) => "NULL".to_string(),
+        }
+    }
+}
+
+impl ToSql for Param<'_> {
+    fn to_sql(
+        &self,
+        ty: &Type,
+        out: &mut BytesMut,
+    ) -> Result<IsNull, Box<dyn Error + Sync + Send>> {
+        match self {
+            Param::Text(value) => value.to_sql(ty, out),
+            Param::OptText(value) => value.to_sql(ty, out),
+            Param::BigInt(value) => value.to_sql(ty, out),
+        }
+    }
+
+    fn accepts(ty: &Type) -> bool {
+        <&str as ToSql>::accepts(ty) || <i64 as ToSql>::accepts(ty)
+    }
+
+    to_sql_checked!();
+}
+
+/// Replace the `$n` parameters of the statement by the literals of their values.
+pub(crate) fn inline(sql: &str, params: &[Param<'_>]) -> String {
+    let mut inlined = String::with_capacity(sql.len());
+    let mut rest = sql;
+    while let Some(start) = rest.find('$') {
+        inlined.push_str(&rest[..start]);
+        rest = &rest[start + 1..];
+        let end = rest
+            .find(|c: char| !c.is_ascii_digit())
+            .unwrap_or(rest.len());
+        match rest[..end].parse::<usize>() {
+            Ok(index) if index >= 1 && index <= params.len() => {
+                inlined.push_str(&params[index - 1].literal())
+            }
+            _ => {
+                inlined.push('$');
+                inlined.push_str(&rest[..end]);
+            }
+        }
+        rest = &rest[end..];
+    }
+    inlined.push_str(rest);
+    inlined
+}
+
+/// Statements of the hot operations for the catalog table
+#[derive(Debug, Clone)]
+pub(crate) struct Statements {
+    /// Whether a table exists. Parameters: catalog, namespace, name
+    pub(crate) exists: String,
+    /// Metadata location and checksum of a table. Parameters: catalog, namespace, name
+    pub(crate) load: String,
+    /// Register a table. Parameters: catalog, namespace, name, metadata location, table
+    /// location, uuid, size, checksum, snapshot summary, current snapshot id, last sequence number
+    pub(crate) insert: String,
+    /// Swap the metadata pointer if it references the expected metadata file. Parameters:
+    /// catalog, namespace, name, new metadata location, uuid, size, checksum, expected metadata
+    /// location, snapshot summary, current snapshot id, last sequence number, expected metadata
+    /// location relative to the warehouse
+    pub(crate) update_if_location: String,
+    /// Swap the metadata pointer if the table has the expected version. Parameters as for
+    /// `update_if_location`, with the expected version instead of the expected locations
+    pub(crate) update_if_version: String,
+}
+
+impl Statements {
+    pub(crate) fn new(table: &CatalogTable) -> Self {
+        let entry = " WHERE ".to_string()
+            + CATALOG_NAME_COLUMN
+            + " = $1 AND "
+            + TABLE_NAMESPACE_COLUMN
+            + " = $2 AND "
+            + TABLE_NAME_COLUMN
+            + " = $3";
+        let update = "UPDATE ".to_string()
+            + &table.qualified
+            + " SET "
+            + METADATA_LOCATION_COLUMN
+            + " = $4, "
+            + PREVIOUS_METADATA_LOCATION_COLUMN
+            + " = "
+            + METADATA_LOCATION_COLUMN
+            + ", "
+            + VERSION_COLUMN
+            + " = "
+            + VERSION_COLUMN
+            + " + 1, "
+            + UPDATED_AT_COLUMN
+            + " = now(), "
+            + TABLE_UUID_COLUMN
+            + " = COALESCE("
+            + TABLE_UUID_COLUMN
+            + ", $5::TEXT::UUID), "
+            + TABLE_SIZE_COLUMN
+            + " = $6, "
+            + METADATA_CHECKSUM_COLUMN
+            + " = $7, "
+            + SNAPSHOT_SUMMARY_COLUMN
+            + " = $9::TEXT::JSONB, "
+            + CURRENT_SNAPSHOT_ID_COLUMN
+            + " = $10, "
+            + LAST_SEQUENCE_NUMBER_COLUMN
+            + " = $11"
+            + &entry
+            // The new metadata has to belong to the same table. Entries without uuid were created
+            // by earlier versions and adopt the uuid of the new metadata.
+            + " AND ($5::TEXT IS NULL OR "
+            + TABLE_UUID_COLUMN
+            + " IS NULL OR "
+            + TABLE_UUID_COLUMN
+            + " = $5::TEXT::UUID) AND "
+            + ARCHIVED_AT_COLUMN
+            + " IS NULL AND ";
+        let returning = " RETURNING ".to_string() + VERSION_COLUMN + ";";
+        Statements {
+            exists: "SELECT EXISTS (SELECT 1 FROM ".to_string() + &table.qualified + &entry + ");",
+            load: "SELECT ".to_string()
+                + METADATA_LOCATION_COLUMN
+                + ", "
+                + METADATA_CHECKSUM_COLUMN
+                + " FROM "
+                + &table.qualified
+                + &entry
+                + ";",
+            insert: "INSERT INTO ".to_string()
+                + &table.qualified
+                + " ("
+                + CATALOG_NAME_COLUMN
+                + ", "
+                + TABLE_NAMESPACE_COLUMN
+                + ", "
+                + TABLE_NAME_COLUMN
+                + ", "
+                + METADATA_LOCATION_COLUMN
+                + ", "
+                + PREVIOUS_METADATA_LOCATION_COLUMN
+                + ", "
+                + TABLE_LOCATION_COLUMN
+                + ", "
+                + TABLE_UUID_COLUMN
+                + ", "
+                + TABLE_SIZE_COLUMN
+                + ", "
+                + METADATA_CHECKSUM_COLUMN
+                + ", "
+                + SNAPSHOT_SUMMARY_COLUMN
+                + ", "
+                + CURRENT_SNAPSHOT_ID_COLUMN
+                + ", "
+                + LAST_SEQUENCE_NUMBER_COLUMN
+                + ") VALUES ($1, $2, $3, $4, NULL, $5, $6::TEXT::UUID, $7, $8, $9::TEXT::JSONB, $10, $11) ON CONFLICT ("
+                + CATALOG_NAME_COLUMN
+                + ", "
+                + TABLE_NAMESPACE_COLUMN
+                + ", "
+                + TABLE_NAME_COLUMN
+                + ") DO NOTHING;",
+            update_if_location: update.clone()
+                + METADATA_LOCATION_COLUMN
+                + " IN ($8, $12)"
+                + &returning,
+            update_if_version: update + VERSION_COLUMN + " = $8" + &returning,
+        }
+    }
+}
+
+/// Statements prepared on a client, dropped when the connection is re-established
+#[derive(Default)]
+pub(crate) struct StatementCache {
+    cache: Mutex<(Weak<Client>, HashMap<String, Statement>)>,
+}
+
+impl StatementCache {
+    /// Statement prepared on the client, if the statement was prepared on it before
+    pub(crate) fn get(&self, client: &Arc<Client>, sql: &str) -> Option<Statement> {
+        let cache = self.cache.lock().ok()?;
+        if Weak::ptr_eq(&cache.0, &Arc::downgrade(client)) {
+            cache.1.get(sql).cloned()
+        } else {
+            None
+        }
+    }
+
+    /// Remember the statement prepared on the client. Statements of earlier clients are dropped.
+    pub(crate) fn insert(&self, client: &Arc<Client>, sql: &str, statement: Statement) {
+        if let Ok(mut cache) = self.cache.lock() {
+            let client = Arc::downgrade(client);
+            if !Weak::ptr_eq(&cache.0, &client) {
+                *cache = (client, HashMap::new());
+            }
+            cache.1.insert(sql.to_string(), statement);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{inline, Param, Statements};
+    use crate::catalog::table::CatalogTable;
+
+    #[test]
+    fn test_inline() {
+        assert_eq!(
+            inline(
+                "SELECT $1, $2, $3, $10;",
+                &[
+                    Param::Text("it's"),
+                    Param::OptText(None),
+                    Param::BigInt(Some(3))
+                ]
+            ),
+            "SELECT 'it''s', NULL, 3, $10;"
+        );
+        assert_eq!(inline("SELECT $$a$$;", &[]), "SELECT $$a$$;");
+    }
+
+    #[test]
+    fn test_statements() {
+        let statements = Statements::new(&CatalogTable::default());
+        assert_eq!(
+            statements.load,
+            "SELECT metadata_location, metadata_checksum FROM \"iceberg_tables\" WHERE catalog_name = $1 AND table_namespace = $2 AND table_name = $3;"
+        );
+        assert!(statements
+            .update_if_location
+            .ends_with("metadata_location IN ($8, $12) RETURNING version;"));
+        assert!(statements
+            .update_if_version
+            .ends_with("version = $8 RETURNING version;"));
+    }
+}
@@ -0,0 +1,595 @@
+/*!
+Commit statistics of the tables, to find hot tables whose writers conflict with each other.
+
+Every commit through [update_table](iceberg_rs::catalog::Catalog::update_table) or
+[update_table_if_version](PostgresCatalog::update_table_if_version) is counted as an attempt that
+either commits, conflicts with a concurrent commit or fails for another reason. The catalog keeps
+the counters and the latencies of the most recent attempts of each table in memory and returns
+them with [PostgresCatalog::stats]. Only the tables that were committed to most recently are
+tracked, the statistics of the table that went longest without a commit are dropped when a new
+table would exceed the limit.
+
+The in-memory statistics only cover the commits of one catalog instance since it was created. With
+[with_commit_stats_persistence](super::builder::PostgresCatalogBuilder::with_commit_stats_persistence)
+the counters are added to a stats table shared by all instances, once per flush interval and when
+the catalog is closed. Counters that fail to be written are kept for the next flush.
+[PostgresCatalog::stored_stats] reads them back. Percentiles can't be aggregated across instances,
+the stats table keeps the total and maximum latency instead.
+*/
+
+use std::{
+    collections::{HashMap, VecDeque},
+    mem,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Mutex,
+    },
+    time::{Duration, Instant},
+};
+
+use anyhow::Result;
+use iceberg_rs::catalog::{namespace::Namespace, table_identifier::TableIdentifier};
+
+use super::{
+    access::Action,
+    namespace::{namespace_condition, namespace_key, table_identifier},
+    query::{literal, CatalogRow},
+    PostgresCatalog, CATALOG_NAME_COLUMN, TABLE_NAMESPACE_COLUMN, TABLE_NAME_COLUMN,
+    UPDATED_AT_COLUMN,
+};
+
+static ATTEMPTS_COLUMN: &str = "attempts";
+static COMMITS_COLUMN: &str = "commits";
+static CONFLICTS_COLUMN: &str = "conflicts";
+static FAILURES_COLUMN: &str = "failures";
+static TOTAL_LATENCY_COLUMN: &str = "total_latency_us";
+static MAX_LATENCY_COLUMN: &str = "max_latency_us";
+
+/// Number of recent latencies per table that the percentiles are computed from
+static LATENCY_SAMPLES: usize = 1024;
+
+/// Number of tables whose statistics are kept in memory
+static MAX_TRACKED_TABLES: usize = 10_000;
+
+/// Interval in which the counters are added to the stats table
+static STATS_FLUSH_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Result of a commit attempt
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum CommitOutcome {
+    /// The metadata pointer was swapped
+    Committed,
+    /// The table was changed concurrently
+    Conflict,
+    /// The commit failed before or while swapping the pointer
+    Failed,
+}
+
+/// Commit statistics of a table
+#[derive(Debug, Clone)]
+pub struct CommitStats {
+    /// Identifier of the table
+    pub identifier: TableIdentifier,
+    /// Number of commit attempts
+    pub attempts: u64,
+    /// Number of successful commits
+    pub commits: u64,
+    /// Number of commits that failed because the table was changed concurrently
+    pub conflicts: u64,
+    /// Number of commits that failed for other reasons
+    pub failures: u64,
+    /// Mean latency of all attempts
+    pub mean_latency: Duration,
+    /// Maximum latency of all attempts
+    pub max_latency: Duration,
+    /// Median latency of the recent attempts, `None` for stored statistics
+    pub p50_latency: Option<Duration>,
+    /// 95th percentile latency of the recent attempts, `None` for stored statistics
+    pub p95_latency: Option<Duration>,
+    /// 99th percentile latency of the recent attempts, `None` for stored statistics
+    pub p99_latency: Option<Duration>,
+}
+
+impl CommitStats {
+    /// Share of the attempts that conflicted with a concurrent commit
+    pub fn conflict_rate(&self) -> f64 {
+        if self.attempts == 0 {
+            0.0
+        } else {
+            self.conflicts as f64 / self.attempts as f64
+        }
+    }
+}
+
+/// Counters of a table
+#[derive(Debug, Clone, Copy, Default)]
+struct Counters {
+    attempts: u64,
+    commits: u64,
+    conflicts: u64,
+    failures: u64,
+    total_latency: Duration,
+    max_latency: Duration,
+}
+
+impl Counters {
+    fn record(&mut self, outcome: CommitOutcome, latency: Duration) {
+        self.attempts += 1;
+        match outcome {
+            CommitOutcome::Committed => self.commits += 1,
+            CommitOutcome::Conflict => self.conflicts += 1,
+            CommitOutcome::Failed => self.failures += 1,
+        }
+        self.total_latency += latency;
+        self.max_latency = self.max_latency.max(latency);
+    }
+
+    fn merge(&mut self, other: &Counters) {
+        self.attempts += other.attempts;
+        self.commits += other.commits;
+        self.conflicts += other.conflicts;
+        self.failures += other.failures;
+        self.total_latency += other.total_latency;
+        self.max_latency = self.max_latency.max(other.max_latency);
+    }
+}
+
+struct TableStats {
+    counters: Counters,
+    latencies: VecDeque<Duration>,
+    /// Sequence number of the last recorded attempt
+    last_recorded: u64,
+}
+
+/// Commit statistics of the tables, and the counters that weren't added to the stats table yet
+pub(crate) struct StatsTracker {
+    tables: Mutex<HashMap<(String, String), TableStats>>,
+    pending: Mutex<(Instant, HashMap<(String, String), Counters>)>,
+    sequence: AtomicU64,
+}
+
+impl Default for StatsTracker {
+    fn default() -> Self {
+        StatsTracker {
+            tables: Mutex::new(HashMap::new()),
+            pending: Mutex::new((Instant::now(), HashMap::new())),
+            sequence: AtomicU64::new(0),
+        }
+    }
+}
+
+impl StatsTracker {
+    /// Record an attempt. With `persist`, returns the counters to write if the flush interval
+    /// elapsed.
+    fn record(
+        &self,
+        identifier: &TableIdentifier,
+        outcome: CommitOutcome,
+        latency: Duration,
+        persist: bool,
+    ) -> Option<HashMap<(String, String), Counters>> {
+        let key = (
+            namespace_key(identifier.namespace()),
+            identifier.name().to_string(),
+        );
+        let sequence = self.sequence.fetch_add(1, Ordering::Relaxed);
+        if let Ok(mut tables) = self.tables.lock() {
+            if !tables.contains_key(&key) && tables.len() >= MAX_TRACKED_TABLES {
+                let idle = tables
+                    .iter()
+                    .min_by_key(|(_, table)| table.last_recorded)
+                    .map(|(key, _)| key.clone());
+                if let Some(idle) = idle {
+                    tables.remove(&idle);
+                }
+            }
+            let table = tables.entry(key.clone()).or_insert_with(|| TableStats {
+                counters: Counters::default(),
+                latencies: VecDeque::new(),
+                last_recorded: sequence,
+            });
+            table.last_recorded = sequence;
+            table.counters.record(outcome, latency);
+            if table.latencies.len() == LATENCY_SAMPLES {
+                table.latencies.pop_front();
+            }
+            table.latencies.push_back(latency);
+        }
+        if !persist {
+            return None;
+        }
+        let mut pending = self.pending.lock().ok()?;
+        pending.1.entry(key).or_default().record(outcome, latency);
+        if pending.0.elapsed() >= STATS_FLUSH_INTERVAL {
+            pending.0 = Instant::now();
+            Some(mem::take(&mut pending.1))
+        } else {
+            None
+        }
+    }
+
+    /// Take all counters that weren't written yet.
+    fn take(&self) -> HashMap<(String, String), Counters> {
+        self.pending
+            .lock()
+            .map(|mut pending| {
+                pending.0 = Instant::now();
+                mem::take(&mut pending.1)
+            })
+            .unwrap_or_default()
+    }
+
+    /// Put back counters that couldn't be written, so that the next flush writes them.
+    fn restore(&self, counters: HashMap<(String, String), Counters>) {
+        if let Ok(mut pending) = self.pending.lock() {
+            for (key, counters) in counters {
+                pending.1.entry(key).or_default().merge(&counters);
+            }
+        }
+    }
+
+    fn stats(&self) -> Vec<CommitStats> {
+        let tables = match self.tables.lock() {
+            Ok(tables) => tables,
+            Err(_) => return Vec::new(),
+        };
+        tables
+            .iter()
+            .filter_map(|((namespace, name), table)| {
+                let mut latencies = table.latencies.iter().copied().collect::<Vec<_>>();
+                latencies.sort();
+                let mut stats =
+                    commit_stats(table_identifier(namespace, name).ok()?, &table.counters);
+                stats.p50_latency = percentile(&latencies, 50);
+                stats.p95_latency = percentile(&latencies, 95);
+                stats.p99_latency = percentile(&latencies, 99);
+                Some(stats)
+            })
+            .collect()
+    }
+}
+
+impl PostgresCatalog {
+    /// Commit statistics of all tables this instance committed to since it was created, tables
+    /// with the most conflicts first.
+    pub fn stats(&self) -> Vec<CommitStats> {
+        let mut stats = self.stats_tracker.stats();
+        sort_by_conflicts(&mut stats);
+        stats
+    }
+
+    /// Commit statistics of the tables in the namespace and its children that were added to the
+    /// stats table by all catalog instances, tables with the most conflicts first. Counters that
+    /// weren't flushed yet are not included.
+    pub async fn stored_stats(&self, namespace: &Namespace) -> Result<Vec<CommitStats>> {
+        let namespace = &self.case_sensitivity.normalize_namespace(namespace)?;
+        self.authorize(Action::Read, namespace, None).await?;
+        let rows = self
+            .query(
+                self.read_connection(),
+                &("SELECT ".to_string()
+                    + &[
+                        TABLE_NAMESPACE_COLUMN,
+                        TABLE_NAME_COLUMN,
+                        ATTEMPTS_COLUMN,
+                        COMMITS_COLUMN,
+                        CONFLICTS_COLUMN,
+                        FAILURES_COLUMN,
+                        TOTAL_LATENCY_COLUMN,
+                        MAX_LATENCY_COLUMN,
+                    ]
+                    .join(", ")
+                    + " FROM "
+                    + &self.catalog_table.stats
+                    + " WHERE "
+                    + CATALOG_NAME_COLUMN
+                    + " = "
+                    + &literal(&self.name)
+                    + " AND ("
+                    + &namespace_condition(namespace)
+                    + ");"),
+            )
+            .await?;
+        let mut stats = rows
+            .iter()
+            .map(stored_commit_stats)
+            .collect::<Result<Vec<_>>>()?;
+        sort_by_conflicts(&mut stats);
+        Ok(stats)
+    }
+
+    /// Add the counters that weren't written yet to the stats table. If writing them fails, they
+    /// are kept for the next flush.
+    pub async fn flush_stats(&self) -> Result<()> {
+        if !self.persist_stats {
+            return Ok(());
+        }
+        let counters = self.stats_tracker.take();
+        let written = self.write_stats(&counters).await;
+        if written.is_err() {
+            self.stats_tracker.restore(counters);
+        }
+        written
+    }
+
+    /// Record the outcome of a commit attempt. Failing to write the counters doesn't fail the
+    /// commit, they are kept for the next flush.
+    pub(crate) async fn record_commit(
+        &self,
+        identifier: &TableIdentifier,
+        outcome: CommitOutcome,
+        latency: Duration,
+    ) {
+        let identifier = match self.case_sensitivity.normalize(identifier) {
+            Ok(identifier) => identifier,
+            Err(_) => return,
+        };
+        if let Some(counters) =
+            self.stats_tracker
+                .record(&identifier, outcome, latency, self.persist_stats)
+        {
+            if self.write_stats(&counters).await.is_err() {
+                self.stats_tracker.restore(counters);
+            }
+        }
+    }
+
+    async fn write_stats(&self, counters: &HashMap<(String, String), Counters>) -> Result<()> {
+        if counters.is_empty() {
+            return Ok(());
+        }
+        let values = counters
+            .iter()
+            .map(|((namespace, name), counters)| {
+                "(".to_string()
+                    + &[
+                        literal(&self.name),
+                        literal(namespace),
+                        literal(name),
+                        counters.attempts.to_string(),
+                        counters.commits.to_string(),
+                        counters.conflicts.to_string(),
+                        counters.failures.to_string(),
+                        counters.total_latency.as_micros().to_string(),
+                        counters.max_latency.as_micros().to_string(),
+                    ]
+                    .join(", ")
+                    + ", now())"
+            })
+            .collect::<Vec<_>>()
+            .join(", ");
+        let add =
+            |column: &str| column.to_string() + " = stats." + column + " + EXCLUDED." + column;
+        // Not marked as a write, recording statistics shouldn't move reads to the primary.
+        self.execute(
+            &self.primary,
+            &("INSERT INTO ".to_string()
+                + &self.catalog_table.stats
+                + " AS stats ("
+                + &[
+                    CATALOG_NAME_COLUMN,
+                    TABLE_NAMESPACE_COLUMN,
+                    TABLE_NAME_COLUMN,
+                    ATTEMPTS_COLUMN,
+                    COMMITS_COLUMN,
+                    CONFLICTS_COLUMN,
+                    FAILURES_COLUMN,
+                    TOTAL_LATENCY_COLUMN,
+                    MAX_LATENCY_COLUMN,
+                    UPDATED_AT_COLUMN,
+                ]
+                .join(", ")
+                + ") VALUES "
+                + &values
+                + " ON CONFLICT ("
+                + CATALOG_NAME_COLUMN
+                + ", "
+                + TABLE_NAMESPACE_COLUMN
+                + ", "
+                + TABLE_NAME_COLUMN
+                + ") DO UPDATE SET "
+                + &[
+                    add(ATTEMPTS_COLUMN),
+                    add(COMMITS_COLUMN),
+                    add(CONFLICTS_COLUMN),
+                    add(FAILURES_COLUMN),
+                    add(TOTAL_LATENCY_COLUMN),
+                    MAX_LATENCY_COLUMN.to_string()
+                        + " = GREATEST(stats."
+                        + MAX_LATENCY_COLUMN
+                        + ", EXCLUDED."
+                        + MAX_LATENCY_COLUMN
+                        + ")",
+                    UPDATED_AT_COLUMN.to_string() + " = now()",
+                ]
+                .join(", ")
+                + ";"),
+        )
+        .await?;
+        Ok(())
+    }
+
+    pub(crate) async fn create_stats_table(&self) -> Result<()> {
+        self.execute(
+            &self.primary,
+            &("CREATE TABLE IF NOT EXISTS ".to_string()
+                + &self.catalog_table.stats
+                + " ("
+                + CATALOG_NAME_COLUMN
+                + " VARCHAR(255) NOT NULL,"
+                + TABLE_NAMESPACE_COLUMN
+                + " VARCHAR(255) NOT NULL,"
+                + TABLE_NAME_COLUMN
+                + " VARCHAR(255) NOT NULL,"
+                + ATTEMPTS_COLUMN
+                + " BIGINT NOT NULL,"
+                + COMMITS_COLUMN
+                + " BIGINT NOT NULL,"
+                + CONFLICTS_COLUMN
+                + " BIGINT NOT NULL,"
+                + FAILURES_COLUMN
+                + " BIGINT NOT NULL,"
+                + TOTAL_LATENCY_COLUMN
+                + " BIGINT NOT NULL,"
+                + MAX_LATENCY_COLUMN
+                + " BIGINT NOT NULL,"
+                + UPDATED_AT_COLUMN
+                + " TIMESTAMPTZ NOT NULL,"
+                + "PRIMARY KEY ("
+                + CATALOG_NAME_COLUMN
+                + ", "
+                + TABLE_NAMESPACE_COLUMN
+                + ", "
+                + TABLE_NAME_COLUMN
+                + ")"
+                + ");"),
+        )
+        .await?;
+        Ok(())
+    }
+}
+
+fn commit_stats(identifier: TableIdentifier, counters: &Counters) -> CommitStats {
+    CommitStats {
+        identifier,
+        attempts: counters.attempts,
+        commits: counters.commits,
+        conflicts: counters.conflicts,
+        failures: counters.failures,
+        mean_latency: if counters.attempts == 0 {
+            Duration::ZERO
+        } else {
+            Duration::from_nanos(
+                (counters.total_latency.as_nanos() / counters.attempts as u128) as u64,
+            )
+        },
+        max_latency: counters.max_latency,
+        p50_latency: None,
+        p95_latency: None,
+        p99_latency: None,
+    }
+}
+
+fn stored_commit_stats(row: &CatalogRow) -> Result<CommitStats> {
+    let count = |column: &str| -> Result<u64> { Ok(row.try_get_i64(column)?.max(0) as u64) };
+    let counters = Counters {
+        attempts: count(ATTEMPTS_COLUMN)?,
+        commits: count(COMMITS_COLUMN)?,
+        conflicts: count(CONFLICTS_COLUMN)?,
+        failures: count(FAILURES_COLUMN)?,
+        total_latency: Duration::from_micros(count(TOTAL_LATENCY_COLUMN)?),
+        max_latency: Duration::from_micros(count(MAX_LATENCY_COLUMN)?),
+    };
+    Ok(commit_stats(
+        table_identifier(
+            &row.try_get_string(TABLE_NAMESPACE_COLUMN)?,
+            &row.try_get_string(TABLE_NAME_COLUMN)?,
+        )?,
+        &counters,
+    ))
+}
+
+/// Nearest-rank percentile of the sorted latencies
+fn percentile(sorted: &[Duration], percent: usize) -> Option<Duration> {
+    if sorted.is_empty() {
+        return None;
+    }
+    let rank = (percent * sorted.len() + 99) / 100;
+    Some(sorted[rank.clamp(1, sorted.len()) - 1])
+}
+
+fn sort_by_conflicts(stats: &mut [CommitStats]) {
+    stats.sort_by(|a, b| {
+        b.conflicts
+            .cmp(&a.conflicts)
+            .then(b.attempts.cmp(&a.attempts))
+            .then_with(|| a.identifier.to_string().cmp(&b.identifier.to_string()))
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use iceberg_rs::catalog::table_identifier::TableIdentifier;
+
+    use super::{percentile, CommitOutcome, StatsTracker, MAX_TRACKED_TABLES};
+
+    #[test]
+    fn test_percentile() {
+        let latencies = (1..=100).map(Duration::from_millis).collect::<Vec<_>>();
+        assert_eq!(percentile(&latencies, 50), Some(Duration::from_millis(50)));
+        assert_eq!(percentile(&latencies, 99), Some(Duration::from_millis(99)));
+        assert_eq!(
+            percentile(&latencies[..1], 95),
+            Some(Duration::from_millis(1))
+        );
+        assert_eq!(percentile(&[], 50), None);
+    }
+
+    #[test]
+    fn test_stats_tracker() {
+        let tracker = StatsTracker::default();
+        let identifier = TableIdentifier::parse("stats.table").unwrap();
+        tracker.record(
+            &identifier,
+            CommitOutcome::Committed,
+            Duration::from_millis(10),
+            true,
+        );
+        tracker.record(
+            &identifier,
+            CommitOutcome::Conflict,
+            Duration::from_millis(30),
+            true,
+        );
+        let stats = tracker.stats();
+        assert_eq!(stats.len(), 1);
+        assert_eq!(stats[0].identifier.to_string(), "stats.table");
+        assert_eq!(
+            (stats[0].attempts, stats[0].commits, stats[0].conflicts),
+            (2, 1, 1)
+        );
+        assert_eq!(stats[0].mean_latency, Duration::from_millis(20));
+        assert_eq!(stats[0].max_latency, Duration::from_millis(30));
+        assert_eq!(stats[0].p50_latency, Some(Duration::from_millis(10)));
+        assert_eq!(stats[0].conflict_rate(), 0.5);
+
+        let pending = tracker.take();
+        assert_eq!(pending.len(), 1);
+        assert!(tracker.take().is_empty());
+
+        // Counters that failed to be written are merged with the newer ones.
+        tracker.record(
+            &identifier,
+            CommitOutcome::Failed,
+            Duration::from_millis(50),
+            true,
+        );
+        tracker.restore(pending);
+        let pending = tracker.take();
+        let counters = pending.values().next().unwrap();
+        assert_eq!((counters.attempts, counters.failures), (3, 1));
+        assert_eq!(counters.max_latency, Duration::from_millis(50));
+    }
+
+    #[test]
+    fn test_stats_tracker_limit() {
+        let tracker = StatsTracker::default();
+        for index in 0..=MAX_TRACKED_TABLES {
+            let identifier = TableIdentifier::parse(&format!("stats.table{}", index)).unwrap();
+            tracker.record(
+                &identifier,
+                CommitOutcome::Committed,
+                Duration::from_millis(1),
+                false,
+            );
+        }
+        let stats = tracker.stats();
+        assert_eq!(stats.len(), MAX_TRACKED_TABLES);
+        // The table that went longest without a commit was dropped.
+        assert!(stats
+            .iter()
+            .all(|stats| stats.identifier.to_string() != "stats.table0"));
+    }
+}
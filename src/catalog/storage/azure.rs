@@ -0,0 +1,126 @@
+/*!
+Azure Blob Storage and ADLS Gen2
+*/
+
+use std::collections::HashMap;
+
+use anyhow::{anyhow, Result};
+use iceberg_rs::object_store::azure::{MicrosoftAzure, MicrosoftAzureBuilder};
+
+use super::split_location;
+use crate::catalog::secret::Secret;
+
+/// Name of the storage account
+pub static ADLS_ACCOUNT_NAME: &str = "adls.account-name";
+/// Shared key of the storage account
+pub static ADLS_ACCOUNT_KEY: &str = "adls.account-key";
+/// Tenant of the service principal
+pub static ADLS_TENANT_ID: &str = "adls.tenant-id";
+/// Client id of the service principal
+pub static ADLS_CLIENT_ID: &str = "adls.client-id";
+/// Client secret of the service principal
+pub static ADLS_CLIENT_SECRET: &str = "adls.client-secret";
+
+/// Configuration of an Azure Blob Storage or ADLS Gen2 object store
+#[derive(Debug, Clone)]
+pub struct AzureConfig {
+    account: String,
+    container: String,
+    access_key: Option<Secret>,
+    service_principal: Option<(String, Secret, String)>,
+    use_emulator: bool,
+}
+
+impl AzureConfig {
+    /// Configuration for the container in the storage account
+    pub fn new(account: &str, container: &str) -> Self {
+        AzureConfig {
+            account: account.to_string(),
+            container: container.to_string(),
+            access_key: None,
+            service_principal: None,
+            use_emulator: false,
+        }
+    }
+
+    /// Configuration for the container of the `warehouse` from catalog properties. The warehouse
+    /// is either given as `abfss://container@account.dfs.core.windows.net/path` or as
+    /// `az://container/path` together with the `adls.account-name` property.
+    pub fn from_properties(warehouse: &str, properties: &HashMap<String, String>) -> Result<Self> {
+        let (_, authority, _) = split_location(warehouse)?;
+        let (container, account) = match authority.split_once('@') {
+            Some((container, host)) => (
+                container,
+                host.split('.').next().unwrap_or(host).to_string(),
+            ),
+            None => (
+                authority,
+                properties.get(ADLS_ACCOUNT_NAME).cloned().ok_or_else(|| {
+                    anyhow!(
+                        "The storage account of the warehouse {} is unknown.",
+                        warehouse
+                    )
+                })?,
+            ),
+        };
+        let mut config = AzureConfig::new(&account, container);
+        if let Some(access_key) = properties.get(ADLS_ACCOUNT_KEY) {
+            config = config.with_access_key(Secret::new(access_key.as_str()));
+        }
+        if let (Some(tenant_id), Some(client_id), Some(client_secret)) = (
+            properties.get(ADLS_TENANT_ID),
+            properties.get(ADLS_CLIENT_ID),
+            properties.get(ADLS_CLIENT_SECRET),
+        ) {
+            config = config.with_service_principal(
+                client_id,
+                Secret::new(client_secret.as_str()),
+                tenant_id,
+            );
+        }
+        Ok(config)
+    }
+
+    /// Authenticate with the shared key of the storage account
+    pub fn with_access_key(mut self, access_key: Secret) -> Self {
+        self.access_key = Some(access_key);
+        self
+    }
+
+    /// Authenticate as a service principal with a client secret
+    pub fn with_service_principal(
+        mut self,
+        client_id: &str,
+        client_secret: Secret,
+        tenant_id: &str,
+    ) -> Self {
+        self.service_principal =
+            Some((client_id.to_string(), client_secret, tenant_id.to_string()));
+        self
+    }
+
+    /// Use the local Azurite emulator
+    pub fn with_emulator(mut self, use_emulator: bool) -> Self {
+        self.use_emulator = use_emulator;
+        self
+    }
+
+    /// Build the object store
+    pub fn build(&self) -> Result<MicrosoftAzure> {
+        let mut builder = MicrosoftAzureBuilder::new()
+            .with_account(&self.account)
+            .with_container_name(&self.container)
+            .with_use_emulator(self.use_emulator);
+        if let Some(access_key) = &self.access_key {
+            builder = builder.with_access_key(access_key.expose());
+        }
+        if let Some((client_id, client_secret, tenant_id)) = &self.service_principal {
+            builder = builder.with_client_secret_authorization(
+                client_id,
+                client_secret.expose(),
+                tenant_id,
+            );
+        }
+        builder.build().map_err(|err| anyhow!(err.to_string()))
+    }
+}
@@ -0,0 +1,62 @@
+/*!
+Google Cloud Storage
+*/
+
+use std::collections::HashMap;
+
+use anyhow::{anyhow, Result};
+use iceberg_rs::object_store::gcp::{GoogleCloudStorage, GoogleCloudStorageBuilder};
+
+use super::split_location;
+
+/// Path to the json key file of a service account
+pub static GCS_SERVICE_ACCOUNT_PATH: &str = "gcs.service-account-path";
+
+/// Configuration of a Google Cloud Storage object store.
+///
+/// Without a service account key file, the path is taken from the `GOOGLE_SERVICE_ACCOUNT`
+/// environment variable.
+#[derive(Debug, Clone)]
+pub struct GcsConfig {
+    bucket: String,
+    service_account_path: Option<String>,
+}
+
+impl GcsConfig {
+    /// Configuration for the given bucket
+    pub fn new(bucket: &str) -> Self {
+        GcsConfig {
+            bucket: bucket.to_string(),
+            service_account_path: None,
+        }
+    }
+
+    /// Configuration for the bucket of the `warehouse` from catalog properties
+    pub fn from_properties(warehouse: &str, properties: &HashMap<String, String>) -> Result<Self> {
+        let (_, bucket, _) = split_location(warehouse)?;
+        let mut config = GcsConfig::new(bucket);
+        if let Some(path) = properties.get(GCS_SERVICE_ACCOUNT_PATH) {
+            config = config.with_service_account_path(path);
+        }
+        Ok(config)
+    }
+
+    /// Authenticate with the json key file of a service account
+    pub fn with_service_account_path(mut self, path: &str) -> Self {
+        self.service_account_path = Some(path.to_string());
+        self
+    }
+
+    /// Build the object store
+    pub fn build(&self) -> Result<GoogleCloudStorage> {
+        let mut builder = GoogleCloudStorageBuilder::new().with_bucket_name(&self.bucket);
+        let service_account_path = self
+            .service_account_path
+            .clone()
+            .or_else(|| std::env::var("GOOGLE_SERVICE_ACCOUNT").ok());
+        if let Some(path) = service_account_path {
+            builder = builder.with_service_account_path(path);
+        }
+        builder.build().map_err(|err| anyhow!(err.to_string()))
+    }
+}
@@ -0,0 +1,228 @@
+/*!
+Physical layout of new tables below the warehouse.
+
+With [with_relative_locations](crate::catalog::builder::PostgresCatalogBuilder::with_relative_locations),
+the catalog stores the locations of tables below the warehouse relative to it, as
+`{warehouse}/namespace/table/metadata/...`, and resolves them against the current warehouse
+when they are read. The warehouse and the catalog can then be moved to another bucket or account
+together, by copying the files and pointing the `warehouse` property to the new location, without
+rewriting the pointers. The metadata files themselves still contain absolute locations.
+*/
+
+use iceberg_rs::catalog::table_identifier::TableIdentifier;
+
+use super::{resolver::object_path, split_location};
+
+/// Prefix of locations that are stored relative to the warehouse
+pub(crate) static WAREHOUSE_PREFIX: &str = "{warehouse}/";
+
+/// Decides where the files of a new table are stored
+pub trait LocationProvider: Send + Sync {
+    /// Location of a new table below the `warehouse_path` of the catalog
+    fn table_location(&self, warehouse_path: &str, identifier: &TableIdentifier) -> String;
+}
+
+/// Stores a table in `warehouse/namespace/table`. The levels of the namespace become directories.
+#[derive(Debug, Clone, Default)]
+pub struct DefaultLocationProvider;
+
+impl LocationProvider for DefaultLocationProvider {
+    fn table_location(&self, warehouse_path: &str, identifier: &TableIdentifier) -> String {
+        join(warehouse_path, &table_path(identifier))
+    }
+}
+
+/// Stores a table in `warehouse/hash/namespace/table`, where the hash of the identifier spreads
+/// the tables over many prefixes. Object stores like S3 limit the request rate per prefix, so
+/// this layout avoids throttling if many tables are accessed at the same time.
+#[derive(Debug, Clone, Default)]
+pub struct HashedLocationProvider;
+
+impl LocationProvider for HashedLocationProvider {
+    fn table_location(&self, warehouse_path: &str, identifier: &TableIdentifier) -> String {
+        let hash = fnv1a(format!("{}", identifier).as_bytes());
+        join(
+            warehouse_path,
+            &(format!("{:08x}/", hash) + &table_path(identifier)),
+        )
+    }
+}
+
+/// Path of the table below the warehouse with a directory for every level of the namespace. The
+/// levels and the name are percent-encoded, so that names with dots or slashes stay one directory.
+fn table_path(identifier: &TableIdentifier) -> String {
+    identifier
+        .namespace()
+        .levels()
+        .iter()
+        .map(|level| encode_segment(level))
+        .chain(std::iter::once(encode_segment(identifier.name())))
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+/// Percent-encode all bytes except unreserved characters. Segments of only dots are encoded
+/// completely, so that they don't refer to the current or parent directory.
+fn encode_segment(segment: &str) -> String {
+    let dots = segment.bytes().all(|byte| byte == b'.');
+    segment
+        .bytes()
+        .map(|byte| match byte {
+            b'.' if dots => "%2E".to_string(),
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' => {
+                (byte as char).to_string()
+            }
+            byte => format!("%{:02X}", byte),
+        })
+        .collect()
+}
+
+fn join(warehouse_path: &str, table_path: &str) -> String {
+    if warehouse_path.is_empty() {
+        table_path.to_string()
+    } else {
+        warehouse_path.trim_end_matches('/').to_string() + "/" + table_path
+    }
+}
+
+/// The location relative to the warehouse, `None` if it isn't below the warehouse. The warehouse
+/// is given by its path within the object store and, if it is known, by its full location.
+pub(crate) fn relative_location(
+    warehouse_path: &str,
+    warehouse: Option<&str>,
+    location: &str,
+) -> Option<String> {
+    if location.starts_with(WAREHOUSE_PREFIX) {
+        return Some(location.to_string());
+    }
+    // Locations with a scheme have to be in the bucket of the warehouse.
+    if location.contains("://") {
+        let (scheme, bucket, _) = split_location(location).ok()?;
+        let (warehouse_scheme, warehouse_bucket, _) = split_location(warehouse?).ok()?;
+        if scheme != warehouse_scheme || bucket != warehouse_bucket {
+            return None;
+        }
+    }
+    let path = object_path(location).trim_start_matches('/');
+    let relative = if warehouse_path.is_empty() {
+        path
+    } else {
+        path.strip_prefix(warehouse_path)?.strip_prefix('/')?
+    };
+    if relative.is_empty() {
+        None
+    } else {
+        Some(WAREHOUSE_PREFIX.to_string() + relative)
+    }
+}
+
+/// The location of a stored location below the warehouse path. Absolute locations are returned
+/// as they are.
+pub(crate) fn resolve_location(warehouse_path: &str, location: &str) -> String {
+    match location.strip_prefix(WAREHOUSE_PREFIX) {
+        Some(relative) => join(warehouse_path, relative),
+        None => location.to_string(),
+    }
+}
+
+/// 32 bit FNV-1a hash. Unlike the hasher of the standard library, it is stable across releases, so
+/// the same identifier always maps to the same prefix.
+pub(crate) fn fnv1a(bytes: &[u8]) -> u32 {
+    bytes.iter().fold(0x811c9dc5, |hash, byte| {
+        (hash ^ *byte as u32).wrapping_mul(0x01000193)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use iceberg_rs::catalog::table_identifier::TableIdentifier;
+
+    use super::{
+        relative_location, resolve_location, DefaultLocationProvider, HashedLocationProvider,
+        LocationProvider,
+    };
+
+    #[test]
+    fn test_default_location() {
+        let identifier = TableIdentifier::parse("test.table1").unwrap();
+        assert_eq!(
+            DefaultLocationProvider.table_location("tmp/warehouse", &identifier),
+            "tmp/warehouse/test/table1"
+        );
+        assert_eq!(
+            DefaultLocationProvider.table_location("", &identifier),
+            "test/table1"
+        );
+        let dotted = TableIdentifier::try_new(&["a.b".to_string(), "c d".to_string()]).unwrap();
+        assert_eq!(
+            DefaultLocationProvider.table_location("warehouse", &dotted),
+            "warehouse/a.b/c%20d"
+        );
+        let nested = TableIdentifier::parse("a.b.c").unwrap();
+        assert_eq!(
+            DefaultLocationProvider.table_location("warehouse", &nested),
+            "warehouse/a/b/c"
+        );
+        let parent = TableIdentifier::try_new(&["..".to_string(), "a/b".to_string()]).unwrap();
+        assert_eq!(
+            DefaultLocationProvider.table_location("warehouse", &parent),
+            "warehouse/%2E%2E/a%2Fb"
+        );
+    }
+
+    #[test]
+    fn test_hashed_location() {
+        let identifier = TableIdentifier::parse("test.table1").unwrap();
+        let location = HashedLocationProvider.table_location("warehouse", &identifier);
+        let parts: Vec<&str> = location.split('/').collect();
+        assert_eq!(parts.len(), 4);
+        assert_eq!(parts[0], "warehouse");
+        assert_eq!(parts[1].len(), 8);
+        assert_eq!(&parts[2..], &["test", "table1"]);
+        assert_eq!(
+            HashedLocationProvider.table_location("warehouse", &identifier),
+            location
+        );
+    }
+
+    #[test]
+    fn test_relative_location() {
+        let location = "data.db/test/table1/metadata/v1.metadata.json";
+        let relative = relative_location("data.db", None, location).unwrap();
+        assert_eq!(
+            relative,
+            "{warehouse}/test/table1/metadata/v1.metadata.json"
+        );
+        assert_eq!(
+            relative_location("data.db", None, &relative).unwrap(),
+            relative
+        );
+        assert_eq!(resolve_location("data.db", &relative), location);
+        assert_eq!(
+            resolve_location("moved/warehouse", &relative),
+            "moved/warehouse/test/table1/metadata/v1.metadata.json"
+        );
+        assert_eq!(resolve_location("data.db", location), location);
+
+        assert_eq!(
+            relative_location(
+                "warehouse",
+                Some("s3://bucket/warehouse"),
+                "s3://bucket/warehouse/test/table1"
+            )
+            .unwrap(),
+            "{warehouse}/test/table1"
+        );
+        assert!(relative_location(
+            "warehouse",
+            Some("s3://bucket/warehouse"),
+            "s3://other/warehouse/test/table1"
+        )
+        .is_none());
+        assert!(
+            relative_location("warehouse", None, "s3://bucket/warehouse/test/table1").is_none()
+        );
+        assert!(relative_location("data.db", None, "data.dbx/test/table1").is_none());
+        assert!(relative_location("data.db", None, "data.db").is_none());
+    }
+}
@@ -0,0 +1,165 @@
+/*!
+Builds the object store of the catalog from catalog properties.
+
+The properties follow the conventions of the Iceberg Java implementation. The `warehouse` property
+selects the backend by its scheme, the remaining properties configure it. Besides the in-memory
+store and the local filesystem (`file://`), the backends are enabled with the `aws` (`s3://`),
+`gcp` (`gs://`) and `azure` (`abfss://`, `az://`) features.
+
+New tables are created below the path of the warehouse within its object store, in the layout of
+the [location::LocationProvider] of the catalog. Catalogs whose
+tables live in different object stores select the store of each table with a
+[resolver::ObjectStoreResolver]. Engines and clients without credentials of their own get scoped
+credentials from [vending] and pre-signed URLs for the metadata of tables from [signing].
+*/
+
+use std::{collections::HashMap, sync::Arc};
+
+use anyhow::{anyhow, Result};
+use iceberg_rs::object_store::{local::LocalFileSystem, memory::InMemory, ObjectStore};
+
+#[cfg(feature = "azure")]
+pub mod azure;
+#[cfg(feature = "gcp")]
+pub mod gcs;
+pub mod location;
+pub mod resolver;
+#[cfg(feature = "aws")]
+pub mod s3;
+pub mod signing;
+pub mod vending;
+
+/// Location of the warehouse, for example `s3://bucket/path`
+pub static WAREHOUSE: &str = "warehouse";
+
+/// Path of new tables within the object store if no warehouse is configured
+pub(crate) static DEFAULT_WAREHOUSE_PATH: &str = "data.db";
+
+/// Object store for the `warehouse` property and whether the warehouse is still required. Without
+/// warehouse, the object store is a placeholder that `initialize` has to replace with the stored
+/// warehouse, see
+/// [with_warehouse_required](super::builder::PostgresCatalogBuilder::with_warehouse_required).
+pub(crate) fn initial_object_store(
+    properties: &HashMap<String, String>,
+) -> Result<(Arc<dyn ObjectStore>, bool)> {
+    Ok(match object_store_from_properties(properties)? {
+        Some(object_store) => (object_store, false),
+        None => (Arc::new(InMemory::new()), true),
+    })
+}
+
+/// Build the object store for the `warehouse` property. Returns `None` if the properties don't
+/// configure a warehouse.
+pub fn object_store_from_properties(
+    properties: &HashMap<String, String>,
+) -> Result<Option<Arc<dyn ObjectStore>>> {
+    let warehouse = match properties.get(WAREHOUSE) {
+        Some(warehouse) => warehouse,
+        None => return Ok(None),
+    };
+    let (scheme, _, _) = split_location(warehouse)?;
+    match scheme {
+        "memory" => Ok(Some(Arc::new(InMemory::new()))),
+        "file" => Ok(Some(Arc::new(local_filesystem(warehouse)?))),
+        #[cfg(feature = "aws")]
+        "s3" | "s3a" => Ok(Some(Arc::new(
+            s3::S3Config::from_properties(warehouse, properties)?.build()?,
+        ))),
+        #[cfg(feature = "gcp")]
+        "gs" => Ok(Some(Arc::new(
+            gcs::GcsConfig::from_properties(warehouse, properties)?.build()?,
+        ))),
+        #[cfg(feature = "azure")]
+        "abfs" | "abfss" | "az" => Ok(Some(Arc::new(
+            azure::AzureConfig::from_properties(warehouse, properties)?.build()?,
+        ))),
+        _ => Err(anyhow!(
+            "The warehouse scheme {} is not supported. Check that the feature of the backend is enabled.",
+            scheme
+        )),
+    }
+}
+
+/// Path of the `warehouse` within its object store. Returns `None` if the properties don't
+/// configure a warehouse.
+pub fn warehouse_path(properties: &HashMap<String, String>) -> Result<Option<String>> {
+    match properties.get(WAREHOUSE) {
+        Some(warehouse) => {
+            let (_, _, path) = split_location(warehouse)?;
+            Ok(Some(path.to_string()))
+        }
+        None => Ok(None),
+    }
+}
+
+/// Object store for a `file:///path/to/warehouse` location. The store is rooted at the root of the
+/// filesystem, so that paths in the store match the path of the warehouse. The warehouse
+/// directory is created if it doesn't exist.
+fn local_filesystem(warehouse: &str) -> Result<LocalFileSystem> {
+    let (_, host, path) = split_location(warehouse)?;
+    if !host.is_empty() && host != "localhost" {
+        return Err(anyhow!(
+            "The warehouse {} is not on the local filesystem.",
+            warehouse
+        ));
+    }
+    std::fs::create_dir_all("/".to_string() + path).map_err(|err| anyhow!(err.to_string()))?;
+    Ok(LocalFileSystem::new())
+}
+
+/// Split a location like `s3://bucket/path` into scheme, bucket and path.
+pub(crate) fn split_location(location: &str) -> Result<(&str, &str, &str)> {
+    let (scheme, rest) = location
+        .split_once("://")
+        .ok_or_else(|| anyhow!("The location {} has no scheme.", location))?;
+    let (bucket, path) = rest.split_once('/').unwrap_or((rest, ""));
+    Ok((scheme, bucket, path.trim_matches('/')))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use super::{object_store_from_properties, split_location, warehouse_path, WAREHOUSE};
+
+    #[test]
+    fn test_split_location() {
+        assert_eq!(
+            split_location("s3://bucket/warehouse/").unwrap(),
+            ("s3", "bucket", "warehouse")
+        );
+        assert_eq!(split_location("memory://").unwrap(), ("memory", "", ""));
+        assert!(split_location("bucket/warehouse").is_err());
+        assert_eq!(
+            split_location("file:///tmp/warehouse").unwrap(),
+            ("file", "", "tmp/warehouse")
+        );
+    }
+
+    #[test]
+    fn test_object_store_from_properties() {
+        assert!(object_store_from_properties(&HashMap::new())
+            .unwrap()
+            .is_none());
+        let properties = HashMap::from_iter(vec![(WAREHOUSE.to_string(), "memory://".to_string())]);
+        assert!(object_store_from_properties(&properties).unwrap().is_some());
+        let properties = HashMap::from_iter(vec![(
+            WAREHOUSE.to_string(),
+            "unknown://bucket".to_string(),
+        )]);
+        assert!(object_store_from_properties(&properties).is_err());
+    }
+
+    #[test]
+    fn test_local_filesystem_warehouse() {
+        let directory = std::env::temp_dir().join("iceberg_catalog_postgres_warehouse");
+        let warehouse = "file://".to_string() + &directory.to_string_lossy();
+        let properties = HashMap::from_iter(vec![(WAREHOUSE.to_string(), warehouse)]);
+        assert!(object_store_from_properties(&properties).unwrap().is_some());
+        assert!(directory.is_dir());
+        assert_eq!(
+            warehouse_path(&properties).unwrap().unwrap(),
+            directory.to_string_lossy().trim_start_matches('/')
+        );
+    }
+}
@@ -0,0 +1,132 @@
+/*!
+Resolution of the object store of a table, for catalogs whose tables are spread over several buckets
+or clouds.
+*/
+
+use std::{collections::HashMap, sync::Arc};
+
+use anyhow::Result;
+use iceberg_rs::{catalog::table_identifier::TableIdentifier, object_store::ObjectStore};
+
+/// Selects the object store of a table. The catalog consults the resolver with the identifier of
+/// the table and the location of its metadata file whenever it reads metadata. Tables for which the
+/// resolver returns `None` use the object store of the catalog.
+pub trait ObjectStoreResolver: Send + Sync {
+    /// Object store that holds the `location` of the table
+    fn resolve(
+        &self,
+        identifier: &TableIdentifier,
+        location: &str,
+    ) -> Result<Option<Arc<dyn ObjectStore>>>;
+}
+
+/// Resolver with object stores registered per namespace and per bucket. Namespaces take precedence
+/// over buckets.
+#[derive(Default)]
+pub struct ObjectStoreRegistry {
+    namespaces: HashMap<String, Arc<dyn ObjectStore>>,
+    buckets: HashMap<String, Arc<dyn ObjectStore>>,
+}
+
+impl ObjectStoreRegistry {
+    /// Create an empty registry
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Use the object store for all tables in the namespace
+    pub fn with_namespace(mut self, namespace: &str, object_store: Arc<dyn ObjectStore>) -> Self {
+        self.namespaces.insert(namespace.to_string(), object_store);
+        self
+    }
+
+    /// Use the object store for all locations in the bucket, given as `scheme://bucket`
+    pub fn with_bucket(mut self, bucket: &str, object_store: Arc<dyn ObjectStore>) -> Self {
+        self.buckets
+            .insert(bucket.trim_end_matches('/').to_string(), object_store);
+        self
+    }
+}
+
+impl ObjectStoreResolver for ObjectStoreRegistry {
+    fn resolve(
+        &self,
+        identifier: &TableIdentifier,
+        location: &str,
+    ) -> Result<Option<Arc<dyn ObjectStore>>> {
+        if let Some(object_store) = self.namespaces.get(&format!("{}", identifier.namespace())) {
+            return Ok(Some(Arc::clone(object_store)));
+        }
+        Ok(bucket(location).and_then(|bucket| self.buckets.get(bucket).cloned()))
+    }
+}
+
+/// The `scheme://bucket` part of a location, if it has a scheme
+fn bucket(location: &str) -> Option<&str> {
+    let authority_start = location.find("://")? + 3;
+    let authority_end = location[authority_start..]
+        .find('/')
+        .map(|end| authority_start + end)
+        .unwrap_or(location.len());
+    Some(&location[..authority_end])
+}
+
+/// Path of the location within its bucket. Locations without scheme are already paths.
+pub(crate) fn object_path(location: &str) -> &str {
+    match bucket(location) {
+        Some(bucket) => location[bucket.len()..].trim_start_matches('/'),
+        None => location,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use iceberg_rs::{
+        catalog::table_identifier::TableIdentifier,
+        object_store::{memory::InMemory, ObjectStore},
+    };
+
+    use super::{object_path, ObjectStoreRegistry, ObjectStoreResolver};
+
+    #[test]
+    fn test_resolve() {
+        let sales: Arc<dyn ObjectStore> = Arc::new(InMemory::new());
+        let bucket: Arc<dyn ObjectStore> = Arc::new(InMemory::new());
+        let registry = ObjectStoreRegistry::new()
+            .with_namespace("sales", Arc::clone(&sales))
+            .with_bucket("s3://bucket/", Arc::clone(&bucket));
+
+        let identifier = TableIdentifier::parse("sales.orders").unwrap();
+        let resolved = registry
+            .resolve(&identifier, "s3://bucket/orders/metadata/v1.metadata.json")
+            .unwrap()
+            .unwrap();
+        assert!(Arc::ptr_eq(&resolved, &sales));
+
+        let identifier = TableIdentifier::parse("test.table1").unwrap();
+        let resolved = registry
+            .resolve(&identifier, "s3://bucket/table1/metadata/v1.metadata.json")
+            .unwrap()
+            .unwrap();
+        assert!(Arc::ptr_eq(&resolved, &bucket));
+
+        assert!(registry
+            .resolve(&identifier, "data.db/test/table1/metadata/v1.metadata.json")
+            .unwrap()
+            .is_none());
+    }
+
+    #[test]
+    fn test_object_path() {
+        assert_eq!(
+            object_path("s3://bucket/table1/metadata/v1.metadata.json"),
+            "table1/metadata/v1.metadata.json"
+        );
+        assert_eq!(
+            object_path("data.db/test/table1/metadata/v1.metadata.json"),
+            "data.db/test/table1/metadata/v1.metadata.json"
+        );
+    }
+}
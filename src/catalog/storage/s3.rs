@@ -0,0 +1,133 @@
+/*!
+Amazon S3 and S3 compatible object stores like MinIO.
+*/
+
+use std::collections::HashMap;
+
+use anyhow::{anyhow, Result};
+use iceberg_rs::object_store::aws::{AmazonS3, AmazonS3Builder};
+
+use super::split_location;
+use crate::catalog::secret::Secret;
+
+/// Endpoint of an S3 compatible service
+pub static S3_ENDPOINT: &str = "s3.endpoint";
+/// Access key id for S3
+pub static S3_ACCESS_KEY_ID: &str = "s3.access-key-id";
+/// Secret access key for S3
+pub static S3_SECRET_ACCESS_KEY: &str = "s3.secret-access-key";
+/// Session token for S3
+pub static S3_SESSION_TOKEN: &str = "s3.session-token";
+/// Use path-style instead of virtual-hosted-style requests for S3
+pub static S3_PATH_STYLE_ACCESS: &str = "s3.path-style-access";
+/// Region of the object store
+pub static CLIENT_REGION: &str = "client.region";
+
+/// Configuration of an S3 object store.
+///
+/// Settings that aren't configured explicitly are taken from the standard AWS environment
+/// variables (`AWS_REGION`, `AWS_ACCESS_KEY_ID`, ...). Without access keys the store falls back to
+/// web identity tokens and the instance metadata service.
+#[derive(Debug, Clone)]
+pub struct S3Config {
+    bucket: String,
+    region: Option<String>,
+    endpoint: Option<String>,
+    access_key: Option<(String, Secret)>,
+    session_token: Option<Secret>,
+    path_style_access: bool,
+}
+
+impl S3Config {
+    /// Configuration for the given bucket
+    pub fn new(bucket: &str) -> Self {
+        S3Config {
+            bucket: bucket.to_string(),
+            region: None,
+            endpoint: None,
+            access_key: None,
+            session_token: None,
+            path_style_access: false,
+        }
+    }
+
+    /// Configuration for the bucket of the `warehouse` from catalog properties
+    pub fn from_properties(warehouse: &str, properties: &HashMap<String, String>) -> Result<Self> {
+        let (_, bucket, _) = split_location(warehouse)?;
+        let mut config = S3Config::new(bucket);
+        if let Some(region) = properties.get(CLIENT_REGION) {
+            config = config.with_region(region);
+        }
+        if let Some(endpoint) = properties.get(S3_ENDPOINT) {
+            config = config.with_endpoint(endpoint);
+        }
+        if let (Some(access_key_id), Some(secret_access_key)) = (
+            properties.get(S3_ACCESS_KEY_ID),
+            properties.get(S3_SECRET_ACCESS_KEY),
+        ) {
+            config = config.with_access_key(access_key_id, Secret::new(secret_access_key.as_str()));
+        }
+        if let Some(session_token) = properties.get(S3_SESSION_TOKEN) {
+            config = config.with_session_token(Secret::new(session_token.as_str()));
+        }
+        if let Some(path_style_access) = properties.get(S3_PATH_STYLE_ACCESS) {
+            config = config.with_path_style_access(path_style_access == "true");
+        }
+        Ok(config)
+    }
+
+    /// Set the region of the bucket
+    pub fn with_region(mut self, region: &str) -> Self {
+        self.region = Some(region.to_string());
+        self
+    }
+
+    /// Use an S3 compatible service like MinIO at the endpoint. Plain http is allowed if the
+    /// endpoint uses it.
+    pub fn with_endpoint(mut self, endpoint: &str) -> Self {
+        self.endpoint = Some(endpoint.to_string());
+        self
+    }
+
+    /// Authenticate with a static access key
+    pub fn with_access_key(mut self, access_key_id: &str, secret_access_key: Secret) -> Self {
+        self.access_key = Some((access_key_id.to_string(), secret_access_key));
+        self
+    }
+
+    /// Session token for temporary access keys
+    pub fn with_session_token(mut self, session_token: Secret) -> Self {
+        self.session_token = Some(session_token);
+        self
+    }
+
+    /// Address the bucket in the path instead of the host name, as required by MinIO
+    pub fn with_path_style_access(mut self, path_style_access: bool) -> Self {
+        self.path_style_access = path_style_access;
+        self
+    }
+
+    /// Build the object store
+    pub fn build(&self) -> Result<AmazonS3> {
+        let mut builder = AmazonS3Builder::from_env()
+            .with_bucket_name(&self.bucket)
+            .with_virtual_hosted_style_request(!self.path_style_access);
+        if let Some(region) = &self.region {
+            builder = builder.with_region(region);
+        }
+        if let Some(endpoint) = &self.endpoint {
+            builder = builder
+                .with_endpoint(endpoint)
+                .with_allow_http(endpoint.starts_with("http://"));
+        }
+        if let Some((access_key_id, secret_access_key)) = &self.access_key {
+            builder = builder
+                .with_access_key_id(access_key_id)
+                .with_secret_access_key(secret_access_key.expose());
+        }
+        if let Some(session_token) = &self.session_token {
+            builder = builder.with_token(session_token.expose());
+        }
+        builder.build().map_err(|err| anyhow!(err.to_string()))
+    }
+}
@@ -0,0 +1,176 @@
+/*!
+Pre-signed URLs for the metadata of tables.
+
+Lightweight clients like browser UIs that only inspect the metadata of a table shouldn't need
+credentials for the bucket. [signed_metadata_urls](PostgresCatalog::signed_metadata_urls) returns
+URLs for the current metadata file of a table, the manifest list of its current snapshot and the
+manifests in that list, which can be fetched with plain HTTP until they expire.
+
+The URLs are signed by the [UrlSigner] configured with
+[with_url_signer](crate::catalog::builder::PostgresCatalogBuilder::with_url_signer), for example
+with the pre-signing of the S3 or GCS SDK.
+*/
+
+use std::time::{Duration, SystemTime};
+
+use anyhow::{anyhow, Result};
+use apache_avro::{types::Value, Reader};
+use futures::future::try_join_all;
+use iceberg_rs::{catalog::table_identifier::TableIdentifier, object_store::path::Path};
+
+use super::resolver::object_path;
+use crate::catalog::{access::Action, PostgresCatalog};
+
+/// Signs URLs for locations in object storage
+#[async_trait::async_trait]
+pub trait UrlSigner: Send + Sync {
+    /// URL that allows to get the object at `location` until `expires_in` elapsed
+    async fn sign(&self, location: &str, expires_in: Duration) -> Result<String>;
+}
+
+/// Pre-signed URLs for the metadata of a table
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SignedMetadataUrls {
+    /// URL of the current metadata file
+    pub metadata: String,
+    /// URL of the manifest list of the current snapshot, if the table has a snapshot
+    pub manifest_list: Option<String>,
+    /// URLs of the manifests of the current snapshot
+    pub manifests: Vec<String>,
+    /// Time the URLs expire
+    pub expires_at: SystemTime,
+}
+
+impl PostgresCatalog {
+    /// Pre-signed URLs for the current metadata file of the table and the manifests of its current
+    /// snapshot, valid for `expires_in`. The caller has to be allowed to read the table.
+    pub async fn signed_metadata_urls(
+        &self,
+        identifier: &TableIdentifier,
+        expires_in: Duration,
+    ) -> Result<SignedMetadataUrls> {
+        let signer = self
+            .url_signer
+            .as_ref()
+            .ok_or_else(|| anyhow!("The catalog has no URL signer."))?;
+        let identifier = &self.case_sensitivity.normalize(identifier)?;
+        self.authorize(
+            Action::Read,
+            identifier.namespace(),
+            Some(identifier.name()),
+        )
+        .await?;
+        let expires_at = SystemTime::now() + expires_in;
+        let location = self.stored_metadata_location(identifier).await?;
+        let metadata = self.read_metadata_json(identifier, &location).await?;
+        let manifest_list =
+            current_manifest_list(&metadata).map(|list| self.resolved_location(list));
+        let manifests = match &manifest_list {
+            Some(manifest_list) => {
+                let object_store = self.object_store_for_location(identifier, manifest_list)?;
+                let bytes = object_store
+                    .get(&Path::from(object_path(manifest_list)))
+                    .await
+                    .map_err(|err| anyhow!(err.to_string()))?
+                    .bytes()
+                    .await
+                    .map_err(|err| anyhow!(err.to_string()))?;
+                manifest_paths(&bytes)?
+            }
+            None => Vec::new(),
+        };
+        let sign = |location: String| async move { signer.sign(&location, expires_in).await };
+        Ok(SignedMetadataUrls {
+            metadata: sign(self.resolved_location(&location)).await?,
+            manifest_list: match manifest_list {
+                Some(manifest_list) => Some(sign(manifest_list).await?),
+                None => None,
+            },
+            manifests: try_join_all(manifests.into_iter().map(sign)).await?,
+            expires_at,
+        })
+    }
+}
+
+/// Location of the manifest list of the current snapshot of the metadata
+fn current_manifest_list(metadata: &serde_json::Value) -> Option<&str> {
+    let current = metadata["current-snapshot-id"].as_i64()?;
+    metadata["snapshots"]
+        .as_array()?
+        .iter()
+        .find(|snapshot| snapshot["snapshot-id"].as_i64() == Some(current))?["manifest-list"]
+        .as_str()
+}
+
+/// Paths of the manifests in the avro manifest list
+fn manifest_paths(manifest_list: &[u8]) -> Result<Vec<String>> {
+    let reader = Reader::new(manifest_list).map_err(|err| anyhow!(err.to_string()))?;
+    let mut paths = Vec::new();
+    for value in reader {
+        let fields = match value.map_err(|err| anyhow!(err.to_string()))? {
+            Value::Record(fields) => fields,
+            _ => {
+                return Err(anyhow!(
+                    "The manifest list contains an entry that is no record."
+                ))
+            }
+        };
+        match fields.into_iter().find(|(name, _)| name == "manifest_path") {
+            Some((_, Value::String(path))) => paths.push(path),
+            _ => {
+                return Err(anyhow!(
+                    "An entry of the manifest list has no manifest path."
+                ))
+            }
+        }
+    }
+    Ok(paths)
+}
+
+#[cfg(test)]
+mod tests {
+    use apache_avro::{types::Record, Schema, Writer};
+
+    use super::{current_manifest_list, manifest_paths};
+
+    #[test]
+    fn test_current_manifest_list() {
+        let metadata = serde_json::json!({
+            "current-snapshot-id": 2,
+            "snapshots": [
+                {"snapshot-id": 1, "manifest-list": "s3://bucket/table/metadata/snap-1.avro"},
+                {"snapshot-id": 2, "manifest-list": "s3://bucket/table/metadata/snap-2.avro"}
+            ]
+        });
+        assert_eq!(
+            current_manifest_list(&metadata),
+            Some("s3://bucket/table/metadata/snap-2.avro")
+        );
+        assert_eq!(
+            current_manifest_list(&serde_json::json!({"current-snapshot-id": -1})),
+            None
+        );
+    }
+
+    #[test]
+    fn test_manifest_paths() {
+        let schema = Schema::parse_str(
+            r#"{"type": "record", "name": "manifest_file", "fields": [
+                {"name": "manifest_path", "type": "string"},
+                {"name": "manifest_length", "type": "long"}
+            ]}"#,
+        )
+        .unwrap();
+        let mut writer = Writer::new(&schema, Vec::new());
+        for path in ["s3://bucket/m-1.avro", "s3://bucket/m-2.avro"] {
+            let mut record = Record::new(&schema).unwrap();
+            record.put("manifest_path", path);
+            record.put("manifest_length", 1024i64);
+            writer.append(record).unwrap();
+        }
+        assert_eq!(
+            manifest_paths(&writer.into_inner().unwrap()).unwrap(),
+            vec!["s3://bucket/m-1.avro", "s3://bucket/m-2.avro"]
+        );
+    }
+}
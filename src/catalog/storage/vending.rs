@@ -0,0 +1,155 @@
+/*!
+Vending of short-lived storage credentials for tables.
+
+Instead of giving every engine long-lived credentials for the whole warehouse, the catalog can mint
+credentials that are scoped to the location of a single table when the table is loaded. The
+[CredentialVendor] configured with
+[with_credential_vendor](crate::catalog::builder::PostgresCatalogBuilder::with_credential_vendor)
+does the minting, for example with AWS STS and a session policy for the prefix of the table.
+[load_table_with_credentials](PostgresCatalog::load_table_with_credentials) returns the table
+together with the credentials, which build an object store with
+[StorageCredentials::object_store].
+
+The credentials grant write access if the access policy of the catalog allows the principal to
+commit to the table, and read access otherwise.
+*/
+
+use std::{collections::HashMap, fmt, sync::Arc, time::SystemTime};
+
+use anyhow::{anyhow, Result};
+use iceberg_rs::{
+    catalog::{table_identifier::TableIdentifier, Catalog},
+    object_store::ObjectStore,
+    table::Table,
+};
+
+use super::{object_store_from_properties, WAREHOUSE};
+use crate::catalog::{access::Action, PostgresCatalog};
+
+/// Access that vended credentials grant
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StorageAccess {
+    /// Read the files of the table
+    Read,
+    /// Read and write the files of the table
+    ReadWrite,
+}
+
+/// Storage credentials scoped to a location
+#[derive(Clone, PartialEq, Eq)]
+pub struct StorageCredentials {
+    /// Location the credentials grant access to, like `s3://bucket/warehouse/table`
+    pub prefix: String,
+    /// Configuration of the object store with the credentials, with the keys of the storage
+    /// properties, like `s3.access-key-id`, `s3.secret-access-key` and `s3.session-token`
+    pub config: HashMap<String, String>,
+    /// Time the credentials expire, if they expire
+    pub expires_at: Option<SystemTime>,
+}
+
+impl StorageCredentials {
+    /// Object store for the prefix that authenticates with the credentials
+    pub fn object_store(&self) -> Result<Arc<dyn ObjectStore>> {
+        let mut properties = self.config.clone();
+        properties.insert(WAREHOUSE.to_string(), self.prefix.clone());
+        object_store_from_properties(&properties)?
+            .ok_or_else(|| anyhow!("The credentials for {} have no object store.", self.prefix))
+    }
+}
+
+impl fmt::Debug for StorageCredentials {
+    /// The values of the configuration contain secrets, only the keys are shown.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut keys = self.config.keys().collect::<Vec<_>>();
+        keys.sort();
+        f.debug_struct("StorageCredentials")
+            .field("prefix", &self.prefix)
+            .field("config", &keys)
+            .field("expires_at", &self.expires_at)
+            .finish()
+    }
+}
+
+/// Mints storage credentials for the location of a table
+#[async_trait::async_trait]
+pub trait CredentialVendor: Send + Sync {
+    /// Credentials with the access to the files below `location`, the location of the table
+    async fn vend(
+        &self,
+        identifier: &TableIdentifier,
+        location: &str,
+        access: StorageAccess,
+    ) -> Result<StorageCredentials>;
+}
+
+impl PostgresCatalog {
+    /// Load the table together with credentials for its location, `None` if the catalog has no
+    /// credential vendor.
+    pub async fn load_table_with_credentials(
+        self: Arc<Self>,
+        identifier: TableIdentifier,
+    ) -> Result<(Table, Option<StorageCredentials>)> {
+        let table = Arc::clone(&self).load_table(identifier.clone()).await?;
+        let metadata =
+            serde_json::to_value(table.metadata()).map_err(|err| anyhow!(err.to_string()))?;
+        let location = metadata["location"]
+            .as_str()
+            .ok_or_else(|| anyhow!("The table metadata contains no location."))?;
+        let credentials = self.vend_credentials(&identifier, location).await?;
+        Ok((table, credentials))
+    }
+
+    /// Credentials for the location of the table from the credential vendor, `None` if the
+    /// catalog has none. The caller has to be allowed to read the table.
+    pub async fn vend_credentials(
+        &self,
+        identifier: &TableIdentifier,
+        location: &str,
+    ) -> Result<Option<StorageCredentials>> {
+        let vendor = match &self.credential_vendor {
+            Some(vendor) => vendor,
+            None => return Ok(None),
+        };
+        let identifier = self.case_sensitivity.normalize(identifier)?;
+        self.authorize(
+            Action::Read,
+            identifier.namespace(),
+            Some(identifier.name()),
+        )
+        .await?;
+        let access = if !self.read_only
+            && self
+                .authorize(
+                    Action::Commit,
+                    identifier.namespace(),
+                    Some(identifier.name()),
+                )
+                .await
+                .is_ok()
+        {
+            StorageAccess::ReadWrite
+        } else {
+            StorageAccess::Read
+        };
+        let location = self.resolved_location(location);
+        vendor.vend(&identifier, &location, access).await.map(Some)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use super::StorageCredentials;
+
+    #[test]
+    fn test_credentials() {
+        let credentials = StorageCredentials {
+            prefix: "memory://".to_string(),
+            config: HashMap::from([("s3.secret-access-key".to_string(), "hunter2".to_string())]),
+            expires_at: None,
+        };
+        assert!(!format!("{:?}", credentials).contains("hunter2"));
+        assert!(credentials.object_store().is_ok());
+    }
+}
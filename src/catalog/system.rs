@@ -0,0 +1,291 @@
+/*!
+Tables about the catalog itself, in the style of an information schema.
+
+[PostgresCatalog::system_tables] materializes four tables for the tables in a namespace and its
+children from the catalog entries and the current metadata files:
+
+- `tables`: one row per table with its metadata location and current snapshot
+- `snapshots`: one row per snapshot of every table
+- `refs`: one row per branch and tag of every table
+- `properties`: one row per table property
+
+The rows are read once per call, with the DataFusion integration they can be queried with SQL in
+the [SYSTEM_SCHEMA] schema.
+*/
+
+use anyhow::{anyhow, Result};
+use iceberg_rs::catalog::{namespace::Namespace, table_identifier::TableIdentifier};
+use serde_json::Value;
+
+use super::{
+    access::Action,
+    history::current_snapshot_id,
+    namespace::{namespace_condition, table_identifier},
+    query::literal,
+    PostgresCatalog, CATALOG_NAME_COLUMN, METADATA_LOCATION_COLUMN, TABLE_NAMESPACE_COLUMN,
+    TABLE_NAME_COLUMN,
+};
+
+/// Name of the schema with the system tables in the DataFusion integration
+pub static SYSTEM_SCHEMA: &str = "system";
+
+/// Row of the `tables` table
+#[derive(Debug, Clone)]
+pub struct TableRow {
+    /// Identifier of the table
+    pub identifier: TableIdentifier,
+    /// Location of the current metadata file
+    pub metadata_location: String,
+    /// Location of the table data
+    pub location: String,
+    /// Format version of the metadata
+    pub format_version: i64,
+    /// Id of the current snapshot, if the table has one
+    pub current_snapshot_id: Option<i64>,
+    /// Time of the last metadata update in milliseconds since the epoch
+    pub last_updated_ms: i64,
+}
+
+/// Row of the `snapshots` table
+#[derive(Debug, Clone)]
+pub struct SnapshotRow {
+    /// Identifier of the table
+    pub identifier: TableIdentifier,
+    /// Id of the snapshot
+    pub snapshot_id: i64,
+    /// Id of the parent snapshot
+    pub parent_snapshot_id: Option<i64>,
+    /// Sequence number of the snapshot, 0 for format version 1
+    pub sequence_number: i64,
+    /// Time the snapshot was created in milliseconds since the epoch
+    pub timestamp_ms: i64,
+    /// Operation that created the snapshot, like `append`
+    pub operation: Option<String>,
+    /// Location of the manifest list
+    pub manifest_list: Option<String>,
+}
+
+/// Row of the `refs` table
+#[derive(Debug, Clone)]
+pub struct RefRow {
+    /// Identifier of the table
+    pub identifier: TableIdentifier,
+    /// Name of the branch or tag
+    pub name: String,
+    /// `branch` or `tag`
+    pub ref_type: String,
+    /// Snapshot the reference points to
+    pub snapshot_id: i64,
+}
+
+/// Row of the `properties` table
+#[derive(Debug, Clone)]
+pub struct PropertyRow {
+    /// Identifier of the table
+    pub identifier: TableIdentifier,
+    /// Key of the property
+    pub key: String,
+    /// Value of the property
+    pub value: String,
+}
+
+/// Rows of the system tables
+#[derive(Debug, Clone, Default)]
+pub struct SystemTables {
+    /// Rows of the `tables` table
+    pub tables: Vec<TableRow>,
+    /// Rows of the `snapshots` table
+    pub snapshots: Vec<SnapshotRow>,
+    /// Rows of the `refs` table
+    pub refs: Vec<RefRow>,
+    /// Rows of the `properties` table
+    pub properties: Vec<PropertyRow>,
+}
+
+impl SystemTables {
+    /// Add the rows of a table from its metadata.
+    fn extend(
+        &mut self,
+        identifier: &TableIdentifier,
+        metadata_location: String,
+        metadata: &Value,
+    ) -> Result<()> {
+        let current_snapshot_id = current_snapshot_id(metadata);
+        self.tables.push(TableRow {
+            identifier: identifier.clone(),
+            metadata_location,
+            location: metadata["location"]
+                .as_str()
+                .ok_or_else(|| anyhow!("The metadata of {} has no location.", identifier))?
+                .to_string(),
+            format_version: metadata["format-version"].as_i64().unwrap_or(1),
+            current_snapshot_id,
+            last_updated_ms: metadata["last-updated-ms"].as_i64().unwrap_or_default(),
+        });
+        self.snapshots.extend(snapshot_rows(identifier, metadata)?);
+        match metadata["refs"].as_object() {
+            Some(refs) => {
+                for (name, reference) in refs {
+                    self.refs.push(RefRow {
+                        identifier: identifier.clone(),
+                        name: name.clone(),
+                        ref_type: reference["type"].as_str().unwrap_or("branch").to_string(),
+                        snapshot_id: reference["snapshot-id"].as_i64().ok_or_else(|| {
+                            anyhow!("The reference {} of {} has no snapshot.", name, identifier)
+                        })?,
+                    });
+                }
+            }
+            // Metadata without refs has an implicit main branch at the current snapshot.
+            None => {
+                if let Some(snapshot_id) = current_snapshot_id {
+                    self.refs.push(RefRow {
+                        identifier: identifier.clone(),
+                        name: "main".to_string(),
+                        ref_type: "branch".to_string(),
+                        snapshot_id,
+                    });
+                }
+            }
+        }
+        for (key, value) in metadata["properties"].as_object().into_iter().flatten() {
+            self.properties.push(PropertyRow {
+                identifier: identifier.clone(),
+                key: key.clone(),
+                value: value
+                    .as_str()
+                    .map(str::to_string)
+                    .unwrap_or_else(|| value.to_string()),
+            });
+        }
+        Ok(())
+    }
+}
+
+/// Rows of the `snapshots` table for the snapshots of the metadata
+pub(crate) fn snapshot_rows(
+    identifier: &TableIdentifier,
+    metadata: &Value,
+) -> Result<Vec<SnapshotRow>> {
+    metadata["snapshots"]
+        .as_array()
+        .into_iter()
+        .flatten()
+        .map(|snapshot| {
+            Ok(SnapshotRow {
+                identifier: identifier.clone(),
+                snapshot_id: snapshot["snapshot-id"].as_i64().ok_or_else(|| {
+                    anyhow!("A snapshot in the metadata of {} has no id.", identifier)
+                })?,
+                parent_snapshot_id: snapshot["parent-snapshot-id"].as_i64(),
+                sequence_number: snapshot["sequence-number"].as_i64().unwrap_or_default(),
+                timestamp_ms: snapshot["timestamp-ms"].as_i64().unwrap_or_default(),
+                operation: snapshot["summary"]["operation"]
+                    .as_str()
+                    .map(str::to_string),
+                manifest_list: snapshot["manifest-list"].as_str().map(str::to_string),
+            })
+        })
+        .collect()
+}
+
+impl PostgresCatalog {
+    /// Rows of the system tables for the tables in the namespace and its children. Reads the
+    /// current metadata file of every table.
+    pub async fn system_tables(&self, namespace: &Namespace) -> Result<SystemTables> {
+        let namespace = &self.case_sensitivity.normalize_namespace(namespace)?;
+        self.authorize(Action::Read, namespace, None).await?;
+        let rows = self
+            .query(
+                self.read_connection(),
+                &("SELECT ".to_string()
+                    + TABLE_NAMESPACE_COLUMN
+                    + ", "
+                    + TABLE_NAME_COLUMN
+                    + ", "
+                    + METADATA_LOCATION_COLUMN
+                    + " FROM "
+                    + &self.catalog_table.qualified
+                    + " WHERE "
+                    + CATALOG_NAME_COLUMN
+                    + " = "
+                    + &literal(&self.name)
+                    + " AND ("
+                    + &namespace_condition(namespace)
+                    + ") ORDER BY "
+                    + TABLE_NAMESPACE_COLUMN
+                    + ", "
+                    + TABLE_NAME_COLUMN
+                    + ";"),
+            )
+            .await?;
+        let mut tables = SystemTables::default();
+        for row in rows {
+            let identifier = table_identifier(
+                &row.try_get_string(TABLE_NAMESPACE_COLUMN)?,
+                &row.try_get_string(TABLE_NAME_COLUMN)?,
+            )?;
+            let metadata_location =
+                self.resolved_location(&row.try_get_string(METADATA_LOCATION_COLUMN)?);
+            let metadata = self
+                .read_metadata_json(&identifier, &metadata_location)
+                .await?;
+            tables.extend(&identifier, metadata_location, &metadata)?;
+        }
+        Ok(tables)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use iceberg_rs::catalog::table_identifier::TableIdentifier;
+    use serde_json::json;
+
+    use super::SystemTables;
+
+    #[test]
+    fn test_extend() {
+        let identifier = TableIdentifier::parse("ns.table1").unwrap();
+        let metadata = json!({
+            "format-version": 2,
+            "location": "s3://bucket/ns/table1",
+            "last-updated-ms": 1000,
+            "current-snapshot-id": 2,
+            "snapshots": [
+                {"snapshot-id": 1, "sequence-number": 1, "timestamp-ms": 500, "manifest-list": "s3://bucket/ns/table1/metadata/snap-1.avro", "summary": {"operation": "append"}},
+                {"snapshot-id": 2, "parent-snapshot-id": 1, "sequence-number": 2, "timestamp-ms": 1000, "manifest-list": "s3://bucket/ns/table1/metadata/snap-2.avro", "summary": {"operation": "overwrite"}}
+            ],
+            "refs": {
+                "main": {"snapshot-id": 2, "type": "branch"},
+                "v1": {"snapshot-id": 1, "type": "tag"}
+            },
+            "properties": {"owner": "analytics"}
+        });
+        let mut tables = SystemTables::default();
+        tables
+            .extend(&identifier, "metadata.json".to_string(), &metadata)
+            .unwrap();
+        assert_eq!(tables.tables.len(), 1);
+        assert_eq!(tables.tables[0].current_snapshot_id, Some(2));
+        assert_eq!(tables.snapshots.len(), 2);
+        assert_eq!(tables.snapshots[1].parent_snapshot_id, Some(1));
+        assert_eq!(tables.snapshots[1].operation.as_deref(), Some("overwrite"));
+        assert_eq!(tables.refs.len(), 2);
+        assert!(tables
+            .refs
+            .iter()
+            .any(|reference| reference.name == "v1" && reference.ref_type == "tag"));
+        assert_eq!(tables.properties[0].key, "owner");
+
+        let mut tables = SystemTables::default();
+        tables
+            .extend(
+                &identifier,
+                "metadata.json".to_string(),
+                &json!({"format-version": 1, "location": "s3://bucket/ns/table1", "current-snapshot-id": -1}),
+            )
+            .unwrap();
+        assert_eq!(tables.tables[0].current_snapshot_id, None);
+        assert!(tables.refs.is_empty());
+    }
+}
@@ -0,0 +1,184 @@
+/*!
+Names and location of the postgres tables that store the catalog entries, the catalog properties
+the tenants, the dropped tables, the metrics reports, the changes, the commit statistics, the
+namespace defaults, the audit of administrative operations, the commit locks and the table keys of
+the catalog.
+*/
+
+use anyhow::{anyhow, Result};
+
+use super::query::identifier;
+
+/// Name of the catalog table without prefix
+static CATALOG_TABLE_NAME: &str = "iceberg_tables";
+/// Name of the properties table without prefix
+static PROPERTIES_TABLE_NAME: &str = "iceberg_catalog_properties";
+/// Name of the tenants table without prefix
+static TENANTS_TABLE_NAME: &str = "iceberg_catalog_tenants";
+/// Name of the table with dropped tables without prefix
+static DROPPED_TABLE_NAME: &str = "iceberg_dropped_tables";
+/// Name of the table with metrics reports without prefix
+static METRICS_TABLE_NAME: &str = "iceberg_metrics_reports";
+/// Name of the table with captured changes without prefix
+static CHANGES_TABLE_NAME: &str = "iceberg_catalog_changes";
+/// Name of the table with commit statistics without prefix
+static STATS_TABLE_NAME: &str = "iceberg_commit_stats";
+/// Name of the table with default table properties of namespaces without prefix
+static NAMESPACE_DEFAULTS_TABLE_NAME: &str = "iceberg_namespace_defaults";
+/// Name of the table with the audit of administrative operations without prefix
+static AUDIT_TABLE_NAME: &str = "iceberg_admin_audit";
+/// Name of the table with the commit locks without prefix
+static LOCKS_TABLE_NAME: &str = "iceberg_commit_locks";
+/// Name of the table with the encryption keys of tables without prefix
+static KEYS_TABLE_NAME: &str = "iceberg_table_keys";
+/// Maximum length of a postgres identifier in bytes
+static MAX_IDENTIFIER_LENGTH: usize = 63;
+/// Longest suffix that is appended to the table name to name its indexes
+static MAX_INDEX_SUFFIX_LENGTH: usize = 16;
+
+/// Qualified name of the catalog table
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct CatalogTable {
+    schema: Option<String>,
+    name: String,
+    /// Quoted and schema-qualified name for use in statements
+    pub(crate) qualified: String,
+    /// Quoted and schema-qualified name of the table with the catalog properties
+    pub(crate) properties: String,
+    /// Quoted and schema-qualified name of the table with the tenants
+    pub(crate) tenants: String,
+    /// Quoted and schema-qualified name of the table with the dropped tables
+    pub(crate) dropped: String,
+    /// Quoted and schema-qualified name of the table with the metrics reports
+    pub(crate) metrics: String,
+    /// Quoted and schema-qualified name of the table with the captured changes
+    pub(crate) changes: String,
+    /// Quoted and schema-qualified name of the table with the commit statistics
+    pub(crate) stats: String,
+    /// Quoted and schema-qualified name of the table with the default properties of namespaces
+    pub(crate) namespace_defaults: String,
+    /// Quoted and schema-qualified name of the table with the audit of administrative operations
+    pub(crate) audit: String,
+    /// Quoted and schema-qualified name of the table with the commit locks
+    pub(crate) locks: String,
+    /// Quoted and schema-qualified name of the table with the encryption keys of tables
+    pub(crate) keys: String,
+}
+
+impl Default for CatalogTable {
+    fn default() -> Self {
+        CatalogTable {
+            schema: None,
+            name: CATALOG_TABLE_NAME.to_string(),
+            qualified: identifier(CATALOG_TABLE_NAME),
+            properties: identifier(PROPERTIES_TABLE_NAME),
+            tenants: identifier(TENANTS_TABLE_NAME),
+            dropped: identifier(DROPPED_TABLE_NAME),
+            metrics: identifier(METRICS_TABLE_NAME),
+            changes: identifier(CHANGES_TABLE_NAME),
+            stats: identifier(STATS_TABLE_NAME),
+            namespace_defaults: identifier(NAMESPACE_DEFAULTS_TABLE_NAME),
+            audit: identifier(AUDIT_TABLE_NAME),
+            locks: identifier(LOCKS_TABLE_NAME),
+            keys: identifier(KEYS_TABLE_NAME),
+        }
+    }
+}
+
+impl CatalogTable {
+    /// Catalog table `prefix` + `iceberg_tables` in `schema`, or in the first schema of the
+    /// `search_path` if no schema is given.
+    pub(crate) fn new(schema: Option<&str>, prefix: &str) -> Result<Self> {
+        let name = prefix.to_string() + CATALOG_TABLE_NAME;
+        if name.len() + MAX_INDEX_SUFFIX_LENGTH > MAX_IDENTIFIER_LENGTH {
+            return Err(anyhow!(
+                "The prefix {} of the catalog table is too long.",
+                prefix
+            ));
+        }
+        if let Some(schema) = schema {
+            if schema.is_empty() || schema.len() > MAX_IDENTIFIER_LENGTH {
+                return Err(anyhow!("The schema name {:?} is invalid.", schema));
+            }
+        }
+        let qualify = |name: &str| match schema {
+            Some(schema) => identifier(schema) + "." + &identifier(name),
+            None => identifier(name),
+        };
+        Ok(CatalogTable {
+            schema: schema.map(ToString::to_string),
+            qualified: qualify(&name),
+            properties: qualify(&(prefix.to_string() + PROPERTIES_TABLE_NAME)),
+            tenants: qualify(&(prefix.to_string() + TENANTS_TABLE_NAME)),
+            dropped: qualify(&(prefix.to_string() + DROPPED_TABLE_NAME)),
+            metrics: qualify(&(prefix.to_string() + METRICS_TABLE_NAME)),
+            changes: qualify(&(prefix.to_string() + CHANGES_TABLE_NAME)),
+            stats: qualify(&(prefix.to_string() + STATS_TABLE_NAME)),
+            namespace_defaults: qualify(&(prefix.to_string() + NAMESPACE_DEFAULTS_TABLE_NAME)),
+            audit: qualify(&(prefix.to_string() + AUDIT_TABLE_NAME)),
+            locks: qualify(&(prefix.to_string() + LOCKS_TABLE_NAME)),
+            keys: qualify(&(prefix.to_string() + KEYS_TABLE_NAME)),
+            name,
+        })
+    }
+
+    /// Quoted schema of the table, if it was given explicitly
+    pub(crate) fn schema(&self) -> Option<String> {
+        self.schema.as_deref().map(identifier)
+    }
+
+    /// Quoted name for an index of the table. Indexes are always created in the schema of the
+    /// table, so the name is not qualified.
+    pub(crate) fn index(&self, suffix: &str) -> String {
+        identifier(&(self.name.clone() + "_" + suffix))
+    }
+
+    /// Quoted name for a row-level security policy. Policy names are scoped to their table, the
+    /// prefix keeps them apart from policies of other tools.
+    pub(crate) fn policy(&self, suffix: &str) -> String {
+        self.index(suffix)
+    }
+
+    /// Name of a notification channel of the table. Channels are not quoted in `pg_notify`, so
+    /// the name is returned as is.
+    pub(crate) fn channel(&self, suffix: &str) -> String {
+        self.name.clone() + "_" + suffix
+    }
+
+    /// Quoted and schema-qualified name for a function that belongs to the table
+    pub(crate) fn function(&self, suffix: &str) -> String {
+        let name = identifier(&(self.name.clone() + "_" + suffix));
+        match self.schema() {
+            Some(schema) => schema + "." + &name,
+            None => name,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::CatalogTable;
+
+    #[test]
+    fn test_catalog_table() {
+        assert_eq!(CatalogTable::default().qualified, "\"iceberg_tables\"");
+        let table = CatalogTable::new(Some("iceberg"), "dev_").unwrap();
+        assert_eq!(table.qualified, "\"iceberg\".\"dev_iceberg_tables\"");
+        assert_eq!(
+            table.properties,
+            "\"iceberg\".\"dev_iceberg_catalog_properties\""
+        );
+        assert_eq!(table.schema().unwrap(), "\"iceberg\"");
+        assert_eq!(
+            table.index("location_idx"),
+            "\"dev_iceberg_tables_location_idx\""
+        );
+        assert_eq!(
+            table.function("record_change"),
+            "\"iceberg\".\"dev_iceberg_tables_record_change\""
+        );
+        assert_eq!(table.channel("exists"), "dev_iceberg_tables_exists");
+        assert!(CatalogTable::new(Some(""), "").is_err());
+        assert!(CatalogTable::new(None, &"a".repeat(40)).is_err());
+    }
+}
@@ -0,0 +1,291 @@
+/*!
+Registry of the encryption keys of tables.
+
+Encrypted Iceberg tables encrypt their files with data encryption keys, which are stored wrapped by
+a master key of a key management service. The catalog keeps the metadata of these keys, the key
+id, the id of the master key in the KMS and the wrapped key material, in the table keys table, so
+that engines can fetch them together with the table through
+[load_table_with_keys](PostgresCatalog::load_table_with_keys). The catalog never sees unwrapped
+keys, unwrapping them is up to the engine and the KMS.
+
+Keys belong to the uuid of a table, so they follow the table when it is renamed, dropped and
+restored. Tables registered by earlier versions of the catalog have no uuid in their entry until
+their next commit and can't have keys before.
+*/
+
+use std::{collections::HashMap, sync::Arc};
+
+use anyhow::{anyhow, Result};
+use iceberg_rs::{
+    catalog::{table_identifier::TableIdentifier, Catalog},
+    table::Table,
+};
+
+use super::{
+    access::Action,
+    namespace::{namespace_key, table_condition},
+    query::literal,
+    tags::parse_tags,
+    PostgresCatalog, CATALOG_NAME_COLUMN, CREATED_AT_COLUMN, TABLE_NAMESPACE_COLUMN,
+    TABLE_NAME_COLUMN, TABLE_UUID_COLUMN,
+};
+
+static KEY_ID_COLUMN: &str = "key_id";
+static KMS_KEY_ID_COLUMN: &str = "kms_key_id";
+static WRAPPED_KEY_COLUMN: &str = "wrapped_key";
+static KEY_PROPERTIES_COLUMN: &str = "key_properties";
+
+/// Metadata of an encryption key of a table
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TableKey {
+    /// Id of the key, as referenced by the encrypted files of the table
+    pub key_id: String,
+    /// Id of the master key in the KMS that wraps the key
+    pub kms_key_id: Option<String>,
+    /// Key material wrapped by the master key, for example base64 encoded
+    pub wrapped_key: String,
+    /// Additional properties of the key, like its algorithm
+    pub properties: HashMap<String, String>,
+}
+
+impl PostgresCatalog {
+    /// Store the key for the table. A key of the table with the same id is replaced.
+    pub async fn put_table_key(&self, identifier: &TableIdentifier, key: &TableKey) -> Result<()> {
+        self.check_writable("Storing the table key")?;
+        let identifier = &self.case_sensitivity.normalize(identifier)?;
+        self.authorize(
+            Action::Commit,
+            identifier.namespace(),
+            Some(identifier.name()),
+        )
+        .await?;
+        let properties =
+            serde_json::to_string(&key.properties).map_err(|err| anyhow!(err.to_string()))?;
+        self.mark_write();
+        let n_rows = self
+            .execute(
+                &self.primary,
+                &("INSERT INTO ".to_string()
+                    + &self.catalog_table.keys
+                    + " ("
+                    + CATALOG_NAME_COLUMN
+                    + ", "
+                    + TABLE_UUID_COLUMN
+                    + ", "
+                    + KEY_ID_COLUMN
+                    + ", "
+                    + KMS_KEY_ID_COLUMN
+                    + ", "
+                    + WRAPPED_KEY_COLUMN
+                    + ", "
+                    + KEY_PROPERTIES_COLUMN
+                    + ") SELECT "
+                    + CATALOG_NAME_COLUMN
+                    + ", "
+                    + TABLE_UUID_COLUMN
+                    + ", "
+                    + &literal(&key.key_id)
+                    + ", "
+                    + &key
+                        .kms_key_id
+                        .as_deref()
+                        .map(literal)
+                        .unwrap_or_else(|| "NULL".to_string())
+                    + ", "
+                    + &literal(&key.wrapped_key)
+                    + ", "
+                    + &literal(&properties)
+                    + "::JSONB FROM "
+                    + &self.catalog_table.qualified
+                    + " WHERE "
+                    + &table_condition(&self.name, identifier)
+                    + " AND "
+                    + TABLE_UUID_COLUMN
+                    + " IS NOT NULL ON CONFLICT ("
+                    + CATALOG_NAME_COLUMN
+                    + ", "
+                    + TABLE_UUID_COLUMN
+                    + ", "
+                    + KEY_ID_COLUMN
+                    + ") DO UPDATE SET "
+                    + KMS_KEY_ID_COLUMN
+                    + " = EXCLUDED."
+                    + KMS_KEY_ID_COLUMN
+                    + ", "
+                    + WRAPPED_KEY_COLUMN
+                    + " = EXCLUDED."
+                    + WRAPPED_KEY_COLUMN
+                    + ", "
+                    + KEY_PROPERTIES_COLUMN
+                    + " = EXCLUDED."
+                    + KEY_PROPERTIES_COLUMN
+                    + ";"),
+            )
+            .await?;
+        if n_rows == 1 {
+            Ok(())
+        } else {
+            Err(anyhow!(
+                "Storing the table key failed. No table with a uuid matched the identifier."
+            ))
+        }
+    }
+
+    /// Keys of the table, ordered by their id
+    pub async fn table_keys(&self, identifier: &TableIdentifier) -> Result<Vec<TableKey>> {
+        let identifier = &self.case_sensitivity.normalize(identifier)?;
+        self.authorize(
+            Action::Read,
+            identifier.namespace(),
+            Some(identifier.name()),
+        )
+        .await?;
+        // The join keeps a row for tables without keys, to tell them apart from missing tables.
+        let rows = self
+            .query(
+                self.read_connection(),
+                &("SELECT table_key.".to_string()
+                    + KEY_ID_COLUMN
+                    + ", table_key."
+                    + KMS_KEY_ID_COLUMN
+                    + ", table_key."
+                    + WRAPPED_KEY_COLUMN
+                    + ", table_key."
+                    + KEY_PROPERTIES_COLUMN
+                    + "::TEXT AS "
+                    + KEY_PROPERTIES_COLUMN
+                    + " FROM "
+                    + &self.catalog_table.qualified
+                    + " AS entry LEFT JOIN "
+                    + &self.catalog_table.keys
+                    + " AS table_key ON table_key."
+                    + CATALOG_NAME_COLUMN
+                    + " = entry."
+                    + CATALOG_NAME_COLUMN
+                    + " AND table_key."
+                    + TABLE_UUID_COLUMN
+                    + " = entry."
+                    + TABLE_UUID_COLUMN
+                    + " WHERE entry."
+                    + CATALOG_NAME_COLUMN
+                    + " = "
+                    + &literal(&self.name)
+                    + " AND entry."
+                    + TABLE_NAMESPACE_COLUMN
+                    + " = "
+                    + &literal(&namespace_key(identifier.namespace()))
+                    + " AND entry."
+                    + TABLE_NAME_COLUMN
+                    + " = "
+                    + &literal(identifier.name())
+                    + " ORDER BY table_key."
+                    + KEY_ID_COLUMN
+                    + ";"),
+            )
+            .await?;
+        if rows.is_empty() {
+            return Err(anyhow!(
+                "Getting the table keys failed. No table matched the identifier."
+            ));
+        }
+        let mut keys = Vec::new();
+        for row in &rows {
+            let key_id = match row.try_get_opt_string(KEY_ID_COLUMN)? {
+                Some(key_id) => key_id,
+                None => continue,
+            };
+            keys.push(TableKey {
+                key_id,
+                kms_key_id: row.try_get_opt_string(KMS_KEY_ID_COLUMN)?,
+                wrapped_key: row.try_get_string(WRAPPED_KEY_COLUMN)?,
+                properties: parse_tags(&row.try_get_string(KEY_PROPERTIES_COLUMN)?)?,
+            });
+        }
+        Ok(keys)
+    }
+
+    /// Remove the key with the id from the table. Returns whether the key existed.
+    pub async fn remove_table_key(
+        &self,
+        identifier: &TableIdentifier,
+        key_id: &str,
+    ) -> Result<bool> {
+        self.check_writable("Removing the table key")?;
+        let identifier = &self.case_sensitivity.normalize(identifier)?;
+        self.authorize(
+            Action::Commit,
+            identifier.namespace(),
+            Some(identifier.name()),
+        )
+        .await?;
+        self.mark_write();
+        let n_rows = self
+            .execute(
+                &self.primary,
+                &("DELETE FROM ".to_string()
+                    + &self.catalog_table.keys
+                    + " WHERE "
+                    + CATALOG_NAME_COLUMN
+                    + " = "
+                    + &literal(&self.name)
+                    + " AND "
+                    + KEY_ID_COLUMN
+                    + " = "
+                    + &literal(key_id)
+                    + " AND "
+                    + TABLE_UUID_COLUMN
+                    + " = (SELECT "
+                    + TABLE_UUID_COLUMN
+                    + " FROM "
+                    + &self.catalog_table.qualified
+                    + " WHERE "
+                    + &table_condition(&self.name, identifier)
+                    + ");"),
+            )
+            .await?;
+        Ok(n_rows > 0)
+    }
+
+    /// Load the table together with its keys.
+    pub async fn load_table_with_keys(
+        self: Arc<Self>,
+        identifier: TableIdentifier,
+    ) -> Result<(Table, Vec<TableKey>)> {
+        let keys = self.table_keys(&identifier).await?;
+        let table = self.load_table(identifier).await?;
+        Ok((table, keys))
+    }
+
+    /// Create the table with the keys of tables.
+    pub(crate) async fn create_keys_table(&self) -> Result<()> {
+        self.execute(
+            &self.primary,
+            &("CREATE TABLE IF NOT EXISTS ".to_string()
+                + &self.catalog_table.keys
+                + " ("
+                + CATALOG_NAME_COLUMN
+                + " VARCHAR(255) NOT NULL,"
+                + TABLE_UUID_COLUMN
+                + " UUID NOT NULL,"
+                + KEY_ID_COLUMN
+                + " TEXT NOT NULL,"
+                + KMS_KEY_ID_COLUMN
+                + " TEXT,"
+                + WRAPPED_KEY_COLUMN
+                + " TEXT NOT NULL,"
+                + KEY_PROPERTIES_COLUMN
+                + " JSONB NOT NULL DEFAULT '{}'::JSONB,"
+                + CREATED_AT_COLUMN
+                + " TIMESTAMPTZ NOT NULL DEFAULT now(),"
+                + "PRIMARY KEY ("
+                + CATALOG_NAME_COLUMN
+                + ", "
+                + TABLE_UUID_COLUMN
+                + ", "
+                + KEY_ID_COLUMN
+                + "));"),
+        )
+        .await?;
+        Ok(())
+    }
+}
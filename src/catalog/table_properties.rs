@@ -0,0 +1,81 @@
+/*!
+Changes of table properties through the catalog.
+*/
+
+use std::{collections::HashMap, sync::Arc};
+
+use anyhow::{anyhow, Result};
+use iceberg_rs::{catalog::table_identifier::TableIdentifier, table::Table};
+use serde_json::{json, Map, Value};
+
+use super::PostgresCatalog;
+
+impl PostgresCatalog {
+    /// Set the properties in `set` and remove the properties in `remove` from the table, and
+    /// commit the change. Keys in `remove` that aren't set are ignored. A key must not be both
+    /// set and removed. Tables whose properties don't change are returned without commit.
+    pub async fn update_table_properties(
+        self: Arc<Self>,
+        identifier: TableIdentifier,
+        set: &HashMap<String, String>,
+        remove: &[&str],
+    ) -> Result<Table> {
+        self.check_writable("Updating the table properties")?;
+        if let Some(key) = remove.iter().find(|key| set.contains_key(**key)) {
+            return Err(anyhow!(
+                "Updating the table properties failed. The property {} is both set and removed.",
+                key
+            ));
+        }
+        self.change_metadata(identifier, |metadata| {
+            update_properties(metadata, set, remove)
+        })
+        .await
+    }
+}
+
+/// Apply the changes to the properties of the metadata. Returns whether a property changed.
+fn update_properties(
+    metadata: &mut Value,
+    set: &HashMap<String, String>,
+    remove: &[&str],
+) -> Result<bool> {
+    let properties = metadata
+        .as_object_mut()
+        .ok_or_else(|| anyhow!("The table metadata is not an object."))?
+        .entry("properties")
+        .or_insert_with(|| Value::Object(Map::new()))
+        .as_object_mut()
+        .ok_or_else(|| anyhow!("The table properties are not an object."))?;
+    let mut changed = false;
+    for (key, value) in set {
+        changed |= properties.insert(key.clone(), json!(value)) != Some(json!(value));
+    }
+    for key in remove {
+        changed |= properties.remove(*key).is_some();
+    }
+    Ok(changed)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use serde_json::json;
+
+    use super::update_properties;
+
+    #[test]
+    fn test_update_properties() {
+        let mut metadata = json!({"properties": {"a": "1", "b": "2"}});
+        let set = HashMap::from_iter(vec![("a".to_string(), "1".to_string())]);
+        assert!(!update_properties(&mut metadata, &set, &["c"]).unwrap());
+        let set = HashMap::from_iter(vec![("c".to_string(), "3".to_string())]);
+        assert!(update_properties(&mut metadata, &set, &["b"]).unwrap());
+        assert_eq!(metadata["properties"], json!({"a": "1", "c": "3"}));
+
+        let mut metadata = json!({});
+        assert!(update_properties(&mut metadata, &set, &[]).unwrap());
+        assert_eq!(metadata["properties"]["c"], "3");
+    }
+}
@@ -0,0 +1,205 @@
+/*!
+Tags of tables for discovery and governance.
+
+Tags are key-value pairs like `domain` or `pii` that are stored with the catalog entry of a table
+instead of in its metadata files. Unlike table properties, changing tags doesn't create new metadata
+and tags are shared by all engines that use the catalog. Dropped tables keep their tags when they
+are restored.
+*/
+
+use std::collections::HashMap;
+
+use anyhow::{anyhow, Result};
+use iceberg_rs::catalog::{namespace::Namespace, table_identifier::TableIdentifier};
+use serde_json::Value;
+
+use super::{
+    access::Action,
+    namespace::{namespace_condition, table_condition, table_identifier},
+    query::literal,
+    PostgresCatalog, ARCHIVED_AT_COLUMN, CATALOG_NAME_COLUMN, TABLE_NAMESPACE_COLUMN,
+    TABLE_NAME_COLUMN, TAGS_COLUMN,
+};
+
+impl PostgresCatalog {
+    /// Add tags to the table. Existing tags with the same keys are overwritten.
+    pub async fn set_tags(
+        &self,
+        identifier: &TableIdentifier,
+        tags: &HashMap<String, String>,
+    ) -> Result<()> {
+        self.check_writable("Setting tags")?;
+        let tags = serde_json::to_string(tags).map_err(|err| anyhow!(err.to_string()))?;
+        self.update_tags(
+            identifier,
+            &(TAGS_COLUMN.to_string() + " || " + &literal(&tags) + "::JSONB"),
+        )
+        .await
+    }
+
+    /// Remove the tags with the keys from the table. Keys without tag are ignored.
+    pub async fn remove_tags(&self, identifier: &TableIdentifier, keys: &[&str]) -> Result<()> {
+        self.check_writable("Removing tags")?;
+        self.update_tags(
+            identifier,
+            &(TAGS_COLUMN.to_string()
+                + " - ARRAY["
+                + &keys
+                    .iter()
+                    .map(|key| literal(key))
+                    .collect::<Vec<_>>()
+                    .join(", ")
+                + "]::TEXT[]"),
+        )
+        .await
+    }
+
+    /// Tags of the table
+    pub async fn get_tags(&self, identifier: &TableIdentifier) -> Result<HashMap<String, String>> {
+        let identifier = &self.case_sensitivity.normalize(identifier)?;
+        self.authorize(
+            Action::Read,
+            identifier.namespace(),
+            Some(identifier.name()),
+        )
+        .await?;
+        let rows = self
+            .query(
+                self.read_connection(),
+                &("SELECT ".to_string()
+                    + TAGS_COLUMN
+                    + "::TEXT AS "
+                    + TAGS_COLUMN
+                    + " FROM "
+                    + &self.catalog_table.qualified
+                    + " WHERE "
+                    + &table_condition(&self.name, identifier)
+                    + ";"),
+            )
+            .await?;
+        match rows.first() {
+            Some(row) => parse_tags(&row.try_get_string(TAGS_COLUMN)?),
+            None => Err(anyhow!(
+                "Getting the tags failed. No table matched the identifier."
+            )),
+        }
+    }
+
+    /// Tables in the namespace and its children that have the tag `key`, with the given value if
+    /// `value` is not `None`.
+    pub async fn find_by_tag(
+        &self,
+        namespace: &Namespace,
+        key: &str,
+        value: Option<&str>,
+    ) -> Result<Vec<TableIdentifier>> {
+        let namespace = &self.case_sensitivity.normalize_namespace(namespace)?;
+        self.authorize(Action::Read, namespace, None).await?;
+        let tag_condition = match value {
+            Some(value) => {
+                let tag = serde_json::to_string(&HashMap::from([(key, value)]))
+                    .map_err(|err| anyhow!(err.to_string()))?;
+                TAGS_COLUMN.to_string() + " @> " + &literal(&tag) + "::JSONB"
+            }
+            None => TAGS_COLUMN.to_string() + " ? " + &literal(key),
+        };
+        let rows = self
+            .query(
+                self.read_connection(),
+                &("SELECT ".to_string()
+                    + TABLE_NAMESPACE_COLUMN
+                    + ", "
+                    + TABLE_NAME_COLUMN
+                    + " FROM "
+                    + &self.catalog_table.qualified
+                    + " WHERE "
+                    + CATALOG_NAME_COLUMN
+                    + " = "
+                    + &literal(&self.name)
+                    + " AND ("
+                    + &namespace_condition(namespace)
+                    + ") AND "
+                    + ARCHIVED_AT_COLUMN
+                    + " IS NULL AND "
+                    + &tag_condition
+                    + " ORDER BY "
+                    + TABLE_NAMESPACE_COLUMN
+                    + ", "
+                    + TABLE_NAME_COLUMN
+                    + ";"),
+            )
+            .await?;
+        rows.iter()
+            .map(|row| {
+                table_identifier(
+                    &row.try_get_string(TABLE_NAMESPACE_COLUMN)?,
+                    &row.try_get_string(TABLE_NAME_COLUMN)?,
+                )
+            })
+            .collect()
+    }
+
+    /// Set the tags of the table to the SQL expression.
+    async fn update_tags(&self, identifier: &TableIdentifier, tags: &str) -> Result<()> {
+        let identifier = &self.case_sensitivity.normalize(identifier)?;
+        self.authorize(
+            Action::Commit,
+            identifier.namespace(),
+            Some(identifier.name()),
+        )
+        .await?;
+        self.mark_write();
+        let n_rows = self
+            .execute(
+                &self.primary,
+                &("UPDATE ".to_string()
+                    + &self.catalog_table.qualified
+                    + " SET "
+                    + TAGS_COLUMN
+                    + " = "
+                    + tags
+                    + " WHERE "
+                    + &table_condition(&self.name, identifier)
+                    + ";"),
+            )
+            .await?;
+        if n_rows == 1 {
+            Ok(())
+        } else {
+            Err(anyhow!(
+                "Updating the tags failed. No table matched the identifier."
+            ))
+        }
+    }
+}
+
+/// Tags of the stored JSON object. Tags with values other than strings are ignored.
+pub(crate) fn parse_tags(tags: &str) -> Result<HashMap<String, String>> {
+    let tags: Value = serde_json::from_str(tags).map_err(|err| anyhow!(err.to_string()))?;
+    Ok(tags
+        .as_object()
+        .into_iter()
+        .flatten()
+        .filter_map(|(key, value)| Some((key.clone(), value.as_str()?.to_string())))
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use super::parse_tags;
+
+    #[test]
+    fn test_parse_tags() {
+        assert_eq!(
+            parse_tags(r#"{"owner": "analytics", "pii": "true", "level": 3}"#).unwrap(),
+            HashMap::from_iter(vec![
+                ("owner".to_string(), "analytics".to_string()),
+                ("pii".to_string(), "true".to_string()),
+            ])
+        );
+        assert!(parse_tags("{}").unwrap().is_empty());
+        assert!(parse_tags("not json").is_err());
+    }
+}
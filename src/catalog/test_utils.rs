@@ -0,0 +1,106 @@
+/*!
+Helpers for integration tests of crates that use the catalog.
+
+[start_catalog] starts a throwaway Postgres server in a docker container with testcontainers,
+initializes a catalog on it with an in-memory object store and returns both. The container is
+removed when the returned [TestCatalog] is dropped, so every test can use its own server:
+
+```no_run
+# async fn test() -> anyhow::Result<()> {
+use iceberg_catalog_postgres::catalog::test_utils::start_catalog;
+use iceberg_rs::catalog::{table_identifier::TableIdentifier, Catalog};
+
+let test = start_catalog("test").await?;
+let identifier = TableIdentifier::parse("test.table1")?;
+assert!(!test.catalog.table_exists(&identifier).await?);
+# Ok(())
+# }
+```
+
+Requires a docker daemon that is reachable by the `docker` command line tool.
+*/
+
+use std::{
+    collections::HashMap,
+    sync::{Arc, OnceLock},
+};
+
+use anyhow::{anyhow, Result};
+use iceberg_rs::{
+    catalog::Catalog,
+    object_store::{memory::InMemory, ObjectStore},
+};
+use testcontainers::{clients::Cli, images::postgres::Postgres, Container};
+
+use super::{builder::PostgresCatalogBuilder, PostgresCatalog};
+
+static POSTGRES_PORT: u16 = 5432;
+
+/// Docker client shared by all containers of the process
+static DOCKER: OnceLock<Cli> = OnceLock::new();
+
+/// Initialized catalog on a Postgres server in a container
+pub struct TestCatalog {
+    /// The catalog
+    pub catalog: Arc<PostgresCatalog>,
+    /// Object store of the catalog
+    pub object_store: Arc<dyn ObjectStore>,
+    /// Connection url of the server, to connect further catalogs
+    pub url: String,
+    _container: Container<'static, Postgres>,
+}
+
+/// Start a Postgres server and initialize a catalog with the given name on it.
+pub async fn start_catalog(name: &str) -> Result<TestCatalog> {
+    start_catalog_with(name, |builder| builder).await
+}
+
+/// Start a Postgres server and initialize a catalog on it, configured by `configure`.
+pub async fn start_catalog_with(
+    name: &str,
+    configure: impl FnOnce(PostgresCatalogBuilder) -> PostgresCatalogBuilder,
+) -> Result<TestCatalog> {
+    // Starting the container blocks until the server accepts connections.
+    let container =
+        tokio::task::spawn_blocking(|| DOCKER.get_or_init(Cli::default).run(Postgres::default()))
+            .await
+            .map_err(|err| anyhow!(err.to_string()))?;
+    let url = format!(
+        "postgres://postgres@127.0.0.1:{}/postgres",
+        container.get_host_port_ipv4(POSTGRES_PORT)
+    );
+    let object_store: Arc<dyn ObjectStore> = Arc::new(InMemory::new());
+    let catalog = Arc::new(
+        configure(PostgresCatalog::builder(
+            name,
+            &url,
+            Arc::clone(&object_store),
+        ))
+        .build()
+        .await?,
+    );
+    Arc::clone(&catalog).initialize(&HashMap::new()).await?;
+    Ok(TestCatalog {
+        catalog,
+        object_store,
+        url,
+        _container: container,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use iceberg_rs::catalog::{table_identifier::TableIdentifier, Catalog};
+
+    use super::start_catalog;
+
+    #[tokio::test]
+    async fn test_start_catalog() {
+        let test = start_catalog("test_utils").await.unwrap();
+        assert!(!test
+            .catalog
+            .table_exists(&TableIdentifier::parse("test.table1").unwrap())
+            .await
+            .unwrap());
+    }
+}
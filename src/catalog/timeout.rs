@@ -0,0 +1,44 @@
+/*!
+Timeouts for the operations of the catalog.
+*/
+
+use std::{future::Future, time::Duration};
+
+use anyhow::Result;
+
+use super::error::CatalogError;
+
+/// Timeouts of the catalog. `None` means that the operation can take arbitrarily long.
+#[derive(Debug, Clone, Copy, Default)]
+pub(crate) struct Timeouts {
+    /// Timeout for establishing a connection
+    pub(crate) connect: Option<Duration>,
+    /// Client side timeout for a single statement
+    pub(crate) query: Option<Duration>,
+    /// Timeout for a commit, including loading the committed table
+    pub(crate) commit: Option<Duration>,
+    /// Server side `statement_timeout` of the session
+    pub(crate) statement: Option<Duration>,
+}
+
+/// Await the future, failing with [CatalogError::TimedOut] if it takes longer than `duration`.
+pub(crate) async fn with_timeout<T, F>(
+    duration: Option<Duration>,
+    operation: &str,
+    future: F,
+) -> Result<T>
+where
+    F: Future<Output = Result<T>>,
+{
+    match duration {
+        Some(duration) => {
+            tokio::time::timeout(duration, future)
+                .await
+                .map_err(|_| CatalogError::TimedOut {
+                    operation: operation.to_string(),
+                    timeout: duration,
+                })?
+        }
+        None => future.await,
+    }
+}
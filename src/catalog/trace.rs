@@ -0,0 +1,193 @@
+/*!
+Propagation of trace context into postgres.
+
+Statements that run within a trace carry the W3C `traceparent` of the trace in a leading comment,
+in the format of sqlcommenter: `/*traceparent='00-<trace id>-<span id>-01'*/ SELECT ...`. The
+comment shows up in `pg_stat_activity` and in the statement log of postgres, so slow statements
+can be attributed to the job and trace that issued them. Traced statements are sent unprepared,
+because the comment changes with every trace.
+
+The trace context is set for a future with [with_trace_context]. With the `opentelemetry` feature,
+the context of the current OpenTelemetry span is used otherwise, and every statement is recorded
+as a client span of the global tracer, which the application exports, for example over OTLP. The
+traceparent of a statement then names its own span.
+
+[with_application_name](super::builder::PostgresCatalogBuilder::with_application_name) sets the
+`application_name` of the connections, which identifies the catalog in `pg_stat_activity`.
+*/
+
+use std::{borrow::Cow, fmt, future::Future};
+
+use anyhow::{anyhow, Result};
+
+tokio::task_local! {
+    static TRACE_CONTEXT: TraceContext;
+}
+
+/// W3C trace context of the operation that issues a statement
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TraceContext {
+    trace_id: String,
+    span_id: String,
+    sampled: bool,
+}
+
+impl TraceContext {
+    /// Trace context with the trace id, the id of the parent span and whether the trace is
+    /// sampled. Ids of zero are invalid.
+    pub fn new(trace_id: u128, span_id: u64, sampled: bool) -> Result<Self> {
+        if trace_id == 0 || span_id == 0 {
+            return Err(anyhow!("The ids of a trace context can't be zero."));
+        }
+        Ok(TraceContext {
+            trace_id: format!("{:032x}", trace_id),
+            span_id: format!("{:016x}", span_id),
+            sampled,
+        })
+    }
+
+    /// Parse the value of a `traceparent` header
+    pub fn parse(traceparent: &str) -> Result<Self> {
+        let invalid = || anyhow!("The traceparent {} is invalid.", traceparent);
+        let parts = traceparent.trim().split('-').collect::<Vec<_>>();
+        let (version, trace_id, span_id, flags) = match parts[..] {
+            [version, trace_id, span_id, flags] => (version, trace_id, span_id, flags),
+            _ => return Err(invalid()),
+        };
+        let hex = |value: &str, len: usize| {
+            value.len() == len && value.chars().all(|c| matches!(c, '0'..='9' | 'a'..='f'))
+        };
+        if version != "00" || !hex(trace_id, 32) || !hex(span_id, 16) || !hex(flags, 2) {
+            return Err(invalid());
+        }
+        TraceContext::new(
+            u128::from_str_radix(trace_id, 16).map_err(|_| invalid())?,
+            u64::from_str_radix(span_id, 16).map_err(|_| invalid())?,
+            u8::from_str_radix(flags, 16).map_err(|_| invalid())? & 1 == 1,
+        )
+    }
+}
+
+impl fmt::Display for TraceContext {
+    /// Value of the `traceparent` header
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "00-{}-{}-{}",
+            self.trace_id,
+            self.span_id,
+            if self.sampled { "01" } else { "00" }
+        )
+    }
+}
+
+/// Run the future with the trace context, so that its statements carry the context.
+pub async fn with_trace_context<F: Future>(context: TraceContext, future: F) -> F::Output {
+    TRACE_CONTEXT.scope(context, future).await
+}
+
+/// Trace context of the current task, if any
+pub(crate) fn current() -> Option<TraceContext> {
+    let context = TRACE_CONTEXT.try_with(Clone::clone).ok();
+    #[cfg(feature = "opentelemetry")]
+    let context = context.or_else(opentelemetry_context);
+    context
+}
+
+/// The statement with the current trace context in a leading comment
+pub(crate) fn comment(sql: &str) -> Cow<'_, str> {
+    match current() {
+        Some(context) => Cow::Owned(commented(sql, &context)),
+        None => Cow::Borrowed(sql),
+    }
+}
+
+fn commented(sql: &str, context: &TraceContext) -> String {
+    "/*traceparent='".to_string() + &context.to_string() + "'*/ " + sql
+}
+
+/// Run a statement within a client span, if the current task is traced with OpenTelemetry.
+pub(crate) async fn statement<T>(sql: &str, future: impl Future<Output = Result<T>>) -> Result<T> {
+    #[cfg(feature = "opentelemetry")]
+    {
+        use opentelemetry::{
+            global,
+            trace::{FutureExt, SpanKind, Status, TraceContextExt, Tracer},
+            Context, KeyValue,
+        };
+
+        let parent = Context::current();
+        if parent.span().span_context().is_valid() {
+            let tracer = global::tracer("iceberg-catalog-postgres");
+            let span = tracer
+                .span_builder("postgres")
+                .with_kind(SpanKind::Client)
+                .with_attributes(vec![
+                    KeyValue::new("db.system", "postgresql"),
+                    KeyValue::new("db.statement", sql.to_string()),
+                ])
+                .start_with_context(&tracer, &parent);
+            let context = parent.with_span(span);
+            let result = future.with_context(context.clone()).await;
+            if let Err(err) = &result {
+                context.span().set_status(Status::error(err.to_string()));
+            }
+            context.span().end();
+            return result;
+        }
+    }
+    #[cfg(not(feature = "opentelemetry"))]
+    let _ = sql;
+    future.await
+}
+
+/// Trace context of the current OpenTelemetry span
+#[cfg(feature = "opentelemetry")]
+fn opentelemetry_context() -> Option<TraceContext> {
+    use opentelemetry::{trace::TraceContextExt, Context};
+
+    let context = Context::current();
+    let span_context = context.span().span_context().clone();
+    if !span_context.is_valid() {
+        return None;
+    }
+    Some(TraceContext {
+        trace_id: format!("{:032x}", span_context.trace_id()),
+        span_id: format!("{:016x}", span_context.span_id()),
+        sampled: span_context.is_sampled(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{comment, commented, with_trace_context, TraceContext};
+
+    #[test]
+    fn test_traceparent() {
+        let traceparent = "00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01";
+        let context = TraceContext::parse(traceparent).unwrap();
+        assert_eq!(context.to_string(), traceparent);
+        assert_eq!(
+            commented("SELECT 1;", &context),
+            "/*traceparent='".to_string() + traceparent + "'*/ SELECT 1;"
+        );
+        assert!(
+            TraceContext::parse("00-00000000000000000000000000000000-00f067aa0ba902b7-01").is_err()
+        );
+        assert!(
+            TraceContext::parse("00-4BF92F3577B34DA6A3CE929D0E0E4736-00f067aa0ba902b7-01").is_err()
+        );
+        assert!(TraceContext::parse("00-4bf92f3577b34da6a3ce929d0e0e4736-01").is_err());
+    }
+
+    #[tokio::test]
+    async fn test_with_trace_context() {
+        assert_eq!(comment("SELECT 1;"), "SELECT 1;");
+        let context = TraceContext::new(1, 2, true).unwrap();
+        let sql = with_trace_context(context, async { comment("SELECT 1;").into_owned() }).await;
+        assert_eq!(
+            sql,
+            "/*traceparent='00-00000000000000000000000000000001-0000000000000002-01'*/ SELECT 1;"
+        );
+    }
+}
@@ -0,0 +1,144 @@
+/*!
+Transactions of several catalog statements.
+
+Most operations of the catalog are single statements, which postgres runs atomically. Operations
+that write an entry and read it back, like registering or restoring a table, run their statements
+in one transaction, so that no concurrent operation, for example dropping the table, can change the
+entry in between.
+
+By default, transactions and the statements that modify the catalog run with the isolation level
+`READ COMMITTED` of postgres. With
+[with_isolation_level](super::builder::PostgresCatalogBuilder::with_isolation_level) they run as
+transactions with a stronger isolation level instead. Postgres aborts such transactions with a
+serialization failure if they conflict with a concurrent transaction. They are rolled back
+completely and retried, up to
+[with_serialization_retries](super::builder::PostgresCatalogBuilder::with_serialization_retries)
+times.
+*/
+
+use std::fmt;
+
+use anyhow::{anyhow, Result};
+
+use super::{
+    query::StatementResult,
+    retry::{retry, RetryPolicy},
+    timeout::with_timeout,
+    PostgresCatalog,
+};
+
+/// Number of retries of transactions that were rolled back because of a serialization failure
+pub(crate) static DEFAULT_SERIALIZATION_RETRIES: u32 = 3;
+
+/// Isolation level of the transactions of the catalog
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum IsolationLevel {
+    /// Every statement sees the data committed before it started, the default of postgres
+    ReadCommitted,
+    /// All statements see the data committed before the transaction started
+    RepeatableRead,
+    /// The transaction behaves as if it ran alone
+    Serializable,
+}
+
+impl Default for IsolationLevel {
+    fn default() -> Self {
+        IsolationLevel::ReadCommitted
+    }
+}
+
+impl fmt::Display for IsolationLevel {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            IsolationLevel::ReadCommitted => f.write_str("READ COMMITTED"),
+            IsolationLevel::RepeatableRead => f.write_str("REPEATABLE READ"),
+            IsolationLevel::Serializable => f.write_str("SERIALIZABLE"),
+        }
+    }
+}
+
+impl PostgresCatalog {
+    /// Run the statements in one transaction with the isolation level on the primary and return
+    /// their results in order. Statements can't have parameters, prepared statements have to be
+    /// [inlined](super::statements::inline).
+    pub(crate) async fn transaction(
+        &self,
+        isolation: IsolationLevel,
+        statements: &[String],
+    ) -> Result<Vec<StatementResult>> {
+        self.check_writable("Transaction")?;
+        let closed = self.closed.read().await;
+        if *closed {
+            return Err(anyhow!("The catalog is closed.".to_string()));
+        }
+        let statements = [
+            vec!["SET TRANSACTION ISOLATION LEVEL ".to_string() + &isolation.to_string() + ";"],
+            statements.to_vec(),
+        ]
+        .concat();
+        let batch = &statements;
+        // Writes are only retried if they were rolled back, so serialization failures are retried
+        // even if the retry policy doesn't retry.
+        let policy = RetryPolicy {
+            max_retries: self
+                .retry_policy
+                .max_retries
+                .max(self.serialization_retries),
+            ..self.retry_policy.clone()
+        };
+        let mut results = retry(
+            &policy,
+            self.circuit_breaker.as_ref(),
+            false,
+            || async move {
+                self.logged(
+                    &batch.join(" "),
+                    &[],
+                    with_timeout(
+                        self.timeouts.query,
+                        "Transaction",
+                        self.primary.transaction(batch),
+                    ),
+                )
+                .await
+            },
+        )
+        .await?;
+        if results.len() != statements.len() {
+            return Err(anyhow!(
+                "The transaction returned {} results for {} statements.",
+                results.len(),
+                statements.len()
+            ));
+        }
+        // The result of setting the isolation level
+        results.remove(0);
+        Ok(results)
+    }
+
+    /// Run a statement that modifies the catalog in a transaction with the isolation level of the
+    /// catalog, `None` if the catalog uses the default isolation level.
+    pub(crate) async fn isolated(&self, sql: &str) -> Result<Option<StatementResult>> {
+        if self.isolation_level == IsolationLevel::ReadCommitted {
+            return Ok(None);
+        }
+        let mut results = self
+            .transaction(self.isolation_level, &[sql.to_string()])
+            .await?;
+        Ok(results.pop())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::IsolationLevel;
+
+    #[test]
+    fn test_isolation_level() {
+        assert_eq!(IsolationLevel::default(), IsolationLevel::ReadCommitted);
+        assert_eq!(
+            IsolationLevel::RepeatableRead.to_string(),
+            "REPEATABLE READ"
+        );
+    }
+}
@@ -0,0 +1,483 @@
+/*!
+Soft delete of tables.
+
+With a retention period configured, dropping a table moves its catalog entry to the dropped tables
+instead of removing it, and leaves its files in place. Within the retention period the table can be
+restored with [restore_table](PostgresCatalog::restore_table). Afterwards
+[purge_dropped_tables](PostgresCatalog::purge_dropped_tables) removes the entries and deletes the
+files, it is meant to be run periodically, for example by a scheduled job.
+
+Purging can be stopped with a [CancellationToken] or by dropping its future. Tables are purged one
+after the other: the location of the table is recorded, its files are deleted and only then its
+entry is removed. A table whose purge was interrupted keeps its entry and is purged completely by
+the next run, so no files are left behind without an entry that refers to them.
+*/
+
+use std::{
+    sync::Arc,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use anyhow::{anyhow, Result};
+use iceberg_rs::{
+    catalog::{namespace::Namespace, table_identifier::TableIdentifier, Catalog},
+    table::Table,
+};
+use tokio_util::sync::CancellationToken;
+
+use super::{
+    access::Action,
+    is_unique_violation,
+    namespace::{namespace_condition, namespace_key, table_identifier},
+    query::{epoch_millis, literal, CatalogRow},
+    PostgresCatalog, CATALOG_NAME_COLUMN, CREATED_AT_COLUMN, CURRENT_SNAPSHOT_ID_COLUMN,
+    DESCRIPTION_COLUMN, LAST_LOADED_AT_COLUMN, LAST_SEQUENCE_NUMBER_COLUMN,
+    METADATA_CHECKSUM_COLUMN, METADATA_LOCATION_COLUMN, OWNER_COLUMN,
+    PREVIOUS_METADATA_LOCATION_COLUMN, SNAPSHOT_SUMMARY_COLUMN, TABLE_LOCATION_COLUMN,
+    TABLE_NAMESPACE_COLUMN, TABLE_NAME_COLUMN, TABLE_SIZE_COLUMN, TABLE_UUID_COLUMN, TAGS_COLUMN,
+    UPDATED_AT_COLUMN, VERSION_COLUMN,
+};
+
+static DROPPED_AT_COLUMN: &str = "dropped_at";
+pub(crate) static IN_USE_COLUMN: &str = "in_use";
+
+/// Columns of the catalog table that are kept for dropped tables
+static MOVED_COLUMNS: [&str; 19] = [
+    CATALOG_NAME_COLUMN,
+    TABLE_NAMESPACE_COLUMN,
+    TABLE_NAME_COLUMN,
+    METADATA_LOCATION_COLUMN,
+    PREVIOUS_METADATA_LOCATION_COLUMN,
+    TABLE_LOCATION_COLUMN,
+    TABLE_UUID_COLUMN,
+    VERSION_COLUMN,
+    TABLE_SIZE_COLUMN,
+    CREATED_AT_COLUMN,
+    UPDATED_AT_COLUMN,
+    LAST_LOADED_AT_COLUMN,
+    METADATA_CHECKSUM_COLUMN,
+    TAGS_COLUMN,
+    OWNER_COLUMN,
+    DESCRIPTION_COLUMN,
+    SNAPSHOT_SUMMARY_COLUMN,
+    CURRENT_SNAPSHOT_ID_COLUMN,
+    LAST_SEQUENCE_NUMBER_COLUMN,
+];
+
+/// A table that was dropped in soft delete mode
+#[derive(Debug, Clone)]
+pub struct DroppedTable {
+    /// Identifier of the table
+    pub identifier: TableIdentifier,
+    /// Location of the current metadata file when the table was dropped
+    pub metadata_location: String,
+    /// Time the table was dropped
+    pub dropped_at: SystemTime,
+}
+
+impl PostgresCatalog {
+    /// Tables of the namespace and its children that were dropped and not purged yet, the most
+    /// recently dropped first.
+    pub async fn dropped_tables(&self, namespace: &Namespace) -> Result<Vec<DroppedTable>> {
+        let namespace = &self.case_sensitivity.normalize_namespace(namespace)?;
+        self.authorize(Action::Read, namespace, None).await?;
+        let rows = self
+            .query(
+                self.read_connection(),
+                &("SELECT ".to_string()
+                    + TABLE_NAMESPACE_COLUMN
+                    + ", "
+                    + TABLE_NAME_COLUMN
+                    + ", "
+                    + METADATA_LOCATION_COLUMN
+                    + ", "
+                    + &epoch_millis(DROPPED_AT_COLUMN)
+                    + " FROM "
+                    + &self.catalog_table.dropped
+                    + " WHERE "
+                    + CATALOG_NAME_COLUMN
+                    + " = "
+                    + &literal(&self.name)
+                    + " AND ("
+                    + &namespace_condition(namespace)
+                    + ") ORDER BY "
+                    + DROPPED_AT_COLUMN
+                    + " DESC;"),
+            )
+            .await?;
+        rows.iter()
+            .map(|row| {
+                Ok(DroppedTable {
+                    identifier: table_identifier(
+                        &row.try_get_string(TABLE_NAMESPACE_COLUMN)?,
+                        &row.try_get_string(TABLE_NAME_COLUMN)?,
+                    )?,
+                    metadata_location: row.try_get_string(METADATA_LOCATION_COLUMN)?,
+                    dropped_at: UNIX_EPOCH
+                        + Duration::from_millis(row.try_get_i64(DROPPED_AT_COLUMN)?.max(0) as u64),
+                })
+            })
+            .collect()
+    }
+
+    /// Restore the most recently dropped table with the identifier. Fails if the retention period
+    /// has passed or if the identifier or location is used by another table.
+    pub async fn restore_table(self: Arc<Self>, identifier: TableIdentifier) -> Result<Table> {
+        self.check_writable("Restoring the table")?;
+        let identifier = self.case_sensitivity.normalize(&identifier)?;
+        self.authorize(
+            Action::Create,
+            identifier.namespace(),
+            Some(identifier.name()),
+        )
+        .await?;
+        let matches_table = CATALOG_NAME_COLUMN.to_string()
+            + " = "
+            + &literal(&self.name)
+            + " AND "
+            + TABLE_NAMESPACE_COLUMN
+            + " = "
+            + &literal(&namespace_key(identifier.namespace()))
+            + " AND "
+            + TABLE_NAME_COLUMN
+            + " = "
+            + &literal(identifier.name());
+        let columns = MOVED_COLUMNS.join(", ");
+        let restore = "WITH restored AS (DELETE FROM ".to_string()
+            + &self.catalog_table.dropped
+            + " WHERE "
+            + &matches_table
+            + " AND "
+            + DROPPED_AT_COLUMN
+            + " > "
+            + &self.retention_start()
+            + " AND "
+            + DROPPED_AT_COLUMN
+            + " = (SELECT max("
+            + DROPPED_AT_COLUMN
+            + ") FROM "
+            + &self.catalog_table.dropped
+            + " WHERE "
+            + &matches_table
+            + ") RETURNING "
+            + &columns
+            + ") INSERT INTO "
+            + &self.catalog_table.qualified
+            + " ("
+            + &columns
+            + ") SELECT "
+            + &columns
+            + " FROM restored;";
+        self.mark_write();
+        // The restored entry is read back in the same transaction, so that a concurrent drop
+        // can't remove it before it is loaded.
+        let results = self
+            .transaction(
+                self.isolation_level,
+                &[
+                    restore,
+                    "SELECT ".to_string()
+                        + METADATA_LOCATION_COLUMN
+                        + ", "
+                        + METADATA_CHECKSUM_COLUMN
+                        + " FROM "
+                        + &self.catalog_table.qualified
+                        + " WHERE "
+                        + &matches_table
+                        + ";",
+                ],
+            )
+            .await
+            .map_err(|err| {
+                if is_unique_violation(&err) {
+                    anyhow!("Restoring the table failed. The identifier or the location is used by another table.")
+                } else {
+                    err
+                }
+            })?;
+        if results[0].n_rows == 1 {
+            self.invalidate_exists(&identifier);
+            self.authorize(
+                Action::Read,
+                identifier.namespace(),
+                Some(identifier.name()),
+            )
+            .await?;
+            let row = results[1]
+                .rows
+                .first()
+                .ok_or_else(|| anyhow!("Restoring the table failed. The entry wasn't written."))?;
+            let location = row.try_get_string(METADATA_LOCATION_COLUMN)?;
+            let checksum = row.try_get_opt_string(METADATA_CHECKSUM_COLUMN)?;
+            self.table_from_entry(identifier, &location, checksum).await
+        } else {
+            Err(anyhow!(
+                "Restoring the table failed. No table with the identifier was dropped within the retention period."
+            ))
+        }
+    }
+
+    /// Remove the dropped tables whose retention period has passed and delete their files. Files
+    /// at a location that was taken over by another table are kept. Returns the number of removed
+    /// tables.
+    pub async fn purge_dropped_tables(&self) -> Result<u64> {
+        self.purge_dropped_tables_cancellable(&CancellationToken::new())
+            .await
+    }
+
+    /// Purge the dropped tables like [purge_dropped_tables](Self::purge_dropped_tables) until the
+    /// token is cancelled. Returns the number of tables that were removed before the
+    /// cancellation, the table that was being purged is purged by the next run.
+    pub async fn purge_dropped_tables_cancellable(
+        &self,
+        cancellation: &CancellationToken,
+    ) -> Result<u64> {
+        self.check_writable("Purging dropped tables")?;
+        let rows = self
+            .query(
+                &self.primary,
+                &("SELECT ".to_string()
+                    + TABLE_NAMESPACE_COLUMN
+                    + ", "
+                    + TABLE_NAME_COLUMN
+                    + ", "
+                    + METADATA_LOCATION_COLUMN
+                    + ", "
+                    + TABLE_LOCATION_COLUMN
+                    + " FROM "
+                    + &self.catalog_table.dropped
+                    + " WHERE "
+                    + CATALOG_NAME_COLUMN
+                    + " = "
+                    + &literal(&self.name)
+                    + " AND "
+                    + DROPPED_AT_COLUMN
+                    + " <= "
+                    + &self.retention_start()
+                    + ";"),
+            )
+            .await?;
+        let mut purged = 0;
+        for row in &rows {
+            if cancellation.is_cancelled() {
+                break;
+            }
+            let namespace = row.try_get_string(TABLE_NAMESPACE_COLUMN)?;
+            let name = row.try_get_string(TABLE_NAME_COLUMN)?;
+            let identifier = table_identifier(&namespace, &name)?;
+            let metadata_location = row.try_get_string(METADATA_LOCATION_COLUMN)?;
+            let table_location = match row.try_get_opt_string(TABLE_LOCATION_COLUMN)? {
+                Some(location) => location,
+                None => self
+                    .read_metadata_json(&identifier, &metadata_location)
+                    .await?["location"]
+                    .as_str()
+                    .ok_or_else(|| anyhow!("The table metadata contains no location."))?
+                    .to_string(),
+            };
+            let matches_entry = "d.".to_string()
+                + CATALOG_NAME_COLUMN
+                + " = "
+                + &literal(&self.name)
+                + " AND d."
+                + TABLE_NAMESPACE_COLUMN
+                + " = "
+                + &literal(&namespace)
+                + " AND d."
+                + TABLE_NAME_COLUMN
+                + " = "
+                + &literal(&name)
+                + " AND d."
+                + METADATA_LOCATION_COLUMN
+                + " = "
+                + &literal(&metadata_location)
+                + " AND d."
+                + DROPPED_AT_COLUMN
+                + " <= "
+                + &self.retention_start();
+            // Recording the location lets an interrupted purge resume after the metadata file
+            // was deleted.
+            let recorded = self
+                .execute_returning(
+                    &("UPDATE ".to_string()
+                        + &self.catalog_table.dropped
+                        + " d SET "
+                        + TABLE_LOCATION_COLUMN
+                        + " = "
+                        + &literal(&table_location)
+                        + " WHERE "
+                        + &matches_entry
+                        + " RETURNING EXISTS (SELECT 1 FROM "
+                        + &self.catalog_table.qualified
+                        + " t WHERE t."
+                        + CATALOG_NAME_COLUMN
+                        + " = d."
+                        + CATALOG_NAME_COLUMN
+                        + " AND t."
+                        + TABLE_LOCATION_COLUMN
+                        + " = d."
+                        + TABLE_LOCATION_COLUMN
+                        + ") AS "
+                        + IN_USE_COLUMN
+                        + ";"),
+                )
+                .await?;
+            self.mark_write();
+            let in_use = match recorded.first() {
+                Some(row) => row.try_get_bool(IN_USE_COLUMN)?,
+                // The entry was purged or restored concurrently.
+                None => continue,
+            };
+            if !in_use {
+                tokio::select! {
+                    biased;
+                    _ = cancellation.cancelled() => break,
+                    deleted = self.delete_files(
+                        &identifier,
+                        &metadata_location,
+                        &table_location,
+                    ) => deleted?,
+                }
+            }
+            purged += self
+                .execute(
+                    &self.primary,
+                    &("DELETE FROM ".to_string()
+                        + &self.catalog_table.dropped
+                        + " d WHERE "
+                        + &matches_entry
+                        + ";"),
+                )
+                .await?;
+        }
+        Ok(purged)
+    }
+
+    /// Move the unprotected entries of the tables matching the condition to the dropped tables.
+    /// Returns the namespace, name, metadata location and table location of the moved entries.
+    pub(crate) async fn move_to_trash(&self, condition: &str) -> Result<Vec<CatalogRow>> {
+        let columns = MOVED_COLUMNS.join(", ");
+        self.execute_returning(
+            &("WITH dropped AS (DELETE FROM ".to_string()
+                + &self.catalog_table.qualified
+                + " WHERE ("
+                + condition
+                + ") AND "
+                + &self.unprotected_condition()
+                + " RETURNING "
+                + &columns
+                + ") INSERT INTO "
+                + &self.catalog_table.dropped
+                + " ("
+                + &columns
+                + ", "
+                + DROPPED_AT_COLUMN
+                + ") SELECT "
+                + &columns
+                + ", clock_timestamp() FROM dropped RETURNING "
+                + TABLE_NAMESPACE_COLUMN
+                + ", "
+                + TABLE_NAME_COLUMN
+                + ", "
+                + METADATA_LOCATION_COLUMN
+                + ", "
+                + TABLE_LOCATION_COLUMN
+                + ";"),
+        )
+        .await
+    }
+
+    /// Create the table for dropped tables if it doesn't exist.
+    pub(crate) async fn create_dropped_table(&self) -> Result<()> {
+        self.execute(
+            &self.primary,
+            &("CREATE TABLE IF NOT EXISTS ".to_string()
+                + &self.catalog_table.dropped
+                + " ("
+                + CATALOG_NAME_COLUMN
+                + " VARCHAR(255) NOT NULL,"
+                + TABLE_NAMESPACE_COLUMN
+                + " VARCHAR(255) NOT NULL,"
+                + TABLE_NAME_COLUMN
+                + " VARCHAR(255) NOT NULL,"
+                + METADATA_LOCATION_COLUMN
+                + " TEXT,"
+                + PREVIOUS_METADATA_LOCATION_COLUMN
+                + " TEXT,"
+                + TABLE_LOCATION_COLUMN
+                + " TEXT,"
+                + TABLE_UUID_COLUMN
+                + " UUID,"
+                + VERSION_COLUMN
+                + " BIGINT NOT NULL,"
+                + TABLE_SIZE_COLUMN
+                + " BIGINT,"
+                + CREATED_AT_COLUMN
+                + " TIMESTAMPTZ NOT NULL,"
+                + UPDATED_AT_COLUMN
+                + " TIMESTAMPTZ NOT NULL,"
+                + LAST_LOADED_AT_COLUMN
+                + " TIMESTAMPTZ,"
+                + METADATA_CHECKSUM_COLUMN
+                + " TEXT,"
+                + TAGS_COLUMN
+                + " JSONB NOT NULL DEFAULT '{}',"
+                + OWNER_COLUMN
+                + " TEXT,"
+                + DESCRIPTION_COLUMN
+                + " TEXT,"
+                + SNAPSHOT_SUMMARY_COLUMN
+                + " JSONB,"
+                + CURRENT_SNAPSHOT_ID_COLUMN
+                + " BIGINT,"
+                + LAST_SEQUENCE_NUMBER_COLUMN
+                + " BIGINT,"
+                + DROPPED_AT_COLUMN
+                + " TIMESTAMPTZ NOT NULL,"
+                + "PRIMARY KEY ("
+                + CATALOG_NAME_COLUMN
+                + ", "
+                + TABLE_NAMESPACE_COLUMN
+                + ", "
+                + TABLE_NAME_COLUMN
+                + ", "
+                + DROPPED_AT_COLUMN
+                + ")"
+                + ");"),
+        )
+        .await?;
+        self.execute(
+            &self.primary,
+            &("ALTER TABLE ".to_string()
+                + &self.catalog_table.dropped
+                + " ADD COLUMN IF NOT EXISTS "
+                + METADATA_CHECKSUM_COLUMN
+                + " TEXT, ADD COLUMN IF NOT EXISTS "
+                + TAGS_COLUMN
+                + " JSONB NOT NULL DEFAULT '{}', ADD COLUMN IF NOT EXISTS "
+                + OWNER_COLUMN
+                + " TEXT, ADD COLUMN IF NOT EXISTS "
+                + DESCRIPTION_COLUMN
+                + " TEXT, ADD COLUMN IF NOT EXISTS "
+                + SNAPSHOT_SUMMARY_COLUMN
+                + " JSONB, ADD COLUMN IF NOT EXISTS "
+                + CURRENT_SNAPSHOT_ID_COLUMN
+                + " BIGINT, ADD COLUMN IF NOT EXISTS "
+                + LAST_SEQUENCE_NUMBER_COLUMN
+                + " BIGINT;"),
+        )
+        .await?;
+        Ok(())
+    }
+
+    /// SQL expression for the oldest drop time that is still within the retention period
+    fn retention_start(&self) -> String {
+        "now() - interval '".to_string()
+            + &self
+                .soft_delete_retention
+                .unwrap_or_default()
+                .as_millis()
+                .to_string()
+            + " milliseconds'"
+    }
+}
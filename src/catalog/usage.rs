@@ -0,0 +1,285 @@
+/*!
+Tracks when tables were last loaded and committed, to find tables that are no longer used.
+
+Commits record their time with the metadata pointer. Loads are collected in memory and written in
+a single statement once per flush interval, so that reading a table doesn't cost a write. Loads that
+weren't flushed yet are written when the catalog is closed. Read-only catalogs don't record loads.
+*/
+
+use std::{
+    collections::HashSet,
+    mem,
+    sync::Mutex,
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
+
+use anyhow::Result;
+use iceberg_rs::catalog::{namespace::Namespace, table_identifier::TableIdentifier};
+
+use super::{
+    access::Action,
+    namespace::{namespace_condition, namespace_key, table_identifier},
+    query::{epoch_millis, literal, CatalogRow},
+    PostgresCatalog, CATALOG_NAME_COLUMN, CREATED_AT_COLUMN, LAST_LOADED_AT_COLUMN,
+    TABLE_NAMESPACE_COLUMN, TABLE_NAME_COLUMN, UPDATED_AT_COLUMN,
+};
+
+/// Interval in which recorded loads are written to the catalog table
+pub(crate) static DEFAULT_LOAD_FLUSH_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Usage of a table
+#[derive(Debug, Clone)]
+pub struct TableUsage {
+    /// Identifier of the table
+    pub identifier: TableIdentifier,
+    /// Time the table was registered with the catalog
+    pub created_at: SystemTime,
+    /// Last time the table was loaded, if a load was recorded
+    pub last_loaded_at: Option<SystemTime>,
+    /// Last time new metadata was committed, if the table was committed since it was registered
+    pub last_committed_at: Option<SystemTime>,
+}
+
+impl TableUsage {
+    /// Last time the table was registered, loaded or committed
+    pub fn last_used_at(&self) -> SystemTime {
+        [self.last_loaded_at, self.last_committed_at]
+            .into_iter()
+            .flatten()
+            .fold(self.created_at, SystemTime::max)
+    }
+}
+
+/// Loads that weren't written to the catalog table yet
+pub(crate) struct LoadTracker {
+    flush_interval: Duration,
+    pending: Mutex<(Instant, HashSet<(String, String)>)>,
+}
+
+impl LoadTracker {
+    pub(crate) fn new(flush_interval: Duration) -> Self {
+        LoadTracker {
+            flush_interval,
+            pending: Mutex::new((Instant::now(), HashSet::new())),
+        }
+    }
+
+    /// Record a load. Returns the loads to write if the flush interval elapsed.
+    fn record(&self, identifier: &TableIdentifier) -> Option<HashSet<(String, String)>> {
+        let mut pending = self.pending.lock().ok()?;
+        pending.1.insert((
+            namespace_key(identifier.namespace()),
+            identifier.name().to_string(),
+        ));
+        if pending.0.elapsed() >= self.flush_interval {
+            pending.0 = Instant::now();
+            Some(mem::take(&mut pending.1))
+        } else {
+            None
+        }
+    }
+
+    /// Take all loads that weren't written yet.
+    fn take(&self) -> HashSet<(String, String)> {
+        self.pending
+            .lock()
+            .map(|mut pending| {
+                pending.0 = Instant::now();
+                mem::take(&mut pending.1)
+            })
+            .unwrap_or_default()
+    }
+}
+
+impl PostgresCatalog {
+    /// Usage of the tables in the namespace and its children.
+    pub async fn table_usage(&self, namespace: &Namespace) -> Result<Vec<TableUsage>> {
+        self.usage(namespace, None).await
+    }
+
+    /// Tables in the namespace and its children that weren't registered, loaded or committed
+    /// since `since`. Loads that weren't flushed yet by other catalog instances are not taken
+    /// into account.
+    pub async fn unused_tables(
+        &self,
+        namespace: &Namespace,
+        since: SystemTime,
+    ) -> Result<Vec<TableUsage>> {
+        self.usage(namespace, Some(since)).await
+    }
+
+    /// Write the recorded loads to the catalog table.
+    pub async fn flush_loads(&self) -> Result<()> {
+        match &self.load_tracker {
+            Some(tracker) => self.write_loads(tracker.take()).await,
+            None => Ok(()),
+        }
+    }
+
+    /// Record that the table was loaded. Failing to write the loads doesn't fail the load.
+    pub(crate) async fn record_load(&self, identifier: &TableIdentifier) {
+        if self.read_only {
+            return;
+        }
+        if let Some(loads) = self
+            .load_tracker
+            .as_ref()
+            .and_then(|tracker| tracker.record(identifier))
+        {
+            let _ = self.write_loads(loads).await;
+        }
+    }
+
+    async fn write_loads(&self, loads: HashSet<(String, String)>) -> Result<()> {
+        if loads.is_empty() || self.read_only {
+            return Ok(());
+        }
+        let tables = loads
+            .iter()
+            .map(|(namespace, name)| {
+                "(".to_string() + &literal(namespace) + ", " + &literal(name) + ")"
+            })
+            .collect::<Vec<_>>()
+            .join(", ");
+        // Not marked as a write, recording loads shouldn't move reads to the primary.
+        self.execute(
+            &self.primary,
+            &("UPDATE ".to_string()
+                + &self.catalog_table.qualified
+                + " SET "
+                + LAST_LOADED_AT_COLUMN
+                + " = now() WHERE "
+                + CATALOG_NAME_COLUMN
+                + " = "
+                + &literal(&self.name)
+                + " AND ("
+                + TABLE_NAMESPACE_COLUMN
+                + ", "
+                + TABLE_NAME_COLUMN
+                + ") IN ("
+                + &tables
+                + ");"),
+        )
+        .await?;
+        Ok(())
+    }
+
+    async fn usage(
+        &self,
+        namespace: &Namespace,
+        since: Option<SystemTime>,
+    ) -> Result<Vec<TableUsage>> {
+        let namespace = &self.case_sensitivity.normalize_namespace(namespace)?;
+        self.authorize(Action::Read, namespace, None).await?;
+        let since_condition = match since {
+            Some(since) => {
+                let since = "to_timestamp(".to_string()
+                    + &since
+                        .duration_since(UNIX_EPOCH)
+                        .unwrap_or_default()
+                        .as_millis()
+                        .to_string()
+                    + " / 1000.0)";
+                [CREATED_AT_COLUMN, LAST_LOADED_AT_COLUMN, UPDATED_AT_COLUMN]
+                    .iter()
+                    .map(|column| {
+                        " AND (".to_string()
+                            + column
+                            + " IS NULL OR "
+                            + column
+                            + " < "
+                            + &since
+                            + ")"
+                    })
+                    .collect::<String>()
+            }
+            None => String::new(),
+        };
+        let rows = self
+            .query(
+                self.read_connection(),
+                &("SELECT ".to_string()
+                    + TABLE_NAMESPACE_COLUMN
+                    + ", "
+                    + TABLE_NAME_COLUMN
+                    + ", "
+                    + &epoch_millis(CREATED_AT_COLUMN)
+                    + ", "
+                    + &epoch_millis(LAST_LOADED_AT_COLUMN)
+                    + ", "
+                    + &epoch_millis(UPDATED_AT_COLUMN)
+                    + " FROM "
+                    + &self.catalog_table.qualified
+                    + " WHERE "
+                    + CATALOG_NAME_COLUMN
+                    + " = "
+                    + &literal(&self.name)
+                    + " AND ("
+                    + &namespace_condition(namespace)
+                    + ")"
+                    + &since_condition
+                    + " ORDER BY "
+                    + TABLE_NAMESPACE_COLUMN
+                    + ", "
+                    + TABLE_NAME_COLUMN
+                    + ";"),
+            )
+            .await?;
+        rows.iter().map(table_usage).collect()
+    }
+}
+
+fn table_usage(row: &CatalogRow) -> Result<TableUsage> {
+    let time = |millis: i64| UNIX_EPOCH + Duration::from_millis(millis.max(0) as u64);
+    let created_at = time(row.try_get_i64(CREATED_AT_COLUMN)?);
+    // Registering a table sets the update time, so only later updates are commits.
+    let last_committed_at = row
+        .try_get_opt_i64(UPDATED_AT_COLUMN)?
+        .map(time)
+        .filter(|updated_at| *updated_at > created_at);
+    Ok(TableUsage {
+        identifier: table_identifier(
+            &row.try_get_string(TABLE_NAMESPACE_COLUMN)?,
+            &row.try_get_string(TABLE_NAME_COLUMN)?,
+        )?,
+        created_at,
+        last_loaded_at: row.try_get_opt_i64(LAST_LOADED_AT_COLUMN)?.map(time),
+        last_committed_at,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, UNIX_EPOCH};
+
+    use iceberg_rs::catalog::table_identifier::TableIdentifier;
+
+    use super::{LoadTracker, TableUsage};
+
+    #[test]
+    fn test_load_tracker() {
+        let identifier = TableIdentifier::parse("test.usage").unwrap();
+        let tracker = LoadTracker::new(Duration::from_secs(3600));
+        assert!(tracker.record(&identifier).is_none());
+        assert!(tracker.record(&identifier).is_none());
+        assert_eq!(tracker.take().len(), 1);
+        assert!(tracker.take().is_empty());
+
+        let tracker = LoadTracker::new(Duration::ZERO);
+        assert_eq!(
+            tracker.record(&identifier).map(|loads| loads.len()),
+            Some(1)
+        );
+    }
+
+    #[test]
+    fn test_last_used_at() {
+        let usage = TableUsage {
+            identifier: TableIdentifier::parse("test.usage").unwrap(),
+            created_at: UNIX_EPOCH + Duration::from_secs(10),
+            last_loaded_at: Some(UNIX_EPOCH + Duration::from_secs(30)),
+            last_committed_at: Some(UNIX_EPOCH + Duration::from_secs(20)),
+        };
+        assert_eq!(usage.last_used_at(), UNIX_EPOCH + Duration::from_secs(30));
+    }
+}
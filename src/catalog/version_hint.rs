@@ -0,0 +1,87 @@
+/*!
+Metadata layout of the Hadoop catalog.
+
+Tools that only know the file-based Hadoop catalog find the current metadata of a table through the
+file `metadata/version-hint.text` in the table location, which contains the version `N` of the
+current metadata file `metadata/vN.metadata.json`. With version hints enabled, the catalog writes a
+copy of the metadata in this naming and refreshes the hint after every commit. The version is the
+version of the catalog entry plus one, so registering a table writes `v1.metadata.json`.
+
+The files are written after the commit succeeded. Failing to write them doesn't fail the commit,
+the next commit writes them again.
+*/
+
+use anyhow::{anyhow, Result};
+use iceberg_rs::{catalog::table_identifier::TableIdentifier, object_store::path::Path};
+
+use super::{storage::resolver::object_path, PostgresCatalog};
+
+/// Name of the file with the current version
+static VERSION_HINT_FILE: &str = "version-hint.text";
+
+impl PostgresCatalog {
+    /// Write the metadata with the content `bytes` as version `version + 1` and point the version
+    /// hint to it, if version hints are enabled.
+    pub(crate) async fn write_version_hint(
+        &self,
+        identifier: &TableIdentifier,
+        metadata: &serde_json::Value,
+        version: i64,
+        bytes: &[u8],
+    ) -> Result<()> {
+        if !self.version_hint {
+            return Ok(());
+        }
+        let table_location = metadata["location"]
+            .as_str()
+            .ok_or_else(|| anyhow!("The table metadata contains no location."))?;
+        let directory = metadata_directory(table_location);
+        let object_store = self.object_store_for_location(identifier, table_location)?;
+        let hadoop_version = version + 1;
+        // The metadata file is written first, so that the hint never points to a missing file.
+        object_store
+            .put(
+                &Path::from(directory.clone() + &format!("v{}.metadata.json", hadoop_version)),
+                bytes.to_vec().into(),
+            )
+            .await
+            .map_err(|err| anyhow!(err.to_string()))?;
+        object_store
+            .put(
+                &Path::from(directory + VERSION_HINT_FILE),
+                hadoop_version.to_string().into_bytes().into(),
+            )
+            .await
+            .map_err(|err| anyhow!(err.to_string()))?;
+        Ok(())
+    }
+}
+
+/// Path of the metadata directory of the table, with a trailing slash
+fn metadata_directory(table_location: &str) -> String {
+    let path = object_path(table_location)
+        .trim_end_matches('/')
+        .to_string();
+    if path.is_empty() {
+        "metadata/".to_string()
+    } else {
+        path + "/metadata/"
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::metadata_directory;
+
+    #[test]
+    fn test_metadata_directory() {
+        assert_eq!(
+            metadata_directory("warehouse/test/table1/"),
+            "warehouse/test/table1/metadata/"
+        );
+        assert_eq!(
+            metadata_directory("s3://bucket/warehouse/test/table1"),
+            "warehouse/test/table1/metadata/"
+        );
+    }
+}
@@ -0,0 +1,278 @@
+/*!
+C interface of the catalog, enabled with the `ffi` feature.
+
+The functions manage the metadata pointers of the tables for engines that aren't written in Rust.
+Reading and writing metadata files is left to the engine. The declarations are in
+`include/iceberg_catalog_postgres.h`. The crate is built as a Rust library only, the shared or
+static library is built with `cargo rustc --release --features ffi --crate-type cdylib` or
+`--crate-type staticlib`.
+
+Functions that fail return a null pointer or `-1`, the message of the error is returned by
+[iceberg_catalog_last_error] on the same thread. Panics don't unwind into the caller, they fail
+the call like errors. Strings returned by the library are freed with [iceberg_string_free],
+catalogs with [iceberg_catalog_free]. All calls block the calling thread.
+*/
+
+use std::{
+    cell::RefCell,
+    collections::HashMap,
+    ffi::{CStr, CString},
+    os::raw::{c_char, c_int},
+    panic::{self, AssertUnwindSafe},
+    ptr,
+    sync::Arc,
+};
+
+use anyhow::{anyhow, Result};
+use iceberg_rs::catalog::{table_identifier::TableIdentifier, Catalog};
+use tokio::runtime::Runtime;
+
+use crate::catalog::{
+    storage::{initial_object_store, WAREHOUSE},
+    PostgresCatalog,
+};
+
+thread_local! {
+    static LAST_ERROR: RefCell<Option<CString>> = RefCell::new(None);
+}
+
+/// Catalog handle of the C interface
+pub struct IcebergCatalog {
+    runtime: Runtime,
+    catalog: Arc<PostgresCatalog>,
+}
+
+fn set_last_error(err: anyhow::Error) {
+    let message = CString::new(err.to_string().replace('\0', " ")).ok();
+    LAST_ERROR.with(|last| *last.borrow_mut() = message);
+}
+
+/// Run the body of an exported function and return its value. Errors and panics are stored as the
+/// last error and the function returns `failed` instead.
+fn guarded<T>(failed: T, body: impl FnOnce() -> Result<T>) -> T {
+    match panic::catch_unwind(AssertUnwindSafe(body)) {
+        Ok(Ok(value)) => value,
+        Ok(Err(err)) => {
+            set_last_error(err);
+            failed
+        }
+        Err(payload) => {
+            let message = payload
+                .downcast_ref::<&str>()
+                .map(ToString::to_string)
+                .or_else(|| payload.downcast_ref::<String>().cloned())
+                .unwrap_or_default();
+            set_last_error(anyhow!("The call panicked. {}", message));
+            failed
+        }
+    }
+}
+
+/// Read a string argument. Null pointers are invalid unless the argument is optional.
+unsafe fn string_argument(value: *const c_char, name: &str) -> Result<Option<String>> {
+    if value.is_null() {
+        return Ok(None);
+    }
+    CStr::from_ptr(value)
+        .to_str()
+        .map(|value| Some(value.to_string()))
+        .map_err(|_| anyhow!("The argument {} is not valid UTF-8.", name))
+}
+
+unsafe fn required_argument(value: *const c_char, name: &str) -> Result<String> {
+    string_argument(value, name)?.ok_or_else(|| anyhow!("The argument {} is null.", name))
+}
+
+unsafe fn catalog_argument<'a>(catalog: *const IcebergCatalog) -> Result<&'a IcebergCatalog> {
+    catalog
+        .as_ref()
+        .ok_or_else(|| anyhow!("The argument catalog is null."))
+}
+
+/// Connect to the catalog `name` on the database at `url` and initialize it. `warehouse` may be
+/// null if the catalog was initialized with a warehouse before, connecting fails if neither names
+/// a warehouse. Returns null on failure.
+///
+/// # Safety
+///
+/// The arguments must be null or valid nul-terminated strings.
+#[no_mangle]
+pub unsafe extern "C" fn iceberg_catalog_connect(
+    name: *const c_char,
+    url: *const c_char,
+    warehouse: *const c_char,
+) -> *mut IcebergCatalog {
+    guarded(ptr::null_mut(), || {
+        let name = required_argument(name, "name")?;
+        let url = required_argument(url, "url")?;
+        let properties = string_argument(warehouse, "warehouse")?
+            .map(|warehouse| HashMap::from_iter(vec![(WAREHOUSE.to_string(), warehouse)]))
+            .unwrap_or_default();
+        let (object_store, warehouse_required) = initial_object_store(&properties)?;
+        let runtime = Runtime::new().map_err(|err| anyhow!(err.to_string()))?;
+        let catalog = runtime.block_on(async {
+            let catalog = Arc::new(
+                PostgresCatalog::builder(&name, &url, object_store)
+                    .with_warehouse_required(warehouse_required)
+                    .build()
+                    .await?,
+            );
+            Arc::clone(&catalog).initialize(&properties).await?;
+            Ok::<_, anyhow::Error>(catalog)
+        })?;
+        Ok(Box::into_raw(Box::new(IcebergCatalog { runtime, catalog })))
+    })
+}
+
+/// Close the connections of the catalog and free it.
+///
+/// # Safety
+///
+/// `catalog` must be null or returned by [iceberg_catalog_connect] and not freed before.
+#[no_mangle]
+pub unsafe extern "C" fn iceberg_catalog_free(catalog: *mut IcebergCatalog) {
+    guarded((), || {
+        if !catalog.is_null() {
+            let catalog = Box::from_raw(catalog);
+            catalog.runtime.block_on(catalog.catalog.close());
+        }
+        Ok(())
+    })
+}
+
+/// Load the table `identifier`, like `ns.table`, and return its current metadata as JSON.
+/// Returns null on failure.
+///
+/// # Safety
+///
+/// `catalog` must be returned by [iceberg_catalog_connect], `identifier` must be a valid
+/// nul-terminated string.
+#[no_mangle]
+pub unsafe extern "C" fn iceberg_catalog_load_table(
+    catalog: *const IcebergCatalog,
+    identifier: *const c_char,
+) -> *mut c_char {
+    guarded(ptr::null_mut(), || {
+        let catalog = catalog_argument(catalog)?;
+        let identifier = TableIdentifier::parse(&required_argument(identifier, "identifier")?)?;
+        let table = catalog
+            .runtime
+            .block_on(Arc::clone(&catalog.catalog).load_table(identifier))?;
+        let metadata =
+            serde_json::to_string(table.metadata()).map_err(|err| anyhow!(err.to_string()))?;
+        Ok(CString::new(metadata)
+            .map_err(|err| anyhow!(err.to_string()))?
+            .into_raw())
+    })
+}
+
+/// Point the table `identifier` to the metadata file `metadata_location` if it currently points
+/// to `previous_metadata_location`. Returns 0 on success and -1 on failure, including when the
+/// table was changed concurrently.
+///
+/// # Safety
+///
+/// `catalog` must be returned by [iceberg_catalog_connect], the other arguments must be valid
+/// nul-terminated strings.
+#[no_mangle]
+pub unsafe extern "C" fn iceberg_catalog_commit(
+    catalog: *const IcebergCatalog,
+    identifier: *const c_char,
+    metadata_location: *const c_char,
+    previous_metadata_location: *const c_char,
+) -> c_int {
+    guarded(-1, || {
+        let catalog = catalog_argument(catalog)?;
+        let identifier = TableIdentifier::parse(&required_argument(identifier, "identifier")?)?;
+        let metadata_location = required_argument(metadata_location, "metadata_location")?;
+        let previous_metadata_location =
+            required_argument(previous_metadata_location, "previous_metadata_location")?;
+        catalog
+            .runtime
+            .block_on(Arc::clone(&catalog.catalog).update_table(
+                identifier,
+                &metadata_location,
+                &previous_metadata_location,
+            ))?;
+        Ok(0)
+    })
+}
+
+/// Message of the last error on the calling thread, or null if no call failed. The string is
+/// owned by the library and valid until the next call on the thread.
+#[no_mangle]
+pub extern "C" fn iceberg_catalog_last_error() -> *const c_char {
+    panic::catch_unwind(|| {
+        LAST_ERROR.with(|last| {
+            last.borrow()
+                .as_ref()
+                .map(|message| message.as_ptr())
+                .unwrap_or(ptr::null())
+        })
+    })
+    .unwrap_or(ptr::null())
+}
+
+/// Free a string returned by the library.
+///
+/// # Safety
+///
+/// `value` must be null or returned by the library and not freed before.
+#[no_mangle]
+pub unsafe extern "C" fn iceberg_string_free(value: *mut c_char) {
+    guarded((), || {
+        if !value.is_null() {
+            drop(CString::from_raw(value));
+        }
+        Ok(())
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        ffi::{CStr, CString},
+        ptr,
+    };
+
+    use super::{
+        guarded, iceberg_catalog_commit, iceberg_catalog_connect, iceberg_catalog_last_error,
+    };
+
+    #[test]
+    fn test_errors() {
+        unsafe {
+            assert!(iceberg_catalog_connect(ptr::null(), ptr::null(), ptr::null()).is_null());
+            assert_eq!(
+                CStr::from_ptr(iceberg_catalog_last_error())
+                    .to_str()
+                    .unwrap(),
+                "The argument name is null."
+            );
+            let identifier = CString::new("ns.table").unwrap();
+            assert_eq!(
+                iceberg_catalog_commit(
+                    ptr::null(),
+                    identifier.as_ptr(),
+                    identifier.as_ptr(),
+                    identifier.as_ptr()
+                ),
+                -1
+            );
+            assert_eq!(
+                CStr::from_ptr(iceberg_catalog_last_error())
+                    .to_str()
+                    .unwrap(),
+                "The argument catalog is null."
+            );
+
+            assert_eq!(guarded(-1, || panic!("Unexpected state")), -1);
+            assert_eq!(
+                CStr::from_ptr(iceberg_catalog_last_error())
+                    .to_str()
+                    .unwrap(),
+                "The call panicked. Unexpected state"
+            );
+        }
+    }
+}
@@ -5,3 +5,7 @@
 This liberary implements an iceberg catalog on top of postgres.
 */
 pub mod catalog;
+#[cfg(feature = "ffi")]
+pub mod ffi;
+#[cfg(feature = "python")]
+pub mod python;
@@ -0,0 +1,269 @@
+/*!
+Python bindings of the catalog, enabled with the `python` feature.
+
+The `iceberg_catalog_postgres` Python module contains a `PostgresCatalog` class that manages the
+metadata pointers of the tables, reading and writing the metadata files is left to the Python
+library, for example pyiceberg:
+
+```python
+from iceberg_catalog_postgres import PostgresCatalog
+
+catalog = PostgresCatalog.connect("analytics", "postgres://postgres@localhost/iceberg_catalog", "s3://bucket/warehouse")
+table = catalog.load_table("sales.orders")
+catalog.commit_table("sales.orders", new_metadata_location, table.metadata_location)
+```
+
+Build the module with `maturin build`, which builds the library as `cdylib` with the `python` and
+`extension-module` features. `extension-module` leaves libpython unlinked as Python extensions
+require, so it is only enabled for the wheel; `cargo test --features python` links libpython. Calls
+block the calling Python thread but release the GIL while they wait for the database.
+*/
+
+use std::{
+    collections::HashMap,
+    future::Future,
+    sync::{Arc, OnceLock},
+};
+
+use anyhow::{anyhow, Result};
+use iceberg_rs::{
+    catalog::{namespace::Namespace, table_identifier::TableIdentifier, Catalog},
+    model::schema::SchemaV2,
+    table::Table,
+};
+use pyo3::{create_exception, exceptions::PyException, prelude::*};
+use tokio::runtime::Runtime;
+
+use crate::catalog::{
+    storage::{initial_object_store, WAREHOUSE},
+    PostgresCatalog,
+};
+
+create_exception!(
+    iceberg_catalog_postgres,
+    CatalogException,
+    PyException,
+    "Error of the catalog"
+);
+
+/// Runtime that runs the catalog operations of all Python catalogs
+static RUNTIME: OnceLock<Runtime> = OnceLock::new();
+
+fn block_on<T: Send>(
+    py: Python<'_>,
+    future: impl Future<Output = Result<T>> + Send,
+) -> PyResult<T> {
+    let runtime = match RUNTIME.get() {
+        Some(runtime) => runtime,
+        None => {
+            let runtime =
+                Runtime::new().map_err(|err| CatalogException::new_err(err.to_string()))?;
+            RUNTIME.get_or_init(|| runtime)
+        }
+    };
+    py.allow_threads(|| runtime.block_on(future))
+        .map_err(|err| CatalogException::new_err(err.to_string()))
+}
+
+fn parse_identifier(identifier: &str) -> PyResult<TableIdentifier> {
+    TableIdentifier::parse(identifier).map_err(|err| CatalogException::new_err(err.to_string()))
+}
+
+fn parse_namespace(namespace: &str) -> PyResult<Namespace> {
+    Namespace::try_new(
+        &namespace
+            .split('.')
+            .map(|level| level.to_string())
+            .collect::<Vec<_>>(),
+    )
+    .map_err(|err| CatalogException::new_err(err.to_string()))
+}
+
+/// Table returned by the Python catalog
+#[pyclass(name = "Table")]
+pub struct PyTable {
+    /// Identifier of the table, like `ns.table`
+    #[pyo3(get)]
+    identifier: String,
+    /// Location of the current metadata file
+    #[pyo3(get)]
+    metadata_location: String,
+    metadata: String,
+}
+
+impl PyTable {
+    fn new(identifier: &TableIdentifier, table: &Table) -> Result<Self> {
+        Ok(PyTable {
+            identifier: identifier.to_string(),
+            metadata_location: table.metadata_location().to_string(),
+            metadata: serde_json::to_string(table.metadata())
+                .map_err(|err| anyhow!(err.to_string()))?,
+        })
+    }
+}
+
+#[pymethods]
+impl PyTable {
+    /// Current table metadata as JSON
+    fn metadata_json(&self) -> String {
+        self.metadata.clone()
+    }
+}
+
+/// [PostgresCatalog] for Python
+#[pyclass(name = "PostgresCatalog")]
+pub struct PyPostgresCatalog {
+    catalog: Arc<PostgresCatalog>,
+}
+
+#[pymethods]
+impl PyPostgresCatalog {
+    /// Connect to the catalog and initialize it. The warehouse is only required if the catalog
+    /// wasn't initialized with a warehouse before, connecting fails without any.
+    #[staticmethod]
+    fn connect(py: Python<'_>, name: &str, url: &str, warehouse: Option<&str>) -> PyResult<Self> {
+        let properties = warehouse
+            .map(|warehouse| {
+                HashMap::from_iter(vec![(WAREHOUSE.to_string(), warehouse.to_string())])
+            })
+            .unwrap_or_default();
+        let name = name.to_string();
+        let url = url.to_string();
+        let catalog = block_on(py, async move {
+            let (object_store, warehouse_required) = initial_object_store(&properties)?;
+            let catalog = Arc::new(
+                PostgresCatalog::builder(&name, &url, object_store)
+                    .with_warehouse_required(warehouse_required)
+                    .build()
+                    .await?,
+            );
+            Arc::clone(&catalog).initialize(&properties).await?;
+            Ok(catalog)
+        })?;
+        Ok(PyPostgresCatalog { catalog })
+    }
+
+    /// Names of the namespaces directly below the parent, or of the top-level namespaces
+    fn list_namespaces(&self, py: Python<'_>, parent: Option<&str>) -> PyResult<Vec<String>> {
+        let parent = parent.map(parse_namespace).transpose()?;
+        let catalog = Arc::clone(&self.catalog);
+        block_on(py, async move {
+            Ok(catalog
+                .list_namespaces(parent.as_ref())
+                .await?
+                .iter()
+                .map(|namespace| namespace.levels().join("."))
+                .collect())
+        })
+    }
+
+    /// Identifiers of the tables in the namespace
+    fn list_tables(&self, py: Python<'_>, namespace: &str) -> PyResult<Vec<String>> {
+        let namespace = parse_namespace(namespace)?;
+        let catalog = Arc::clone(&self.catalog);
+        block_on(py, async move {
+            Ok(catalog
+                .list_tables(&namespace)
+                .await?
+                .iter()
+                .map(|identifier| identifier.to_string())
+                .collect())
+        })
+    }
+
+    /// Whether the table exists
+    fn table_exists(&self, py: Python<'_>, identifier: &str) -> PyResult<bool> {
+        let identifier = parse_identifier(identifier)?;
+        let catalog = Arc::clone(&self.catalog);
+        block_on(py, async move { catalog.table_exists(&identifier).await })
+    }
+
+    /// Load the table
+    fn load_table(&self, py: Python<'_>, identifier: &str) -> PyResult<PyTable> {
+        let identifier = parse_identifier(identifier)?;
+        let catalog = Arc::clone(&self.catalog);
+        block_on(py, async move {
+            let table = catalog.load_table(identifier.clone()).await?;
+            PyTable::new(&identifier, &table)
+        })
+    }
+
+    /// Create a table with the schema, given as JSON of the Iceberg spec
+    fn create_table(&self, py: Python<'_>, identifier: &str, schema: &str) -> PyResult<PyTable> {
+        let identifier = parse_identifier(identifier)?;
+        let schema: SchemaV2 = serde_json::from_str(schema)
+            .map_err(|err| CatalogException::new_err(err.to_string()))?;
+        let catalog = Arc::clone(&self.catalog);
+        block_on(py, async move {
+            let table = catalog.create_table(identifier.clone(), schema).await?;
+            PyTable::new(&identifier, &table)
+        })
+    }
+
+    /// Register an existing metadata file as a new table
+    fn register_table(
+        &self,
+        py: Python<'_>,
+        identifier: &str,
+        metadata_location: &str,
+    ) -> PyResult<PyTable> {
+        let identifier = parse_identifier(identifier)?;
+        let metadata_location = metadata_location.to_string();
+        let catalog = Arc::clone(&self.catalog);
+        block_on(py, async move {
+            let table = catalog
+                .register_table(identifier.clone(), &metadata_location)
+                .await?;
+            PyTable::new(&identifier, &table)
+        })
+    }
+
+    /// Point the table to a new metadata file. Fails if the current metadata file isn't
+    /// `previous_metadata_location`, because the table was changed concurrently.
+    fn commit_table(
+        &self,
+        py: Python<'_>,
+        identifier: &str,
+        metadata_location: &str,
+        previous_metadata_location: &str,
+    ) -> PyResult<PyTable> {
+        let identifier = parse_identifier(identifier)?;
+        let metadata_location = metadata_location.to_string();
+        let previous_metadata_location = previous_metadata_location.to_string();
+        let catalog = Arc::clone(&self.catalog);
+        block_on(py, async move {
+            let table = catalog
+                .update_table(
+                    identifier.clone(),
+                    &metadata_location,
+                    &previous_metadata_location,
+                )
+                .await?;
+            PyTable::new(&identifier, &table)
+        })
+    }
+
+    /// Drop the table from the catalog
+    fn drop_table(&self, py: Python<'_>, identifier: &str) -> PyResult<()> {
+        let identifier = parse_identifier(identifier)?;
+        let catalog = Arc::clone(&self.catalog);
+        block_on(py, async move { catalog.drop_table(&identifier).await })
+    }
+
+    /// Close the connections of the catalog
+    fn close(&self, py: Python<'_>) -> PyResult<()> {
+        let catalog = Arc::clone(&self.catalog);
+        block_on(py, async move {
+            catalog.close().await;
+            Ok(())
+        })
+    }
+}
+
+#[pymodule]
+fn iceberg_catalog_postgres(py: Python<'_>, module: &PyModule) -> PyResult<()> {
+    module.add_class::<PyPostgresCatalog>()?;
+    module.add_class::<PyTable>()?;
+    module.add("CatalogException", py.get_type::<CatalogException>())?;
+    Ok(())
+}